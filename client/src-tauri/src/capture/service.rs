@@ -1,3 +1,5 @@
+mod audio_sender;
+mod bandwidth_estimator;
 mod encoder_backend;
 mod ffmpeg_ivf_encoder;
 mod h264_encoder;
@@ -7,6 +9,7 @@ mod nvenc_encoder;
 mod nvenc_sdk;
 mod rtp_packetizer;
 mod rtp_sender;
+mod srtp;
 
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -19,9 +22,12 @@ use super::windows_capture::{
     self, NativeCaptureSource, NativeCaptureSourceKind, NativeCaptureStartRequest,
     NativeFramePacket,
 };
+use audio_sender::NativeAudioConfig;
 use encoder_backend::{create_encoder_backend_for_codec, NativeCodecTarget};
 use metrics::{NativeSenderMetrics, NativeSenderSharedMetrics, NativeSenderSnapshotInput};
 use native_sender::{run_native_sender_worker, NativeSenderRuntimeConfig};
+use rtp_packetizer::{CongestionControlConfig, RtxConfig};
+use srtp::SrtpConfig;
 
 #[cfg(target_os = "windows")]
 use super::dxgi_capture;
@@ -45,6 +51,7 @@ struct ActiveCaptureSession {
     bitrate_kbps: Option<u32>,
     encoder_backend: Option<String>,
     codec_mime_type: Option<String>,
+    audio_ssrc: Option<u32>,
 }
 
 #[derive(Debug)]
@@ -98,6 +105,15 @@ pub struct StartNativeCaptureRequest {
     pub rtp_target: Option<String>,
     pub payload_type: Option<u8>,
     pub ssrc: Option<u32>,
+    pub rtx_payload_type: Option<u8>,
+    pub rtx_ssrc: Option<u32>,
+    pub transport_cc_extension_id: Option<u8>,
+    pub abs_send_time_extension_id: Option<u8>,
+    pub srtp_crypto_suite: Option<String>,
+    pub srtp_key_base64: Option<String>,
+    pub srtp_remote_key_base64: Option<String>,
+    pub audio_payload_type: Option<u8>,
+    pub audio_ssrc: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -275,6 +291,18 @@ impl NativeCaptureService {
                 fallback_triggered_events: 0,
                 fallback_completed_events: 0,
                 encoder_backend_runtime_fallback_events: 0,
+                nack_requests: 0,
+                retransmitted_packets: 0,
+                retransmit_misses: 0,
+                sender_reports_sent: 0,
+                estimated_bitrate_kbps: None,
+                remb_bitrate_kbps: None,
+                encoder_target_bitrate_kbps: None,
+                srtp_enabled: false,
+                srtcp_auth_failures: 0,
+                audio_active: false,
+                audio_packets_sent: 0,
+                audio_capture_errors: 0,
                 encoder_backend: None,
                 encoder_backend_requested: None,
                 encoder_backend_fallback_reason: None,
@@ -457,6 +485,10 @@ impl NativeCaptureService {
         rtp_target: Option<String>,
         payload_type: u8,
         ssrc: u32,
+        rtx: Option<RtxConfig>,
+        congestion_control: Option<CongestionControlConfig>,
+        srtp: Option<SrtpConfig>,
+        audio: Option<NativeAudioConfig>,
     ) -> Result<(), String> {
         self.stop_sender_worker()?;
         windows_capture::reset_frame_dispatch_stats();
@@ -490,6 +522,10 @@ impl NativeCaptureService {
                         target_rtp: worker_target_rtp,
                         payload_type,
                         ssrc,
+                        rtx,
+                        congestion_control,
+                        srtp,
+                        audio,
                     },
                     receiver,
                     worker_stop_signal,
@@ -616,6 +652,100 @@ fn normalize_ssrc(ssrc: Option<u32>) -> Result<u32, String> {
     Ok(value)
 }
 
+fn normalize_rtx_config(
+    payload_type: Option<u8>,
+    ssrc: Option<u32>,
+    video_payload_type: u8,
+    video_ssrc: u32,
+) -> Result<Option<RtxConfig>, String> {
+    let (Some(payload_type), Some(ssrc)) = (payload_type, ssrc) else {
+        return Ok(None);
+    };
+
+    if payload_type > 127 || payload_type == video_payload_type {
+        return Err(
+            "Invalid RTX payload type. Expected a value between 0 and 127 other than the video payload type."
+                .to_string(),
+        );
+    }
+    if ssrc == 0 || ssrc == video_ssrc {
+        return Err(
+            "Invalid RTX SSRC. Value must be non-zero and differ from the video SSRC.".to_string(),
+        );
+    }
+
+    Ok(Some(RtxConfig { payload_type, ssrc }))
+}
+
+fn normalize_congestion_control_config(
+    transport_cc_extension_id: Option<u8>,
+    abs_send_time_extension_id: Option<u8>,
+) -> Result<Option<CongestionControlConfig>, String> {
+    let (Some(transport_cc_extension_id), Some(abs_send_time_extension_id)) =
+        (transport_cc_extension_id, abs_send_time_extension_id)
+    else {
+        return Ok(None);
+    };
+
+    // One-byte header extension ids; 15 is reserved.
+    let valid = |id: u8| (1..=14).contains(&id);
+    if !valid(transport_cc_extension_id)
+        || !valid(abs_send_time_extension_id)
+        || transport_cc_extension_id == abs_send_time_extension_id
+    {
+        return Err(
+            "Invalid RTP header extension ids. Expected two distinct values between 1 and 14."
+                .to_string(),
+        );
+    }
+
+    Ok(Some(CongestionControlConfig {
+        transport_cc_extension_id,
+        abs_send_time_extension_id,
+    }))
+}
+
+fn normalize_srtp_config(
+    crypto_suite: Option<String>,
+    key_base64: Option<String>,
+    remote_key_base64: Option<String>,
+) -> Result<Option<SrtpConfig>, String> {
+    match (crypto_suite, key_base64, remote_key_base64) {
+        (None, None, None) => Ok(None),
+        (Some(crypto_suite), Some(key_base64), Some(remote_key_base64)) => {
+            SrtpConfig::from_base64(crypto_suite.trim(), &key_base64, &remote_key_base64).map(Some)
+        }
+        _ => Err(
+            "Incomplete SRTP parameters. Expected a crypto suite, a key and a remote key."
+                .to_string(),
+        ),
+    }
+}
+
+fn normalize_audio_config(
+    payload_type: Option<u8>,
+    ssrc: Option<u32>,
+    video_ssrc: u32,
+) -> Result<Option<NativeAudioConfig>, String> {
+    let (Some(payload_type), Some(ssrc)) = (payload_type, ssrc) else {
+        return Ok(None);
+    };
+
+    if payload_type > 127 {
+        return Err(
+            "Invalid audio RTP payload type. Expected a value between 0 and 127.".to_string(),
+        );
+    }
+    if ssrc == 0 || ssrc == video_ssrc {
+        return Err(
+            "Invalid audio RTP SSRC. Value must be non-zero and differ from the video SSRC."
+                .to_string(),
+        );
+    }
+
+    Ok(Some(NativeAudioConfig { payload_type, ssrc }))
+}
+
 #[tauri::command]
 pub fn list_native_capture_sources(window: Window) -> Result<Vec<NativeCaptureSource>, String> {
     windows_capture::list_sources(&window)
@@ -626,6 +756,11 @@ pub fn native_codec_capabilities() -> Vec<NativeCodecCapability> {
     native_codec_capabilities_snapshot()
 }
 
+#[tauri::command]
+pub fn native_audio_capture_supported() -> bool {
+    audio_sender::native_audio_capture_supported()
+}
+
 #[tauri::command]
 pub fn start_native_capture(
     window: Window,
@@ -646,6 +781,23 @@ pub fn start_native_capture(
     let codec_mime_type = normalize_codec_mime_type(request.codec_mime_type)?;
     let payload_type = normalize_payload_type(request.payload_type)?;
     let ssrc = normalize_ssrc(request.ssrc)?;
+    let rtx = normalize_rtx_config(
+        request.rtx_payload_type,
+        request.rtx_ssrc,
+        payload_type,
+        ssrc,
+    )?;
+    let congestion_control = normalize_congestion_control_config(
+        request.transport_cc_extension_id,
+        request.abs_send_time_extension_id,
+    )?;
+    let srtp = normalize_srtp_config(
+        request.srtp_crypto_suite,
+        request.srtp_key_base64,
+        request.srtp_remote_key_base64,
+    )?;
+    let audio = normalize_audio_config(request.audio_payload_type, request.audio_ssrc, ssrc)?;
+    let audio_ssrc = audio.as_ref().map(|audio| audio.ssrc);
 
     let sources = windows_capture::list_sources(&window)?;
     let selected_source = sources
@@ -671,6 +823,7 @@ pub fn start_native_capture(
             && active.bitrate_kbps == bitrate_kbps
             && active.encoder_backend == encoder_backend
             && active.codec_mime_type == codec_mime_type
+            && active.audio_ssrc == audio_ssrc
             && service.is_worker_active_for(normalized)?
             && windows_capture::is_capture_active_for(normalized)?
         {
@@ -699,6 +852,10 @@ pub fn start_native_capture(
         rtp_target,
         payload_type,
         ssrc,
+        rtx,
+        congestion_control,
+        srtp,
+        audio,
     )?;
 
     // Route screen sources through DXGI Desktop Duplication (no yellow border),
@@ -737,6 +894,7 @@ pub fn start_native_capture(
         bitrate_kbps,
         encoder_backend,
         codec_mime_type,
+        audio_ssrc,
     });
 
     drop(active_session);
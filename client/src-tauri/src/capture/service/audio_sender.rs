@@ -0,0 +1,353 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::capture::unix_timestamp_ms;
+
+use super::metrics::NativeSenderSharedMetrics;
+use super::rtp_sender::NativeRtpSender;
+
+const DEFAULT_FFMPEG_BIN: &str = "ffmpeg";
+const FFMPEG_PATH_ENV_VAR: &str = "YANKCORD_NATIVE_AUDIO_FFMPEG_PATH";
+const DEFAULT_AUDIO_BITRATE_KBPS: u32 = 128;
+const OPUS_SAMPLES_PER_MS: u64 = 48;
+const OPUS_DEFAULT_PACKET_SAMPLES: u64 = 960;
+/// Re-anchor the audio RTP clock once it drifts this far from the wall
+/// clock that video frame timestamps are taken from.
+const MAX_CLOCK_DRIFT_MS: u64 = 80;
+const PACKET_WAIT_MS: u64 = 250;
+
+#[cfg(target_os = "windows")]
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+#[derive(Debug, Clone)]
+pub struct NativeAudioConfig {
+    pub payload_type: u8,
+    pub ssrc: u32,
+}
+
+/// ffmpeg input arguments for the system audio mix.
+///
+/// On Linux this is the monitor of the default PulseAudio sink, which also
+/// covers PipeWire through pipewire-pulse; `YANKCORD_NATIVE_AUDIO_SOURCE`
+/// selects another source, e.g. the monitor of a dedicated null sink. Windows
+/// needs a DirectShow loopback device named in
+/// `YANKCORD_NATIVE_AUDIO_DSHOW_DEVICE`.
+fn capture_input_args() -> Result<Vec<String>, String> {
+    #[cfg(target_os = "linux")]
+    {
+        let source = env_string("YANKCORD_NATIVE_AUDIO_SOURCE")
+            .unwrap_or_else(|| "@DEFAULT_MONITOR@".to_string());
+        Ok(vec!["-f".into(), "pulse".into(), "-i".into(), source])
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let device = env_string("YANKCORD_NATIVE_AUDIO_DSHOW_DEVICE").ok_or_else(|| {
+            "System audio capture needs a loopback device in YANKCORD_NATIVE_AUDIO_DSHOW_DEVICE"
+                .to_string()
+        })?;
+        Ok(vec![
+            "-f".into(),
+            "dshow".into(),
+            "-i".into(),
+            format!("audio={device}"),
+        ])
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        Err("System audio capture is not supported on this platform".to_string())
+    }
+}
+
+fn env_string(key: &str) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+pub fn native_audio_capture_supported() -> bool {
+    capture_input_args().is_ok()
+}
+
+fn spawn_ffmpeg_opus_capture(ffmpeg_bin: &str, input_args: &[String]) -> Result<Child, String> {
+    let mut command = Command::new(ffmpeg_bin);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    command
+        .arg("-hide_banner")
+        .arg("-loglevel")
+        .arg("error")
+        .arg("-nostats")
+        .arg("-fflags")
+        .arg("nobuffer")
+        .args(input_args)
+        .arg("-vn")
+        .arg("-ac")
+        .arg("2")
+        .arg("-ar")
+        .arg("48000")
+        .arg("-c:a")
+        .arg("libopus")
+        .arg("-application")
+        .arg("audio")
+        .arg("-b:a")
+        .arg(format!("{DEFAULT_AUDIO_BITRATE_KBPS}k"))
+        .arg("-frame_duration")
+        .arg("20")
+        // One Opus packet per Ogg page, flushed right away, so packets reach
+        // the sender as they are encoded.
+        .arg("-page_duration")
+        .arg("20000")
+        .arg("-flush_packets")
+        .arg("1")
+        .arg("-f")
+        .arg("ogg")
+        .arg("pipe:1")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+
+    command
+        .spawn()
+        .map_err(|error| format!("failed to spawn ffmpeg for system audio: {error}"))
+}
+
+/// Splits an Ogg Opus stream into Opus packets, skipping the `OpusHead`
+/// and `OpusTags` header packets.
+struct OggOpusReader<R> {
+    reader: R,
+    partial_packet: Vec<u8>,
+    packets: VecDeque<Vec<u8>>,
+    header_packets_seen: u8,
+}
+
+impl<R: Read> OggOpusReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            partial_packet: Vec::new(),
+            packets: VecDeque::new(),
+            header_packets_seen: 0,
+        }
+    }
+
+    fn next_packet(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(packet) = self.packets.pop_front() {
+                return Ok(Some(packet));
+            }
+
+            if !self.read_page()? {
+                return Ok(None);
+            }
+        }
+    }
+
+    fn read_page(&mut self) -> std::io::Result<bool> {
+        let mut header = [0u8; 27];
+        if let Err(error) = self.reader.read_exact(&mut header) {
+            if error.kind() == std::io::ErrorKind::UnexpectedEof {
+                return Ok(false);
+            }
+            return Err(error);
+        }
+
+        if &header[..4] != b"OggS" {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "ffmpeg audio output is not Ogg (OggS capture pattern missing)",
+            ));
+        }
+
+        let continued = header[5] & 0x01 != 0;
+        if !continued {
+            self.partial_packet.clear();
+        }
+
+        let mut lacing = vec![0u8; header[26] as usize];
+        self.reader.read_exact(&mut lacing)?;
+        let body_len: usize = lacing.iter().map(|segment| *segment as usize).sum();
+        let mut body = vec![0u8; body_len];
+        self.reader.read_exact(&mut body)?;
+
+        let mut offset = 0usize;
+        for segment in lacing {
+            let segment = segment as usize;
+            self.partial_packet
+                .extend_from_slice(&body[offset..offset + segment]);
+            offset += segment;
+
+            if segment < 255 {
+                let packet = std::mem::take(&mut self.partial_packet);
+                if self.header_packets_seen < 2 {
+                    self.header_packets_seen += 1;
+                } else if !packet.is_empty() {
+                    self.packets.push_back(packet);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// Number of 48 kHz samples in an Opus packet, from its TOC byte.
+fn opus_packet_samples(packet: &[u8]) -> Option<u64> {
+    let toc = *packet.first()?;
+    let config = toc >> 3;
+    let frame_samples = match config {
+        0..=11 => [480, 960, 1920, 2880][(config % 4) as usize],
+        12..=15 => [480, 960][(config % 2) as usize],
+        _ => [120, 240, 480, 960][(config % 4) as usize],
+    };
+    let frames = match toc & 0x03 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => u64::from(*packet.get(1)? & 0x3F),
+    };
+    Some(frame_samples * frames)
+}
+
+/// Audio RTP timestamps on the same wall clock as the video's: packets
+/// advance by their sample count, and the clock jumps back to the wall
+/// clock, with a marker bit, if capture stalls or runs ahead.
+#[derive(Debug, Default)]
+struct AudioRtpClock {
+    next_timestamp: Option<u64>,
+}
+
+impl AudioRtpClock {
+    fn timestamp_for(&mut self, now_ms: u64, samples: u64) -> (u32, bool) {
+        let wall_clock = now_ms.wrapping_mul(OPUS_SAMPLES_PER_MS);
+        let (timestamp, marker) = match self.next_timestamp {
+            Some(next) if next.abs_diff(wall_clock) <= MAX_CLOCK_DRIFT_MS * OPUS_SAMPLES_PER_MS => {
+                (next, false)
+            }
+            _ => (wall_clock, true),
+        };
+
+        self.next_timestamp = Some(timestamp.wrapping_add(samples));
+        (timestamp as u32, marker)
+    }
+}
+
+fn spawn_packet_reader(child: &mut Child) -> Result<(Receiver<Vec<u8>>, JoinHandle<()>), String> {
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| "ffmpeg system audio stdout was not captured".to_string())?;
+    let (packet_tx, packet_rx) = mpsc::channel();
+
+    let handle = thread::Builder::new()
+        .name("native-audio-reader".to_string())
+        .spawn(move || {
+            let mut reader = OggOpusReader::new(stdout);
+            loop {
+                match reader.next_packet() {
+                    Ok(Some(packet)) => {
+                        if packet_tx.send(packet).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(error) => {
+                        eprintln!("[native-sender] event=audio_read_error detail=\"{error}\"");
+                        break;
+                    }
+                }
+            }
+        })
+        .map_err(|error| format!("failed to start system audio reader: {error}"))?;
+
+    Ok((packet_rx, handle))
+}
+
+/// Captures system audio with ffmpeg and sends it as Opus RTP until
+/// `stop_signal` is set. Audio problems never stop the video: the error is
+/// recorded in the metrics and the screen share goes on without sound.
+pub fn run_native_audio_sender(
+    config: NativeAudioConfig,
+    mut sender: NativeRtpSender,
+    source_id: String,
+    stop_signal: Arc<AtomicBool>,
+    shared: Arc<NativeSenderSharedMetrics>,
+) {
+    let ffmpeg_bin =
+        env_string(FFMPEG_PATH_ENV_VAR).unwrap_or_else(|| DEFAULT_FFMPEG_BIN.to_string());
+    let started = capture_input_args()
+        .and_then(|input_args| spawn_ffmpeg_opus_capture(&ffmpeg_bin, &input_args))
+        .and_then(|mut child| {
+            let reader = spawn_packet_reader(&mut child);
+            if reader.is_err() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            reader.map(|reader| (child, reader))
+        });
+    let (mut child, (packet_rx, reader_handle)) = match started {
+        Ok(started) => started,
+        Err(error) => {
+            shared.audio_capture_errors.fetch_add(1, Ordering::Relaxed);
+            eprintln!(
+                "[native-sender] event=audio_capture_unavailable source={} detail=\"{}\"",
+                source_id, error,
+            );
+            return;
+        }
+    };
+
+    shared.audio_active.store(true, Ordering::Relaxed);
+    eprintln!(
+        "[native-sender] event=audio_sender_started source={} pt={} ssrc={}",
+        source_id, config.payload_type, config.ssrc,
+    );
+
+    let mut clock = AudioRtpClock::default();
+    while !stop_signal.load(Ordering::Relaxed) {
+        match packet_rx.recv_timeout(Duration::from_millis(PACKET_WAIT_MS)) {
+            Ok(packet) => {
+                let samples = opus_packet_samples(&packet).unwrap_or(OPUS_DEFAULT_PACKET_SAMPLES);
+                let (rtp_timestamp, marker) = clock.timestamp_for(unix_timestamp_ms(), samples);
+                let sent = sender.send_opus_packet(&packet, rtp_timestamp, marker);
+                shared
+                    .audio_packets_sent
+                    .fetch_add(sent as u64, Ordering::Relaxed);
+                if sender.take_and_reset_error() {
+                    shared.rtp_send_errors.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                shared.audio_capture_errors.fetch_add(1, Ordering::Relaxed);
+                eprintln!(
+                    "[native-sender] event=audio_capture_ended source={}",
+                    source_id
+                );
+                break;
+            }
+        }
+    }
+
+    shared.audio_active.store(false, Ordering::Relaxed);
+    let _ = child.kill();
+    let _ = child.wait();
+    let _ = reader_handle.join();
+    eprintln!(
+        "[native-sender] event=audio_sender_stopped source={} packets={}",
+        source_id,
+        shared.audio_packets_sent.load(Ordering::Relaxed),
+    );
+}
@@ -0,0 +1,320 @@
+use std::collections::VecDeque;
+
+const MIN_BITRATE_BPS: f64 = 300_000.0;
+const BURST_GROUP_MS: f64 = 5.0;
+const TRENDLINE_WINDOW: usize = 20;
+const TRENDLINE_SMOOTHING: f64 = 0.9;
+const TRENDLINE_GAIN: f64 = 4.0;
+const MAX_TREND_DELTAS: f64 = 60.0;
+const INITIAL_THRESHOLD: f64 = 12.5;
+const THRESHOLD_MIN: f64 = 6.0;
+const THRESHOLD_MAX: f64 = 600.0;
+const THRESHOLD_GAIN_UP: f64 = 0.0087;
+const THRESHOLD_GAIN_DOWN: f64 = 0.039;
+const OVERUSE_TIME_MS: f64 = 10.0;
+const DECREASE_FACTOR: f64 = 0.85;
+const INCREASE_PER_SECOND: f64 = 1.08;
+const ACKED_WINDOW_MS: f64 = 500.0;
+const LOSS_LOW: f64 = 0.02;
+const LOSS_HIGH: f64 = 0.10;
+const LOSS_INCREASE_FACTOR: f64 = 1.05;
+const SENT_HISTORY: usize = 4096;
+
+#[derive(Debug, Clone, Copy)]
+struct SentPacket {
+    transport_seq: u16,
+    send_time_ms: f64,
+    size: usize,
+}
+
+/// One packet of a transport-cc feedback, with its arrival time on the
+/// receiver's clock (`None` if it was reported lost).
+#[derive(Debug, Clone, Copy)]
+pub struct PacketArrival {
+    pub transport_seq: u16,
+    pub arrival_time_ms: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PacketGroup {
+    first_send_ms: f64,
+    last_send_ms: f64,
+    last_arrival_ms: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BandwidthUsage {
+    Normal,
+    Underusing,
+    Overusing,
+}
+
+#[derive(Debug)]
+struct TrendlineEstimator {
+    accumulated_delay_ms: f64,
+    smoothed_delay_ms: f64,
+    first_arrival_ms: Option<f64>,
+    samples: VecDeque<(f64, f64)>,
+    num_deltas: f64,
+    threshold: f64,
+    last_threshold_update_ms: Option<f64>,
+    overuse_started_ms: Option<f64>,
+    previous_trend: f64,
+    usage: BandwidthUsage,
+}
+
+impl TrendlineEstimator {
+    fn new() -> Self {
+        Self {
+            accumulated_delay_ms: 0.0,
+            smoothed_delay_ms: 0.0,
+            first_arrival_ms: None,
+            samples: VecDeque::with_capacity(TRENDLINE_WINDOW),
+            num_deltas: 0.0,
+            threshold: INITIAL_THRESHOLD,
+            last_threshold_update_ms: None,
+            overuse_started_ms: None,
+            previous_trend: 0.0,
+            usage: BandwidthUsage::Normal,
+        }
+    }
+
+    fn update(&mut self, delay_variation_ms: f64, arrival_ms: f64) {
+        let first_arrival_ms = *self.first_arrival_ms.get_or_insert(arrival_ms);
+        self.num_deltas = (self.num_deltas + 1.0).min(MAX_TREND_DELTAS);
+        self.accumulated_delay_ms += delay_variation_ms;
+        self.smoothed_delay_ms = TRENDLINE_SMOOTHING * self.smoothed_delay_ms
+            + (1.0 - TRENDLINE_SMOOTHING) * self.accumulated_delay_ms;
+
+        self.samples
+            .push_back((arrival_ms - first_arrival_ms, self.smoothed_delay_ms));
+        if self.samples.len() > TRENDLINE_WINDOW {
+            self.samples.pop_front();
+        }
+        if self.samples.len() < TRENDLINE_WINDOW {
+            return;
+        }
+
+        let trend = linear_fit_slope(&self.samples).unwrap_or(self.previous_trend);
+        let modified_trend = self.num_deltas * trend * TRENDLINE_GAIN;
+        self.detect(modified_trend, trend, arrival_ms);
+        self.update_threshold(modified_trend, arrival_ms);
+        self.previous_trend = trend;
+    }
+
+    fn detect(&mut self, modified_trend: f64, trend: f64, now_ms: f64) {
+        if modified_trend > self.threshold {
+            let started = *self.overuse_started_ms.get_or_insert(now_ms);
+            if now_ms - started >= OVERUSE_TIME_MS && trend >= self.previous_trend {
+                self.usage = BandwidthUsage::Overusing;
+            }
+        } else if modified_trend < -self.threshold {
+            self.overuse_started_ms = None;
+            self.usage = BandwidthUsage::Underusing;
+        } else {
+            self.overuse_started_ms = None;
+            self.usage = BandwidthUsage::Normal;
+        }
+    }
+
+    fn update_threshold(&mut self, modified_trend: f64, now_ms: f64) {
+        let last_update = self.last_threshold_update_ms.replace(now_ms);
+        let magnitude = modified_trend.abs();
+        // Large spikes (e.g. a route change) should not drag the threshold.
+        if magnitude > self.threshold + 15.0 {
+            return;
+        }
+
+        let gain = if magnitude < self.threshold {
+            THRESHOLD_GAIN_DOWN
+        } else {
+            THRESHOLD_GAIN_UP
+        };
+        let elapsed_ms = last_update.map_or(0.0, |last| (now_ms - last).min(100.0));
+        self.threshold = (self.threshold + gain * (magnitude - self.threshold) * elapsed_ms)
+            .clamp(THRESHOLD_MIN, THRESHOLD_MAX);
+    }
+}
+
+fn linear_fit_slope(samples: &VecDeque<(f64, f64)>) -> Option<f64> {
+    let count = samples.len() as f64;
+    let mean_x = samples.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = samples.iter().map(|(_, y)| y).sum::<f64>() / count;
+    let (numerator, denominator) = samples.iter().fold((0.0, 0.0), |(num, den), (x, y)| {
+        (
+            num + (x - mean_x) * (y - mean_y),
+            den + (x - mean_x) * (x - mean_x),
+        )
+    });
+
+    (denominator != 0.0).then(|| numerator / denominator)
+}
+
+/// GCC-style send-side bandwidth estimation for the native sender.
+///
+/// The delay-based part follows the transport-cc feedback: packets are
+/// grouped into send bursts, a trendline over the one-way delay variation
+/// detects queues building up on the path, and an AIMD controller moves the
+/// rate. The loss-based part follows the fraction lost in receiver reports,
+/// and REMB, when the router sends it, caps the result.
+#[derive(Debug)]
+pub struct BandwidthEstimator {
+    sent: Vec<Option<SentPacket>>,
+    current_group: Option<PacketGroup>,
+    previous_group: Option<PacketGroup>,
+    trendline: TrendlineEstimator,
+    acked: VecDeque<(f64, usize)>,
+    delay_based_bps: f64,
+    loss_based_bps: f64,
+    remb_bps: Option<f64>,
+    max_bps: f64,
+    last_rate_update_ms: Option<f64>,
+}
+
+impl BandwidthEstimator {
+    pub fn new(start_bitrate_bps: u64, max_bitrate_bps: u64) -> Self {
+        let max_bps = (max_bitrate_bps as f64).max(MIN_BITRATE_BPS);
+        let start_bps = (start_bitrate_bps as f64).clamp(MIN_BITRATE_BPS, max_bps);
+
+        Self {
+            sent: vec![None; SENT_HISTORY],
+            current_group: None,
+            previous_group: None,
+            trendline: TrendlineEstimator::new(),
+            acked: VecDeque::new(),
+            delay_based_bps: start_bps,
+            loss_based_bps: start_bps,
+            remb_bps: None,
+            max_bps,
+            last_rate_update_ms: None,
+        }
+    }
+
+    pub fn on_packet_sent(&mut self, transport_seq: u16, send_time_ms: f64, size: usize) {
+        self.sent[transport_seq as usize % SENT_HISTORY] = Some(SentPacket {
+            transport_seq,
+            send_time_ms,
+            size,
+        });
+    }
+
+    pub fn on_transport_feedback(&mut self, arrivals: &[PacketArrival], now_ms: f64) {
+        for arrival in arrivals {
+            let Some(arrival_time_ms) = arrival.arrival_time_ms else {
+                continue;
+            };
+            let Some(sent) = self.sent[arrival.transport_seq as usize % SENT_HISTORY]
+                .filter(|sent| sent.transport_seq == arrival.transport_seq)
+            else {
+                continue;
+            };
+
+            self.record_acked(arrival_time_ms, sent.size);
+            self.add_to_group(sent.send_time_ms, arrival_time_ms);
+        }
+
+        self.update_delay_based_rate(now_ms);
+    }
+
+    /// `fraction_lost` is the receiver report's 8-bit fixed point value.
+    pub fn on_receiver_report(&mut self, fraction_lost: u8) {
+        let loss = f64::from(fraction_lost) / 256.0;
+        if loss < LOSS_LOW {
+            self.loss_based_bps *= LOSS_INCREASE_FACTOR;
+        } else if loss > LOSS_HIGH {
+            self.loss_based_bps *= 1.0 - 0.5 * loss;
+        }
+        self.loss_based_bps = self.loss_based_bps.clamp(MIN_BITRATE_BPS, self.max_bps);
+    }
+
+    pub fn on_remb(&mut self, bitrate_bps: u64) {
+        self.remb_bps = Some((bitrate_bps as f64).max(MIN_BITRATE_BPS));
+    }
+
+    pub fn remb_bps(&self) -> Option<u64> {
+        self.remb_bps.map(|remb| remb as u64)
+    }
+
+    pub fn estimate_bps(&self) -> u64 {
+        let estimate = self
+            .delay_based_bps
+            .min(self.loss_based_bps)
+            .min(self.remb_bps.unwrap_or(f64::MAX));
+        estimate.clamp(MIN_BITRATE_BPS, self.max_bps) as u64
+    }
+
+    fn record_acked(&mut self, arrival_time_ms: f64, size: usize) {
+        self.acked.push_back((arrival_time_ms, size));
+        while self
+            .acked
+            .front()
+            .is_some_and(|(arrival, _)| arrival_time_ms - arrival > ACKED_WINDOW_MS)
+        {
+            self.acked.pop_front();
+        }
+    }
+
+    fn acknowledged_bps(&self) -> Option<f64> {
+        let (first, _) = self.acked.front()?;
+        let (last, _) = self.acked.back()?;
+        let span_ms = (last - first).max(50.0);
+        let bytes: usize = self.acked.iter().map(|(_, size)| size).sum();
+        Some(bytes as f64 * 8.0 * 1000.0 / span_ms)
+    }
+
+    fn add_to_group(&mut self, send_time_ms: f64, arrival_time_ms: f64) {
+        match self.current_group.as_mut() {
+            Some(group) if send_time_ms - group.first_send_ms <= BURST_GROUP_MS => {
+                group.last_send_ms = group.last_send_ms.max(send_time_ms);
+                group.last_arrival_ms = group.last_arrival_ms.max(arrival_time_ms);
+                return;
+            }
+            // Reordered packet from an earlier group: nothing to learn.
+            Some(group) if send_time_ms < group.first_send_ms => return,
+            _ => {}
+        }
+
+        let finished = self.current_group.replace(PacketGroup {
+            first_send_ms: send_time_ms,
+            last_send_ms: send_time_ms,
+            last_arrival_ms: arrival_time_ms,
+        });
+        let Some(finished) = finished else {
+            return;
+        };
+
+        if let Some(previous) = self.previous_group {
+            let send_delta = finished.last_send_ms - previous.last_send_ms;
+            let arrival_delta = finished.last_arrival_ms - previous.last_arrival_ms;
+            self.trendline
+                .update(arrival_delta - send_delta, finished.last_arrival_ms);
+        }
+        self.previous_group = Some(finished);
+    }
+
+    fn update_delay_based_rate(&mut self, now_ms: f64) {
+        let elapsed_ms = self
+            .last_rate_update_ms
+            .replace(now_ms)
+            .map_or(0.0, |last| (now_ms - last).clamp(0.0, 1_000.0));
+        let acknowledged = self.acknowledged_bps();
+
+        match self.trendline.usage {
+            BandwidthUsage::Overusing => {
+                let basis = acknowledged.unwrap_or(self.delay_based_bps);
+                self.delay_based_bps = (basis * DECREASE_FACTOR).min(self.delay_based_bps);
+            }
+            BandwidthUsage::Normal => {
+                let increased =
+                    self.delay_based_bps * INCREASE_PER_SECOND.powf(elapsed_ms / 1_000.0);
+                // Never grow far ahead of what actually reaches the receiver,
+                // but a quiet screen sending little is no reason to shrink.
+                let ceiling = acknowledged.map_or(f64::MAX, |acked| acked * 1.5 + 10_000.0);
+                self.delay_based_bps = self.delay_based_bps.max(increased.min(ceiling));
+            }
+            BandwidthUsage::Underusing => {}
+        }
+
+        self.delay_based_bps = self.delay_based_bps.clamp(MIN_BITRATE_BPS, self.max_bps);
+    }
+}
@@ -1,6 +1,7 @@
 use super::ffmpeg_ivf_encoder::{try_build_av1_backend, try_build_vp8_backend, try_build_vp9_backend};
 use super::h264_encoder::{
-    build_h264_encoder_state, encode_bgra_frame, force_intra_frame, H264EncoderState,
+    build_h264_encoder_state, encode_bgra_frame, force_intra_frame, h264_encoder_ready,
+    H264EncoderState,
 };
 use super::metrics::NativeSenderSharedMetrics;
 use super::nvenc_encoder::try_build_nvenc_backend;
@@ -39,6 +40,13 @@ pub trait VideoEncoderBackend: Send {
     ) -> Option<Vec<Vec<u8>>>;
     fn request_keyframe(&mut self) -> bool;
 
+    /// Retarget the encoder's bitrate, e.g. to follow the network estimate.
+    /// Returns `false` if the backend keeps the bitrate it was built with.
+    #[allow(unused_variables)]
+    fn set_target_bitrate_kbps(&mut self, bitrate_kbps: u32) -> bool {
+        false
+    }
+
     /// Try to encode a GPU-resident texture directly (zero-copy path).
     /// Default implementation returns `NotSupported`, causing the caller
     /// to fall back to CPU readback + `encode_frame`.
@@ -317,12 +325,14 @@ pub(super) fn split_annex_b_nals(bitstream: &[u8]) -> Vec<Vec<u8>> {
 
 pub struct OpenH264EncoderBackend {
     state: H264EncoderState,
+    target_fps: Option<u32>,
 }
 
 impl OpenH264EncoderBackend {
     pub fn new(target_fps: Option<u32>, target_bitrate_kbps: Option<u32>) -> Self {
         Self {
             state: build_h264_encoder_state(target_fps, target_bitrate_kbps),
+            target_fps,
         }
     }
 }
@@ -350,4 +360,11 @@ impl VideoEncoderBackend for OpenH264EncoderBackend {
     fn request_keyframe(&mut self) -> bool {
         force_intra_frame(&mut self.state)
     }
+
+    fn set_target_bitrate_kbps(&mut self, bitrate_kbps: u32) -> bool {
+        // A fresh encoder starts on an IDR frame, so receivers pick the new
+        // rate up without a separate keyframe request.
+        self.state = build_h264_encoder_state(self.target_fps, Some(bitrate_kbps));
+        h264_encoder_ready(&self.state)
+    }
 }
@@ -288,6 +288,17 @@ impl VideoEncoderBackend for FfmpegIvfEncoderBackend {
     fn request_keyframe(&mut self) -> bool {
         false
     }
+
+    fn set_target_bitrate_kbps(&mut self, bitrate_kbps: u32) -> bool {
+        let bitrate_kbps = bitrate_kbps.max(1_000);
+        if bitrate_kbps != self.target_bitrate_kbps {
+            // ffmpeg takes the bitrate on its command line; the next frame
+            // respawns it, starting on a keyframe.
+            self.target_bitrate_kbps = bitrate_kbps;
+            self.restart_process();
+        }
+        true
+    }
 }
 
 impl Drop for FfmpegIvfEncoderBackend {
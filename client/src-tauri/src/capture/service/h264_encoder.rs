@@ -33,6 +33,10 @@ pub fn build_h264_encoder_state(
     }
 }
 
+pub fn h264_encoder_ready(encoder_state: &H264EncoderState) -> bool {
+    encoder_state.encoder.is_some()
+}
+
 pub fn force_intra_frame(encoder_state: &mut H264EncoderState) -> bool {
     let Some(active_encoder) = encoder_state.encoder.as_mut() else {
         return false;
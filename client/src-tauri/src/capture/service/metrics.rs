@@ -28,6 +28,18 @@ pub struct NativeSenderSharedMetrics {
     pub fallback_triggered_events: AtomicU64,
     pub fallback_completed_events: AtomicU64,
     pub encoder_backend_runtime_fallback_events: AtomicU64,
+    pub nack_requests: AtomicU64,
+    pub retransmitted_packets: AtomicU64,
+    pub retransmit_misses: AtomicU64,
+    pub sender_reports_sent: AtomicU64,
+    pub estimated_bitrate_kbps: AtomicU64,
+    pub remb_bitrate_kbps: AtomicU64,
+    pub encoder_target_bitrate_kbps: AtomicU64,
+    pub srtcp_auth_failures: AtomicU64,
+    pub srtp_enabled: AtomicBool,
+    pub audio_packets_sent: AtomicU64,
+    pub audio_capture_errors: AtomicU64,
+    pub audio_active: AtomicBool,
     pub transport_connected: AtomicBool,
     pub producer_connected: AtomicBool,
     pub degradation_level: AtomicU64,
@@ -82,6 +94,18 @@ pub struct NativeSenderMetrics {
     pub fallback_triggered_events: u64,
     pub fallback_completed_events: u64,
     pub encoder_backend_runtime_fallback_events: u64,
+    pub nack_requests: u64,
+    pub retransmitted_packets: u64,
+    pub retransmit_misses: u64,
+    pub sender_reports_sent: u64,
+    pub estimated_bitrate_kbps: Option<u32>,
+    pub remb_bitrate_kbps: Option<u32>,
+    pub encoder_target_bitrate_kbps: Option<u32>,
+    pub srtp_enabled: bool,
+    pub srtcp_auth_failures: u64,
+    pub audio_active: bool,
+    pub audio_packets_sent: u64,
+    pub audio_capture_errors: u64,
     pub encoder_backend: Option<String>,
     pub encoder_backend_requested: Option<String>,
     pub encoder_backend_fallback_reason: Option<String>,
@@ -168,6 +192,9 @@ impl NativeSenderSharedMetrics {
         let dropped_before_encode = self.dropped_before_encode.load(Ordering::Relaxed);
         let dropped_during_send = self.dropped_during_send.load(Ordering::Relaxed);
         let degradation_level = self.degradation_level.load(Ordering::Relaxed);
+        let estimated_bitrate_kbps = self.estimated_bitrate_kbps.load(Ordering::Relaxed);
+        let remb_bitrate_kbps = self.remb_bitrate_kbps.load(Ordering::Relaxed);
+        let encoder_target_bitrate_kbps = self.encoder_target_bitrate_kbps.load(Ordering::Relaxed);
         let pressure_window_avg_depth = self.pressure_window_avg_depth.load(Ordering::Relaxed);
         let pressure_window_peak_depth = self.pressure_window_peak_depth.load(Ordering::Relaxed);
         let pressure_window_max_avg_depth =
@@ -253,6 +280,30 @@ impl NativeSenderSharedMetrics {
             encoder_backend_runtime_fallback_events: self
                 .encoder_backend_runtime_fallback_events
                 .load(Ordering::Relaxed),
+            nack_requests: self.nack_requests.load(Ordering::Relaxed),
+            retransmitted_packets: self.retransmitted_packets.load(Ordering::Relaxed),
+            retransmit_misses: self.retransmit_misses.load(Ordering::Relaxed),
+            sender_reports_sent: self.sender_reports_sent.load(Ordering::Relaxed),
+            estimated_bitrate_kbps: if estimated_bitrate_kbps == 0 {
+                None
+            } else {
+                Some(estimated_bitrate_kbps as u32)
+            },
+            remb_bitrate_kbps: if remb_bitrate_kbps == 0 {
+                None
+            } else {
+                Some(remb_bitrate_kbps as u32)
+            },
+            encoder_target_bitrate_kbps: if encoder_target_bitrate_kbps == 0 {
+                None
+            } else {
+                Some(encoder_target_bitrate_kbps as u32)
+            },
+            srtp_enabled: self.srtp_enabled.load(Ordering::Relaxed),
+            srtcp_auth_failures: self.srtcp_auth_failures.load(Ordering::Relaxed),
+            audio_active: self.audio_active.load(Ordering::Relaxed),
+            audio_packets_sent: self.audio_packets_sent.load(Ordering::Relaxed),
+            audio_capture_errors: self.audio_capture_errors.load(Ordering::Relaxed),
             encoder_backend,
             encoder_backend_requested,
             encoder_backend_fallback_reason,
@@ -2,15 +2,21 @@ use std::collections::VecDeque;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc::Receiver;
 use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 
 use crate::capture::windows_capture::{self, NativeFrameData, NativeFramePacket};
 
+use super::audio_sender::{run_native_audio_sender, NativeAudioConfig};
 use super::encoder_backend::{
     create_encoder_backend_for_codec, create_openh264_backend, NativeCodecTarget,
+    VideoEncoderBackend,
 };
 use super::metrics::NativeSenderSharedMetrics;
-use super::rtp_packetizer::{CodecRtpPacketizer, RtpCodecKind, RtpPacketizer};
+use super::rtp_packetizer::{
+    CodecRtpPacketizer, CongestionControlConfig, RtpCodecKind, RtpPacketizer, RtxConfig,
+};
+use super::srtp::SrtpConfig;
 
 const FAILURE_WINDOW_MS: u64 = 12_000;
 const ENCODE_FAILURE_THRESHOLD: u64 = 18;
@@ -30,6 +36,17 @@ const LEVEL2_SCALE_DIVISOR_DEFAULT: u32 = 2;
 const LEVEL3_SCALE_DIVISOR_DEFAULT: u32 = 2;
 const LEVEL3_BITRATE_NUMERATOR_DEFAULT: u32 = 7;
 const LEVEL3_BITRATE_DENOMINATOR_DEFAULT: u32 = 10;
+const NETWORK_LEVEL1_PERCENT_DEFAULT: u32 = 85;
+const NETWORK_LEVEL2_PERCENT_DEFAULT: u32 = 60;
+const NETWORK_LEVEL3_PERCENT_DEFAULT: u32 = 40;
+/// Extra headroom, in percent of the target bitrate, the estimate must
+/// regain before a network degradation level is lifted.
+const NETWORK_RECOVER_MARGIN_PERCENT: u32 = 10;
+/// Encoder bitrate as a share of the estimate, leaving room for RTX, audio
+/// and the estimate's own overshoot.
+const NETWORK_ENCODER_SHARE_PERCENT: u32 = 90;
+const ENCODER_RETARGET_MIN_CHANGE_PERCENT: u32 = 15;
+const ENCODER_RETARGET_MIN_INTERVAL_MS: u64 = 2_000;
 const DEFAULT_TARGET_BITRATE_KBPS: u32 = 8_000;
 const MIN_DEGRADED_BITRATE_KBPS: u32 = 1_200;
 const NVENC_RUNTIME_FALLBACK_ENCODE_FAILURES_DEFAULT: u64 = 12;
@@ -39,9 +56,7 @@ const DEFAULT_FPS_LIMIT: u32 = 30;
 
 fn create_packetizer_for_codec(
     mime_type: &str,
-    target_rtp: Option<String>,
-    payload_type: u8,
-    ssrc: u32,
+    config: &NativeSenderRuntimeConfig,
 ) -> Result<Box<dyn RtpPacketizer>, String> {
     let codec = if mime_type.eq_ignore_ascii_case("video/h264") {
         RtpCodecKind::H264
@@ -58,12 +73,20 @@ fn create_packetizer_for_codec(
         ));
     };
 
-    Ok(Box::new(CodecRtpPacketizer::new(
+    let packetizer = CodecRtpPacketizer::new(
         codec,
-        target_rtp,
-        payload_type,
-        ssrc,
-    )))
+        config.target_rtp.clone(),
+        config.payload_type,
+        config.ssrc,
+        config.rtx,
+        config.congestion_control,
+        config
+            .target_bitrate_kbps
+            .unwrap_or(DEFAULT_TARGET_BITRATE_KBPS),
+    )
+    .with_srtp(config.srtp.as_ref());
+
+    Ok(Box::new(packetizer))
 }
 
 #[derive(Debug, Clone)]
@@ -80,6 +103,9 @@ struct DegradationTuning {
     level3_scale_divisor: u32,
     level3_bitrate_numerator: u32,
     level3_bitrate_denominator: u32,
+    network_level1_percent: u32,
+    network_level2_percent: u32,
+    network_level3_percent: u32,
 }
 
 impl DegradationTuning {
@@ -156,6 +182,24 @@ impl DegradationTuning {
             level3_bitrate_numerator,
             100,
         );
+        let network_level1_percent = env_u32(
+            "YANKCORD_NATIVE_DEGRADE_NETWORK_LEVEL1_PERCENT",
+            NETWORK_LEVEL1_PERCENT_DEFAULT,
+            1,
+            100,
+        );
+        let network_level2_percent = env_u32(
+            "YANKCORD_NATIVE_DEGRADE_NETWORK_LEVEL2_PERCENT",
+            NETWORK_LEVEL2_PERCENT_DEFAULT,
+            1,
+            network_level1_percent,
+        );
+        let network_level3_percent = env_u32(
+            "YANKCORD_NATIVE_DEGRADE_NETWORK_LEVEL3_PERCENT",
+            NETWORK_LEVEL3_PERCENT_DEFAULT,
+            1,
+            network_level2_percent,
+        );
 
         Self {
             level1_avg_depth,
@@ -170,6 +214,9 @@ impl DegradationTuning {
             level3_scale_divisor,
             level3_bitrate_numerator,
             level3_bitrate_denominator,
+            network_level1_percent,
+            network_level2_percent,
+            network_level3_percent,
         }
     }
 }
@@ -200,6 +247,10 @@ pub struct NativeSenderRuntimeConfig {
     pub target_rtp: Option<String>,
     pub payload_type: u8,
     pub ssrc: u32,
+    pub rtx: Option<RtxConfig>,
+    pub congestion_control: Option<CongestionControlConfig>,
+    pub srtp: Option<SrtpConfig>,
+    pub audio: Option<NativeAudioConfig>,
 }
 
 #[derive(Debug, Clone)]
@@ -283,9 +334,15 @@ fn trigger_native_fallback(reason: &str, source_id: &str, shared: &NativeSenderS
     );
 }
 
+/// Quality degradation driven by two signals: local frame-queue pressure and
+/// the network bandwidth estimate. The effective level is the worse of the
+/// two.
 #[derive(Debug, Default)]
 struct AdaptiveDegradationState {
     level: u64,
+    queue_level: u64,
+    network_level: u64,
+    network_cap_kbps: Option<u32>,
     frame_index: u64,
     pressure_samples: VecDeque<u64>,
     last_sample_at_ms: u64,
@@ -303,7 +360,7 @@ impl AdaptiveDegradationState {
         let (avg_depth, peak_depth) = self.pressure_stats();
         shared.update_pressure_window(avg_depth, peak_depth);
 
-        let next_level = if avg_depth >= tuning.level3_avg_depth
+        self.queue_level = if avg_depth >= tuning.level3_avg_depth
             || peak_depth >= tuning.level3_peak_depth
         {
             3
@@ -311,15 +368,63 @@ impl AdaptiveDegradationState {
             2
         } else if avg_depth >= tuning.level1_avg_depth || peak_depth >= tuning.level1_peak_depth {
             1
-        } else if self.level > 0
+        } else if self.queue_level > 0
             && avg_depth <= tuning.recover_avg_depth
             && peak_depth <= tuning.recover_peak_depth
         {
-            self.level.saturating_sub(1)
+            self.queue_level.saturating_sub(1)
+        } else {
+            self.queue_level
+        };
+
+        self.apply_level(shared);
+    }
+
+    /// Maps the bandwidth estimate, as a share of the target bitrate, onto a
+    /// degradation level. Levels drop one at a time, and only once the
+    /// estimate has some headroom above the threshold that raised them.
+    fn update_network_level(
+        &mut self,
+        estimated_bitrate_kbps: u32,
+        target_bitrate_kbps: u32,
+        shared: &NativeSenderSharedMetrics,
+        tuning: &DegradationTuning,
+    ) {
+        let percent = (u64::from(estimated_bitrate_kbps) * 100
+            / u64::from(target_bitrate_kbps.max(1))) as u32;
+        let thresholds = [
+            tuning.network_level1_percent,
+            tuning.network_level2_percent,
+            tuning.network_level3_percent,
+        ];
+        let measured_level = thresholds
+            .iter()
+            .filter(|threshold| percent < **threshold)
+            .count() as u64;
+
+        self.network_level = if measured_level >= self.network_level {
+            measured_level
         } else {
-            self.level
+            let lifted_threshold = thresholds[self.network_level as usize - 1];
+            if percent >= lifted_threshold + NETWORK_RECOVER_MARGIN_PERCENT {
+                self.network_level - 1
+            } else {
+                self.network_level
+            }
         };
+        self.network_cap_kbps =
+            (estimated_bitrate_kbps < target_bitrate_kbps).then_some(estimated_bitrate_kbps);
 
+        self.apply_level(shared);
+    }
+
+    fn force_minimum_level(&mut self, level: u64, shared: &NativeSenderSharedMetrics) {
+        self.queue_level = self.queue_level.max(level);
+        self.apply_level(shared);
+    }
+
+    fn apply_level(&mut self, shared: &NativeSenderSharedMetrics) {
+        let next_level = self.queue_level.max(self.network_level);
         if next_level != self.level {
             self.level = next_level;
             shared.set_degradation_level(next_level);
@@ -379,18 +484,20 @@ impl AdaptiveDegradationState {
         target_bitrate_kbps: Option<u32>,
         tuning: &DegradationTuning,
     ) -> Option<u32> {
-        if self.level < 3 {
-            return None;
-        }
-
-        let baseline = target_bitrate_kbps
-            .unwrap_or(DEFAULT_TARGET_BITRATE_KBPS)
-            .max(MIN_DEGRADED_BITRATE_KBPS);
-        let reduced = baseline
-            .saturating_mul(tuning.level3_bitrate_numerator)
-            .saturating_div(tuning.level3_bitrate_denominator)
-            .max(MIN_DEGRADED_BITRATE_KBPS);
-        Some(reduced)
+        let level3_cap_kbps = (self.level >= 3).then(|| {
+            let baseline = target_bitrate_kbps
+                .unwrap_or(DEFAULT_TARGET_BITRATE_KBPS)
+                .max(MIN_DEGRADED_BITRATE_KBPS);
+            baseline
+                .saturating_mul(tuning.level3_bitrate_numerator)
+                .saturating_div(tuning.level3_bitrate_denominator)
+                .max(MIN_DEGRADED_BITRATE_KBPS)
+        });
+
+        match (level3_cap_kbps, self.network_cap_kbps) {
+            (Some(level3), Some(network)) => Some(level3.min(network)),
+            (level3, network) => level3.or(network),
+        }
     }
 }
 
@@ -539,6 +646,151 @@ impl BitrateLimiter {
     }
 }
 
+/// Follows the bandwidth estimate with the encoder's target bitrate,
+/// retargeting only on sizeable changes since encoders restart or reset
+/// their rate control to apply a new bitrate.
+#[derive(Debug)]
+struct NetworkRateController {
+    target_bitrate_kbps: u32,
+    encoder_bitrate_kbps: u32,
+    last_retarget_at_ms: u64,
+}
+
+impl NetworkRateController {
+    fn new(target_bitrate_kbps: Option<u32>) -> Self {
+        let target_bitrate_kbps = target_bitrate_kbps.unwrap_or(DEFAULT_TARGET_BITRATE_KBPS);
+        Self {
+            target_bitrate_kbps,
+            encoder_bitrate_kbps: target_bitrate_kbps,
+            last_retarget_at_ms: 0,
+        }
+    }
+
+    fn update(
+        &mut self,
+        estimated_bitrate_kbps: u32,
+        encoder: &mut dyn VideoEncoderBackend,
+        source_id: &str,
+        shared: &NativeSenderSharedMetrics,
+    ) {
+        let now_ms = unix_timestamp_ms();
+        let desired_kbps = (u64::from(estimated_bitrate_kbps)
+            * u64::from(NETWORK_ENCODER_SHARE_PERCENT)
+            / 100) as u32;
+        let desired_kbps = desired_kbps
+            .max(MIN_DEGRADED_BITRATE_KBPS)
+            .min(self.target_bitrate_kbps);
+        let change_percent = desired_kbps.abs_diff(self.encoder_bitrate_kbps) as u64 * 100
+            / u64::from(self.encoder_bitrate_kbps.max(1));
+        if change_percent < u64::from(ENCODER_RETARGET_MIN_CHANGE_PERCENT)
+            || now_ms.saturating_sub(self.last_retarget_at_ms) < ENCODER_RETARGET_MIN_INTERVAL_MS
+        {
+            return;
+        }
+
+        self.last_retarget_at_ms = now_ms;
+        if !encoder.set_target_bitrate_kbps(desired_kbps) {
+            return;
+        }
+
+        eprintln!(
+            "[native-sender] event=encoder_bitrate_retargeted source={} from_kbps={} to_kbps={} estimate_kbps={}",
+            source_id, self.encoder_bitrate_kbps, desired_kbps, estimated_bitrate_kbps,
+        );
+        self.encoder_bitrate_kbps = desired_kbps;
+        shared
+            .encoder_target_bitrate_kbps
+            .store(u64::from(desired_kbps), Ordering::Relaxed);
+    }
+}
+
+fn process_transport_feedback(
+    packetizer: &mut dyn RtpPacketizer,
+    encoder: &mut dyn VideoEncoderBackend,
+    source_id: &str,
+    shared: &NativeSenderSharedMetrics,
+    degradation_state: &mut AdaptiveDegradationState,
+    rate_controller: &mut NetworkRateController,
+    tuning: &DegradationTuning,
+) {
+    let feedback = packetizer.poll_feedback();
+    if feedback.keyframe_requests > 0 {
+        shared
+            .keyframe_requests
+            .fetch_add(feedback.keyframe_requests, Ordering::Relaxed);
+        if encoder.request_keyframe() {
+            eprintln!(
+                "[native-sender] event=keyframe_requested source={} requests={}",
+                source_id, feedback.keyframe_requests,
+            );
+        }
+    }
+
+    shared
+        .nack_requests
+        .fetch_add(feedback.nack_requests, Ordering::Relaxed);
+    shared
+        .srtcp_auth_failures
+        .fetch_add(feedback.srtcp_auth_failures, Ordering::Relaxed);
+    shared
+        .retransmitted_packets
+        .fetch_add(feedback.retransmitted_packets, Ordering::Relaxed);
+    shared
+        .retransmit_misses
+        .fetch_add(feedback.retransmit_misses, Ordering::Relaxed);
+
+    if packetizer.send_sender_report_if_due() {
+        shared.sender_reports_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    if let Some(remb_bitrate_kbps) = feedback.remb_bitrate_kbps {
+        shared
+            .remb_bitrate_kbps
+            .store(u64::from(remb_bitrate_kbps), Ordering::Relaxed);
+    }
+    let Some(estimated_bitrate_kbps) = feedback.estimated_bitrate_kbps else {
+        return;
+    };
+    shared
+        .estimated_bitrate_kbps
+        .store(u64::from(estimated_bitrate_kbps), Ordering::Relaxed);
+    degradation_state.update_network_level(
+        estimated_bitrate_kbps,
+        rate_controller.target_bitrate_kbps,
+        shared,
+        tuning,
+    );
+    rate_controller.update(estimated_bitrate_kbps, encoder, source_id, shared);
+}
+
+fn spawn_audio_sender(
+    audio: NativeAudioConfig,
+    packetizer: &dyn RtpPacketizer,
+    config: &NativeSenderRuntimeConfig,
+    stop_signal: &Arc<std::sync::atomic::AtomicBool>,
+    shared: &Arc<NativeSenderSharedMetrics>,
+) -> Option<thread::JoinHandle<()>> {
+    let Some(sender) = packetizer.audio_sender(audio.payload_type, audio.ssrc) else {
+        shared.audio_capture_errors.fetch_add(1, Ordering::Relaxed);
+        eprintln!(
+            "[native-sender] event=audio_sender_unavailable source={} detail=no_rtp_transport",
+            config.source_id,
+        );
+        return None;
+    };
+
+    let source_id = config.source_id.clone();
+    let stop_signal = Arc::clone(stop_signal);
+    let shared = Arc::clone(shared);
+    thread::Builder::new()
+        .name("native-audio-sender".to_string())
+        .spawn(move || run_native_audio_sender(audio, sender, source_id, stop_signal, shared))
+        .inspect_err(|error| {
+            eprintln!("[native-sender] event=audio_sender_spawn_failed detail=\"{error}\"");
+        })
+        .ok()
+}
+
 pub fn run_native_sender_worker(
     config: NativeSenderRuntimeConfig,
     receiver: Receiver<NativeFramePacket>,
@@ -576,12 +828,7 @@ pub fn run_native_sender_worker(
         }
     };
     let codec = encoder.codec_descriptor();
-    let mut packetizer = match create_packetizer_for_codec(
-        codec.mime_type,
-        config.target_rtp.clone(),
-        config.payload_type,
-        config.ssrc,
-    ) {
+    let mut packetizer = match create_packetizer_for_codec(codec.mime_type, &config) {
         Ok(packetizer) => packetizer,
         Err(error) => {
             shared.encode_errors.fetch_add(1, Ordering::Relaxed);
@@ -595,6 +842,9 @@ pub fn run_native_sender_worker(
             return;
         }
     };
+    let audio_handle = config.audio.clone().and_then(|audio| {
+        spawn_audio_sender(audio, packetizer.as_ref(), &config, &stop_signal, &shared)
+    });
     let now_ms = unix_timestamp_ms();
     let degradation_tuning = DegradationTuning::from_env();
 
@@ -605,6 +855,17 @@ pub fn run_native_sender_worker(
     shared.set_encoder_backend_fallback_reason(encoder_selection.fallback_reason.as_deref());
     shared.set_recent_fallback_reason(None);
     shared.set_degradation_level(0);
+    shared
+        .srtp_enabled
+        .store(config.srtp.is_some(), Ordering::Relaxed);
+    shared.encoder_target_bitrate_kbps.store(
+        u64::from(
+            config
+                .target_bitrate_kbps
+                .unwrap_or(DEFAULT_TARGET_BITRATE_KBPS),
+        ),
+        Ordering::Relaxed,
+    );
     shared
         .transport_connected
         .store(packetizer.transport_connected(), Ordering::Relaxed);
@@ -613,7 +874,7 @@ pub fn run_native_sender_worker(
         .store(packetizer.transport_connected(), Ordering::Relaxed);
 
     eprintln!(
-        "[native-sender] event=sender_started source={} codec={} encoder_backend={} encoder_requested={} encoder_fallback_reason={} pt={} ssrc={} clock={} packetization={} profile={} target={} degrade_l1(avg={},peak={}) degrade_l2(avg={},peak={},scale={}) degrade_l3(avg={},peak={},scale={},bitrate={}/{}) recover(avg={},peak={})",
+        "[native-sender] event=sender_started source={} codec={} encoder_backend={} encoder_requested={} encoder_fallback_reason={} pt={} ssrc={} rtx={} congestion_control={} srtp={} audio={} clock={} packetization={} profile={} target={} degrade_l1(avg={},peak={}) degrade_l2(avg={},peak={},scale={}) degrade_l3(avg={},peak={},scale={},bitrate={}/{}) recover(avg={},peak={})",
         config.source_id,
         codec.mime_type,
         encoder_selection.selected_backend,
@@ -624,6 +885,27 @@ pub fn run_native_sender_worker(
             .unwrap_or("none"),
         config.payload_type,
         config.ssrc,
+        config
+            .rtx
+            .map(|rtx| format!("pt{}/ssrc{}", rtx.payload_type, rtx.ssrc))
+            .unwrap_or_else(|| "disabled".to_string()),
+        config
+            .congestion_control
+            .map(|cc| format!(
+                "twcc{}/abs{}",
+                cc.transport_cc_extension_id, cc.abs_send_time_extension_id
+            ))
+            .unwrap_or_else(|| "disabled".to_string()),
+        if config.srtp.is_some() {
+            "enabled"
+        } else {
+            "disabled"
+        },
+        config
+            .audio
+            .as_ref()
+            .map(|audio| format!("pt{}/ssrc{}", audio.payload_type, audio.ssrc))
+            .unwrap_or_else(|| "disabled".to_string()),
         codec.clock_rate,
         codec
             .packetization_mode
@@ -651,6 +933,7 @@ pub fn run_native_sender_worker(
     let mut resolution_cap_buffer = DownscaleBuffer::default();
     let mut fps_limiter = FpsLimiter::new(config.target_fps);
     let mut bitrate_limiter = BitrateLimiter::default();
+    let mut rate_controller = NetworkRateController::new(config.target_bitrate_kbps);
     let mut consecutive_encode_failures = 0u64;
     let mut active_encoder_backend = encoder_selection.selected_backend;
     let nvenc_runtime_fallback_encode_failures = env_u64(
@@ -665,18 +948,15 @@ pub fn run_native_sender_worker(
             Ok(packet) => {
                 shared.received_packets.fetch_add(1, Ordering::Relaxed);
 
-                let keyframe_feedback = packetizer.poll_feedback();
-                if keyframe_feedback.keyframe_requests > 0 {
-                    shared
-                        .keyframe_requests
-                        .fetch_add(keyframe_feedback.keyframe_requests, Ordering::Relaxed);
-                    if encoder.request_keyframe() {
-                        eprintln!(
-                            "[native-sender] event=keyframe_requested source={} requests={}",
-                            config.source_id, keyframe_feedback.keyframe_requests,
-                        );
-                    }
-                }
+                process_transport_feedback(
+                    packetizer.as_mut(),
+                    encoder.as_mut(),
+                    &config.source_id,
+                    &shared,
+                    &mut degradation_state,
+                    &mut rate_controller,
+                    &degradation_tuning,
+                );
 
                 if packet.source_id != config.source_id {
                     continue;
@@ -830,10 +1110,13 @@ pub fn run_native_sender_worker(
 
                         // Force at least degradation level 1 to halve frame rate,
                         // giving the software encoder breathing room.
-                        if degradation_state.level < 1 {
-                            degradation_state.level = 1;
-                            shared.set_degradation_level(1);
-                        }
+                        degradation_state.force_minimum_level(1, &shared);
+                        // The replacement encoder starts at the full target.
+                        rate_controller.encoder_bitrate_kbps = rate_controller.target_bitrate_kbps;
+                        shared.encoder_target_bitrate_kbps.store(
+                            u64::from(rate_controller.target_bitrate_kbps),
+                            Ordering::Relaxed,
+                        );
 
                         eprintln!(
                             "[native-sender] event=encoder_backend_runtime_fallback source={} from=nvenc to=openh264 reason=encode_failure_threshold threshold={} forced_degrade_level={}",
@@ -902,7 +1185,19 @@ pub fn run_native_sender_worker(
                     );
                 }
             }
-            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                // Static content can go a while without frames; NACKs and
+                // sender reports still need answering.
+                process_transport_feedback(
+                    packetizer.as_mut(),
+                    encoder.as_mut(),
+                    &config.source_id,
+                    &shared,
+                    &mut degradation_state,
+                    &mut rate_controller,
+                    &degradation_tuning,
+                );
+            }
             Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
                 shared.disconnected_events.fetch_add(1, Ordering::Relaxed);
                 break;
@@ -919,6 +1214,11 @@ pub fn run_native_sender_worker(
         }
     }
 
+    stop_signal.store(true, Ordering::Relaxed);
+    if let Some(handle) = audio_handle {
+        let _ = handle.join();
+    }
+
     shared.sender_stopped_events.fetch_add(1, Ordering::Relaxed);
     shared.producer_connected.store(false, Ordering::Relaxed);
     shared.transport_connected.store(false, Ordering::Relaxed);
@@ -1,10 +1,15 @@
 use super::rtp_sender::{FeedbackPollResult, NativeRtpSender};
+use super::srtp::{SrtpConfig, SrtpSession};
+
+const OPUS_CLOCK_RATE: u32 = 48_000;
 
 pub trait RtpPacketizer: Send {
     fn transport_connected(&self) -> bool;
     fn send_encoded_frames(&mut self, frames: &[Vec<u8>], timestamp_ms: u64) -> usize;
     fn poll_feedback(&mut self) -> FeedbackPollResult;
+    fn send_sender_report_if_due(&mut self) -> bool;
     fn take_and_reset_error_reason(&mut self) -> Option<String>;
+    fn audio_sender(&self, payload_type: u8, ssrc: u32) -> Option<NativeRtpSender>;
 }
 
 pub enum RtpCodecKind {
@@ -14,17 +19,55 @@ pub enum RtpCodecKind {
     Av1,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct RtxConfig {
+    pub payload_type: u8,
+    pub ssrc: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CongestionControlConfig {
+    pub transport_cc_extension_id: u8,
+    pub abs_send_time_extension_id: u8,
+}
+
 pub struct CodecRtpPacketizer {
     sender: NativeRtpSender,
     codec: RtpCodecKind,
 }
 
 impl CodecRtpPacketizer {
-    pub fn new(codec: RtpCodecKind, target: Option<String>, payload_type: u8, ssrc: u32) -> Self {
-        Self {
-            sender: NativeRtpSender::new(target, payload_type, ssrc),
-            codec,
+    pub fn new(
+        codec: RtpCodecKind,
+        target: Option<String>,
+        payload_type: u8,
+        ssrc: u32,
+        rtx: Option<RtxConfig>,
+        congestion_control: Option<CongestionControlConfig>,
+        max_bitrate_kbps: u32,
+    ) -> Self {
+        let sender = NativeRtpSender::new(target, payload_type, ssrc);
+        let sender = match rtx {
+            Some(rtx) => sender.with_rtx(rtx.payload_type, rtx.ssrc),
+            None => sender,
+        };
+        let sender = match congestion_control {
+            Some(config) => sender.with_congestion_control(
+                config.transport_cc_extension_id,
+                config.abs_send_time_extension_id,
+                max_bitrate_kbps,
+            ),
+            None => sender,
+        };
+
+        Self { sender, codec }
+    }
+
+    pub fn with_srtp(mut self, srtp: Option<&SrtpConfig>) -> Self {
+        if let Some(config) = srtp {
+            self.sender = self.sender.with_srtp(SrtpSession::new(config));
         }
+        self
     }
 }
 
@@ -46,6 +89,10 @@ impl RtpPacketizer for CodecRtpPacketizer {
         self.sender.poll_feedback()
     }
 
+    fn send_sender_report_if_due(&mut self) -> bool {
+        self.sender.send_sender_report_if_due()
+    }
+
     fn take_and_reset_error_reason(&mut self) -> Option<String> {
         if self.sender.take_and_reset_error() {
             return Some("udp_send_failed".to_string());
@@ -53,4 +100,9 @@ impl RtpPacketizer for CodecRtpPacketizer {
 
         None
     }
+
+    fn audio_sender(&self, payload_type: u8, ssrc: u32) -> Option<NativeRtpSender> {
+        self.sender
+            .for_additional_stream(payload_type, ssrc, OPUS_CLOCK_RATE)
+    }
 }
@@ -1,13 +1,152 @@
 use std::net::{SocketAddr, UdpSocket};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::capture::unix_timestamp_ms;
+
+use super::bandwidth_estimator::{BandwidthEstimator, PacketArrival};
+use super::srtp::SrtpSession;
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct FeedbackPollResult {
     pub keyframe_requests: u64,
+    pub nack_requests: u64,
+    pub retransmitted_packets: u64,
+    pub retransmit_misses: u64,
+    pub estimated_bitrate_kbps: Option<u32>,
+    pub remb_bitrate_kbps: Option<u32>,
+    pub srtcp_auth_failures: u64,
 }
 
+const RTCP_PACKET_TYPE_SR: u8 = 200;
+const RTCP_PACKET_TYPE_RR: u8 = 201;
+const RTCP_PACKET_TYPE_RTPFB: u8 = 205;
 const RTCP_PACKET_TYPE_PSFB: u8 = 206;
+const RTCP_FMT_GENERIC_NACK: u8 = 1;
+const RTCP_FMT_TRANSPORT_CC: u8 = 15;
 const RTCP_FMT_PLI: u8 = 1;
 const RTCP_FMT_FIR: u8 = 4;
+const RTCP_FMT_REMB: u8 = 15;
+const RTCP_REPORT_BLOCK_LEN: usize = 24;
+const RTP_HEADER_LEN: usize = 12;
+/// One-byte header extension block (RFC 8285) carrying the transport-wide
+/// sequence number and abs-send-time, padded to a whole word.
+const CONGESTION_EXTENSION_LEN: usize = 12;
+const TRANSPORT_SEQ_OFFSET: usize = RTP_HEADER_LEN + 5;
+const ABS_SEND_TIME_OFFSET: usize = RTP_HEADER_LEN + 8;
+const TRANSPORT_CC_REFERENCE_TIME_MS: f64 = 64.0;
+const TRANSPORT_CC_DELTA_MS: f64 = 0.25;
+/// Room for the SRTP authentication tag (and the SRTCP index).
+const SRTP_OVERHEAD_LEN: usize = 14;
+const VIDEO_CLOCK_RATE: u32 = 90_000;
+/// Sent packets kept for NACKs: about a second of 1080p60 screen content.
+const RETRANSMISSION_HISTORY_PACKETS: usize = 1024;
+const RETRANSMISSION_MAX_AGE_MS: u64 = 1_000;
+/// Receivers repeat NACKs until the packet shows up; ignore repeats that
+/// arrive before the previous retransmission could have.
+const RETRANSMISSION_MIN_INTERVAL_MS: u64 = 25;
+const SENDER_REPORT_INTERVAL_MS: u64 = 1_000;
+const NTP_UNIX_EPOCH_OFFSET_SECS: u64 = 2_208_988_800;
+
+#[derive(Debug, Clone, Copy)]
+struct RtxStream {
+    payload_type: u8,
+    ssrc: u32,
+    sequence_number: u16,
+}
+
+#[derive(Debug)]
+struct SentPacket {
+    sequence_number: u16,
+    sent_at_ms: u64,
+    last_resent_at_ms: Option<u64>,
+    packet: Vec<u8>,
+}
+
+/// Recently sent packets by sequence number, overwritten in send order.
+#[derive(Debug)]
+struct RetransmissionHistory {
+    slots: Vec<Option<SentPacket>>,
+}
+
+impl RetransmissionHistory {
+    fn new() -> Self {
+        Self {
+            slots: std::iter::repeat_with(|| None)
+                .take(RETRANSMISSION_HISTORY_PACKETS)
+                .collect(),
+        }
+    }
+
+    fn slot(sequence_number: u16) -> usize {
+        sequence_number as usize % RETRANSMISSION_HISTORY_PACKETS
+    }
+
+    fn record(&mut self, sequence_number: u16, packet: &[u8], now_ms: u64) {
+        self.slots[Self::slot(sequence_number)] = Some(SentPacket {
+            sequence_number,
+            sent_at_ms: now_ms,
+            last_resent_at_ms: None,
+            packet: packet.to_vec(),
+        });
+    }
+
+    fn get_mut(&mut self, sequence_number: u16) -> Option<&mut SentPacket> {
+        self.slots[Self::slot(sequence_number)]
+            .as_mut()
+            .filter(|sent| sent.sequence_number == sequence_number)
+    }
+}
+
+/// Transport-wide congestion control state: every media packet carries a
+/// transport-wide sequence number whose arrival the router reports back in
+/// transport-cc feedback, which drives the bandwidth estimate.
+#[derive(Debug)]
+struct CongestionControl {
+    transport_cc_extension_id: u8,
+    abs_send_time_extension_id: u8,
+    transport_sequence_number: u16,
+    estimator: BandwidthEstimator,
+}
+
+impl CongestionControl {
+    fn extension_block(&self) -> [u8; CONGESTION_EXTENSION_LEN] {
+        [
+            0xBE,
+            0xDE,
+            0x00,
+            0x02,
+            (self.transport_cc_extension_id << 4) | 1,
+            0,
+            0,
+            (self.abs_send_time_extension_id << 4) | 2,
+            0,
+            0,
+            0,
+            0,
+        ]
+    }
+
+    /// Gives a packet laid out by `extension_block` its own transport-wide
+    /// sequence number and send time.
+    fn stamp(&mut self, packet: &mut [u8], now_ms: u64) {
+        if packet.len() < ABS_SEND_TIME_OFFSET + 3 {
+            return;
+        }
+
+        let sequence = self.transport_sequence_number;
+        self.transport_sequence_number = self.transport_sequence_number.wrapping_add(1);
+        packet[TRANSPORT_SEQ_OFFSET..TRANSPORT_SEQ_OFFSET + 2]
+            .copy_from_slice(&sequence.to_be_bytes());
+
+        // 6.18 fixed-point seconds, wrapping every 64 seconds.
+        let abs_send_time = (((now_ms << 18) / 1_000) & 0x00FF_FFFF) as u32;
+        packet[ABS_SEND_TIME_OFFSET..ABS_SEND_TIME_OFFSET + 3]
+            .copy_from_slice(&abs_send_time.to_be_bytes()[1..]);
+
+        self.estimator
+            .on_packet_sent(sequence, now_ms as f64, packet.len());
+    }
+}
 
 #[derive(Debug)]
 pub struct NativeRtpSender {
@@ -17,8 +156,16 @@ pub struct NativeRtpSender {
     payload_type: u8,
     sequence_number: u16,
     ssrc: u32,
+    clock_rate: u32,
     mtu: usize,
     had_send_error: bool,
+    rtx: Option<RtxStream>,
+    history: Option<RetransmissionHistory>,
+    packets_sent: u32,
+    octets_sent: u32,
+    last_sender_report_ms: u64,
+    congestion_control: Option<CongestionControl>,
+    srtp: Option<SrtpSession>,
 }
 
 impl NativeRtpSender {
@@ -44,15 +191,109 @@ impl NativeRtpSender {
             payload_type,
             sequence_number: 1,
             ssrc,
+            clock_rate: VIDEO_CLOCK_RATE,
             mtu: 1200,
             had_send_error: false,
+            rtx: None,
+            history: Some(RetransmissionHistory::new()),
+            packets_sent: 0,
+            octets_sent: 0,
+            last_sender_report_ms: 0,
+            congestion_control: None,
+            srtp: None,
         }
     }
 
+    /// Answers NACKs on a separate RTX stream instead of resending the
+    /// original packets on the media SSRC.
+    pub fn with_rtx(mut self, payload_type: u8, ssrc: u32) -> Self {
+        self.rtx = Some(RtxStream {
+            payload_type,
+            ssrc,
+            sequence_number: 1,
+        });
+        self
+    }
+
+    /// Tags media packets with the transport-wide sequence number and
+    /// abs-send-time header extensions and estimates the available
+    /// bandwidth from transport-cc, REMB and receiver report feedback. The
+    /// estimate starts at, and never exceeds, `max_bitrate_kbps`.
+    pub fn with_congestion_control(
+        mut self,
+        transport_cc_extension_id: u8,
+        abs_send_time_extension_id: u8,
+        max_bitrate_kbps: u32,
+    ) -> Self {
+        let max_bitrate_bps = u64::from(max_bitrate_kbps) * 1_000;
+        self.mtu = self.mtu.saturating_sub(CONGESTION_EXTENSION_LEN);
+        self.congestion_control = Some(CongestionControl {
+            transport_cc_extension_id,
+            abs_send_time_extension_id,
+            transport_sequence_number: 1,
+            estimator: BandwidthEstimator::new(max_bitrate_bps, max_bitrate_bps),
+        });
+        self
+    }
+
+    /// Protects everything sent with SRTP/SRTCP and drops feedback that
+    /// fails authentication.
+    pub fn with_srtp(mut self, srtp: SrtpSession) -> Self {
+        self.mtu = self.mtu.saturating_sub(SRTP_OVERHEAD_LEN);
+        self.srtp = Some(srtp);
+        self
+    }
+
+    /// A sender for another RTP stream on the same socket, so the plain
+    /// transport (which learns its remote address from the first packet)
+    /// sees both streams coming from one tuple. Feedback is still read by
+    /// the original sender.
+    /// No retransmission history or congestion control is kept for it, since
+    /// only the original sender reads feedback.
+    pub fn for_additional_stream(
+        &self,
+        payload_type: u8,
+        ssrc: u32,
+        clock_rate: u32,
+    ) -> Option<Self> {
+        let socket = self.socket.as_ref()?.try_clone().ok()?;
+
+        Some(Self {
+            socket: Some(socket),
+            target: self.target,
+            diagnostics_mirror: None,
+            payload_type,
+            sequence_number: 1,
+            ssrc,
+            clock_rate,
+            mtu: self.mtu,
+            had_send_error: false,
+            rtx: None,
+            history: None,
+            packets_sent: 0,
+            octets_sent: 0,
+            last_sender_report_ms: 0,
+            congestion_control: None,
+            srtp: self.srtp.clone(),
+        })
+    }
+
     pub fn transport_connected(&self) -> bool {
         self.socket.is_some() && self.target.is_some()
     }
 
+    pub fn send_opus_packet(&mut self, packet: &[u8], rtp_timestamp: u32, marker: bool) -> usize {
+        if packet.is_empty() || packet.len() > self.mtu.saturating_sub(12) {
+            return 0;
+        }
+
+        let mut rtp_packet = Vec::with_capacity(12 + packet.len());
+        rtp_packet.extend_from_slice(&self.build_rtp_header(rtp_timestamp, marker));
+        rtp_packet.extend_from_slice(packet);
+        self.write_packet(&rtp_packet);
+        1
+    }
+
     pub fn send_h264_nalus(&mut self, nals: &[Vec<u8>], timestamp_ms: u64) -> usize {
         let mut sent = 0usize;
         let rtp_timestamp = timestamp_ms.wrapping_mul(90) as u32;
@@ -104,12 +345,37 @@ impl NativeRtpSender {
         };
 
         let mut requests = 0u64;
+        let mut nacked = Vec::new();
+        let mut arrivals = Vec::new();
+        let mut remb_bps = None;
+        let mut fraction_lost = None;
+        let mut srtcp_auth_failures = 0u64;
         let mut buffer = [0u8; 2048];
 
         loop {
             match socket.recv_from(&mut buffer) {
                 Ok((size, _)) => {
-                    requests = requests.saturating_add(parse_keyframe_requests(&buffer[..size]));
+                    let unprotected;
+                    let packet = match self.srtp.as_ref() {
+                        Some(srtp) => match srtp.unprotect_rtcp(&buffer[..size]) {
+                            Some(packet) => {
+                                unprotected = packet;
+                                unprotected.as_slice()
+                            }
+                            None => {
+                                srtcp_auth_failures += 1;
+                                continue;
+                            }
+                        },
+                        None => &buffer[..size],
+                    };
+                    requests = requests.saturating_add(parse_keyframe_requests(packet));
+                    parse_generic_nacks(packet, self.ssrc, &mut nacked);
+                    if self.congestion_control.is_some() {
+                        parse_transport_feedback(packet, &mut arrivals);
+                        remb_bps = parse_remb(packet).or(remb_bps);
+                        fraction_lost = parse_fraction_lost(packet, self.ssrc).or(fraction_lost);
+                    }
                 }
                 Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {
                     break;
@@ -124,9 +390,100 @@ impl NativeRtpSender {
             }
         }
 
-        FeedbackPollResult {
+        let mut result = FeedbackPollResult {
             keyframe_requests: requests,
+            nack_requests: nacked.len() as u64,
+            srtcp_auth_failures,
+            ..FeedbackPollResult::default()
+        };
+        self.retransmit(&nacked, &mut result);
+
+        if let Some(congestion_control) = self.congestion_control.as_mut() {
+            let estimator = &mut congestion_control.estimator;
+            if !arrivals.is_empty() {
+                estimator.on_transport_feedback(&arrivals, unix_timestamp_ms() as f64);
+            }
+            if let Some(remb_bps) = remb_bps {
+                estimator.on_remb(remb_bps);
+            }
+            if let Some(fraction_lost) = fraction_lost {
+                estimator.on_receiver_report(fraction_lost);
+            }
+            result.estimated_bitrate_kbps = Some((estimator.estimate_bps() / 1_000) as u32);
+            result.remb_bitrate_kbps = estimator
+                .remb_bps()
+                .map(|remb_bps| (remb_bps / 1_000) as u32);
+        }
+        result
+    }
+
+    fn retransmit(&mut self, nacked: &[u16], result: &mut FeedbackPollResult) {
+        let Some(mut history) = self.history.take() else {
+            return;
+        };
+        let now_ms = unix_timestamp_ms();
+
+        for sequence_number in nacked {
+            let Some(sent) = history
+                .get_mut(*sequence_number)
+                .filter(|sent| now_ms.saturating_sub(sent.sent_at_ms) <= RETRANSMISSION_MAX_AGE_MS)
+            else {
+                result.retransmit_misses += 1;
+                continue;
+            };
+
+            if sent.last_resent_at_ms.is_some_and(|resent_at| {
+                now_ms.saturating_sub(resent_at) < RETRANSMISSION_MIN_INTERVAL_MS
+            }) {
+                continue;
+            }
+            sent.last_resent_at_ms = Some(now_ms);
+
+            let mut packet = match self.rtx.as_mut() {
+                Some(rtx) => build_rtx_packet(&sent.packet, rtx),
+                None => sent.packet.clone(),
+            };
+            if let Some(congestion_control) = self.congestion_control.as_mut() {
+                congestion_control.stamp(&mut packet, now_ms);
+            }
+            self.transmit_rtp(&packet);
+            result.retransmitted_packets += 1;
+        }
+
+        self.history = Some(history);
+    }
+
+    /// Sends an RTCP sender report once per interval. Its RTP timestamp is
+    /// read off the same wall clock the media timestamps come from, which is
+    /// what lets receivers line up audio and video.
+    pub fn send_sender_report_if_due(&mut self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let now_ms = now.as_millis() as u64;
+        if self.packets_sent == 0
+            || now_ms.saturating_sub(self.last_sender_report_ms) < SENDER_REPORT_INTERVAL_MS
+        {
+            return false;
         }
+        self.last_sender_report_ms = now_ms;
+
+        let ntp_seconds = (now.as_secs() + NTP_UNIX_EPOCH_OFFSET_SECS) as u32;
+        let ntp_fraction = ((u64::from(now.subsec_nanos()) << 32) / 1_000_000_000) as u32;
+        let rtp_timestamp = now_ms.wrapping_mul(u64::from(self.clock_rate / 1000)) as u32;
+
+        let mut report = Vec::with_capacity(28);
+        report.push(0x80);
+        report.push(RTCP_PACKET_TYPE_SR);
+        report.extend_from_slice(&6u16.to_be_bytes());
+        report.extend_from_slice(&self.ssrc.to_be_bytes());
+        report.extend_from_slice(&ntp_seconds.to_be_bytes());
+        report.extend_from_slice(&ntp_fraction.to_be_bytes());
+        report.extend_from_slice(&rtp_timestamp.to_be_bytes());
+        report.extend_from_slice(&self.packets_sent.to_be_bytes());
+        report.extend_from_slice(&self.octets_sent.to_be_bytes());
+        self.transmit_rtcp(&report);
+        true
     }
 
     fn send_nal(&mut self, nal: &[u8], rtp_timestamp: u32, marker: bool) -> usize {
@@ -326,6 +683,59 @@ impl NativeRtpSender {
     }
 
     fn write_packet(&mut self, packet: &[u8]) {
+        if packet.len() < RTP_HEADER_LEN {
+            return;
+        }
+
+        let now_ms = unix_timestamp_ms();
+        let payload_len = packet.len() - RTP_HEADER_LEN;
+        let mut extended;
+        let packet = match self.congestion_control.as_mut() {
+            Some(congestion_control) => {
+                extended = Vec::with_capacity(packet.len() + CONGESTION_EXTENSION_LEN);
+                extended.push(packet[0] | 0x10);
+                extended.extend_from_slice(&packet[1..RTP_HEADER_LEN]);
+                extended.extend_from_slice(&congestion_control.extension_block());
+                extended.extend_from_slice(&packet[RTP_HEADER_LEN..]);
+                congestion_control.stamp(&mut extended, now_ms);
+                extended.as_slice()
+            }
+            None => packet,
+        };
+
+        if let Some(history) = self.history.as_mut() {
+            let sequence_number = u16::from_be_bytes([packet[2], packet[3]]);
+            history.record(sequence_number, packet, now_ms);
+        }
+        self.packets_sent = self.packets_sent.wrapping_add(1);
+        self.octets_sent = self.octets_sent.wrapping_add(payload_len as u32);
+
+        self.transmit_rtp(packet);
+    }
+
+    fn transmit_rtp(&mut self, packet: &[u8]) {
+        match self.srtp.as_mut() {
+            Some(srtp) => {
+                let protected = srtp.protect_rtp(packet, rtp_header_len(packet));
+                self.transmit(&protected, packet);
+            }
+            None => self.transmit(packet, packet),
+        }
+    }
+
+    fn transmit_rtcp(&mut self, packet: &[u8]) {
+        match self.srtp.as_mut() {
+            Some(srtp) => {
+                let protected = srtp.protect_rtcp(packet);
+                self.transmit(&protected, packet);
+            }
+            None => self.transmit(packet, packet),
+        }
+    }
+
+    /// Sends `wire` to the transport; the diagnostics mirror always gets
+    /// the unprotected `packet`, so captures stay readable with SRTP on.
+    fn transmit(&mut self, wire: &[u8], packet: &[u8]) {
         let Some(socket) = self.socket.as_ref() else {
             return;
         };
@@ -334,7 +744,7 @@ impl NativeRtpSender {
         };
 
         loop {
-            match socket.send_to(packet, target) {
+            match socket.send_to(wire, target) {
                 Ok(_) => break,
                 Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {
                     continue;
@@ -361,6 +771,37 @@ impl NativeRtpSender {
     }
 }
 
+/// RFC 4588 retransmission: the original header, extensions included,
+/// moved onto the RTX stream, with the original sequence number ahead of
+/// the payload.
+fn build_rtx_packet(original: &[u8], rtx: &mut RtxStream) -> Vec<u8> {
+    let sequence = rtx.sequence_number;
+    rtx.sequence_number = rtx.sequence_number.wrapping_add(1);
+    let header_len = rtp_header_len(original);
+
+    let mut packet = Vec::with_capacity(original.len() + 2);
+    packet.push(original[0]);
+    packet.push((original[1] & 0x80) | (rtx.payload_type & 0x7F));
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(&original[4..8]);
+    packet.extend_from_slice(&rtx.ssrc.to_be_bytes());
+    packet.extend_from_slice(&original[RTP_HEADER_LEN..header_len]);
+    packet.extend_from_slice(&original[2..4]);
+    packet.extend_from_slice(&original[header_len..]);
+    packet
+}
+
+/// Length of the fixed header plus any header extension block.
+fn rtp_header_len(packet: &[u8]) -> usize {
+    if packet[0] & 0x10 == 0 || packet.len() < RTP_HEADER_LEN + 4 {
+        return RTP_HEADER_LEN;
+    }
+
+    let extension_words =
+        u16::from_be_bytes([packet[RTP_HEADER_LEN + 2], packet[RTP_HEADER_LEN + 3]]) as usize;
+    (RTP_HEADER_LEN + 4 + extension_words * 4).min(packet.len())
+}
+
 fn leb128_len(mut value: usize) -> usize {
     let mut len = 1usize;
     while value >= 0x80 {
@@ -440,15 +881,19 @@ impl DiagnosticsMirrorTarget {
     }
 }
 
-fn parse_keyframe_requests(packet: &[u8]) -> u64 {
-    let mut requests = 0u64;
+/// Walks a compound RTCP packet, yielding `(fmt, packet_type, block)`.
+fn rtcp_blocks(packet: &[u8]) -> impl Iterator<Item = (u8, u8, &[u8])> {
     let mut offset = 0usize;
 
-    while offset + 4 <= packet.len() {
+    std::iter::from_fn(move || {
+        if offset + 4 > packet.len() {
+            return None;
+        }
+
         let first = packet[offset];
         let version = first >> 6;
         if version != 2 {
-            break;
+            return None;
         }
 
         let fmt = first & 0x1F;
@@ -457,21 +902,170 @@ fn parse_keyframe_requests(packet: &[u8]) -> u64 {
         let block_len = words_minus_one.saturating_add(1).saturating_mul(4);
 
         if block_len == 0 || offset + block_len > packet.len() {
-            break;
+            return None;
         }
 
+        let block = &packet[offset..offset + block_len];
+        offset = offset.saturating_add(block_len);
+        Some((fmt, packet_type, block))
+    })
+}
+
+fn parse_keyframe_requests(packet: &[u8]) -> u64 {
+    let mut requests = 0u64;
+
+    for (fmt, packet_type, block) in rtcp_blocks(packet) {
         if packet_type == RTCP_PACKET_TYPE_PSFB {
             if fmt == RTCP_FMT_PLI {
                 requests = requests.saturating_add(1);
             } else if fmt == RTCP_FMT_FIR {
-                let fir_entries = block_len.saturating_sub(12) / 8;
+                let fir_entries = block.len().saturating_sub(12) / 8;
                 let fir_count = fir_entries.max(1) as u64;
                 requests = requests.saturating_add(fir_count);
             }
         }
-
-        offset = offset.saturating_add(block_len);
     }
 
     requests
 }
+
+/// Collects the sequence numbers of Generic NACKs (RFC 4585) for `ssrc`.
+fn parse_generic_nacks(packet: &[u8], ssrc: u32, nacked: &mut Vec<u16>) {
+    for (fmt, packet_type, block) in rtcp_blocks(packet) {
+        if packet_type != RTCP_PACKET_TYPE_RTPFB || fmt != RTCP_FMT_GENERIC_NACK {
+            continue;
+        }
+        if block.len() < 12 || block[8..12] != ssrc.to_be_bytes() {
+            continue;
+        }
+
+        for entry in block[12..].chunks_exact(4) {
+            let packet_id = u16::from_be_bytes([entry[0], entry[1]]);
+            let lost_bitmask = u16::from_be_bytes([entry[2], entry[3]]);
+            nacked.push(packet_id);
+            for bit in 0..16u16 {
+                if lost_bitmask & (1 << bit) != 0 {
+                    nacked.push(packet_id.wrapping_add(bit + 1));
+                }
+            }
+        }
+    }
+}
+
+/// Reads transport-cc feedback (draft-holmer-rmcat-transport-wide-cc-
+/// extensions-01) into per-packet arrival times on the receiver's clock.
+fn parse_transport_feedback(packet: &[u8], arrivals: &mut Vec<PacketArrival>) {
+    for (fmt, packet_type, block) in rtcp_blocks(packet) {
+        if packet_type != RTCP_PACKET_TYPE_RTPFB || fmt != RTCP_FMT_TRANSPORT_CC {
+            continue;
+        }
+        if block.len() < 20 {
+            continue;
+        }
+
+        let base_sequence = u16::from_be_bytes([block[12], block[13]]);
+        let status_count = u16::from_be_bytes([block[14], block[15]]) as usize;
+        // 24-bit signed reference time in multiples of 64ms.
+        let reference_time = (i32::from_be_bytes([block[16], block[17], block[18], 0]) >> 8) as f64;
+
+        let mut statuses = Vec::with_capacity(status_count);
+        let mut offset = 20usize;
+        while statuses.len() < status_count && offset + 2 <= block.len() {
+            let chunk = u16::from_be_bytes([block[offset], block[offset + 1]]);
+            offset += 2;
+            let remaining = status_count - statuses.len();
+
+            if chunk & 0x8000 == 0 {
+                let status = ((chunk >> 13) & 0x03) as u8;
+                let run_length = (chunk & 0x1FFF) as usize;
+                statuses.extend(std::iter::repeat(status).take(run_length.min(remaining)));
+            } else if chunk & 0x4000 == 0 {
+                statuses.extend(
+                    (0..14)
+                        .map(|bit| ((chunk >> (13 - bit)) & 0x01) as u8)
+                        .take(remaining),
+                );
+            } else {
+                statuses.extend(
+                    (0..7)
+                        .map(|symbol| ((chunk >> (12 - symbol * 2)) & 0x03) as u8)
+                        .take(remaining),
+                );
+            }
+        }
+
+        let mut arrival_time_ms = reference_time * TRANSPORT_CC_REFERENCE_TIME_MS;
+        for (index, status) in statuses.into_iter().enumerate() {
+            let transport_seq = base_sequence.wrapping_add(index as u16);
+            let delta = match status {
+                1 if offset < block.len() => {
+                    offset += 1;
+                    Some(f64::from(block[offset - 1]))
+                }
+                2 if offset + 2 <= block.len() => {
+                    offset += 2;
+                    Some(f64::from(i16::from_be_bytes([
+                        block[offset - 2],
+                        block[offset - 1],
+                    ])))
+                }
+                _ => None,
+            };
+
+            arrivals.push(PacketArrival {
+                transport_seq,
+                arrival_time_ms: delta.map(|delta| {
+                    arrival_time_ms += delta * TRANSPORT_CC_DELTA_MS;
+                    arrival_time_ms
+                }),
+            });
+        }
+    }
+}
+
+/// Reads the bitrate of a Receiver Estimated Maximum Bitrate message
+/// (draft-alvestrand-rmcat-remb), in bits per second.
+fn parse_remb(packet: &[u8]) -> Option<u64> {
+    rtcp_blocks(packet)
+        .filter(|(fmt, packet_type, block)| {
+            *packet_type == RTCP_PACKET_TYPE_PSFB
+                && *fmt == RTCP_FMT_REMB
+                && block.len() >= 20
+                && &block[12..16] == b"REMB"
+        })
+        .map(|(_, _, block)| {
+            let exponent = u32::from(block[17] >> 2);
+            let mantissa = (u64::from(block[17] & 0x03) << 16)
+                | (u64::from(block[18]) << 8)
+                | u64::from(block[19]);
+            mantissa.checked_shl(exponent).unwrap_or(u64::MAX)
+        })
+        .last()
+}
+
+/// Reads the fraction lost reported for `ssrc` from sender or receiver
+/// report blocks.
+fn parse_fraction_lost(packet: &[u8], ssrc: u32) -> Option<u8> {
+    let mut fraction_lost = None;
+
+    for (report_count, packet_type, block) in rtcp_blocks(packet) {
+        let reports_offset = match packet_type {
+            RTCP_PACKET_TYPE_SR => 28,
+            RTCP_PACKET_TYPE_RR => 8,
+            _ => continue,
+        };
+
+        for report in block
+            .get(reports_offset..)
+            .unwrap_or_default()
+            .chunks_exact(RTCP_REPORT_BLOCK_LEN)
+            .take(report_count as usize)
+        {
+            if report[..4] == ssrc.to_be_bytes() {
+                fraction_lost = Some(report[4]);
+            }
+        }
+    }
+
+    fraction_lost
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+
+use aes::cipher::{KeyIvInit, StreamCipher};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+type Aes128Ctr = ctr::Ctr128BE<aes::Aes128>;
+type HmacSha1 = Hmac<Sha1>;
+
+pub const SRTP_CRYPTO_SUITE: &str = "AES_CM_128_HMAC_SHA1_80";
+const MASTER_KEY_LEN: usize = 16;
+const MASTER_SALT_LEN: usize = 14;
+const AUTH_KEY_LEN: usize = 20;
+const AUTH_TAG_LEN: usize = 10;
+const SRTCP_INDEX_LEN: usize = 4;
+const SRTCP_ENCRYPTED_FLAG: u32 = 0x8000_0000;
+const RTCP_HEADER_LEN: usize = 8;
+const LABEL_RTP_ENCRYPTION: u8 = 0;
+const LABEL_RTP_AUTH: u8 = 1;
+const LABEL_RTP_SALT: u8 = 2;
+const LABEL_RTCP_ENCRYPTION: u8 = 3;
+const LABEL_RTCP_AUTH: u8 = 4;
+const LABEL_RTCP_SALT: u8 = 5;
+
+/// Master keys for both directions of a native sender session, as handed
+/// out by the server over the media signal.
+#[derive(Clone)]
+pub struct SrtpConfig {
+    local_master_key: Vec<u8>,
+    remote_master_key: Vec<u8>,
+}
+
+impl SrtpConfig {
+    pub fn from_base64(
+        crypto_suite: &str,
+        key_base64: &str,
+        remote_key_base64: &str,
+    ) -> Result<Self, String> {
+        if crypto_suite != SRTP_CRYPTO_SUITE {
+            return Err(format!(
+                "Unsupported SRTP crypto suite {crypto_suite}. Expected {SRTP_CRYPTO_SUITE}."
+            ));
+        }
+
+        let decode = |value: &str| {
+            BASE64
+                .decode(value.trim())
+                .ok()
+                .filter(|key| key.len() == MASTER_KEY_LEN + MASTER_SALT_LEN)
+                .ok_or_else(|| {
+                    "Invalid SRTP key. Expected a base64 master key and salt of 30 bytes."
+                        .to_string()
+                })
+        };
+
+        Ok(Self {
+            local_master_key: decode(key_base64)?,
+            remote_master_key: decode(remote_key_base64)?,
+        })
+    }
+}
+
+impl std::fmt::Debug for SrtpConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SrtpConfig").finish_non_exhaustive()
+    }
+}
+
+/// Session keys derived from one master key (RFC 3711 section 4.3).
+#[derive(Clone)]
+struct SessionKeys {
+    rtp_encryption: [u8; MASTER_KEY_LEN],
+    rtp_auth: [u8; AUTH_KEY_LEN],
+    rtp_salt: [u8; MASTER_SALT_LEN],
+    rtcp_encryption: [u8; MASTER_KEY_LEN],
+    rtcp_auth: [u8; AUTH_KEY_LEN],
+    rtcp_salt: [u8; MASTER_SALT_LEN],
+}
+
+impl SessionKeys {
+    fn derive(master: &[u8]) -> Self {
+        let (master_key, master_salt) = master.split_at(MASTER_KEY_LEN);
+        let mut keys = Self {
+            rtp_encryption: [0; MASTER_KEY_LEN],
+            rtp_auth: [0; AUTH_KEY_LEN],
+            rtp_salt: [0; MASTER_SALT_LEN],
+            rtcp_encryption: [0; MASTER_KEY_LEN],
+            rtcp_auth: [0; AUTH_KEY_LEN],
+            rtcp_salt: [0; MASTER_SALT_LEN],
+        };
+
+        let derive = |label, output: &mut [u8]| derive_key(master_key, master_salt, label, output);
+        derive(LABEL_RTP_ENCRYPTION, &mut keys.rtp_encryption);
+        derive(LABEL_RTP_AUTH, &mut keys.rtp_auth);
+        derive(LABEL_RTP_SALT, &mut keys.rtp_salt);
+        derive(LABEL_RTCP_ENCRYPTION, &mut keys.rtcp_encryption);
+        derive(LABEL_RTCP_AUTH, &mut keys.rtcp_auth);
+        derive(LABEL_RTCP_SALT, &mut keys.rtcp_salt);
+        keys
+    }
+}
+
+/// AES-CM key derivation with a key derivation rate of zero: the label
+/// goes into the salt ahead of an all-zero packet index.
+fn derive_key(master_key: &[u8], master_salt: &[u8], label: u8, output: &mut [u8]) {
+    let mut iv = [0u8; 16];
+    iv[..MASTER_SALT_LEN].copy_from_slice(master_salt);
+    iv[7] ^= label;
+
+    output.fill(0);
+    Aes128Ctr::new(master_key.into(), &iv.into()).apply_keystream(output);
+}
+
+fn counter_iv(salt: &[u8; MASTER_SALT_LEN], ssrc: u32, index: u64) -> [u8; 16] {
+    let mut iv = [0u8; 16];
+    iv[..MASTER_SALT_LEN].copy_from_slice(salt);
+    for (byte, ssrc_byte) in iv[4..8].iter_mut().zip(ssrc.to_be_bytes()) {
+        *byte ^= ssrc_byte;
+    }
+    for (byte, index_byte) in iv[8..14].iter_mut().zip(&index.to_be_bytes()[2..]) {
+        *byte ^= index_byte;
+    }
+    iv
+}
+
+fn auth_tag(auth_key: &[u8], parts: &[&[u8]]) -> [u8; AUTH_TAG_LEN] {
+    let mut mac = HmacSha1::new_from_slice(auth_key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+
+    let mut tag = [0u8; AUTH_TAG_LEN];
+    tag.copy_from_slice(&mac.finalize().into_bytes()[..AUTH_TAG_LEN]);
+    tag
+}
+
+/// Tracks the rollover counter of one outgoing SSRC, so retransmissions of
+/// packets sent before a sequence number wrap keep their original index.
+#[derive(Debug, Clone, Copy, Default)]
+struct RolloverCounter {
+    roc: u32,
+    highest_sequence: Option<u16>,
+}
+
+impl RolloverCounter {
+    fn index_for(&mut self, sequence: u16) -> u64 {
+        let Some(highest) = self.highest_sequence else {
+            self.highest_sequence = Some(sequence);
+            return u64::from(sequence);
+        };
+
+        let roc = if sequence.wrapping_sub(highest) < 0x8000 {
+            if sequence < highest {
+                self.roc = self.roc.wrapping_add(1);
+            }
+            self.highest_sequence = Some(sequence);
+            self.roc
+        } else if sequence > highest {
+            self.roc.wrapping_sub(1)
+        } else {
+            self.roc
+        };
+
+        (u64::from(roc) << 16) | u64::from(sequence)
+    }
+}
+
+/// AES_CM_128_HMAC_SHA1_80 protection for the native sender: outgoing RTP
+/// and RTCP with the local key, incoming RTCP feedback with the router's.
+#[derive(Clone)]
+pub struct SrtpSession {
+    outbound: SessionKeys,
+    inbound: SessionKeys,
+    rollover: HashMap<u32, RolloverCounter>,
+    srtcp_index: u32,
+}
+
+impl SrtpSession {
+    pub fn new(config: &SrtpConfig) -> Self {
+        Self {
+            outbound: SessionKeys::derive(&config.local_master_key),
+            inbound: SessionKeys::derive(&config.remote_master_key),
+            rollover: HashMap::new(),
+            srtcp_index: 0,
+        }
+    }
+
+    /// Encrypts the payload after `header_len` (header extensions stay in
+    /// the clear for the router) and appends the authentication tag.
+    pub fn protect_rtp(&mut self, packet: &[u8], header_len: usize) -> Vec<u8> {
+        let ssrc = u32::from_be_bytes([packet[8], packet[9], packet[10], packet[11]]);
+        let sequence = u16::from_be_bytes([packet[2], packet[3]]);
+        let index = self.rollover.entry(ssrc).or_default().index_for(sequence);
+
+        let mut protected = Vec::with_capacity(packet.len() + AUTH_TAG_LEN);
+        protected.extend_from_slice(packet);
+        let iv = counter_iv(&self.outbound.rtp_salt, ssrc, index);
+        Aes128Ctr::new(&self.outbound.rtp_encryption.into(), &iv.into())
+            .apply_keystream(&mut protected[header_len..]);
+
+        let roc = ((index >> 16) as u32).to_be_bytes();
+        let tag = auth_tag(&self.outbound.rtp_auth, &[&protected, &roc]);
+        protected.extend_from_slice(&tag);
+        protected
+    }
+
+    /// Encrypts everything after the first header and sender SSRC, then
+    /// appends the SRTCP index and authentication tag.
+    pub fn protect_rtcp(&mut self, packet: &[u8]) -> Vec<u8> {
+        let ssrc = u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]);
+        let index = self.srtcp_index;
+        self.srtcp_index = (self.srtcp_index + 1) & !SRTCP_ENCRYPTED_FLAG;
+
+        let mut protected = Vec::with_capacity(packet.len() + SRTCP_INDEX_LEN + AUTH_TAG_LEN);
+        protected.extend_from_slice(packet);
+        let iv = counter_iv(&self.outbound.rtcp_salt, ssrc, u64::from(index));
+        Aes128Ctr::new(&self.outbound.rtcp_encryption.into(), &iv.into())
+            .apply_keystream(&mut protected[RTCP_HEADER_LEN..]);
+
+        protected.extend_from_slice(&(SRTCP_ENCRYPTED_FLAG | index).to_be_bytes());
+        let tag = auth_tag(&self.outbound.rtcp_auth, &[&protected]);
+        protected.extend_from_slice(&tag);
+        protected
+    }
+
+    /// Checks and decrypts SRTCP from the router. Returns `None` for
+    /// anything that fails authentication.
+    pub fn unprotect_rtcp(&self, packet: &[u8]) -> Option<Vec<u8>> {
+        if packet.len() < RTCP_HEADER_LEN + SRTCP_INDEX_LEN + AUTH_TAG_LEN {
+            return None;
+        }
+
+        let tag_start = packet.len() - AUTH_TAG_LEN;
+        let mut mac = HmacSha1::new_from_slice(&self.inbound.rtcp_auth)
+            .expect("HMAC accepts keys of any length");
+        mac.update(&packet[..tag_start]);
+        mac.verify_truncated_left(&packet[tag_start..]).ok()?;
+
+        let index_start = tag_start - SRTCP_INDEX_LEN;
+        let e_and_index = u32::from_be_bytes([
+            packet[index_start],
+            packet[index_start + 1],
+            packet[index_start + 2],
+            packet[index_start + 3],
+        ]);
+        let mut rtcp = packet[..index_start].to_vec();
+        if e_and_index & SRTCP_ENCRYPTED_FLAG != 0 {
+            let ssrc = u32::from_be_bytes([rtcp[4], rtcp[5], rtcp[6], rtcp[7]]);
+            let index = u64::from(e_and_index & !SRTCP_ENCRYPTED_FLAG);
+            let iv = counter_iv(&self.inbound.rtcp_salt, ssrc, index);
+            Aes128Ctr::new(&self.inbound.rtcp_encryption.into(), &iv.into())
+                .apply_keystream(&mut rtcp[RTCP_HEADER_LEN..]);
+        }
+
+        Some(rtcp)
+    }
+}
+
+impl std::fmt::Debug for SrtpSession {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SrtpSession")
+            .field("streams", &self.rollover.len())
+            .field("srtcp_index", &self.srtcp_index)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCAL_KEY: &str = "+gib0WJiJj7d6jU0C5QsB+avk/TOoHXAWv84G3Zu";
+    const REMOTE_KEY: &str = "pFsumLzRTLScCT8ttS2qIt9Ixv5gCXISKF7uO47k";
+
+    fn hex(value: &str) -> Vec<u8> {
+        (0..value.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&value[index..index + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// RFC 3711 appendix B.2: AES-CM keystream for a session key and salt,
+    /// including the blocks around the 16-bit counter carry.
+    #[test]
+    fn aes_cm_keystream_matches_rfc_3711() {
+        let key = hex("2B7E151628AED2A6ABF7158809CF4F3C");
+        let salt: [u8; MASTER_SALT_LEN] = hex("F0F1F2F3F4F5F6F7F8F9FAFBFCFD").try_into().unwrap();
+
+        let mut keystream = [0u8; 48];
+        let iv = counter_iv(&salt, 0, 0);
+        Aes128Ctr::new(key.as_slice().into(), &iv.into()).apply_keystream(&mut keystream);
+        assert_eq!(
+            keystream.to_vec(),
+            hex(concat!(
+                "E03EAD0935C95E80E166B16DD92B4EB4",
+                "D23513162B02D0F72A43A2FE4A5F97AB",
+                "41E95B3BB0A2E8DD477901E4FCA894C0",
+            ))
+        );
+
+        let mut iv = counter_iv(&salt, 0, 0);
+        iv[14..].copy_from_slice(&[0xFE, 0xFF]);
+        let mut keystream = [0u8; 48];
+        Aes128Ctr::new(key.as_slice().into(), &iv.into()).apply_keystream(&mut keystream);
+        assert_eq!(
+            keystream.to_vec(),
+            hex(concat!(
+                "EC8CDF7398607CB0F2D21675EA9EA1E4",
+                "362B7C3C6773516318A077D7FC5073AE",
+                "6A2CC3787889374FBEB4C81B17BA6C44",
+            ))
+        );
+    }
+
+    /// RFC 3711 appendix B.3: session key, salt and auth key derivation.
+    #[test]
+    fn key_derivation_matches_rfc_3711() {
+        let mut master = hex("E1F97A0D3E018BE0D64FA32C06DE4139");
+        master.extend(hex("0EC675AD498AFEEBB6960B3AABE6"));
+        let keys = SessionKeys::derive(&master);
+
+        assert_eq!(
+            keys.rtp_encryption.to_vec(),
+            hex("C61E7A93744F39EE10734AFE3FF7A087")
+        );
+        assert_eq!(keys.rtp_salt.to_vec(), hex("30CBBC08863D8C85D49DB34A9AE1"));
+        assert_eq!(
+            keys.rtp_auth.to_vec(),
+            hex("CEBE321F6FF7716B6FD4AB49AF256A156D38BAA4")
+        );
+    }
+
+    /// The router's session uses the same pair of keys with the directions
+    /// swapped, so each side must accept what the other protects.
+    #[test]
+    fn round_trips_with_server_keys() {
+        let client_config =
+            SrtpConfig::from_base64(SRTP_CRYPTO_SUITE, LOCAL_KEY, REMOTE_KEY).unwrap();
+        let router_config =
+            SrtpConfig::from_base64(SRTP_CRYPTO_SUITE, REMOTE_KEY, LOCAL_KEY).unwrap();
+        let mut client = SrtpSession::new(&client_config);
+        let mut router = SrtpSession::new(&router_config);
+
+        // Receiver report from the router to the native sender.
+        let rtcp = hex("81C90007DEADBEEF123456780000000000000001000000000000000000000000");
+        let mut protected = router.protect_rtcp(&rtcp);
+        assert_eq!(client.unprotect_rtcp(&protected), Some(rtcp.clone()));
+
+        let last = protected.len() - 1;
+        protected[last] ^= 1;
+        assert_eq!(client.unprotect_rtcp(&protected), None);
+
+        let rtp = hex("80600001000000640000ABCD0102030405060708");
+        let protected = client.protect_rtp(&rtp, 12);
+        let (body, tag) = protected.split_at(protected.len() - AUTH_TAG_LEN);
+        assert_eq!(
+            auth_tag(&router.inbound.rtp_auth, &[body, &0u32.to_be_bytes()]).to_vec(),
+            tag
+        );
+
+        let mut payload = body.to_vec();
+        let iv = counter_iv(&router.inbound.rtp_salt, 0xABCD, 1);
+        Aes128Ctr::new(&router.inbound.rtp_encryption.into(), &iv.into())
+            .apply_keystream(&mut payload[12..]);
+        assert_eq!(payload, rtp);
+    }
+}
@@ -10,6 +10,7 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             capture::service::list_native_capture_sources,
             capture::service::native_codec_capabilities,
+            capture::service::native_audio_capture_supported,
             capture::service::start_native_capture,
             capture::service::stop_native_capture,
             capture::service::native_capture_status
@@ -0,0 +1,202 @@
+import type { DataProducer } from "mediasoup-client/types";
+import { requestMediaSignal } from "./signaling";
+import {
+  callDataSubscribers,
+  dataProducerUsernameById,
+  device,
+  initializedForChannelId,
+  localDataProducers,
+  queuedDataProducerAnnouncements,
+  recvTransport,
+  remoteDataConsumers,
+  sendTransport,
+} from "./state";
+import type { CallDataMessage } from "./types";
+
+export interface CallDataChannelOptions {
+  ordered?: boolean;
+  maxRetransmits?: number;
+  maxPacketLifeTime?: number;
+}
+
+/**
+ * Opens (or reuses) a data channel with the given label on the current
+ * call. Other participants receive its messages through `subscribeCallData`.
+ * Reactions and pointer updates should pass `ordered: false` with a small
+ * `maxRetransmits` so stale messages are dropped instead of queued.
+ */
+export async function openCallDataChannel(
+  label: string,
+  options: CallDataChannelOptions = {},
+): Promise<DataProducer> {
+  const existing = localDataProducers.get(label);
+  if (existing && !existing.closed) {
+    return existing;
+  }
+
+  if (!sendTransport || !initializedForChannelId) {
+    throw new Error("Join a voice channel before opening a data channel");
+  }
+
+  const producer = await sendTransport.produceData({
+    label,
+    ordered: options.ordered ?? true,
+    maxRetransmits: options.maxRetransmits,
+    maxPacketLifeTime: options.maxPacketLifeTime,
+  });
+
+  producer.on("transportclose", () => {
+    if (localDataProducers.get(label) === producer) {
+      localDataProducers.delete(label);
+    }
+  });
+  localDataProducers.set(label, producer);
+  return producer;
+}
+
+/** Sends on an open call data channel. Returns false if it is not open. */
+export function sendCallData(label: string, data: string | ArrayBuffer): boolean {
+  const producer = localDataProducers.get(label);
+  if (!producer || producer.closed || producer.readyState !== "open") {
+    return false;
+  }
+
+  producer.send(data);
+  return true;
+}
+
+export function closeCallDataChannel(label: string) {
+  const producer = localDataProducers.get(label);
+  if (!producer) {
+    return;
+  }
+
+  localDataProducers.delete(label);
+  producer.close();
+
+  const channelId = initializedForChannelId;
+  if (!channelId) {
+    return;
+  }
+
+  requestMediaSignal(channelId, "data_close_producer", {
+    data_producer_id: producer.id,
+  }).catch((error) => {
+    console.warn("[media] Failed to close data producer", error);
+  });
+}
+
+export function subscribeCallData(subscriber: (message: CallDataMessage) => void): () => void {
+  callDataSubscribers.add(subscriber);
+
+  return () => {
+    callDataSubscribers.delete(subscriber);
+  };
+}
+
+function notifyCallDataSubscribers(message: CallDataMessage) {
+  for (const subscriber of callDataSubscribers) {
+    subscriber(message);
+  }
+}
+
+async function consumeRemoteDataProducer(channelId: string, dataProducerId: string) {
+  if (!recvTransport) {
+    throw new Error("Recv transport is not ready");
+  }
+
+  const response = await requestMediaSignal(channelId, "data_consume", {
+    data_producer_id: dataProducerId,
+  });
+  const description = response.data_consumer;
+  if (response.action !== "data_consumer_created" || !description?.sctp_stream_parameters) {
+    throw new Error("Unexpected data_consume response from server");
+  }
+
+  if (!recvTransport || initializedForChannelId !== channelId) {
+    return;
+  }
+
+  const dataConsumer = await recvTransport.consumeData({
+    id: description.id,
+    dataProducerId: description.data_producer_id,
+    sctpStreamParameters: description.sctp_stream_parameters,
+    label: description.label,
+    protocol: description.protocol,
+  });
+  dataConsumer.binaryType = "arraybuffer";
+
+  dataConsumer.on("message", (data: string | ArrayBuffer) => {
+    notifyCallDataSubscribers({
+      label: description.label,
+      username: dataProducerUsernameById.get(dataProducerId),
+      data,
+    });
+  });
+  dataConsumer.on("transportclose", () => {
+    remoteDataConsumers.delete(dataProducerId);
+  });
+
+  remoteDataConsumers.set(dataProducerId, dataConsumer);
+}
+
+export function queueOrConsumeDataProducer(
+  channelId: string,
+  dataProducerId: string,
+  label: string,
+  username?: string,
+) {
+  if (username) {
+    dataProducerUsernameById.set(dataProducerId, username);
+  }
+
+  if (remoteDataConsumers.has(dataProducerId)) {
+    return;
+  }
+
+  if (!device || !recvTransport || initializedForChannelId !== channelId) {
+    queuedDataProducerAnnouncements.set(dataProducerId, { label, username });
+    return;
+  }
+
+  void consumeRemoteDataProducer(channelId, dataProducerId).catch((error) => {
+    console.warn("[media] Failed to consume data producer", error);
+  });
+}
+
+export function flushQueuedDataProducerAnnouncements(channelId: string) {
+  const queued = Array.from(queuedDataProducerAnnouncements.entries());
+  queuedDataProducerAnnouncements.clear();
+
+  for (const [dataProducerId, announcement] of queued) {
+    queueOrConsumeDataProducer(channelId, dataProducerId, announcement.label, announcement.username);
+  }
+}
+
+export function disposeRemoteDataConsumer(dataProducerId: string) {
+  queuedDataProducerAnnouncements.delete(dataProducerId);
+  dataProducerUsernameById.delete(dataProducerId);
+
+  const dataConsumer = remoteDataConsumers.get(dataProducerId);
+  if (!dataConsumer) {
+    return;
+  }
+
+  remoteDataConsumers.delete(dataProducerId);
+  dataConsumer.close();
+}
+
+/** Drops all data channel state; the transports are being closed. */
+export function closeCallDataChannels() {
+  for (const producer of localDataProducers.values()) {
+    producer.close();
+  }
+  localDataProducers.clear();
+
+  for (const dataConsumer of remoteDataConsumers.values()) {
+    dataConsumer.close();
+  }
+  remoteDataConsumers.clear();
+  dataProducerUsernameById.clear();
+  queuedDataProducerAnnouncements.clear();
+}
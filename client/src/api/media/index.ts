@@ -1,6 +1,7 @@
 // Re-export public types
 export type {
   AudioDeviceInventory,
+  CallDataMessage,
   AudioDeviceOption,
   CameraActionResult,
   CameraDeviceOption,
@@ -37,6 +38,14 @@ export {
   stopLocalScreenProducer,
 } from "./producers";
 
+// Re-export call data channel functions
+export {
+  closeCallDataChannel,
+  openCallDataChannel,
+  sendCallData,
+  subscribeCallData,
+} from "./dataChannels";
+
 // Re-export consumer functions
 export { retryAudioPlayback } from "./consumers";
 
@@ -50,6 +59,9 @@ export {
   subscribeVideoTiles,
 } from "./subscriptions";
 
+// Re-export signaling functions
+export { requestMediaStats } from "./signaling";
+
 // Re-export transport functions
 export {
   cleanupMediaTransports,
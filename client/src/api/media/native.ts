@@ -59,6 +59,10 @@ export async function armNativeCapture(
   rtpTarget: string,
   payloadType: number,
   ssrc: number,
+  rtx?: { payloadType: number; ssrc: number },
+  audio?: { payloadType: number; ssrc: number },
+  congestionControl?: { transportCcExtensionId: number; absSendTimeExtensionId: number },
+  srtp?: { cryptoSuite: string; keyBase64: string; remoteKeyBase64: string },
 ): Promise<void> {
   await startNativeCapture({
     source_id: options.sourceId!,
@@ -69,6 +73,15 @@ export async function armNativeCapture(
     rtp_target: rtpTarget,
     payload_type: payloadType,
     ssrc,
+    rtx_payload_type: rtx?.payloadType,
+    rtx_ssrc: rtx?.ssrc,
+    audio_payload_type: audio?.payloadType,
+    audio_ssrc: audio?.ssrc,
+    transport_cc_extension_id: congestionControl?.transportCcExtensionId,
+    abs_send_time_extension_id: congestionControl?.absSendTimeExtensionId,
+    srtp_crypto_suite: srtp?.cryptoSuite,
+    srtp_key_base64: srtp?.keyBase64,
+    srtp_remote_key_base64: srtp?.remoteKeyBase64,
   });
 }
 
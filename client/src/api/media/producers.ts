@@ -497,6 +497,8 @@ async function startNativeScreenProducer(
   // Always use H264 for native capture - universal hardware decode support
   const response = await requestMediaSignal(channelId, "create_native_sender_session", {
     preferred_codecs: nativePreferredCodecsFor(),
+    include_audio: options.includeAudio === true,
+    enable_srtp: true,
   });
   if (
     response.action !== "native_sender_session_created"
@@ -577,6 +579,26 @@ async function startNativeScreenProducer(
       response.rtp_target,
       response.payload_type,
       response.ssrc,
+      response.rtx
+        ? { payloadType: response.rtx.payload_type, ssrc: response.rtx.ssrc }
+        : undefined,
+      response.audio
+        ? { payloadType: response.audio.payload_type, ssrc: response.audio.ssrc }
+        : undefined,
+      typeof response.transport_cc_extension_id === "number"
+        && typeof response.abs_send_time_extension_id === "number"
+        ? {
+          transportCcExtensionId: response.transport_cc_extension_id,
+          absSendTimeExtensionId: response.abs_send_time_extension_id,
+        }
+        : undefined,
+      response.srtp
+        ? {
+          cryptoSuite: response.srtp.crypto_suite,
+          keyBase64: response.srtp.key_base64,
+          remoteKeyBase64: response.srtp.remote_key_base64,
+        }
+        : undefined,
     );
     const backendStatus = await readNativeSenderBackendStatus();
     reportNativeSenderDiagnostic(
@@ -16,6 +16,8 @@ import {
 import type { MediaSignalPayload, TransportOptions } from "./types";
 import { notifyVideoTilesSubscribers } from "./subscriptions";
 import { disposeRemoteConsumer, queueOrConsumeProducer } from "./consumers";
+import { disposeRemoteDataConsumer, queueOrConsumeDataProducer } from "./dataChannels";
+import { renegotiateMediaTransports } from "./transports";
 
 export function toMediaSignalPayload(value: unknown): MediaSignalPayload | null {
   if (!isObject(value)) {
@@ -100,6 +102,29 @@ export function ensureSignalListener() {
       if (consumerId) {
         disposeRemoteConsumer(consumerId);
       }
+      return;
+    }
+
+    if (payload.action === "new_data_producer" && payload.data_producer_id) {
+      queueOrConsumeDataProducer(
+        msg.channel_id,
+        payload.data_producer_id,
+        payload.label ?? "",
+        payload.username,
+      );
+      return;
+    }
+
+    if (payload.action === "data_producer_closed" && payload.data_producer_id) {
+      disposeRemoteDataConsumer(payload.data_producer_id);
+      return;
+    }
+
+    if (payload.action === "media_renegotiate") {
+      console.info("[media] Server requested renegotiation", payload.reason);
+      renegotiateMediaTransports(msg.channel_id).catch((error) => {
+        console.warn("[media] Failed to renegotiate media transports", error);
+      });
     }
   });
 }
@@ -129,6 +154,15 @@ export function requestMediaSignal(channelId: string, action: string, extra: Rec
   });
 }
 
+export async function requestMediaStats(channelId: string): Promise<unknown> {
+  const response = await requestMediaSignal(channelId, "media_get_stats");
+  if (response.action !== "media_stats") {
+    throw new Error("Unexpected media stats response from media signaling");
+  }
+
+  return response.stats;
+}
+
 export function toTransportOptions(payload: MediaSignalPayload): TransportOptions {
   const transport = payload.transport;
   if (!transport) {
@@ -140,6 +174,8 @@ export function toTransportOptions(payload: MediaSignalPayload): TransportOption
     iceParameters: transport.ice_parameters,
     iceCandidates: transport.ice_candidates,
     dtlsParameters: transport.dtls_parameters,
+    sctpParameters: transport.sctp_parameters ?? undefined,
+    iceServers: payload.ice_servers && payload.ice_servers.length > 0 ? payload.ice_servers : undefined,
   };
 }
 
@@ -1,9 +1,11 @@
 import type { Device } from "mediasoup-client";
-import type { Consumer, Producer, Transport } from "mediasoup-client/types";
+import type { Consumer, DataConsumer, DataProducer, Producer, Transport } from "mediasoup-client/types";
 import type {
+  CallDataMessage,
   CameraStateSnapshot,
   MediaSource,
   PendingRequest,
+  QueuedDataProducerAnnouncement,
   QueuedProducerAnnouncement,
   RemoteVideoTile,
   RoutingMode,
@@ -77,6 +79,13 @@ export const producerRoutingModeById = new Map<string, RoutingMode>();
 export const remoteVideoTilesByProducerId = new Map<string, RemoteVideoTile>();
 export const pendingRequests = new Map<string, PendingRequest>();
 
+// Call data channels: local producers by label, remote consumers by the
+// announced data producer id
+export const localDataProducers = new Map<string, DataProducer>();
+export const remoteDataConsumers = new Map<string, DataConsumer>();
+export const dataProducerUsernameById = new Map<string, string>();
+export const queuedDataProducerAnnouncements = new Map<string, QueuedDataProducerAnnouncement>();
+
 // Per-user volume: GainNode routing state
 export let remotePlaybackAudioContext: AudioContext | null = null;
 export const consumerSourceNodes = new Map<string, MediaStreamAudioSourceNode>();
@@ -94,6 +103,7 @@ export const videoTilesSubscribers = new Set<(tiles: RemoteVideoTile[]) => void>
 export const cameraStateSubscribers = new Set<(snapshot: CameraStateSnapshot) => void>();
 export const screenStateSubscribers = new Set<(snapshot: ScreenShareStateSnapshot) => void>();
 export const audioPlaybackErrorSubscribers = new Set<(username: string | undefined) => void>();
+export const callDataSubscribers = new Set<(message: CallDataMessage) => void>();
 
 // State setters
 export function setDevice(value: Device | null) { device = value; }
@@ -3,9 +3,10 @@ import type { Transport } from "mediasoup-client/types";
 import type { TransportHealthState } from "./types";
 import { isObject } from "./codecs";
 import { flushQueuedProducerAnnouncements, disposeRemoteConsumer } from "./consumers";
+import { closeCallDataChannels, flushQueuedDataProducerAnnouncements } from "./dataChannels";
 import { registerDeviceChangeListener, unregisterDeviceChangeListener } from "./devices";
 import { disarmNativeCapture } from "./native";
-import { startLocalAudioProducer } from "./producers";
+import { startLocalAudioProducer, startLocalCameraProducer } from "./producers";
 import {
   reportVoiceActivity,
   reportVoiceMuteState,
@@ -13,6 +14,7 @@ import {
   toTransportOptions,
 } from "./signaling";
 import {
+  cameraEnabled,
   cameraProducer,
   cameraStream,
   cameraTrack,
@@ -24,6 +26,7 @@ import {
   micStream,
   micTrack,
   microphoneMuted,
+  nativeScreenProducerId,
   pendingRequests,
   producerRoutingModeById,
   producerSourceById,
@@ -32,6 +35,7 @@ import {
   recvTransport,
   remoteAudioElements,
   remoteConsumers,
+  screenEnabled,
   screenProducer,
   screenStream,
   screenTrack,
@@ -49,6 +53,8 @@ import {
   setMicProducer,
   setMicStream,
   setMicTrack,
+  setNativeCaptureAttempted,
+  setNativeScreenProducerId,
   setRecvTransport,
   setScreenEnabled,
   setScreenError,
@@ -167,6 +173,27 @@ function wireSendTransportProduce(channelId: string, transport: Transport) {
   });
 }
 
+function wireSendTransportProduceData(channelId: string, transport: Transport) {
+  transport.on("producedata", ({ sctpStreamParameters, label, protocol }, callback, errback) => {
+    requestMediaSignal(channelId, "data_produce", {
+      sctp_stream_parameters: sctpStreamParameters,
+      label,
+      protocol,
+    })
+      .then((response) => {
+        if (response.action !== "data_produced" || !response.id) {
+          throw new Error("Unexpected data_produce response from server");
+        }
+
+        callback({ id: response.id });
+      })
+      .catch((error) => {
+        const normalized = error instanceof Error ? error : new Error("Failed to open data channel");
+        errback(normalized);
+      });
+  });
+}
+
 export function closeTransports() {
   if (disconnectRecoveryTimer) {
     clearTimeout(disconnectRecoveryTimer);
@@ -233,6 +260,7 @@ export function closeTransports() {
   for (const consumerId of remoteConsumers.keys()) {
     disposeRemoteConsumer(consumerId);
   }
+  closeCallDataChannels();
 
   sendTransport?.close();
   recvTransport?.close();
@@ -322,6 +350,7 @@ export async function initializeMediaTransports(channelId: string) {
       wireTransportConnect(channelId, nextSendTransport);
       wireTransportConnect(channelId, nextRecvTransport);
       wireSendTransportProduce(channelId, nextSendTransport);
+      wireSendTransportProduceData(channelId, nextSendTransport);
       wireTransportConnectionStateMonitor(nextSendTransport);
       wireTransportConnectionStateMonitor(nextRecvTransport);
 
@@ -336,6 +365,7 @@ export async function initializeMediaTransports(channelId: string) {
       await startLocalAudioProducer(channelId);
       registerDeviceChangeListener();
       flushQueuedProducerAnnouncements(channelId);
+      flushQueuedDataProducerAnnouncements(channelId);
     })();
 
     const overallTimeout = new Promise<never>((_, reject) => {
@@ -362,6 +392,31 @@ export async function initializeMediaTransports(channelId: string) {
   }
 }
 
+export async function renegotiateMediaTransports(channelId: string) {
+  if (initializedForChannelId !== channelId) {
+    return;
+  }
+
+  // The server has already closed every producer on the old router. A
+  // screen share cannot be re-produced without the user picking a source
+  // again, so it ends here and the user is asked to share again.
+  const screenShareEnded = Boolean(screenEnabled || screenProducer || nativeScreenProducerId);
+  const restoreCamera = cameraEnabled;
+  closeTransports();
+  if (screenShareEnded) {
+    setNativeScreenProducerId(null);
+    setNativeCaptureAttempted(false);
+    setScreenError("Screen sharing stopped because the channel's voice settings changed. Share again to continue.");
+    notifyScreenStateSubscribers();
+  }
+
+  await initializeMediaTransports(channelId);
+
+  if (restoreCamera) {
+    await startLocalCameraProducer(channelId);
+  }
+}
+
 export function setMicrophoneMuted(muted: boolean) {
   setMicrophoneMutedState(muted);
   updateOutgoingMicrophoneMuted(muted);
@@ -25,11 +25,33 @@ export interface DtlsParameters {
   }>;
 }
 
+export interface IceServer {
+  urls: string[];
+  username?: string;
+  credential?: string;
+}
+
+export interface SctpParameters {
+  port: number;
+  OS: number;
+  MIS: number;
+  maxMessageSize: number;
+}
+
+export interface SctpStreamParameters {
+  streamId: number;
+  ordered?: boolean;
+  maxPacketLifeTime?: number;
+  maxRetransmits?: number;
+}
+
 export interface TransportOptions {
   id: string;
   iceParameters: IceParameters;
   iceCandidates: IceCandidate[];
   dtlsParameters: DtlsParameters;
+  sctpParameters?: SctpParameters;
+  iceServers?: IceServer[];
 }
 
 export type MediaSignalAction =
@@ -43,10 +65,17 @@ export type MediaSignalAction =
   | "media_consumer_resumed"
   | "native_sender_session_created"
   | "producer_closed"
+  | "media_renegotiate"
+  | "media_stats"
+  | "data_produced"
+  | "data_consumer_created"
+  | "media_data_producer_closed"
+  | "new_data_producer"
+  | "data_producer_closed"
   | "signal_error";
 
 export type MediaKind = "audio" | "video";
-export type MediaSource = "microphone" | "camera" | "screen";
+export type MediaSource = "microphone" | "camera" | "screen" | "soundboard";
 export type RoutingMode = "sfu";
 
 export interface MediaConsumerDescription {
@@ -56,17 +85,29 @@ export interface MediaConsumerDescription {
   rtp_parameters: unknown;
 }
 
+export interface MediaDataConsumerDescription {
+  id: string;
+  data_producer_id: string;
+  sctp_stream_parameters?: SctpStreamParameters;
+  label: string;
+  protocol: string;
+}
+
 export interface MediaSignalPayload {
   action?: MediaSignalAction | string;
   request_id?: string;
   message?: string;
+  reason?: string;
   rtp_capabilities?: unknown;
   transport?: {
     id: string;
     ice_parameters: IceParameters;
     ice_candidates: IceCandidate[];
     dtls_parameters: DtlsParameters;
+    sctp_parameters?: SctpParameters | null;
   };
+  ice_servers?: IceServer[];
+  stats?: unknown;
   transport_id?: string;
   direction?: "send" | "recv";
   producer_id?: string;
@@ -76,6 +117,11 @@ export interface MediaSignalPayload {
   routing_mode?: RoutingMode;
   consumer?: MediaConsumerDescription;
   consumer_id?: string;
+  id?: string;
+  data_producer_id?: string;
+  label?: string;
+  protocol?: string;
+  data_consumer?: MediaDataConsumerDescription;
   rtp_target?: string;
   payload_type?: number;
   ssrc?: number;
@@ -99,6 +145,25 @@ export interface MediaSignalPayload {
     profile_level_id?: string;
     readiness?: "ready" | "planned" | string;
   }>;
+  rtx?: {
+    payload_type: number;
+    ssrc: number;
+  };
+  transport_cc_extension_id?: number;
+  abs_send_time_extension_id?: number;
+  srtp?: {
+    crypto_suite: string;
+    key_base64: string;
+    remote_key_base64: string;
+  } | null;
+  audio?: {
+    producer_id: string;
+    payload_type: number;
+    ssrc: number;
+    mime_type: string;
+    clock_rate: number;
+    channels: number;
+  } | null;
 }
 
 export interface PendingRequest {
@@ -136,6 +201,7 @@ export interface ScreenShareStartOptions {
   sourceKind: ScreenShareSourceKind;
   sourceId?: string;
   sourceTitle?: string;
+  includeAudio?: boolean;
 }
 
 export interface CameraStateSnapshot {
@@ -166,6 +232,18 @@ export interface QueuedProducerAnnouncement {
   username?: string;
 }
 
+export interface QueuedDataProducerAnnouncement {
+  label: string;
+  username?: string;
+}
+
+/** A message received on a call data channel from another participant. */
+export interface CallDataMessage {
+  label: string;
+  username?: string;
+  data: string | ArrayBuffer;
+}
+
 export type TransportHealthState = "new" | "connected" | "disconnected" | "failed" | "closed";
 
 export type SinkableAudioElement = HTMLAudioElement & {
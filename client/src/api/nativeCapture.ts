@@ -20,6 +20,15 @@ export interface StartNativeCaptureRequest {
   rtp_target?: string;
   payload_type?: number;
   ssrc?: number;
+  rtx_payload_type?: number;
+  rtx_ssrc?: number;
+  transport_cc_extension_id?: number;
+  abs_send_time_extension_id?: number;
+  srtp_crypto_suite?: string;
+  srtp_key_base64?: string;
+  srtp_remote_key_base64?: string;
+  audio_payload_type?: number;
+  audio_ssrc?: number;
 }
 
 export interface NativeCaptureStatus {
@@ -69,6 +78,18 @@ export interface NativeCaptureStatus {
     fallback_triggered_events: number;
     fallback_completed_events: number;
     encoder_backend_runtime_fallback_events: number;
+    nack_requests: number;
+    retransmitted_packets: number;
+    retransmit_misses: number;
+    sender_reports_sent: number;
+    estimated_bitrate_kbps: number | null;
+    remb_bitrate_kbps: number | null;
+    encoder_target_bitrate_kbps: number | null;
+    srtp_enabled: boolean;
+    srtcp_auth_failures: number;
+    audio_active: boolean;
+    audio_packets_sent: number;
+    audio_capture_errors: number;
     encoder_backend: "openh264" | "nvenc" | string | null;
     encoder_backend_requested: "auto" | "openh264" | "nvenc" | string | null;
     encoder_backend_fallback_reason: string | null;
@@ -91,6 +112,11 @@ export async function nativeCodecCapabilities(): Promise<NativeCodecCapability[]
   return capabilities;
 }
 
+export async function nativeAudioCaptureSupported(): Promise<boolean> {
+  const supported = await invoke<boolean>("native_audio_capture_supported");
+  return supported;
+}
+
 export async function startNativeCapture(request: StartNativeCaptureRequest): Promise<NativeCaptureStatus> {
   const status = await invoke<NativeCaptureStatus>("start_native_capture", { request });
   return status;
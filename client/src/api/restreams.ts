@@ -0,0 +1,24 @@
+import { del, post } from "./http";
+import { getApiBaseUrl } from "../stores/auth";
+
+export interface HlsRestream {
+  id: string;
+  channel_id: string;
+  producer_id: string;
+  record: boolean;
+  live: boolean;
+  playlist_path: string;
+}
+
+export function startHlsRestream(producerId: string, record = false): Promise<HlsRestream> {
+  return post<HlsRestream>("/media/restreams", { producer_id: producerId, record });
+}
+
+export async function stopHlsRestream(restreamId: string): Promise<void> {
+  await del<unknown>(`/media/restreams/${restreamId}`);
+}
+
+export function hlsPlaylistUrl(restream: HlsRestream): string {
+  const apiBase = getApiBaseUrl().replace(/\/+$/, "");
+  return `${apiBase}${restream.playlist_path.replace(/^\/api/, "")}`;
+}
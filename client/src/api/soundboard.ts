@@ -0,0 +1,62 @@
+import { del, get, post } from "./http";
+import { getApiBaseUrl, token } from "../stores/auth";
+
+export interface SoundboardClip {
+  id: string;
+  name: string;
+  url: string;
+  duration_ms: number;
+  uploaded_by: string;
+  approved: boolean;
+  created_at: string;
+}
+
+function toAbsoluteClipUrl(clip: SoundboardClip): SoundboardClip {
+  const serverBaseUrl = getApiBaseUrl().replace(/\/+$/, "").replace(/\/api$/i, "");
+  return {
+    ...clip,
+    url: /^https?:\/\//i.test(clip.url) ? clip.url : `${serverBaseUrl}${clip.url}`,
+  };
+}
+
+export async function listSoundboardClips(): Promise<SoundboardClip[]> {
+  const clips = await get<SoundboardClip[]>("/soundboard/clips");
+  return clips.map(toAbsoluteClipUrl);
+}
+
+export async function uploadSoundboardClip(name: string, file: File): Promise<SoundboardClip> {
+  const formData = new FormData();
+  formData.append("name", name);
+  formData.append("file", file);
+
+  const headers: Record<string, string> = {};
+  const currentToken = token();
+  if (currentToken) {
+    headers.Authorization = `Bearer ${currentToken}`;
+  }
+
+  const res = await fetch(`${getApiBaseUrl()}/soundboard/clips`, {
+    method: "POST",
+    headers,
+    body: formData,
+  });
+
+  if (!res.ok) {
+    const body = await res.json().catch(() => ({ error: res.statusText }));
+    throw new Error(body.error || res.statusText);
+  }
+
+  return toAbsoluteClipUrl(await res.json() as SoundboardClip);
+}
+
+export async function approveSoundboardClip(clipId: string): Promise<SoundboardClip> {
+  return toAbsoluteClipUrl(await post<SoundboardClip>(`/soundboard/clips/${clipId}/approve`));
+}
+
+export async function deleteSoundboardClip(clipId: string): Promise<void> {
+  await del<unknown>(`/soundboard/clips/${clipId}`);
+}
+
+export async function playSoundboardClip(clipId: string, channelId: string): Promise<void> {
+  await post<unknown>(`/soundboard/clips/${clipId}/play`, { channel_id: channelId });
+}
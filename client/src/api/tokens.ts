@@ -0,0 +1,32 @@
+import { del, get, post } from "./http";
+import { getApiBaseUrl } from "../stores/auth";
+
+export interface PersonalToken {
+  id: string;
+  name: string;
+  created_at: string;
+  last_used_at: string | null;
+}
+
+export interface CreatedPersonalToken {
+  id: string;
+  name: string;
+  token: string;
+  created_at: string;
+}
+
+export function listPersonalTokens(): Promise<PersonalToken[]> {
+  return get<PersonalToken[]>("/users/me/tokens");
+}
+
+export function createPersonalToken(name: string): Promise<CreatedPersonalToken> {
+  return post<CreatedPersonalToken>("/users/me/tokens", { name });
+}
+
+export async function revokePersonalToken(tokenId: string): Promise<void> {
+  await del<unknown>(`/users/me/tokens/${tokenId}`);
+}
+
+export function whipBaseUrl(): string {
+  return `${getApiBaseUrl().replace(/\/+$/, "")}/whip/channels/`;
+}
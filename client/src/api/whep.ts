@@ -0,0 +1,98 @@
+import { post } from "./http";
+import { getApiBaseUrl, token } from "../stores/auth";
+
+export interface StreamViewerLink {
+  token: string;
+  watch_path: string;
+  whep_path: string;
+  expires_at: string;
+}
+
+export interface WhepPlayback {
+  stream: MediaStream;
+  close: () => Promise<void>;
+}
+
+export function createStreamViewerLink(producerId: string): Promise<StreamViewerLink> {
+  return post<StreamViewerLink>(`/whep/producers/${encodeURIComponent(producerId)}/links`);
+}
+
+function waitForIceGathering(peer: RTCPeerConnection): Promise<void> {
+  if (peer.iceGatheringState === "complete") {
+    return Promise.resolve();
+  }
+
+  return new Promise((resolve) => {
+    const timeout = setTimeout(finish, 2000);
+    function finish() {
+      clearTimeout(timeout);
+      peer.removeEventListener("icegatheringstatechange", onChange);
+      resolve();
+    }
+    function onChange() {
+      if (peer.iceGatheringState === "complete") {
+        finish();
+      }
+    }
+    peer.addEventListener("icegatheringstatechange", onChange);
+  });
+}
+
+/**
+ * Plays a screen share or camera over WHEP without joining voice. Uses the
+ * signed-in session unless a stream-viewer link token is given.
+ */
+export async function startWhepPlayback(producerId: string, viewerToken?: string): Promise<WhepPlayback> {
+  const bearer = viewerToken ?? token();
+  if (!bearer) {
+    throw new Error("Sign in or use a viewer link to watch this stream");
+  }
+
+  const serverBaseUrl = getApiBaseUrl().replace(/\/+$/, "").replace(/\/api$/i, "");
+  const peer = new RTCPeerConnection();
+  const stream = new MediaStream();
+  peer.addTransceiver("video", { direction: "recvonly" });
+  peer.addTransceiver("audio", { direction: "recvonly" });
+  peer.addEventListener("track", (event) => {
+    stream.addTrack(event.track);
+  });
+
+  let sessionUrl: string | null = null;
+  const close = async () => {
+    peer.close();
+    if (sessionUrl) {
+      const url = sessionUrl;
+      sessionUrl = null;
+      await fetch(url, { method: "DELETE" }).catch(() => undefined);
+    }
+  };
+
+  try {
+    await peer.setLocalDescription(await peer.createOffer());
+    await waitForIceGathering(peer);
+
+    const response = await fetch(`${getApiBaseUrl()}/whep/producers/${encodeURIComponent(producerId)}`, {
+      method: "POST",
+      headers: {
+        "Content-Type": "application/sdp",
+        Authorization: `Bearer ${bearer}`,
+      },
+      body: peer.localDescription?.sdp ?? "",
+    });
+    if (!response.ok) {
+      const body = await response.json().catch(() => ({ error: response.statusText }));
+      throw new Error(body.error || response.statusText);
+    }
+
+    const location = response.headers.get("Location");
+    if (location) {
+      sessionUrl = location.startsWith("/") ? `${serverBaseUrl}${location}` : location;
+    }
+    await peer.setRemoteDescription({ type: "answer", sdp: await response.text() });
+  } catch (error) {
+    await close();
+    throw error;
+  }
+
+  return { stream, close };
+}
@@ -1,14 +1,25 @@
 import { clearAuthSession, getWsUrl, token } from "../stores/auth";
 import type { Channel } from "../stores/chat";
 
+export interface AttachmentThumbnail {
+  url: string;
+  width: number;
+  height: number;
+}
+
 export interface MessageAttachment {
   media_id: string;
   mime_type: string;
+  kind: "image" | "video" | "audio" | "file";
+  filename: string | null;
   bytes: number;
   width: number | null;
   height: number | null;
+  duration_ms: number | null;
   status: "processing" | "ready" | "failed";
+  placeholder: string | null;
   thumbnail_url: string | null;
+  thumbnails: AttachmentThumbnail[];
   display_url: string | null;
   original_url: string;
 }
@@ -92,7 +103,13 @@ export type ServerMessage =
   | { type: "dm_unread_updated"; thread_id: string; unread_count: number }
   | {
     type: "voice_presence_snapshot";
-    channels: { channel_id: string; usernames: string[]; mute_states: Record<string, VoiceMuteState> }[];
+    channels: {
+      channel_id: string;
+      usernames: string[];
+      mute_states: Record<string, VoiceMuteState>;
+      viewers?: string[];
+      anonymous_viewers?: number;
+    }[];
   }
   | { type: "voice_joined"; channel_id: string; user_id: string }
   | { type: "voice_left"; channel_id: string; user_id: string }
@@ -112,7 +129,33 @@ export type ServerMessage =
     mic_muted: boolean;
     speaker_muted: boolean;
   }
+  | {
+    type: "voice_network_quality";
+    channel_id: string;
+    members: Array<{
+      username: string;
+      score: number;
+      level: "good" | "fair" | "poor";
+      rtt_ms: number | null;
+      packet_loss: number | null;
+    }>;
+  }
+  | { type: "voice_channel_viewers"; channel_id: string; viewers: string[]; anonymous_viewers: number }
+  | {
+    type: "soundboard_clip_played";
+    channel_id: string;
+    clip_id: string;
+    clip_name: string;
+    username: string;
+  }
   | { type: "media_signal"; channel_id: string; payload: unknown }
+  | {
+    type: "upload_quarantined";
+    media_id: string;
+    username: string;
+    filename: string;
+    signature: string;
+  }
   | {
     type: "reaction_added";
     channel_id: string;
@@ -157,6 +200,7 @@ let statusHandlers: StatusHandler[] = [];
 let pendingSends: string[] = [];
 let latestPresenceUsers: PresenceUser[] | null = null;
 let latestVoicePresenceChannels: { channel_id: string; usernames: string[]; mute_states: Record<string, VoiceMuteState> }[] | null = null;
+let latestVoiceChannelViewers: Record<string, { viewers: string[]; anonymous_viewers: number }> = {};
 let manualDisconnect = false;
 let awaitingAuthentication = false;
 let reconnectTimer: ReturnType<typeof setTimeout> | null = null;
@@ -351,6 +395,22 @@ export function connect(url = getWsUrl(), reconnectAttempt = false) {
           usernames: [...new Set(channel.usernames)].sort((a, b) => a.localeCompare(b)),
           mute_states: { ...(channel.mute_states ?? {}) },
         }));
+        latestVoiceChannelViewers = Object.fromEntries(
+          msg.channels
+            .filter((channel) => (channel.viewers?.length ?? 0) > 0 || (channel.anonymous_viewers ?? 0) > 0)
+            .map((channel) => [
+              channel.channel_id,
+              { viewers: [...(channel.viewers ?? [])], anonymous_viewers: channel.anonymous_viewers ?? 0 },
+            ]),
+        );
+      } else if (msg.type === "voice_channel_viewers") {
+        const next = { ...latestVoiceChannelViewers };
+        if (msg.viewers.length === 0 && msg.anonymous_viewers === 0) {
+          delete next[msg.channel_id];
+        } else {
+          next[msg.channel_id] = { viewers: [...msg.viewers], anonymous_viewers: msg.anonymous_viewers };
+        }
+        latestVoiceChannelViewers = next;
       } else if (msg.type === "user_connected") {
         const current = latestPresenceUsers ?? [];
         const usersByUsername = new Map(current.map((user) => [user.username, user]));
@@ -484,6 +544,7 @@ export function disconnect() {
   pendingSends = [];
   latestPresenceUsers = null;
   latestVoicePresenceChannels = null;
+  latestVoiceChannelViewers = {};
 }
 
 export function send(data: unknown) {
@@ -532,6 +593,15 @@ export function onMessage(handler: MessageHandler) {
       });
   }
 
+  for (const [channelId, viewers] of Object.entries(latestVoiceChannelViewers)) {
+    handler({
+      type: "voice_channel_viewers",
+      channel_id: channelId,
+      viewers: [...viewers.viewers],
+      anonymous_viewers: viewers.anonymous_viewers,
+    });
+  }
+
   return () => {
     handlers = handlers.filter((h) => h !== handler);
   };
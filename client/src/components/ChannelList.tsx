@@ -27,6 +27,7 @@ import {
 } from "../api/media";
 import {
     listNativeCaptureSources,
+    nativeAudioCaptureSupported,
     nativeCaptureStatus,
     type NativeCaptureStatus,
     type NativeCaptureSource,
@@ -51,6 +52,7 @@ import {
     preferredScreenShareFps,
     preferredScreenShareResolution,
     preferredScreenShareSourceKind,
+    preferredScreenShareAudioEnabled,
     savePreferredScreenShareSourceKind,
     closeSettings,
     settingsOpen,
@@ -70,6 +72,7 @@ import {
     applyVoiceJoined,
     applyVoiceLeft,
     applyVoiceMuteState,
+    applyVoiceNetworkQuality,
     applyVoiceSpeaking,
     applyVoiceSnapshot,
     clearVoiceRejoinNotice,
@@ -99,12 +102,19 @@ import {
     toggleMicMuted,
     toggleSpeakerMuted,
     voiceMemberMuteState,
+    voiceMemberNetworkQuality,
+    voiceChannelViewers,
     voiceRejoinNotice,
     voiceActionState,
     videoTiles,
     watchedStreamProducerId,
     startWatchingStream,
+    applyVoiceChannelViewers,
+    type VoiceChannelViewers,
+    type VoiceNetworkQuality,
 } from "../stores/voice";
+import { createStreamViewerLink } from "../api/whep";
+import { hlsPlaylistUrl, startHlsRestream, stopHlsRestream } from "../api/restreams";
 import AsyncContent from "./AsyncContent";
 import UserSettingsDock from "./UserSettingsDock";
 import { MicrophoneIcon, PlusIcon, SpeakerIcon } from "./icons";
@@ -139,6 +149,32 @@ async function fetchChannels() {
     return listChannels();
 }
 
+function networkQualityTitle(quality: VoiceNetworkQuality): string {
+    const details: string[] = [];
+    if (quality.rttMs !== null) {
+        details.push(`${Math.round(quality.rttMs)} ms`);
+    }
+    if (quality.packetLoss !== null) {
+        details.push(`${(quality.packetLoss * 100).toFixed(1)}% loss`);
+    }
+
+    const label = quality.level === "poor" ? "Poor connection" : "Unstable connection";
+    return details.length > 0 ? `${label} (${details.join(", ")})` : label;
+}
+
+function viewersLabel(viewers: VoiceChannelViewers): string {
+    const count = viewers.viewers.length + viewers.anonymousViewers;
+    return count === 1 ? "1 watching" : `${count} watching`;
+}
+
+function viewersTitle(viewers: VoiceChannelViewers): string {
+    const names = viewers.viewers.map((username) => displayNameFor(username));
+    if (viewers.anonymousViewers > 0) {
+        names.push(`${viewers.anonymousViewers} via viewer link`);
+    }
+    return `Watching without joining: ${names.join(", ")}`;
+}
+
 export default function ChannelList() {
     const [channels, setChannels] = createSignal<Channel[]>([]);
     const [isLoading, setIsLoading] = createSignal(true);
@@ -162,6 +198,8 @@ export default function ChannelList() {
     const [channelEditOpusBitrate, setChannelEditOpusBitrate] = createSignal<
         number | undefined
     >(undefined);
+    const [channelEditOpusDtx, setChannelEditOpusDtx] = createSignal(false);
+    const [channelEditOpusFec, setChannelEditOpusFec] = createSignal(false);
     const [loadError, setLoadError] = createSignal("");
     const [toastError, setToastError] = createSignal("");
     const [audioFixNeeded, setAudioFixNeeded] = createSignal(false);
@@ -170,6 +208,8 @@ export default function ChannelList() {
     const [screenShareModalOpen, setScreenShareModalOpen] = createSignal(false);
     const [nativeSourcesLoading, setNativeSourcesLoading] = createSignal(false);
     const [nativeSourcesError, setNativeSourcesError] = createSignal("");
+    const [nativeAudioSupported, setNativeAudioSupported] =
+        createSignal(false);
     const [nativeSources, setNativeSources] = createSignal<
         NativeCaptureSource[]
     >([]);
@@ -189,6 +229,12 @@ export default function ChannelList() {
     const [memberHoverPopoverKey, setMemberHoverPopoverKey] = createSignal<
         string | null
     >(null);
+    const [copiedViewerLinkProducerId, setCopiedViewerLinkProducerId] =
+        createSignal<string | null>(null);
+    const [copiedHlsLinkProducerId, setCopiedHlsLinkProducerId] =
+        createSignal<string | null>(null);
+    const [recordingRestreamByProducer, setRecordingRestreamByProducer] =
+        createSignal<Record<string, string>>({});
     const pulseTimers = new Map<string, ReturnType<typeof setTimeout>>();
     const readMarkerInFlightByChannel = new Set<string>();
     const tauriRuntime = isTauriRuntime();
@@ -304,6 +350,8 @@ export default function ChannelList() {
             sourceKind,
             sourceId: selected?.id,
             sourceTitle: selected?.title,
+            includeAudio:
+                nativeAudioSupported() && preferredScreenShareAudioEnabled(),
         };
     }
 
@@ -315,6 +363,10 @@ export default function ChannelList() {
         setNativeSourcesLoading(true);
         setNativeSourcesError("");
 
+        void nativeAudioCaptureSupported()
+            .then(setNativeAudioSupported)
+            .catch(() => setNativeAudioSupported(false));
+
         try {
             const sources = await listNativeCaptureSources();
             setNativeSources(sources);
@@ -683,6 +735,8 @@ export default function ChannelList() {
         setChannelEditName(channel.name);
         setChannelEditDescription(channel.description ?? "");
         setChannelEditOpusBitrate(channel.opus_bitrate ?? undefined);
+        setChannelEditOpusDtx(channel.opus_dtx ?? false);
+        setChannelEditOpusFec(channel.opus_fec ?? false);
     }
 
     function closeEditChannel() {
@@ -690,6 +744,54 @@ export default function ChannelList() {
         setChannelEditName("");
         setChannelEditDescription("");
         setChannelEditOpusBitrate(undefined);
+        setChannelEditOpusDtx(false);
+        setChannelEditOpusFec(false);
+    }
+
+    async function handleCopyViewerLink(producerId: string) {
+        try {
+            const link = await createStreamViewerLink(producerId);
+            await navigator.clipboard.writeText(
+                `${window.location.origin}${link.watch_path}`,
+            );
+            setCopiedViewerLinkProducerId(producerId);
+            setTimeout(() => setCopiedViewerLinkProducerId(null), 2000);
+        } catch (error) {
+            showErrorToast(errorMessage(error, "Failed to create viewer link"));
+        }
+    }
+
+    async function handleCopyHlsLink(producerId: string) {
+        try {
+            const restream = await startHlsRestream(producerId);
+            await navigator.clipboard.writeText(hlsPlaylistUrl(restream));
+            setCopiedHlsLinkProducerId(producerId);
+            setTimeout(() => setCopiedHlsLinkProducerId(null), 2000);
+        } catch (error) {
+            showErrorToast(errorMessage(error, "Failed to start HLS restream"));
+        }
+    }
+
+    async function handleToggleStreamRecording(producerId: string) {
+        const restreamId = recordingRestreamByProducer()[producerId];
+        try {
+            if (restreamId) {
+                await stopHlsRestream(restreamId);
+                setRecordingRestreamByProducer((current) => {
+                    const { [producerId]: _stopped, ...rest } = current;
+                    return rest;
+                });
+                return;
+            }
+
+            const restream = await startHlsRestream(producerId, true);
+            setRecordingRestreamByProducer((current) => ({
+                ...current,
+                [producerId]: restream.id,
+            }));
+        } catch (error) {
+            showErrorToast(errorMessage(error, "Failed to update stream recording"));
+        }
     }
 
     async function handleCreateChannel(
@@ -756,6 +858,8 @@ export default function ChannelList() {
         rawName: string,
         rawDescription: string,
         opusBitrate?: number,
+        opusDtx?: boolean,
+        opusFec?: boolean,
     ) {
         if (isSaving()) {
             return;
@@ -776,6 +880,8 @@ export default function ChannelList() {
                 description:
                     trimmedDescription.length > 0 ? trimmedDescription : null,
                 opus_bitrate: channel.kind === "voice" ? opusBitrate : null,
+                opus_dtx: channel.kind === "voice" ? opusDtx : null,
+                opus_fec: channel.kind === "voice" ? opusFec : null,
             });
             closeEditChannel();
         } catch (error) {
@@ -934,6 +1040,27 @@ export default function ChannelList() {
                 return;
             }
 
+            if (msg.type === "voice_network_quality") {
+                applyVoiceNetworkQuality(
+                    msg.channel_id,
+                    Object.fromEntries(msg.members.map((member) => [member.username, {
+                        score: member.score,
+                        level: member.level,
+                        rttMs: member.rtt_ms,
+                        packetLoss: member.packet_loss,
+                    }])),
+                );
+                return;
+            }
+
+            if (msg.type === "voice_channel_viewers") {
+                applyVoiceChannelViewers(msg.channel_id, {
+                    viewers: msg.viewers,
+                    anonymousViewers: msg.anonymous_viewers,
+                });
+                return;
+            }
+
             if (msg.type === "voice_user_mute_state") {
                 applyVoiceMuteState(msg.channel_id, msg.username, {
                     micMuted: msg.mic_muted,
@@ -1487,6 +1614,20 @@ export default function ChannelList() {
                                                                         </Show>
                                                                     </span>
                                                                 </Show>
+                                                                <Show
+                                                                    when={(() => {
+                                                                        const quality = voiceMemberNetworkQuality(channel.id, memberUsername);
+                                                                        return quality && quality.level !== "good" ? quality : null;
+                                                                    })()}
+                                                                >
+                                                                    {(quality) => (
+                                                                        <span
+                                                                            class={`channel-voice-member-quality is-${quality().level}`}
+                                                                            title={networkQualityTitle(quality())}
+                                                                            aria-label={`${displayNameFor(memberUsername)} has a ${quality().level} connection`}
+                                                                        />
+                                                                    )}
+                                                                </Show>
                                                                 <Show
                                                                     when={streamTileForVoiceMember(channel.id, memberUsername)}
                                                                 >
@@ -1534,6 +1675,47 @@ export default function ChannelList() {
                                                                                             ? "Watching"
                                                                                             : "Watch Stream"}
                                                                                     </button>
+                                                                                    <button
+                                                                                        type="button"
+                                                                                        class="channel-stream-watch-button"
+                                                                                        onClick={(event) => {
+                                                                                            event.preventDefault();
+                                                                                            event.stopPropagation();
+                                                                                            void handleCopyViewerLink(liveTile().producerId);
+                                                                                        }}
+                                                                                    >
+                                                                                        {copiedViewerLinkProducerId() ===
+                                                                                            liveTile().producerId
+                                                                                            ? "Link copied"
+                                                                                            : "Copy viewer link"}
+                                                                                    </button>
+                                                                                    <button
+                                                                                        type="button"
+                                                                                        class="channel-stream-watch-button"
+                                                                                        onClick={(event) => {
+                                                                                            event.preventDefault();
+                                                                                            event.stopPropagation();
+                                                                                            void handleCopyHlsLink(liveTile().producerId);
+                                                                                        }}
+                                                                                    >
+                                                                                        {copiedHlsLinkProducerId() ===
+                                                                                            liveTile().producerId
+                                                                                            ? "HLS link copied"
+                                                                                            : "Copy HLS link"}
+                                                                                    </button>
+                                                                                    <button
+                                                                                        type="button"
+                                                                                        class="channel-stream-watch-button"
+                                                                                        onClick={(event) => {
+                                                                                            event.preventDefault();
+                                                                                            event.stopPropagation();
+                                                                                            void handleToggleStreamRecording(liveTile().producerId);
+                                                                                        }}
+                                                                                    >
+                                                                                        {recordingRestreamByProducer()[liveTile().producerId]
+                                                                                            ? "Stop recording"
+                                                                                            : "Record stream"}
+                                                                                    </button>
                                                                                 </div>
                                                                             </Show>
                                                                         </>
@@ -1544,6 +1726,16 @@ export default function ChannelList() {
                                                     </For>
                                                 </ul>
                                             </Show>
+                                            <Show when={voiceChannelViewers(channel.id)}>
+                                                {(viewers) => (
+                                                    <p
+                                                        class="channel-voice-viewers"
+                                                        title={viewersTitle(viewers())}
+                                                    >
+                                                        {viewersLabel(viewers())}
+                                                    </p>
+                                                )}
+                                            </Show>
                                         </ChannelRow>
                                     )}
                                 </For>
@@ -1581,13 +1773,26 @@ export default function ChannelList() {
                     setDescription={setChannelEditDescription}
                     opusBitrate={channelEditOpusBitrate}
                     setOpusBitrate={setChannelEditOpusBitrate}
+                    opusDtx={channelEditOpusDtx}
+                    setOpusDtx={setChannelEditOpusDtx}
+                    opusFec={channelEditOpusFec}
+                    setOpusFec={setChannelEditOpusFec}
                     isSaving={isSaving()}
-                    onSubmit={(channel, name, description, opusBitrate) =>
+                    onSubmit={(
+                        channel,
+                        name,
+                        description,
+                        opusBitrate,
+                        opusDtx,
+                        opusFec,
+                    ) =>
                         void handleUpdateChannel(
                             channel,
                             name,
                             description,
                             opusBitrate,
+                            opusDtx,
+                            opusFec,
                         )
                     }
                 />
@@ -1615,6 +1820,7 @@ export default function ChannelList() {
                     </Show>
                     <Show when={joinedVoiceChannelId()}>
                         <VoiceDock
+                            connectedChannelId={joinedVoiceChannelId()}
                             connectedChannelName={connectedVoiceChannelName()}
                             nativeDebugEnabled={nativeDebugEnabled}
                             nativeSenderMetrics={nativeSenderMetrics()}
@@ -1641,6 +1847,7 @@ export default function ChannelList() {
                         nativeSources={nativeSources()}
                         selectedNativeSourceId={selectedNativeSourceId()}
                         onSelectNativeSource={setSelectedNativeSourceId}
+                        nativeAudioSupported={nativeAudioSupported()}
                         screenSharePreviewStream={screenSharePreviewStream()}
                         screenSharePreviewError={screenSharePreviewError()}
                         screenSharePreviewVideoRef={() =>
@@ -36,8 +36,10 @@ import { useTypingPresence } from "./useTypingPresence";
 import { clearDmTypingUsers, dmThreadById, dmTypingUsernames, removeDmTypingUser, setDmUnreadCount, touchDmTypingUser } from "../stores/dms";
 import {
   toAbsoluteMediaUrl,
+  isImageAttachment,
   uploadError,
   uploadMediaFile,
+  validateFileAttachment,
   validateImageAttachment,
   waitForMediaDerivative,
 } from "./messageAttachments";
@@ -535,7 +537,9 @@ export default function MessageArea() {
   }
 
   async function uploadAttachment(file: File) {
-    const validationError = validateImageAttachment(file);
+    // Images go through the preview pipeline; anything else is a plain file.
+    const isImage = isImageAttachment(file);
+    const validationError = isImage ? validateImageAttachment(file) : validateFileAttachment(file);
     if (validationError) {
       setWsError(validationError);
       return;
@@ -562,7 +566,7 @@ export default function MessageArea() {
 
     const apiBaseUrl = getApiBaseUrl();
     try {
-      const payload = await uploadMediaFile(apiBaseUrl, currentToken, file);
+      const payload = await uploadMediaFile(apiBaseUrl, currentToken, file, isImage ? "upload" : "files");
       upsertPendingAttachment(clientId, {
         media_id: payload.id,
         status: payload.status === "ready" ? "ready" : "processing",
@@ -570,7 +574,7 @@ export default function MessageArea() {
       });
 
       if (payload.status !== "ready") {
-        void waitForMediaDerivative(apiBaseUrl, currentToken, payload.id)
+        void waitForMediaDerivative(apiBaseUrl, currentToken, payload.id, isImage ? "thumb_320" : "poster")
           .then(() => {
             upsertPendingAttachment(clientId, { status: "ready", error: null });
           })
@@ -1174,6 +1178,14 @@ export default function MessageArea() {
         return;
       }
 
+      if (msg.type === "upload_quarantined") {
+        void sendDesktopNotification("Upload quarantined", {
+          body: `${msg.filename} from @${msg.username} matched ${msg.signature}`,
+          tag: `quarantine-${msg.media_id}`,
+        });
+        return;
+      }
+
       if (msg.type === "user_profile_updated") {
         upsertUserProfile({
           username: msg.username,
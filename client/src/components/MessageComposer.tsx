@@ -279,8 +279,8 @@ export default function MessageComposer(props: MessageComposerProps) {
             class="message-attach-button"
             onClick={() => fileInputRef?.click()}
             disabled={!props.activeChannelId || props.isSending || !!props.savingMessageId || !!props.deletingMessageId}
-            aria-label="Add image"
-            title="Add image"
+            aria-label="Add attachment"
+            title="Add attachment"
           >
             <svg viewBox="0 0 20 20" width="18" height="18" aria-hidden="true">
               <path d="M4 4.5A1.5 1.5 0 0 1 5.5 3h9A1.5 1.5 0 0 1 16 4.5v11a1.5 1.5 0 0 1-1.5 1.5h-9A1.5 1.5 0 0 1 4 15.5z" fill="none" stroke="currentColor" stroke-width="1.6" />
@@ -291,7 +291,6 @@ export default function MessageComposer(props: MessageComposerProps) {
           <input
             ref={fileInputRef}
             type="file"
-            accept="image/jpeg,image/png,image/webp,image/gif"
             multiple
             class="message-attach-input"
             onChange={props.onAttachmentInput}
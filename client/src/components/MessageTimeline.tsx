@@ -1,6 +1,7 @@
 import { For, Show, createEffect, createMemo, createSignal, onCleanup, onMount } from "solid-js";
 import { Portal } from "solid-js/web";
 import type { MessageReactionDetail } from "../api/reactions";
+import type { MessageAttachment } from "../api/ws";
 import type { Channel } from "../stores/chat";
 import { username } from "../stores/auth";
 import {
@@ -10,6 +11,7 @@ import {
   setContextMenuTarget,
 } from "../stores/contextMenu";
 import AsyncContent from "./AsyncContent";
+import { formatAttachmentDuration, formatAttachmentSize } from "./messageAttachments";
 import MessageRichContent from "./MessageRichContent";
 import ReactionPicker from "./ReactionPicker";
 import UserAvatar from "./UserAvatar";
@@ -20,6 +22,7 @@ import { toggleMobileNav } from "../stores/chat";
 import { displayNameFor } from "../stores/userProfiles";
 import { loadEmojis, useEmojiStore } from "../stores/emojis";
 import { errorMessage } from "../utils/error";
+import { thumbHashToDataUrl } from "../utils/thumbhash";
 
 interface MessageTimelineProps {
   activeChannel: Channel | null | undefined;
@@ -50,7 +53,17 @@ interface MessageTimelineProps {
 
 interface LazyAttachmentImageProps {
   src: string;
+  srcSet?: string;
+  sizes?: string;
   alt: string;
+  placeholder?: string | null;
+  width?: number | null;
+  height?: number | null;
+}
+
+interface FileAttachmentProps {
+  attachment: MessageAttachment;
+  toAbsoluteMediaUrl: (path: string) => string;
 }
 
 interface AttachmentPreview {
@@ -70,8 +83,88 @@ function reactionKey(reaction: { emoji_id: string | null; unicode_emoji: string
   return `${reaction.emoji_id ?? ""}:${reaction.unicode_emoji ?? ""}`;
 }
 
+const PLAYABLE_VIDEO_MIME_TYPES = ["video/mp4", "video/webm"];
+// Matches the attachment card width: full width on narrow screens, else 400px.
+const ATTACHMENT_IMAGE_SIZES = "(max-width: 440px) 100vw, 400px";
+
+function placeholderBackground(placeholder: string | null | undefined): string | undefined {
+  const dataUrl = placeholder ? thumbHashToDataUrl(placeholder) : null;
+  return dataUrl ? `center / cover no-repeat url("${dataUrl}")` : undefined;
+}
+
+function thumbnailSrcSet(
+  attachment: MessageAttachment,
+  toAbsoluteMediaUrl: (path: string) => string,
+): string | undefined {
+  if (attachment.thumbnails.length === 0) {
+    return undefined;
+  }
+  return attachment.thumbnails
+    .map((thumbnail) => `${toAbsoluteMediaUrl(thumbnail.url)} ${thumbnail.width}w`)
+    .join(", ");
+}
+
+function FileAttachment(props: FileAttachmentProps) {
+  const name = () => props.attachment.filename ?? "attachment";
+  const originalUrl = () => props.toAbsoluteMediaUrl(props.attachment.original_url);
+  // A transcoded variant always plays; otherwise only browser-native originals do.
+  const playableVideoUrl = () => {
+    if (props.attachment.kind !== "video" || props.attachment.status !== "ready") {
+      return null;
+    }
+    if (props.attachment.display_url) {
+      return props.toAbsoluteMediaUrl(props.attachment.display_url);
+    }
+    return PLAYABLE_VIDEO_MIME_TYPES.includes(props.attachment.mime_type) ? originalUrl() : null;
+  };
+  const details = () => {
+    const size = formatAttachmentSize(props.attachment.bytes);
+    return props.attachment.duration_ms ? `${formatAttachmentDuration(props.attachment.duration_ms)} · ${size}` : size;
+  };
+
+  return (
+    <figure class="message-attachment message-attachment-file" data-kind={props.attachment.kind} data-status={props.attachment.status}>
+      <Show when={props.attachment.kind === "video" && props.attachment.status === "processing"}>
+        <div class="message-attachment-placeholder">Video processing...</div>
+      </Show>
+      <Show when={playableVideoUrl()}>
+        {(url) => (
+          <video
+            src={url()}
+            poster={props.attachment.thumbnail_url ? props.toAbsoluteMediaUrl(props.attachment.thumbnail_url) : undefined}
+            style={{ background: placeholderBackground(props.attachment.placeholder) }}
+            controls
+            preload="metadata"
+          />
+        )}
+      </Show>
+      <Show when={props.attachment.kind === "audio"}>
+        <audio src={originalUrl()} controls preload="metadata" />
+      </Show>
+      <figcaption class="message-attachment-file-info">
+        <span class="message-attachment-file-name" title={name()}>{name()}</span>
+        <span class="message-attachment-file-size">{details()}</span>
+        <a
+          class="message-attachment-file-download"
+          href={originalUrl()}
+          download={name()}
+          aria-label={`Download ${name()}`}
+          title="Download"
+        >
+          <DownloadIcon />
+        </a>
+      </figcaption>
+    </figure>
+  );
+}
+
 function LazyAttachmentImage(props: LazyAttachmentImageProps) {
   const [isVisible, setIsVisible] = createSignal(false);
+  const [isLoaded, setIsLoaded] = createSignal(false);
+  // The placeholder is dropped once the image loads so it never shows
+  // through transparent pixels.
+  const background = () => (isLoaded() ? undefined : placeholderBackground(props.placeholder));
+  const aspectRatio = () => (props.width && props.height ? `${props.width} / ${props.height}` : undefined);
   let containerRef: HTMLDivElement | undefined;
   let observer: IntersectionObserver | null = null;
 
@@ -108,11 +201,30 @@ function LazyAttachmentImage(props: LazyAttachmentImageProps) {
   });
 
   return (
-    <div class="message-attachment-image-slot" ref={(element) => {
+    <div class="message-attachment-image-slot" style={{ background: background() }} ref={(element) => {
       containerRef = element;
     }}>
-      <Show when={isVisible()} fallback={<div class="message-attachment-image-placeholder" aria-hidden="true" />}>
-        <img src={props.src} alt={props.alt} loading="lazy" decoding="async" />
+      <Show
+        when={isVisible()}
+        fallback={
+          <div
+            class="message-attachment-image-placeholder"
+            classList={{ "message-attachment-image-placeholder-hashed": Boolean(props.placeholder) }}
+            style={{ "aspect-ratio": aspectRatio() }}
+            aria-hidden="true"
+          />
+        }
+      >
+        <img
+          src={props.src}
+          srcset={props.srcSet}
+          sizes={props.srcSet ? props.sizes : undefined}
+          style={{ "aspect-ratio": aspectRatio() }}
+          alt={props.alt}
+          loading="lazy"
+          decoding="async"
+          onLoad={() => setIsLoaded(true)}
+        />
       </Show>
     </div>
   );
@@ -453,53 +565,63 @@ export default function MessageTimeline(props: MessageTimelineProps) {
                                 <div class="message-attachments">
                                   <For each={message.attachments}>
                                     {(attachment) => (
-                                      <figure
-                                        class={`message-attachment${attachment.mime_type === "image/gif" ? " message-attachment-gif" : ""}`}
-                                        data-status={attachment.status}
+                                      <Show
+                                        when={attachment.kind === "image"}
+                                        fallback={<FileAttachment attachment={attachment} toAbsoluteMediaUrl={props.toAbsoluteMediaUrl} />}
                                       >
-                                        <Show
-                                          when={attachment.status === "ready" && (attachment.thumbnail_url || attachment.display_url)}
-                                          fallback={<div class="message-attachment-placeholder">Image processing...</div>}
+                                        <figure
+                                          class={`message-attachment${attachment.mime_type === "image/gif" ? " message-attachment-gif" : ""}`}
+                                          data-status={attachment.status}
                                         >
-                                          <div class="message-attachment-media">
-                                            <button
-                                              type="button"
-                                              class="message-attachment-open"
-                                              onClick={() => {
-                                                setAttachmentPreview({
-                                                  displayUrl: props.toAbsoluteMediaUrl(attachment.display_url ?? attachment.original_url),
-                                                  originalUrl: props.toAbsoluteMediaUrl(attachment.original_url),
-                                                });
-                                              }}
-                                              aria-label="Open image preview"
-                                              title="Open image preview"
-                                            >
-                                              <LazyAttachmentImage
-                                                src={props.toAbsoluteMediaUrl(
-                                                  attachment.mime_type === "image/gif"
-                                                    ? (attachment.display_url ?? attachment.original_url)
-                                                    : (attachment.thumbnail_url ?? attachment.display_url ?? attachment.original_url),
-                                                )}
-                                                alt="Shared attachment"
-                                              />
-                                            </button>
-                                            <button
-                                              type="button"
-                                              class="message-attachment-preview-overlay"
-                                              onClick={() => {
-                                                setAttachmentPreview({
-                                                  displayUrl: props.toAbsoluteMediaUrl(attachment.display_url ?? attachment.original_url),
-                                                  originalUrl: props.toAbsoluteMediaUrl(attachment.original_url),
-                                                });
-                                              }}
-                                              aria-label="Open image preview"
-                                              title="Open image preview"
-                                            >
-                                              <ZoomIcon />
-                                            </button>
-                                          </div>
-                                        </Show>
-                                      </figure>
+                                          <Show
+                                            when={attachment.status === "ready" && (attachment.thumbnail_url || attachment.display_url)}
+                                            fallback={<div class="message-attachment-placeholder">Image processing...</div>}
+                                          >
+                                            <div class="message-attachment-media">
+                                              <button
+                                                type="button"
+                                                class="message-attachment-open"
+                                                onClick={() => {
+                                                  setAttachmentPreview({
+                                                    displayUrl: props.toAbsoluteMediaUrl(attachment.display_url ?? attachment.original_url),
+                                                    originalUrl: props.toAbsoluteMediaUrl(attachment.original_url),
+                                                  });
+                                                }}
+                                                aria-label="Open image preview"
+                                                title="Open image preview"
+                                              >
+                                                <LazyAttachmentImage
+                                                  src={props.toAbsoluteMediaUrl(
+                                                    attachment.mime_type === "image/gif"
+                                                      ? (attachment.display_url ?? attachment.original_url)
+                                                      : (attachment.thumbnail_url ?? attachment.display_url ?? attachment.original_url),
+                                                  )}
+                                                  srcSet={attachment.mime_type === "image/gif" ? undefined : thumbnailSrcSet(attachment, props.toAbsoluteMediaUrl)}
+                                                  sizes={ATTACHMENT_IMAGE_SIZES}
+                                                  placeholder={attachment.placeholder}
+                                                  width={attachment.width}
+                                                  height={attachment.height}
+                                                  alt="Shared attachment"
+                                                />
+                                              </button>
+                                              <button
+                                                type="button"
+                                                class="message-attachment-preview-overlay"
+                                                onClick={() => {
+                                                  setAttachmentPreview({
+                                                    displayUrl: props.toAbsoluteMediaUrl(attachment.display_url ?? attachment.original_url),
+                                                    originalUrl: props.toAbsoluteMediaUrl(attachment.original_url),
+                                                  });
+                                                }}
+                                                aria-label="Open image preview"
+                                                title="Open image preview"
+                                              >
+                                                <ZoomIcon />
+                                              </button>
+                                            </div>
+                                          </Show>
+                                        </figure>
+                                      </Show>
                                     )}
                                   </For>
                                 </div>
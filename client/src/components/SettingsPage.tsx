@@ -3,7 +3,7 @@ import { useNavigate } from "@solidjs/router";
 import { patch } from "../api/http";
 import { errorMessage } from "../utils/error";
 import { CloseIcon } from "./icons";
-import { EmojiSettings } from "./settings-sections";
+import { EmojiSettings, SoundboardSettings, TokenSettings } from "./settings-sections";
 import {
   isSpeakerSelectionSupported,
   listAudioDevices,
@@ -97,7 +97,9 @@ const NAV_ITEMS: { key: SettingsSection; label: string }[] = [
   { key: "profile", label: "Profile" },
   { key: "audio", label: "Audio" },
   { key: "emojis", label: "Emojis" },
+  { key: "soundboard", label: "Soundboard" },
   { key: "notifications", label: "Notifications" },
+  { key: "tokens", label: "Tokens" },
   { key: "session", label: "Session" },
 ];
 
@@ -678,6 +680,14 @@ export default function SettingsPage() {
             <EmojiSettings isOperatorOrAdmin={role() === "operator" || role() === "admin"} />
           </Show>
 
+          <Show when={activeSettingsSection() === "soundboard"}>
+            <SoundboardSettings isOperatorOrAdmin={role() === "operator" || role() === "admin"} />
+          </Show>
+
+          <Show when={activeSettingsSection() === "tokens"}>
+            <TokenSettings />
+          </Show>
+
           <Show when={activeSettingsSection() === "session"}>
             <>
               <UpdaterSettings />
@@ -11,8 +11,19 @@ export interface EditChannelModalProps {
   setDescription: Setter<string>;
   opusBitrate: Accessor<number | undefined>;
   setOpusBitrate: Setter<number | undefined>;
+  opusDtx: Accessor<boolean>;
+  setOpusDtx: Setter<boolean>;
+  opusFec: Accessor<boolean>;
+  setOpusFec: Setter<boolean>;
   isSaving: boolean;
-  onSubmit: (channel: Channel, name: string, description: string, opusBitrate?: number) => void;
+  onSubmit: (
+    channel: Channel,
+    name: string,
+    description: string,
+    opusBitrate?: number,
+    opusDtx?: boolean,
+    opusFec?: boolean,
+  ) => void;
 }
 
 export default function EditChannelModal(props: EditChannelModalProps): JSX.Element {
@@ -39,6 +50,8 @@ export default function EditChannelModal(props: EditChannelModalProps): JSX.Elem
             props.name(),
             props.description(),
             props.opusBitrate(),
+            props.opusDtx(),
+            props.opusFec(),
           );
         }}
       >
@@ -105,6 +118,33 @@ export default function EditChannelModal(props: EditChannelModalProps): JSX.Elem
               <option value="192000">192 kbps</option>
               <option value="256000">256 kbps</option>
             </select>
+
+            <label class="settings-label settings-checkbox-label">
+              <input
+                type="checkbox"
+                checked={props.opusDtx()}
+                onChange={(event) => props.setOpusDtx(event.currentTarget.checked)}
+                disabled={props.isSaving}
+              />
+              <span style={{ "margin-left": "8px" }}>
+                Enable DTX (discontinuous transmission)
+              </span>
+            </label>
+
+            <label class="settings-label settings-checkbox-label">
+              <input
+                type="checkbox"
+                checked={props.opusFec()}
+                onChange={(event) => props.setOpusFec(event.currentTarget.checked)}
+                disabled={props.isSaving}
+              />
+              <span style={{ "margin-left": "8px" }}>
+                Enable FEC (forward error correction)
+              </span>
+            </label>
+            <p class="settings-help">
+              Codec changes apply live; connected members briefly reconnect their audio.
+            </p>
           </>
         )}
 
@@ -5,6 +5,8 @@ import {
   preferredScreenShareFps,
   preferredScreenShareResolution,
   preferredScreenShareSourceKind,
+  preferredScreenShareAudioEnabled,
+  savePreferredScreenShareAudioEnabled,
   savePreferredScreenShareBitrateMode,
   savePreferredScreenShareCustomBitrateKbps,
   savePreferredScreenShareFps,
@@ -31,6 +33,7 @@ export interface ScreenShareModalProps {
   nativeSources: NativeCaptureSource[];
   selectedNativeSourceId: string | null;
   onSelectNativeSource: (id: string) => void;
+  nativeAudioSupported: boolean;
   screenSharePreviewStream: MediaStream | null;
   screenSharePreviewError: string;
   screenSharePreviewVideoRef: Accessor<HTMLVideoElement | undefined>;
@@ -240,6 +243,20 @@ export default function ScreenShareModal(props: ScreenShareModalProps): JSX.Elem
 
         <p class="settings-help">Estimated target bitrate: {effectiveScreenShareBitrateLabel(selectedScreenShareBitrateKbps())}</p>
 
+        <Show when={props.nativeAudioSupported}>
+          <h5>Audio</h5>
+          <label class="settings-checkbox" for="voice-share-audio">
+            <input
+              id="voice-share-audio"
+              type="checkbox"
+              checked={preferredScreenShareAudioEnabled()}
+              onChange={(event) => savePreferredScreenShareAudioEnabled(event.currentTarget.checked)}
+            />
+            Share system audio
+          </label>
+          <p class="settings-help">Captures everything your computer plays, including voices from this call. Use headphones to avoid echo.</p>
+        </Show>
+
         <div class="settings-actions">
           <button
             type="button"
@@ -0,0 +1,98 @@
+import { For, Show, createSignal, onCleanup, onMount, type JSX } from "solid-js";
+import { listSoundboardClips, playSoundboardClip, type SoundboardClip } from "../../api/soundboard";
+import { onMessage } from "../../api/ws";
+import { errorMessage } from "../../utils/error";
+
+export interface SoundboardPanelProps {
+  channelId: string;
+}
+
+export default function SoundboardPanel(props: SoundboardPanelProps): JSX.Element {
+  const [open, setOpen] = createSignal(false);
+  const [clips, setClips] = createSignal<SoundboardClip[]>([]);
+  const [playingClipId, setPlayingClipId] = createSignal<string | null>(null);
+  const [lastPlayed, setLastPlayed] = createSignal("");
+  const [error, setError] = createSignal("");
+
+  onMount(() => {
+    const unsubscribe = onMessage((msg) => {
+      if (msg.type === "soundboard_clip_played" && msg.channel_id === props.channelId) {
+        setLastPlayed(`${msg.username} played ${msg.clip_name}`);
+      }
+    });
+    onCleanup(unsubscribe);
+  });
+
+  async function toggleOpen() {
+    const nextOpen = !open();
+    setOpen(nextOpen);
+    setError("");
+    if (!nextOpen) {
+      return;
+    }
+
+    try {
+      const available = await listSoundboardClips();
+      setClips(available.filter((clip) => clip.approved));
+    } catch (loadError) {
+      setError(errorMessage(loadError, "Failed to load soundboard"));
+    }
+  }
+
+  async function play(clip: SoundboardClip) {
+    if (playingClipId()) {
+      return;
+    }
+
+    setError("");
+    setPlayingClipId(clip.id);
+    try {
+      await playSoundboardClip(clip.id, props.channelId);
+    } catch (playError) {
+      setError(errorMessage(playError, "Failed to play clip"));
+    } finally {
+      setPlayingClipId(null);
+    }
+  }
+
+  return (
+    <div class="voice-dock-soundboard">
+      <button
+        type="button"
+        class="settings-secondary voice-dock-soundboard-toggle"
+        onClick={() => void toggleOpen()}
+        aria-expanded={open()}
+      >
+        Soundboard
+      </button>
+      <Show when={open()}>
+        <Show
+          when={clips().length > 0}
+          fallback={<p class="voice-dock-channel">No approved clips yet.</p>}
+        >
+          <div class="voice-dock-soundboard-grid">
+            <For each={clips()}>
+              {(clip) => (
+                <button
+                  type="button"
+                  class="voice-dock-soundboard-clip"
+                  onClick={() => void play(clip)}
+                  disabled={playingClipId() !== null}
+                  title={`${clip.name} (${(clip.duration_ms / 1000).toFixed(1)}s)`}
+                >
+                  {clip.name}
+                </button>
+              )}
+            </For>
+          </div>
+        </Show>
+      </Show>
+      <Show when={lastPlayed()}>
+        <p class="voice-dock-channel">{lastPlayed()}</p>
+      </Show>
+      <Show when={error()}>
+        <p class="voice-dock-error">{error()}</p>
+      </Show>
+    </div>
+  );
+}
@@ -20,8 +20,10 @@ import {
   ScreenShareIcon,
 } from "../icons";
 import { formatNativeSenderRate, voiceHealthLabel } from "./helpers";
+import SoundboardPanel from "./SoundboardPanel";
 
 export interface VoiceDockProps {
+  connectedChannelId: string | null;
   connectedChannelName: string | null;
   nativeDebugEnabled: boolean;
   nativeSenderMetrics: NativeCaptureStatus["native_sender"] | null;
@@ -97,6 +99,9 @@ export default function VoiceDock(props: VoiceDockProps): JSX.Element {
           Retry connection
         </button>
       </Show>
+      <Show when={props.connectedChannelId} keyed>
+        {(channelId) => <SoundboardPanel channelId={channelId} />}
+      </Show>
       <Show when={cameraError()}>
         <p class="voice-dock-error">{cameraError()}</p>
       </Show>
@@ -111,11 +116,14 @@ export default function VoiceDock(props: VoiceDockProps): JSX.Element {
           <p class="voice-dock-channel">Queue backlog: {props.nativeSenderMetrics?.estimated_queue_depth ?? 0} | Drop(full): {props.nativeSenderMetrics?.dropped_full ?? 0} | Drop(pre-encode): {props.nativeSenderMetrics?.dropped_before_encode ?? 0}</p>
           <p class="voice-dock-channel">Latency: {props.nativeSenderMetrics?.last_encode_latency_ms ?? 0} ms | Encode errors: {props.nativeSenderMetrics?.encode_errors ?? 0} | RTP errors: {props.nativeSenderMetrics?.rtp_send_errors ?? 0} | Drop(send): {props.nativeSenderMetrics?.dropped_during_send ?? 0}</p>
           <p class="voice-dock-channel">Keyframe requests: {props.nativeSenderMetrics?.keyframe_requests ?? 0} | Drop(no BGRA): {props.nativeSenderMetrics?.dropped_missing_bgra ?? 0}</p>
-          <p class="voice-dock-channel">Transport: {props.nativeSenderMetrics?.transport_connected ? "connected" : "disconnected"} | Producer: {props.nativeSenderMetrics?.producer_connected ? "connected" : "disconnected"}</p>
+          <p class="voice-dock-channel">NACKed: {props.nativeSenderMetrics?.nack_requests ?? 0} | Retransmitted: {props.nativeSenderMetrics?.retransmitted_packets ?? 0} | Missed: {props.nativeSenderMetrics?.retransmit_misses ?? 0} | SR: {props.nativeSenderMetrics?.sender_reports_sent ?? 0}</p>
+          <p class="voice-dock-channel">Bandwidth estimate: {props.nativeSenderMetrics?.estimated_bitrate_kbps ?? "n/a"} kbps | REMB: {props.nativeSenderMetrics?.remb_bitrate_kbps ?? "n/a"} kbps | Encoder target: {props.nativeSenderMetrics?.encoder_target_bitrate_kbps ?? "n/a"} kbps</p>
+          <p class="voice-dock-channel">Transport: {props.nativeSenderMetrics?.transport_connected ? "connected" : "disconnected"} | Producer: {props.nativeSenderMetrics?.producer_connected ? "connected" : "disconnected"} | SRTP: {props.nativeSenderMetrics?.srtp_enabled ? "on" : "off"} (auth failures: {props.nativeSenderMetrics?.srtcp_auth_failures ?? 0})</p>
           <p class="voice-dock-channel">Degradation: {props.nativeSenderMetrics?.degradation_level ?? "none"} | Fallback: {props.nativeSenderMetrics?.recent_fallback_reason ?? "none"}</p>
           <p class="voice-dock-channel">Pressure(avg/peak/max): {props.nativeSenderMetrics?.pressure_window_avg_depth ?? 0}/{props.nativeSenderMetrics?.pressure_window_peak_depth ?? 0}/{props.nativeSenderMetrics?.pressure_window_max_peak_depth ?? 0}</p>
           <p class="voice-dock-channel">Encoder backend: {props.nativeSenderMetrics?.encoder_backend ?? "unknown"}</p>
           <p class="voice-dock-channel">Encoder requested: {props.nativeSenderMetrics?.encoder_backend_requested ?? "unknown"} | Backend fallback: {props.nativeSenderMetrics?.encoder_backend_fallback_reason ?? "none"}</p>
+          <p class="voice-dock-channel">Audio: {props.nativeSenderMetrics?.audio_active ? "active" : "off"} | Packets: {props.nativeSenderMetrics?.audio_packets_sent ?? 0} | Capture errors: {props.nativeSenderMetrics?.audio_capture_errors ?? 0}</p>
           <p class="voice-dock-channel">Backend runtime fallback events: {props.nativeSenderMetrics?.encoder_backend_runtime_fallback_events ?? 0}</p>
           <Show when={props.nativeSenderMetrics?.rtp_target}>
             <p class="voice-dock-channel">RTP target: {props.nativeSenderMetrics?.rtp_target}</p>
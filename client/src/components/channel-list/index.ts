@@ -2,6 +2,7 @@ export { default as ChannelRow, type ChannelRowProps } from "./ChannelRow";
 export { default as CreateChannelModal, type CreateChannelModalProps } from "./CreateChannelModal";
 export { default as EditChannelModal, type EditChannelModalProps } from "./EditChannelModal";
 export { default as ScreenShareModal, type ScreenShareModalProps } from "./ScreenShareModal";
+export { default as SoundboardPanel, type SoundboardPanelProps } from "./SoundboardPanel";
 export { default as VoiceDock, type VoiceDockProps } from "./VoiceDock";
 export * from "./helpers";
 export * from "./hooks";
@@ -32,11 +32,13 @@ export async function waitForMediaDerivative(
   apiBaseUrl: string,
   authToken: string,
   mediaId: string,
+  variant: "thumb_320" | "poster" = "thumb_320",
 ): Promise<void> {
-  const maxAttempts = 24;
+  // Videos are transcoded before their poster is written, which takes longer.
+  const maxAttempts = variant === "poster" ? 240 : 24;
   for (let attempt = 0; attempt < maxAttempts; attempt += 1) {
     try {
-      const probeUrl = `${toAbsoluteMediaUrl(apiBaseUrl, `/media/${mediaId}/thumbnail`)}?v=${Date.now()}-${attempt}`;
+      const probeUrl = `${toAbsoluteMediaUrl(apiBaseUrl, `/media/${mediaId}/${variant}`)}?v=${Date.now()}-${attempt}`;
       const response = await fetch(probeUrl, {
         headers: {
           Authorization: `Bearer ${authToken}`,
@@ -54,17 +56,21 @@ export async function waitForMediaDerivative(
     });
   }
 
-  throw new Error("Timed out preparing image preview");
+  throw new Error("Timed out preparing preview");
 }
 
-export function validateImageAttachment(file: File): string | null {
+export function isImageAttachment(file: File): boolean {
   const mimeType = file.type.toLowerCase();
   const name = file.name.toLowerCase();
 
   const hasAllowedMimeType = ["image/jpeg", "image/jpg", "image/pjpeg", "image/png", "image/webp", "image/gif"].includes(mimeType);
   const hasAllowedExtension = [".jpg", ".jpeg", ".png", ".webp", ".gif"].some((extension) => name.endsWith(extension));
 
-  if (!hasAllowedMimeType && !hasAllowedExtension) {
+  return hasAllowedMimeType || hasAllowedExtension;
+}
+
+export function validateImageAttachment(file: File): string | null {
+  if (!isImageAttachment(file)) {
     return "Only JPEG, PNG, WEBP, and GIF files are supported";
   }
 
@@ -75,15 +81,45 @@ export function validateImageAttachment(file: File): string | null {
   return null;
 }
 
+export function formatAttachmentDuration(durationMs: number): string {
+  const totalSeconds = Math.round(durationMs / 1000);
+  const minutes = Math.floor(totalSeconds / 60);
+  const seconds = totalSeconds % 60;
+  return `${minutes}:${seconds.toString().padStart(2, "0")}`;
+}
+
+export function validateFileAttachment(file: File): string | null {
+  if (file.size === 0) {
+    return "File is empty";
+  }
+
+  if (file.size > 25 * 1024 * 1024) {
+    return "File upload must be 25 MB or smaller";
+  }
+
+  return null;
+}
+
+export function formatAttachmentSize(bytes: number): string {
+  if (bytes < 1024) {
+    return `${bytes} B`;
+  }
+  if (bytes < 1024 * 1024) {
+    return `${(bytes / 1024).toFixed(1)} KB`;
+  }
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+}
+
 export async function uploadMediaFile(
   apiBaseUrl: string,
   authToken: string,
   file: File,
+  endpoint: "upload" | "files" = "upload",
 ): Promise<UploadMediaResponse> {
   const formData = new FormData();
   formData.append("file", file);
 
-  const response = await fetch(`${apiBaseUrl}/media/upload`, {
+  const response = await fetch(`${apiBaseUrl}/media/${endpoint}`, {
     method: "POST",
     headers: {
       Authorization: `Bearer ${authToken}`,
@@ -100,5 +136,5 @@ export async function uploadMediaFile(
 }
 
 export function uploadError(error: unknown): string {
-  return errorMessage(error, "Failed to upload image");
+  return errorMessage(error, "Failed to upload file");
 }
@@ -0,0 +1,233 @@
+import { For, Show, createSignal, onMount } from "solid-js";
+import {
+  approveSoundboardClip,
+  deleteSoundboardClip,
+  listSoundboardClips,
+  uploadSoundboardClip,
+  type SoundboardClip,
+} from "../../api/soundboard";
+import { userId } from "../../stores/auth";
+import { errorMessage } from "../../utils/error";
+
+export interface SoundboardSettingsProps {
+  isOperatorOrAdmin: boolean;
+}
+
+function formatClipDuration(durationMs: number): string {
+  return `${(durationMs / 1000).toFixed(1)}s`;
+}
+
+export default function SoundboardSettings(props: SoundboardSettingsProps) {
+  const [clips, setClips] = createSignal<SoundboardClip[]>([]);
+  const [loading, setLoading] = createSignal(false);
+  const [name, setName] = createSignal("");
+  const [file, setFile] = createSignal<File | null>(null);
+  const [isUploading, setIsUploading] = createSignal(false);
+  const [pendingClipId, setPendingClipId] = createSignal<string | null>(null);
+  const [formError, setFormError] = createSignal("");
+
+  let fileInputRef: HTMLInputElement | undefined;
+
+  async function loadClips() {
+    setLoading(true);
+    try {
+      setClips(await listSoundboardClips());
+    } catch (error) {
+      setFormError(errorMessage(error, "Failed to load soundboard"));
+    } finally {
+      setLoading(false);
+    }
+  }
+
+  onMount(() => {
+    void loadClips();
+  });
+
+  function validateUpload(fileValue: File, nameValue: string): string | null {
+    if (!nameValue || nameValue.length > 32) {
+      return "Name must be between 1 and 32 characters.";
+    }
+
+    if (fileValue.size > 2 * 1024 * 1024) {
+      return "Clip must be 2 MB or smaller.";
+    }
+
+    const fileName = fileValue.name.toLowerCase();
+    if (![".ogg", ".opus", ".mp3"].some((extension) => fileName.endsWith(extension))) {
+      return "Clip must be Opus, OGG, or MP3.";
+    }
+
+    return null;
+  }
+
+  async function handleUpload(event: Event) {
+    event.preventDefault();
+    if (isUploading()) {
+      return;
+    }
+
+    const nextFile = file();
+    if (!nextFile) {
+      setFormError("Please choose an audio file.");
+      return;
+    }
+
+    const nextName = name().trim();
+    const validationError = validateUpload(nextFile, nextName);
+    if (validationError) {
+      setFormError(validationError);
+      return;
+    }
+
+    setFormError("");
+    setIsUploading(true);
+    try {
+      await uploadSoundboardClip(nextName, nextFile);
+      setName("");
+      setFile(null);
+      if (fileInputRef) {
+        fileInputRef.value = "";
+      }
+      await loadClips();
+    } catch (error) {
+      setFormError(errorMessage(error, "Failed to upload clip"));
+    } finally {
+      setIsUploading(false);
+    }
+  }
+
+  async function runClipAction(clipId: string, action: () => Promise<unknown>, fallback: string) {
+    if (pendingClipId()) {
+      return;
+    }
+
+    setFormError("");
+    setPendingClipId(clipId);
+    try {
+      await action();
+      await loadClips();
+    } catch (error) {
+      setFormError(errorMessage(error, fallback));
+    } finally {
+      setPendingClipId(null);
+    }
+  }
+
+  function canDelete(clip: SoundboardClip): boolean {
+    return props.isOperatorOrAdmin || (!clip.approved && clip.uploaded_by === userId());
+  }
+
+  return (
+    <section class="settings-section">
+      <h5>Soundboard</h5>
+      <div class="emoji-settings-layout">
+        <form class="settings-audio-row emoji-upload-card" onSubmit={(event) => void handleUpload(event)}>
+          <h6 class="emoji-settings-card-title">Upload clip</h6>
+
+          <label class="settings-label" for="soundboard-name">Name</label>
+          <input
+            id="soundboard-name"
+            type="text"
+            value={name()}
+            maxlength={32}
+            placeholder="Airhorn"
+            onInput={(event) => setName(event.currentTarget.value)}
+            disabled={isUploading()}
+          />
+
+          <label class="settings-label" for="soundboard-file">Audio file</label>
+          <input
+            ref={fileInputRef}
+            id="soundboard-file"
+            type="file"
+            accept="audio/ogg,audio/opus,audio/mpeg,.ogg,.opus,.mp3"
+            onChange={(event) => setFile(event.currentTarget.files?.[0] ?? null)}
+            disabled={isUploading()}
+          />
+
+          <p class="settings-help">
+            Opus/OGG/MP3, up to 2 MB and 10 seconds. Volume is normalized on upload.
+            <Show when={!props.isOperatorOrAdmin}> An operator or admin has to approve new clips.</Show>
+          </p>
+
+          <div class="settings-actions">
+            <button type="submit" disabled={isUploading()}>{isUploading() ? "Uploading..." : "Upload clip"}</button>
+          </div>
+        </form>
+
+        <div class="emoji-library-card">
+          <div class="emoji-library-head">
+            <h6 class="emoji-settings-card-title">Clips</h6>
+            <Show when={clips().length > 0}>
+              <span class="emoji-library-count">{clips().length} total</span>
+            </Show>
+          </div>
+
+          <Show when={loading() && clips().length === 0}>
+            <p class="settings-help">Loading clips...</p>
+          </Show>
+
+          <Show when={!loading() && clips().length === 0}>
+            <p class="settings-help">No clips uploaded yet.</p>
+          </Show>
+
+          <Show when={clips().length > 0}>
+            <ul class="emoji-settings-list">
+              <For each={clips()}>
+                {(clip) => (
+                  <li class="emoji-settings-item">
+                    <div class="emoji-settings-item-main">
+                      <div>
+                        <p class="emoji-settings-shortcode">{clip.name}</p>
+                        <p class="emoji-settings-name">
+                          {formatClipDuration(clip.duration_ms)}
+                          {clip.approved ? "" : " · awaiting approval"}
+                        </p>
+                      </div>
+                    </div>
+                    <audio controls preload="none" src={clip.url} />
+                    <Show when={props.isOperatorOrAdmin && !clip.approved}>
+                      <button
+                        type="button"
+                        onClick={() => void runClipAction(
+                          clip.id,
+                          () => approveSoundboardClip(clip.id),
+                          "Failed to approve clip",
+                        )}
+                        disabled={pendingClipId() === clip.id}
+                      >
+                        Approve
+                      </button>
+                    </Show>
+                    <Show when={canDelete(clip)}>
+                      <button
+                        type="button"
+                        class="settings-secondary"
+                        onClick={() => {
+                          if (window.confirm(`Delete "${clip.name}"?`)) {
+                            void runClipAction(
+                              clip.id,
+                              () => deleteSoundboardClip(clip.id),
+                              "Failed to delete clip",
+                            );
+                          }
+                        }}
+                        disabled={pendingClipId() === clip.id}
+                      >
+                        {pendingClipId() === clip.id ? "Working..." : "Delete"}
+                      </button>
+                    </Show>
+                  </li>
+                )}
+              </For>
+            </ul>
+          </Show>
+        </div>
+      </div>
+
+      <Show when={formError()}>
+        <p class="error">{formError()}</p>
+      </Show>
+    </section>
+  );
+}
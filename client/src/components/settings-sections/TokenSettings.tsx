@@ -0,0 +1,151 @@
+import { For, Show, createSignal, onMount } from "solid-js";
+import {
+  createPersonalToken,
+  listPersonalTokens,
+  revokePersonalToken,
+  whipBaseUrl,
+  type CreatedPersonalToken,
+  type PersonalToken,
+} from "../../api/tokens";
+import { errorMessage } from "../../utils/error";
+
+function formatTokenTimestamp(value: string | null): string {
+  if (!value) {
+    return "Never";
+  }
+  const date = new Date(value);
+  if (Number.isNaN(date.getTime())) {
+    return "Invalid date";
+  }
+  return date.toLocaleString();
+}
+
+export default function TokenSettings() {
+  const [tokens, setTokens] = createSignal<PersonalToken[]>([]);
+  const [tokenName, setTokenName] = createSignal("");
+  const [createdToken, setCreatedToken] = createSignal<CreatedPersonalToken | null>(null);
+  const [tokenError, setTokenError] = createSignal("");
+  const [isCreatingToken, setIsCreatingToken] = createSignal(false);
+  const [copied, setCopied] = createSignal(false);
+
+  onMount(() => {
+    void refreshTokens();
+  });
+
+  async function refreshTokens() {
+    try {
+      setTokens(await listPersonalTokens());
+    } catch {
+      // non-blocking
+    }
+  }
+
+  async function handleCreateToken(event: Event) {
+    event.preventDefault();
+    setTokenError("");
+
+    const name = tokenName().trim();
+    if (!name) {
+      setTokenError("Give the token a name");
+      return;
+    }
+
+    setIsCreatingToken(true);
+    try {
+      const created = await createPersonalToken(name);
+      setCreatedToken(created);
+      setTokenName("");
+      setTokens((prev) => [
+        { id: created.id, name: created.name, created_at: created.created_at, last_used_at: null },
+        ...prev,
+      ]);
+    } catch (err) {
+      setTokenError(errorMessage(err, "Failed to create token"));
+    } finally {
+      setIsCreatingToken(false);
+    }
+  }
+
+  async function handleRevokeToken(id: string) {
+    setTokenError("");
+    try {
+      await revokePersonalToken(id);
+      setTokens((prev) => prev.filter((token) => token.id !== id));
+      if (createdToken()?.id === id) {
+        setCreatedToken(null);
+      }
+    } catch (err) {
+      setTokenError(errorMessage(err, "Failed to revoke token"));
+    }
+  }
+
+  function handleCopyToken(value: string) {
+    void navigator.clipboard.writeText(value).then(() => {
+      setCopied(true);
+      setTimeout(() => setCopied(false), 2000);
+    });
+  }
+
+  return (
+    <section class="settings-section">
+      <h5>Personal tokens</h5>
+      <p class="settings-help">
+        Tokens let external tools such as OBS stream into a voice channel over WHIP. Use{" "}
+        <code>{whipBaseUrl()}&lt;channel_id&gt;</code> as the server and the token as bearer token.
+      </p>
+
+      <form class="settings-actions" onSubmit={(event) => void handleCreateToken(event)}>
+        <input
+          id="settings-token-name"
+          type="text"
+          maxlength="64"
+          placeholder="Token name"
+          value={tokenName()}
+          onInput={(event) => setTokenName(event.currentTarget.value)}
+        />
+        <button type="submit" disabled={isCreatingToken()}>
+          {isCreatingToken() ? "Creating..." : "Create token"}
+        </button>
+      </form>
+      <Show when={tokenError()}>
+        <p class="error">{tokenError()}</p>
+      </Show>
+
+      <Show when={createdToken()}>
+        {(created) => (
+          <div class="invite-card">
+            <div class="invite-card-header">
+              <code class="invite-code">{created().token}</code>
+              <button type="button" class="invite-copy-btn" onClick={() => handleCopyToken(created().token)}>
+                {copied() ? "Copied" : "Copy"}
+              </button>
+            </div>
+            <p class="settings-help">Copy this token now. It will not be shown again.</p>
+          </div>
+        )}
+      </Show>
+
+      <div class="invite-list">
+        <For each={tokens()}>
+          {(token) => (
+            <div class="invite-card">
+              <div class="invite-card-header">
+                <span>{token.name}</span>
+                <button type="button" class="invite-revoke-btn" onClick={() => void handleRevokeToken(token.id)}>
+                  Revoke
+                </button>
+              </div>
+              <div class="invite-card-meta">
+                <span>Created {formatTokenTimestamp(token.created_at)}</span>
+                <span>Last used {formatTokenTimestamp(token.last_used_at)}</span>
+              </div>
+            </div>
+          )}
+        </For>
+        <Show when={tokens().length === 0}>
+          <p class="settings-help">No personal tokens yet.</p>
+        </Show>
+      </div>
+    </section>
+  );
+}
@@ -1,2 +1,4 @@
 export { default as InviteSettings, type InviteSettingsProps } from "./InviteSettings";
 export { default as EmojiSettings, type EmojiSettingsProps } from "./EmojiSettings";
+export { default as TokenSettings } from "./TokenSettings";
+export { default as SoundboardSettings, type SoundboardSettingsProps } from "./SoundboardSettings";
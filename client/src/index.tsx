@@ -8,6 +8,7 @@ import Setup from "./pages/Setup";
 import Register from "./pages/Register";
 import Chat from "./pages/Chat";
 import AdminSettings from "./pages/AdminSettings";
+import Watch from "./pages/Watch";
 import { isAuthenticated, normalizeServerUrl, serverUrl } from "./stores/auth";
 import "./styles/global.css";
 
@@ -103,6 +104,7 @@ render(
       <Route path="/invite/:code?" component={RegisterRoute} />
       <Route path="/chat" component={ChatRoute} />
       <Route path="/admin/settings" component={AdminSettingsRoute} />
+      <Route path="/watch/:producerId" component={Watch} />
       <Route path="/" component={RootRoute} />
     </Router>
   ),
@@ -0,0 +1,57 @@
+import { Show, createSignal, onCleanup, onMount } from "solid-js";
+import { useParams, useSearchParams } from "@solidjs/router";
+import { startWhepPlayback, type WhepPlayback } from "../api/whep";
+import { errorMessage } from "../utils/error";
+
+export default function Watch() {
+  const params = useParams<{ producerId: string }>();
+  const [searchParams] = useSearchParams<{ token?: string }>();
+  const [error, setError] = createSignal("");
+  const [isConnecting, setIsConnecting] = createSignal(true);
+  let videoRef: HTMLVideoElement | undefined;
+  let playback: WhepPlayback | null = null;
+  let disposed = false;
+
+  onMount(() => {
+    void (async () => {
+      try {
+        const started = await startWhepPlayback(params.producerId, searchParams.token);
+        if (disposed) {
+          await started.close();
+          return;
+        }
+
+        playback = started;
+        if (videoRef) {
+          videoRef.srcObject = started.stream;
+          void videoRef.play().catch(() => undefined);
+        }
+      } catch (err) {
+        setError(errorMessage(err, "Could not start the stream"));
+      } finally {
+        setIsConnecting(false);
+      }
+    })();
+  });
+
+  onCleanup(() => {
+    disposed = true;
+    if (videoRef) {
+      videoRef.srcObject = null;
+    }
+    void playback?.close();
+    playback = null;
+  });
+
+  return (
+    <div class="stream-viewer-page">
+      <video ref={videoRef} class="stream-viewer-video" autoplay playsinline controls />
+      <Show when={isConnecting()}>
+        <p class="stream-viewer-status">Connecting to stream...</p>
+      </Show>
+      <Show when={error()}>
+        <p class="stream-viewer-status error">{error()}</p>
+      </Show>
+    </div>
+  );
+}
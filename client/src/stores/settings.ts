@@ -9,6 +9,7 @@ const SCREEN_SHARE_FPS_KEY = "yankcord_screen_share_fps";
 const SCREEN_SHARE_BITRATE_MODE_KEY = "yankcord_screen_share_bitrate_mode";
 const SCREEN_SHARE_CUSTOM_BITRATE_KEY = "yankcord_screen_share_custom_bitrate_kbps";
 const SCREEN_SHARE_SOURCE_KIND_KEY = "yankcord_screen_share_source_kind";
+const SCREEN_SHARE_AUDIO_ENABLED_KEY = "yankcord_screen_share_audio_enabled";
 const VOICE_JOIN_SOUND_ENABLED_KEY = "yankcord_voice_join_sound_enabled";
 const VOICE_LEAVE_SOUND_ENABLED_KEY = "yankcord_voice_leave_sound_enabled";
 const MESSAGE_NOTIFICATION_SOUND_ENABLED_KEY = "yankcord_message_notification_sound_enabled";
@@ -153,6 +154,10 @@ const [preferredScreenShareSourceKind, setPreferredScreenShareSourceKind] = crea
   readScreenShareSourceKind(),
 );
 
+const [preferredScreenShareAudioEnabled, setPreferredScreenShareAudioEnabled] = createSignal<boolean>(
+  readBooleanPreference(SCREEN_SHARE_AUDIO_ENABLED_KEY, false),
+);
+
 const [voiceJoinSoundEnabled, setVoiceJoinSoundEnabled] = createSignal<boolean>(
   readBooleanPreference(VOICE_JOIN_SOUND_ENABLED_KEY, true),
 );
@@ -199,6 +204,7 @@ export {
   preferredScreenShareBitrateMode,
   preferredScreenShareCustomBitrateKbps,
   preferredScreenShareSourceKind,
+  preferredScreenShareAudioEnabled,
   voiceJoinSoundEnabled,
   voiceLeaveSoundEnabled,
   messageNotificationSoundEnabled,
@@ -276,6 +282,11 @@ export function savePreferredScreenShareSourceKind(kind: ScreenShareSourceKind)
   setPreferredScreenShareSourceKind(kind);
 }
 
+export function savePreferredScreenShareAudioEnabled(enabled: boolean) {
+  localStorage.setItem(SCREEN_SHARE_AUDIO_ENABLED_KEY, String(enabled));
+  setPreferredScreenShareAudioEnabled(enabled);
+}
+
 export function saveVoiceJoinSoundEnabled(enabled: boolean) {
   localStorage.setItem(VOICE_JOIN_SOUND_ENABLED_KEY, String(enabled));
   setVoiceJoinSoundEnabled(enabled);
@@ -323,7 +334,7 @@ export function saveVoiceOutgoingVolume(volume: number) {
   setVoiceOutgoingVolume(normalized);
 }
 
-export type SettingsSection = "profile" | "audio" | "emojis" | "notifications" | "session";
+export type SettingsSection = "profile" | "audio" | "emojis" | "soundboard" | "notifications" | "tokens" | "session";
 
 const [settingsOpen, setSettingsOpen] = createSignal(false);
 const [activeSettingsSection, setActiveSettingsSection] = createSignal<SettingsSection>("profile");
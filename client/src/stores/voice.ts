@@ -18,6 +18,8 @@ export interface VoicePresenceChannel {
   channel_id: string;
   usernames: string[];
   mute_states: Record<string, { mic_muted: boolean; speaker_muted: boolean }>;
+  viewers?: string[];
+  anonymous_viewers?: number;
 }
 
 export interface VoiceMuteState {
@@ -25,10 +27,28 @@ export interface VoiceMuteState {
   speakerMuted: boolean;
 }
 
+export interface VoiceChannelViewers {
+  viewers: string[];
+  anonymousViewers: number;
+}
+
+export type NetworkQualityLevel = "good" | "fair" | "poor";
+
+export interface VoiceNetworkQuality {
+  score: number;
+  level: NetworkQualityLevel;
+  rttMs: number | null;
+  packetLoss: number | null;
+}
+
 const [joinedVoiceChannelId, setJoinedVoiceChannelId] = createSignal<string | null>(null);
 const [participantsByChannel, setParticipantsByChannel] = createSignal<Record<string, string[]>>({});
 const [speakingByChannel, setSpeakingByChannel] = createSignal<Record<string, string[]>>({});
 const [muteStateByChannel, setMuteStateByChannel] = createSignal<Record<string, Record<string, VoiceMuteState>>>({});
+const [networkQualityByChannel, setNetworkQualityByChannel] = createSignal<
+  Record<string, Record<string, VoiceNetworkQuality>>
+>({});
+const [viewersByChannel, setViewersByChannel] = createSignal<Record<string, VoiceChannelViewers>>({});
 const [voiceActionState, setVoiceActionState] = createSignal<VoiceActionState>("idle");
 const [micMuted, setMicMuted] = createSignal(false);
 const [speakerMuted, setSpeakerMuted] = createSignal(false);
@@ -69,8 +89,15 @@ function sortUnique(usernames: string[]): string[] {
 export function applyVoiceSnapshot(channels: VoicePresenceChannel[]) {
   const next: Record<string, string[]> = {};
   const nextMuteState: Record<string, Record<string, VoiceMuteState>> = {};
+  const nextViewers: Record<string, VoiceChannelViewers> = {};
   for (const channel of channels) {
     next[channel.channel_id] = sortUnique(channel.usernames);
+    if ((channel.viewers?.length ?? 0) > 0 || (channel.anonymous_viewers ?? 0) > 0) {
+      nextViewers[channel.channel_id] = {
+        viewers: sortUnique(channel.viewers ?? []),
+        anonymousViewers: channel.anonymous_viewers ?? 0,
+      };
+    }
     nextMuteState[channel.channel_id] = Object.fromEntries(
       Object.entries(channel.mute_states).map(([username, state]) => [
         username,
@@ -83,6 +110,7 @@ export function applyVoiceSnapshot(channels: VoicePresenceChannel[]) {
   }
   setParticipantsByChannel(next);
   setMuteStateByChannel(nextMuteState);
+  setViewersByChannel(nextViewers);
   setSpeakingByChannel({});
 }
 
@@ -163,6 +191,20 @@ export function applyVoiceLeft(channelId: string, leftUsername: string) {
       [channelId]: nextMembers,
     };
   });
+
+  setNetworkQualityByChannel((current) => {
+    const existing = current[channelId] ?? {};
+    if (!existing[leftUsername]) {
+      return current;
+    }
+
+    const nextMembers = { ...existing };
+    delete nextMembers[leftUsername];
+    return {
+      ...current,
+      [channelId]: nextMembers,
+    };
+  });
 }
 
 export function removeVoiceChannelState(channelId: string) {
@@ -195,6 +237,26 @@ export function removeVoiceChannelState(channelId: string) {
     delete next[channelId];
     return next;
   });
+
+  setNetworkQualityByChannel((current) => {
+    if (!current[channelId]) {
+      return current;
+    }
+
+    const next = { ...current };
+    delete next[channelId];
+    return next;
+  });
+
+  setViewersByChannel((current) => {
+    if (!current[channelId]) {
+      return current;
+    }
+
+    const next = { ...current };
+    delete next[channelId];
+    return next;
+  });
 }
 
 export function applyVoiceMuteState(channelId: string, username: string, muteState: VoiceMuteState) {
@@ -210,6 +272,25 @@ export function applyVoiceMuteState(channelId: string, username: string, muteSta
   });
 }
 
+export function applyVoiceNetworkQuality(channelId: string, qualities: Record<string, VoiceNetworkQuality>) {
+  setNetworkQualityByChannel((current) => ({
+    ...current,
+    [channelId]: qualities,
+  }));
+}
+
+export function applyVoiceChannelViewers(channelId: string, viewers: VoiceChannelViewers) {
+  setViewersByChannel((current) => {
+    const next = { ...current };
+    if (viewers.viewers.length === 0 && viewers.anonymousViewers === 0) {
+      delete next[channelId];
+    } else {
+      next[channelId] = { viewers: sortUnique(viewers.viewers), anonymousViewers: viewers.anonymousViewers };
+    }
+    return next;
+  });
+}
+
 export function applyVoiceSpeaking(channelId: string, username: string, speaking: boolean) {
   setSpeakingByChannel((current) => {
     const existing = current[channelId] ?? [];
@@ -259,6 +340,14 @@ export function voiceMemberMuteState(channelId: string, username: string): Voice
   return muteStateByChannel()[channelId]?.[username] ?? { micMuted: false, speakerMuted: false };
 }
 
+export function voiceMemberNetworkQuality(channelId: string, username: string): VoiceNetworkQuality | null {
+  return networkQualityByChannel()[channelId]?.[username] ?? null;
+}
+
+export function voiceChannelViewers(channelId: string): VoiceChannelViewers | null {
+  return viewersByChannel()[channelId] ?? null;
+}
+
 export function setJoinedVoiceChannel(channelId: string | null) {
   setJoinedVoiceChannelId(channelId);
 }
@@ -411,6 +500,8 @@ export function resetVoiceState() {
   setParticipantsByChannel({});
   setSpeakingByChannel({});
   setMuteStateByChannel({});
+  setNetworkQualityByChannel({});
+  setViewersByChannel({});
   setVoiceActionState("idle");
   setMicMuted(false);
   setSpeakerMuted(false);
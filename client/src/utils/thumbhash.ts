@@ -0,0 +1,148 @@
+// Decoder for the ThumbHash placeholders the server attaches to processed
+// images and video posters (https://evanw.github.io/thumbhash/).
+
+const dataUrlCache = new Map<string, string | null>();
+
+interface DecodedThumbHash {
+  width: number;
+  height: number;
+  rgba: Uint8ClampedArray;
+}
+
+function decodeBase64(value: string): Uint8Array | null {
+  try {
+    const binary = atob(value);
+    const bytes = new Uint8Array(binary.length);
+    for (let index = 0; index < binary.length; index += 1) {
+      bytes[index] = binary.charCodeAt(index);
+    }
+    return bytes;
+  } catch {
+    return null;
+  }
+}
+
+function thumbHashToRgba(hash: Uint8Array): DecodedThumbHash {
+  const header24 = hash[0] | (hash[1] << 8) | (hash[2] << 16);
+  const header16 = hash[3] | (hash[4] << 8);
+  const lDc = (header24 & 63) / 63;
+  const pDc = ((header24 >> 6) & 63) / 31.5 - 1;
+  const qDc = ((header24 >> 12) & 63) / 31.5 - 1;
+  const lScale = ((header24 >> 18) & 31) / 31;
+  const hasAlpha = (header24 >> 23) !== 0;
+  const pScale = ((header16 >> 3) & 63) / 63;
+  const qScale = ((header16 >> 9) & 63) / 63;
+  const isLandscape = (header16 >> 15) !== 0;
+  const lx = Math.max(3, isLandscape ? (hasAlpha ? 5 : 7) : header16 & 7);
+  const ly = Math.max(3, isLandscape ? header16 & 7 : (hasAlpha ? 5 : 7));
+  const aDc = hasAlpha ? (hash[5] & 15) / 15 : 1;
+  const aScale = (hash[5] >> 4) / 15;
+
+  const acStart = hasAlpha ? 6 : 5;
+  let acIndex = 0;
+  const decodeChannel = (nx: number, ny: number, scale: number): number[] => {
+    const ac: number[] = [];
+    for (let cy = 0; cy < ny; cy += 1) {
+      for (let cx = cy ? 0 : 1; cx * ny < nx * (ny - cy); cx += 1) {
+        const nibble = (hash[acStart + (acIndex >> 1)] >> ((acIndex & 1) << 2)) & 15;
+        ac.push((nibble / 7.5 - 1) * scale);
+        acIndex += 1;
+      }
+    }
+    return ac;
+  };
+  const lAc = decodeChannel(lx, ly, lScale);
+  // Saturation is boosted to make up for quantization, as the reference does.
+  const pAc = decodeChannel(3, 3, pScale * 1.25);
+  const qAc = decodeChannel(3, 3, qScale * 1.25);
+  const aAc = hasAlpha ? decodeChannel(5, 5, aScale) : [];
+
+  const ratio = thumbHashAspectRatio(hash);
+  const width = Math.round(ratio > 1 ? 32 : 32 * ratio);
+  const height = Math.round(ratio > 1 ? 32 / ratio : 32);
+  const rgba = new Uint8ClampedArray(width * height * 4);
+  const fx: number[] = [];
+  const fy: number[] = [];
+  for (let y = 0, i = 0; y < height; y += 1) {
+    for (let x = 0; x < width; x += 1, i += 4) {
+      let l = lDc;
+      let p = pDc;
+      let q = qDc;
+      let a = aDc;
+
+      for (let cx = 0, n = Math.max(lx, hasAlpha ? 5 : 3); cx < n; cx += 1) {
+        fx[cx] = Math.cos((Math.PI / width) * (x + 0.5) * cx);
+      }
+      for (let cy = 0, n = Math.max(ly, hasAlpha ? 5 : 3); cy < n; cy += 1) {
+        fy[cy] = Math.cos((Math.PI / height) * (y + 0.5) * cy);
+      }
+
+      for (let cy = 0, j = 0; cy < ly; cy += 1) {
+        for (let cx = cy ? 0 : 1, fy2 = fy[cy] * 2; cx * ly < lx * (ly - cy); cx += 1, j += 1) {
+          l += lAc[j] * fx[cx] * fy2;
+        }
+      }
+      for (let cy = 0, j = 0; cy < 3; cy += 1) {
+        for (let cx = cy ? 0 : 1, fy2 = fy[cy] * 2; cx < 3 - cy; cx += 1, j += 1) {
+          const f = fx[cx] * fy2;
+          p += pAc[j] * f;
+          q += qAc[j] * f;
+        }
+      }
+      if (hasAlpha) {
+        for (let cy = 0, j = 0; cy < 5; cy += 1) {
+          for (let cx = cy ? 0 : 1, fy2 = fy[cy] * 2; cx < 5 - cy; cx += 1, j += 1) {
+            a += aAc[j] * fx[cx] * fy2;
+          }
+        }
+      }
+
+      const b = l - (2 / 3) * p;
+      const r = (3 * l - b + q) / 2;
+      const g = r - q;
+      rgba[i] = 255 * r;
+      rgba[i + 1] = 255 * g;
+      rgba[i + 2] = 255 * b;
+      rgba[i + 3] = 255 * a;
+    }
+  }
+
+  return { width, height, rgba };
+}
+
+function thumbHashAspectRatio(hash: Uint8Array): number {
+  const header = hash[3];
+  const hasAlpha = (hash[2] & 0x80) !== 0;
+  const isLandscape = (hash[4] & 0x80) !== 0;
+  const lx = isLandscape ? (hasAlpha ? 5 : 7) : header & 7;
+  const ly = isLandscape ? header & 7 : (hasAlpha ? 5 : 7);
+  return lx / ly;
+}
+
+/**
+ * Renders a base64 ThumbHash to a small PNG data URL suitable for a CSS
+ * background. Returns null for malformed hashes or without a canvas.
+ */
+export function thumbHashToDataUrl(placeholder: string): string | null {
+  const cached = dataUrlCache.get(placeholder);
+  if (cached !== undefined) {
+    return cached;
+  }
+
+  let dataUrl: string | null = null;
+  const hash = decodeBase64(placeholder);
+  if (hash && hash.length >= 5 && typeof document !== "undefined") {
+    const { width, height, rgba } = thumbHashToRgba(hash);
+    const canvas = document.createElement("canvas");
+    canvas.width = width;
+    canvas.height = height;
+    const context = canvas.getContext("2d");
+    if (context) {
+      context.putImageData(new ImageData(rgba, width, height), 0, 0);
+      dataUrl = canvas.toDataURL();
+    }
+  }
+
+  dataUrlCache.set(placeholder, dataUrl);
+  return dataUrl;
+}
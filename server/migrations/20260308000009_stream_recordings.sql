@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS stream_recordings (
+    media_id    UUID PRIMARY KEY REFERENCES media_assets(id) ON DELETE CASCADE,
+    channel_id  UUID NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+    started_by  UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    started_at  TIMESTAMPTZ NOT NULL,
+    ended_at    TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_stream_recordings_started_by
+    ON stream_recordings (started_by, ended_at DESC);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS soundboard_clips (
+    id           UUID PRIMARY KEY,
+    name         TEXT NOT NULL,
+    media_id     UUID NOT NULL REFERENCES media_assets(id) ON DELETE CASCADE,
+    duration_ms  INTEGER NOT NULL,
+    uploaded_by  UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- NULL until an operator or admin adds the clip to the soundboard.
+    approved_by  UUID REFERENCES users(id) ON DELETE SET NULL,
+    approved_at  TIMESTAMPTZ,
+    created_at   TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_soundboard_clips_uploaded_by ON soundboard_clips (uploaded_by);
+CREATE INDEX IF NOT EXISTS idx_soundboard_clips_approved_at ON soundboard_clips (approved_at);
@@ -0,0 +1,6 @@
+-- Generic file attachments keep the name they were uploaded with.
+ALTER TABLE media_assets
+    ADD COLUMN IF NOT EXISTS original_filename TEXT;
+
+ALTER TABLE message_attachments
+    ADD COLUMN IF NOT EXISTS filename TEXT;
@@ -0,0 +1,3 @@
+-- Playback length of processed video uploads.
+ALTER TABLE media_assets
+    ADD COLUMN IF NOT EXISTS duration_ms INTEGER;
@@ -0,0 +1,39 @@
+-- Content-addressed storage objects shared by identical uploads. Each blob
+-- lives at `blobs/<sha256 prefix>/<sha256>`; `ref_count` follows the
+-- media_assets rows pointing at it, including rows removed by cascades.
+CREATE TABLE IF NOT EXISTS media_blobs (
+    storage_key TEXT PRIMARY KEY,
+    checksum    TEXT NOT NULL UNIQUE,
+    bytes       BIGINT NOT NULL,
+    ref_count   INTEGER NOT NULL DEFAULT 0,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- Last time an upload claimed the blob; unreferenced blobs are only
+    -- collected once this is older than the cleanup grace period.
+    updated_at  TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_media_blobs_unreferenced
+    ON media_blobs (updated_at ASC)
+    WHERE ref_count <= 0;
+
+ALTER TABLE media_assets DROP CONSTRAINT IF EXISTS media_assets_storage_key_key;
+
+CREATE INDEX IF NOT EXISTS idx_media_assets_storage_key
+    ON media_assets (storage_key);
+
+CREATE OR REPLACE FUNCTION media_blobs_track_references() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        UPDATE media_blobs SET ref_count = ref_count - 1 WHERE storage_key = OLD.storage_key;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE media_blobs SET ref_count = ref_count + 1 WHERE storage_key = NEW.storage_key;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS media_assets_blob_references ON media_assets;
+CREATE TRIGGER media_assets_blob_references
+    AFTER INSERT OR DELETE OR UPDATE OF storage_key ON media_assets
+    FOR EACH ROW EXECUTE FUNCTION media_blobs_track_references();
@@ -0,0 +1,21 @@
+-- tus resumable uploads in progress. Received bytes are staged as one
+-- storage object per PATCH at `uploads/<id>/<offset>`, listed in
+-- `chunk_offsets`, until the upload completes and becomes a media asset.
+CREATE TABLE IF NOT EXISTS resumable_uploads (
+    id              UUID PRIMARY KEY,
+    owner_id        UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    upload_length   BIGINT NOT NULL CHECK (upload_length > 0),
+    upload_offset   BIGINT NOT NULL DEFAULT 0,
+    chunk_offsets   BIGINT[] NOT NULL DEFAULT '{}',
+    filename        TEXT,
+    -- Hex SHA-256 the client declared in `Upload-Metadata`, checked once
+    -- every byte has arrived.
+    expected_sha256 TEXT,
+    media_id        UUID REFERENCES media_assets(id) ON DELETE SET NULL,
+    created_at      TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at      TIMESTAMPTZ NOT NULL,
+    CHECK (upload_offset BETWEEN 0 AND upload_length)
+);
+
+CREATE INDEX IF NOT EXISTS idx_resumable_uploads_expires_at
+    ON resumable_uploads (expires_at);
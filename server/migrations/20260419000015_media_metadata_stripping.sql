@@ -0,0 +1,4 @@
+-- Whether EXIF, XMP, IPTC or ICC data was removed from the stored asset
+-- when its upload was re-encoded.
+ALTER TABLE media_assets
+    ADD COLUMN IF NOT EXISTS metadata_stripped BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,5 @@
+-- ThumbHash (https://evanw.github.io/thumbhash/) of processed images and
+-- video posters, base64-encoded, for clients to draw while the real
+-- thumbnail loads.
+ALTER TABLE media_assets
+    ADD COLUMN IF NOT EXISTS placeholder TEXT;
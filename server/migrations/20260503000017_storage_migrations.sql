@@ -0,0 +1,20 @@
+-- Objects already copied and verified by `yankcord-server migrate-storage`,
+-- so an interrupted run resumes where it stopped.
+CREATE TABLE IF NOT EXISTS storage_migration_objects (
+    target_backend TEXT NOT NULL,
+    storage_key TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    bytes BIGINT NOT NULL,
+    copied_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (target_backend, storage_key)
+);
+
+-- The backend a finished migration switched to. It applies while the
+-- configured backend is still the one migrated from, until the operator
+-- updates the configuration to match.
+CREATE TABLE IF NOT EXISTS storage_backend_switch (
+    singleton BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (singleton),
+    source_backend TEXT NOT NULL,
+    target_backend TEXT NOT NULL,
+    switched_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,9 @@
+-- When an upload was flagged by the malware scanner and moved under
+-- `quarantine/`. Quarantined assets stay `failed`, are never served, and
+-- are kept for admins instead of being cleaned up with other failures.
+ALTER TABLE media_assets
+    ADD COLUMN IF NOT EXISTS quarantined_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_media_assets_quarantined
+    ON media_assets (quarantined_at)
+    WHERE quarantined_at IS NOT NULL;
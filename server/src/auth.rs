@@ -8,6 +8,7 @@ use argon2::{
 use chrono::Utc;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 use crate::errors::AppError;
@@ -111,17 +112,79 @@ pub fn generate_invite_code() -> String {
     )
 }
 
-pub fn extract_claims(headers: &axum::http::HeaderMap, secret: &str) -> Result<Claims, AppError> {
+/// Prefix that distinguishes personal access tokens from session JWTs.
+pub const PERSONAL_TOKEN_PREFIX: &str = "ycp_";
+
+pub fn generate_personal_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    format!("{PERSONAL_TOKEN_PREFIX}{}", hex::encode(bytes))
+}
+
+/// Personal tokens are high-entropy, so a plain SHA-256 digest is enough to
+/// avoid storing them in the clear while keeping lookups indexable.
+pub fn hash_personal_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StreamViewerClaims {
+    pub scope: String,
+    pub producer_id: String,
+    pub exp: usize,
+}
+
+const STREAM_VIEWER_SCOPE: &str = "stream_viewer";
+
+/// Signs a stream-viewer link token that lets anyone holding it watch one
+/// producer over WHEP until `expires_at`, without an account.
+pub fn create_stream_viewer_token(
+    producer_id: &str,
+    secret: &str,
+    expires_at: chrono::DateTime<Utc>,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = StreamViewerClaims {
+        scope: STREAM_VIEWER_SCOPE.to_string(),
+        producer_id: producer_id.to_string(),
+        exp: expires_at.timestamp() as usize,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+}
+
+/// Returns the producer id a stream-viewer link token was issued for.
+pub fn validate_stream_viewer_token(token: &str, secret: &str) -> Result<String, AppError> {
+    let token_data = decode::<StreamViewerClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|e| AppError::Unauthorized(e.to_string()))?;
+
+    if token_data.claims.scope != STREAM_VIEWER_SCOPE {
+        return Err(AppError::Unauthorized("Invalid stream viewer token".into()));
+    }
+
+    Ok(token_data.claims.producer_id)
+}
+
+pub fn extract_bearer_token(headers: &axum::http::HeaderMap) -> Result<&str, AppError> {
     let header = headers
         .get("authorization")
         .and_then(|v| v.to_str().ok())
         .ok_or_else(|| AppError::Unauthorized("Missing authorization header".into()))?;
 
-    let token = header
+    header
         .strip_prefix("Bearer ")
-        .ok_or_else(|| AppError::Unauthorized("Invalid authorization format".into()))?;
+        .ok_or_else(|| AppError::Unauthorized("Invalid authorization format".into()))
+}
 
-    validate_token(token, secret)
+pub fn extract_claims(headers: &axum::http::HeaderMap, secret: &str) -> Result<Claims, AppError> {
+    validate_token(extract_bearer_token(headers)?, secret)
 }
 
 pub fn is_operator_or_admin_role(role: &str) -> bool {
@@ -172,4 +235,19 @@ mod tests {
             _ => panic!("expected unauthorized error"),
         }
     }
+
+    #[test]
+    fn stream_viewer_tokens_are_not_session_tokens() {
+        let expires_at = Utc::now() + chrono::Duration::hours(1);
+        let token = create_stream_viewer_token("producer-1", "secret", expires_at).unwrap();
+
+        assert_eq!(
+            validate_stream_viewer_token(&token, "secret").unwrap(),
+            "producer-1"
+        );
+        assert!(validate_token(&token, "secret").is_err());
+
+        let session = create_token(Uuid::new_v4(), "tester", "member", "secret", 1).unwrap();
+        assert!(validate_stream_viewer_token(&session, "secret").is_err());
+    }
 }
@@ -11,6 +11,8 @@ pub struct AppConfig {
     pub storage: StorageConfig,
     #[serde(default)]
     pub integrations: IntegrationsConfig,
+    #[serde(default)]
+    pub turn: TurnConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -45,6 +47,29 @@ pub struct MediaConfig {
     pub native_rtp_listen_ip: String,
     #[serde(default)]
     pub native_rtp_announced_ip: Option<String>,
+    /// Runs one mediasoup `WebRtcServer` per worker so that all WebRTC
+    /// transports share a fixed port instead of one random port each.
+    #[serde(default)]
+    pub webrtc_server_enabled: bool,
+    /// First UDP/TCP port used by the per-worker `WebRtcServer`s. Worker `n`
+    /// listens on `webrtc_server_port + n`.
+    #[serde(default = "default_webrtc_server_port")]
+    pub webrtc_server_port: u16,
+    /// Offers ICE-TCP candidates next to UDP for networks that block UDP.
+    #[serde(default = "default_webrtc_enable_tcp")]
+    pub webrtc_enable_tcp: bool,
+    /// Port range for per-transport sockets (WebRTC without a
+    /// `WebRtcServer`, and native RTP plain transports).
+    #[serde(default)]
+    pub rtc_min_port: Option<u16>,
+    #[serde(default)]
+    pub rtc_max_port: Option<u16>,
+    /// ffmpeg binary used to turn restreamed producers into HLS.
+    #[serde(default = "default_ffmpeg_bin")]
+    pub ffmpeg_bin: String,
+    /// ffprobe binary used to read duration and dimensions of video uploads.
+    #[serde(default = "default_ffprobe_bin")]
+    pub ffprobe_bin: String,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -59,6 +84,33 @@ pub struct StorageConfig {
     pub cleanup_interval_seconds: u64,
     #[serde(default = "default_media_failed_retention_hours")]
     pub failed_retention_hours: i64,
+    /// Size limit for generic file attachments (`POST /api/media/files`).
+    #[serde(default = "default_media_max_file_upload_bytes")]
+    pub max_file_upload_bytes: usize,
+    /// File attachment types to accept: MIME types, `type/*` families or
+    /// `.ext` extensions. Empty accepts anything not denied.
+    #[serde(default)]
+    pub file_allowed_types: Vec<String>,
+    /// File attachment types to reject, in the same format. Defaults to
+    /// executables and scripts.
+    #[serde(default = "default_file_denied_types")]
+    pub file_denied_types: Vec<String>,
+    /// Web-safe variant made for videos browsers cannot play as uploaded:
+    /// `h264` (MP4 with AAC), `vp9` (WebM with Opus) or `off`.
+    #[serde(default = "default_media_video_transcode")]
+    pub video_transcode: String,
+    /// Total bytes of media one user may own, derivatives included. `0`
+    /// disables the limit.
+    #[serde(default = "default_media_user_quota_bytes")]
+    pub user_quota_bytes: u64,
+    /// Total bytes of stored media across the instance, counting shared
+    /// blobs once. `0` disables the limit.
+    #[serde(default)]
+    pub total_quota_bytes: u64,
+    /// clamd that scans file attachments before they are stored:
+    /// `host:port`, or `unix:/path` for a socket. Unset disables scanning.
+    #[serde(default)]
+    pub clamd_address: Option<String>,
     #[serde(default)]
     pub s3: S3Config,
 }
@@ -74,6 +126,37 @@ pub struct S3Config {
     pub force_path_style: bool,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct TurnConfig {
+    /// Runs the embedded TURN/STUN server and hands out relay credentials.
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_turn_listen_ip")]
+    pub listen_ip: String,
+    #[serde(default = "default_turn_port")]
+    pub port: u16,
+    #[serde(default = "default_turn_enable_tcp")]
+    pub enable_tcp: bool,
+    /// Host clients use in `turn:` URLs. Defaults to the WebRTC announced IP.
+    #[serde(default)]
+    pub public_host: Option<String>,
+    /// Address advertised for relayed candidates. Defaults to the WebRTC
+    /// announced IP, then the listen IP.
+    #[serde(default)]
+    pub relay_ip: Option<String>,
+    #[serde(default)]
+    pub relay_min_port: Option<u16>,
+    #[serde(default)]
+    pub relay_max_port: Option<u16>,
+    #[serde(default = "default_turn_realm")]
+    pub realm: String,
+    #[serde(default = "default_turn_credential_ttl_seconds")]
+    pub credential_ttl_seconds: u64,
+    /// Concurrent TURN-over-TCP clients; each one runs its own allocation.
+    #[serde(default = "default_turn_max_tcp_connections")]
+    pub max_tcp_connections: usize,
+}
+
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct IntegrationsConfig {
     #[serde(alias = "tenor_api_key")]
@@ -84,6 +167,72 @@ fn default_native_rtp_listen_ip() -> String {
     "127.0.0.1".to_string()
 }
 
+fn default_ffmpeg_bin() -> String {
+    "ffmpeg".to_string()
+}
+
+fn default_ffprobe_bin() -> String {
+    "ffprobe".to_string()
+}
+
+fn default_webrtc_server_port() -> u16 {
+    44_444
+}
+
+fn default_webrtc_enable_tcp() -> bool {
+    false
+}
+
+/// Whether every media worker gets its own `WebRtcServer` port below 65536.
+fn webrtc_server_ports_fit(base_port: u16, worker_count: usize) -> bool {
+    usize::from(base_port) + worker_count <= usize::from(u16::MAX) + 1
+}
+
+fn parse_bool_env(env_key: &str) -> Option<bool> {
+    std::env::var(env_key).ok().map(|value| {
+        matches!(
+            value.to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes" | "on"
+        )
+    })
+}
+
+fn parse_optional_port_env(env_key: &str) -> Option<u16> {
+    std::env::var(env_key)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .unwrap_or_else(|_| panic!("{env_key} must be a port number"))
+        })
+}
+
+fn default_turn_listen_ip() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_turn_port() -> u16 {
+    3478
+}
+
+fn default_turn_enable_tcp() -> bool {
+    true
+}
+
+fn default_turn_realm() -> String {
+    "yankcord".to_string()
+}
+
+fn default_turn_credential_ttl_seconds() -> u64 {
+    3600
+}
+
+fn default_turn_max_tcp_connections() -> usize {
+    256
+}
+
 fn default_storage_backend() -> String {
     "local".to_string()
 }
@@ -145,6 +294,42 @@ fn default_media_max_upload_bytes() -> usize {
     10 * 1024 * 1024
 }
 
+fn default_media_max_file_upload_bytes() -> usize {
+    25 * 1024 * 1024
+}
+
+fn default_media_user_quota_bytes() -> u64 {
+    1024 * 1024 * 1024
+}
+
+fn default_media_video_transcode() -> String {
+    "h264".to_string()
+}
+
+fn default_file_denied_types() -> Vec<String> {
+    [
+        "application/x-msdownload",
+        "application/x-executable",
+        "application/x-mach-binary",
+        ".exe",
+        ".dll",
+        ".scr",
+        ".msi",
+        ".bat",
+        ".cmd",
+        ".com",
+        ".ps1",
+        ".vbs",
+        ".js",
+        ".jar",
+        ".lnk",
+        ".sh",
+    ]
+    .into_iter()
+    .map(ToOwned::to_owned)
+    .collect()
+}
+
 fn default_media_cleanup_interval_seconds() -> u64 {
     900
 }
@@ -161,11 +346,36 @@ impl Default for StorageConfig {
             max_upload_bytes: default_media_max_upload_bytes(),
             cleanup_interval_seconds: default_media_cleanup_interval_seconds(),
             failed_retention_hours: default_media_failed_retention_hours(),
+            max_file_upload_bytes: default_media_max_file_upload_bytes(),
+            file_allowed_types: Vec::new(),
+            file_denied_types: default_file_denied_types(),
+            video_transcode: default_media_video_transcode(),
+            user_quota_bytes: default_media_user_quota_bytes(),
+            total_quota_bytes: 0,
+            clamd_address: None,
             s3: S3Config::default(),
         }
     }
 }
 
+impl Default for TurnConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_ip: default_turn_listen_ip(),
+            port: default_turn_port(),
+            enable_tcp: default_turn_enable_tcp(),
+            public_host: None,
+            relay_ip: None,
+            relay_min_port: None,
+            relay_max_port: None,
+            realm: default_turn_realm(),
+            credential_ttl_seconds: default_turn_credential_ttl_seconds(),
+            max_tcp_connections: default_turn_max_tcp_connections(),
+        }
+    }
+}
+
 impl AppConfig {
     pub fn load() -> Self {
         let config_path = std::env::var("CONFIG_PATH").unwrap_or_else(|_| "config.toml".into());
@@ -220,6 +430,21 @@ impl AppConfig {
                     native_rtp_announced_ip: std::env::var("NATIVE_RTP_ANNOUNCED_IP")
                         .ok()
                         .or_else(|| std::env::var("WEBRTC_ANNOUNCED_IP").ok()),
+                    webrtc_server_enabled: parse_bool_env("WEBRTC_SERVER_ENABLED").unwrap_or(false),
+                    webrtc_server_port: parse_optional_port_env("WEBRTC_SERVER_PORT")
+                        .unwrap_or_else(default_webrtc_server_port),
+                    webrtc_enable_tcp: parse_bool_env("WEBRTC_ENABLE_TCP")
+                        .unwrap_or_else(default_webrtc_enable_tcp),
+                    rtc_min_port: parse_optional_port_env("RTC_MIN_PORT"),
+                    rtc_max_port: parse_optional_port_env("RTC_MAX_PORT"),
+                    ffmpeg_bin: std::env::var("FFMPEG_BIN")
+                        .ok()
+                        .filter(|value| !value.trim().is_empty())
+                        .unwrap_or_else(default_ffmpeg_bin),
+                    ffprobe_bin: std::env::var("FFPROBE_BIN")
+                        .ok()
+                        .filter(|value| !value.trim().is_empty())
+                        .unwrap_or_else(default_ffprobe_bin),
                 },
                 storage: StorageConfig {
                     backend: std::env::var("STORAGE_BACKEND")
@@ -238,21 +463,40 @@ impl AppConfig {
                         .unwrap_or_else(|_| default_media_failed_retention_hours().to_string())
                         .parse()
                         .expect("MEDIA_FAILED_RETENTION_HOURS must be a number"),
+                    max_file_upload_bytes: std::env::var("MEDIA_MAX_FILE_UPLOAD_BYTES")
+                        .unwrap_or_else(|_| default_media_max_file_upload_bytes().to_string())
+                        .parse()
+                        .expect("MEDIA_MAX_FILE_UPLOAD_BYTES must be a number"),
+                    file_allowed_types: parse_csv_env_or_default(
+                        "MEDIA_FILE_ALLOWED_TYPES",
+                        Vec::new(),
+                    ),
+                    file_denied_types: parse_csv_env_or_default(
+                        "MEDIA_FILE_DENIED_TYPES",
+                        default_file_denied_types(),
+                    ),
+                    video_transcode: std::env::var("MEDIA_VIDEO_TRANSCODE")
+                        .ok()
+                        .filter(|value| !value.trim().is_empty())
+                        .unwrap_or_else(default_media_video_transcode),
+                    user_quota_bytes: std::env::var("MEDIA_USER_QUOTA_BYTES")
+                        .unwrap_or_else(|_| default_media_user_quota_bytes().to_string())
+                        .parse()
+                        .expect("MEDIA_USER_QUOTA_BYTES must be a number"),
+                    total_quota_bytes: std::env::var("MEDIA_TOTAL_QUOTA_BYTES")
+                        .unwrap_or_else(|_| "0".to_string())
+                        .parse()
+                        .expect("MEDIA_TOTAL_QUOTA_BYTES must be a number"),
+                    clamd_address: std::env::var("MEDIA_CLAMD_ADDRESS")
+                        .ok()
+                        .filter(|value| !value.trim().is_empty()),
                     s3: S3Config {
                         endpoint: std::env::var("S3_ENDPOINT").ok(),
                         region: std::env::var("S3_REGION").ok(),
                         bucket: std::env::var("S3_BUCKET").ok(),
                         access_key_id: std::env::var("S3_ACCESS_KEY_ID").ok(),
                         secret_access_key: std::env::var("S3_SECRET_ACCESS_KEY").ok(),
-                        force_path_style: std::env::var("S3_FORCE_PATH_STYLE")
-                            .ok()
-                            .map(|value| {
-                                matches!(
-                                    value.to_ascii_lowercase().as_str(),
-                                    "1" | "true" | "yes" | "on"
-                                )
-                            })
-                            .unwrap_or(false),
+                        force_path_style: parse_bool_env("S3_FORCE_PATH_STYLE").unwrap_or(false),
                     },
                 },
                 integrations: IntegrationsConfig {
@@ -260,6 +504,31 @@ impl AppConfig {
                         .ok()
                         .or_else(|| std::env::var("TENOR_API_KEY").ok()),
                 },
+                turn: TurnConfig {
+                    enabled: parse_bool_env("TURN_ENABLED").unwrap_or(false),
+                    listen_ip: std::env::var("TURN_LISTEN_IP")
+                        .unwrap_or_else(|_| default_turn_listen_ip()),
+                    port: parse_optional_port_env("TURN_PORT").unwrap_or_else(default_turn_port),
+                    enable_tcp: parse_bool_env("TURN_ENABLE_TCP")
+                        .unwrap_or_else(default_turn_enable_tcp),
+                    public_host: std::env::var("TURN_PUBLIC_HOST")
+                        .ok()
+                        .filter(|value| !value.trim().is_empty()),
+                    relay_ip: std::env::var("TURN_RELAY_IP")
+                        .ok()
+                        .filter(|value| !value.trim().is_empty()),
+                    relay_min_port: parse_optional_port_env("TURN_RELAY_MIN_PORT"),
+                    relay_max_port: parse_optional_port_env("TURN_RELAY_MAX_PORT"),
+                    realm: std::env::var("TURN_REALM").unwrap_or_else(|_| default_turn_realm()),
+                    credential_ttl_seconds: std::env::var("TURN_CREDENTIAL_TTL_SECONDS")
+                        .unwrap_or_else(|_| default_turn_credential_ttl_seconds().to_string())
+                        .parse()
+                        .expect("TURN_CREDENTIAL_TTL_SECONDS must be a number"),
+                    max_tcp_connections: std::env::var("TURN_MAX_TCP_CONNECTIONS")
+                        .unwrap_or_else(|_| default_turn_max_tcp_connections().to_string())
+                        .parse()
+                        .expect("TURN_MAX_TCP_CONNECTIONS must be a number"),
+                },
             }
         };
 
@@ -267,6 +536,59 @@ impl AppConfig {
             panic!("MEDIA_WORKER_COUNT must be at least 1");
         }
 
+        match (config.media.rtc_min_port, config.media.rtc_max_port) {
+            (Some(min), Some(max)) if min > max => {
+                panic!("RTC_MIN_PORT must not be greater than RTC_MAX_PORT");
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                panic!("RTC_MIN_PORT and RTC_MAX_PORT must be set together");
+            }
+            _ => {}
+        }
+
+        match (config.turn.relay_min_port, config.turn.relay_max_port) {
+            (Some(min), Some(max)) if min > max => {
+                panic!("TURN_RELAY_MIN_PORT must not be greater than TURN_RELAY_MAX_PORT");
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                panic!("TURN_RELAY_MIN_PORT and TURN_RELAY_MAX_PORT must be set together");
+            }
+            _ => {}
+        }
+
+        if config.turn.credential_ttl_seconds == 0 {
+            panic!("TURN_CREDENTIAL_TTL_SECONDS must be at least 1");
+        }
+
+        if config.turn.enable_tcp && config.turn.max_tcp_connections == 0 {
+            panic!("TURN_MAX_TCP_CONNECTIONS must be at least 1");
+        }
+
+        if config.media.webrtc_server_enabled
+            && !webrtc_server_ports_fit(config.media.webrtc_server_port, config.media.worker_count)
+        {
+            panic!("WEBRTC_SERVER_PORT leaves no room for one port per media worker");
+        }
+
         config
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ice_tcp_is_off_by_default() {
+        assert!(!default_webrtc_enable_tcp());
+    }
+
+    #[test]
+    fn webrtc_server_ports_must_fit_below_65536() {
+        assert!(webrtc_server_ports_fit(44_444, 4));
+        assert!(webrtc_server_ports_fit(u16::MAX, 1));
+        assert!(webrtc_server_ports_fit(u16::MAX - 3, 4));
+        assert!(!webrtc_server_ports_fit(u16::MAX, 2));
+        assert!(!webrtc_server_ports_fit(u16::MAX - 3, 5));
+    }
+}
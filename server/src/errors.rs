@@ -8,6 +8,7 @@ pub enum AppError {
     Unauthorized(String),
     NotFound(String),
     Conflict(String),
+    PayloadTooLarge(String),
     TooManyRequests(String),
     Internal(String),
 }
@@ -19,6 +20,7 @@ impl IntoResponse for AppError {
             AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            AppError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg),
             AppError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg),
             AppError::Internal(msg) => {
                 tracing::error!("Internal error: {msg}");
@@ -4,9 +4,12 @@ mod errors;
 mod media;
 mod message_attachments;
 mod models;
+mod restream;
 mod routes;
+mod soundboard;
 mod storage;
 mod telemetry;
+mod turn_server;
 mod uploads;
 mod ws;
 
@@ -23,6 +26,8 @@ use tokio::time::{interval, Duration};
 use tower_http::cors::{Any, CorsLayer};
 use uuid::Uuid;
 
+const NETWORK_QUALITY_REPORT_INTERVAL: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Clone)]
 pub struct MediaSignalRateState {
     pub window_started_at: Instant,
@@ -35,6 +40,40 @@ pub struct VoiceMuteState {
     pub speaker_muted: bool,
 }
 
+#[derive(Debug, Clone)]
+pub struct WhipSessionOwner {
+    pub user_id: Uuid,
+    pub username: String,
+    pub channel_id: Uuid,
+}
+
+#[derive(Debug, Clone)]
+pub struct WhepViewer {
+    pub channel_id: Uuid,
+    pub producer_id: String,
+    /// `None` for viewers that came in through a stream-viewer link.
+    pub username: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RestreamSession {
+    pub channel_id: Uuid,
+    pub producer_id: String,
+    pub started_by: Uuid,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub record: bool,
+    /// Set once the first playlist has been uploaded to storage.
+    pub live: bool,
+    pub stop_tx: mpsc::UnboundedSender<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SoundboardPlayback {
+    pub channel_id: Uuid,
+    /// Who played the clip; its producer is announced under this name.
+    pub username: String,
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub db: sqlx::PgPool,
@@ -54,6 +93,12 @@ pub struct AppState {
     pub voice_members_by_channel: Arc<RwLock<HashMap<Uuid, HashSet<String>>>>,
     pub voice_mute_state_by_username: Arc<RwLock<HashMap<String, VoiceMuteState>>>,
     pub media_signal_rate_by_connection: Arc<RwLock<HashMap<Uuid, MediaSignalRateState>>>,
+    pub whip_sessions: Arc<RwLock<HashMap<Uuid, WhipSessionOwner>>>,
+    pub whep_sessions: Arc<RwLock<HashMap<Uuid, WhepViewer>>>,
+    pub restreams: Arc<RwLock<HashMap<Uuid, RestreamSession>>>,
+    pub soundboard_playbacks: Arc<RwLock<HashMap<Uuid, SoundboardPlayback>>>,
+    /// When each user last played a soundboard clip.
+    pub soundboard_last_played: Arc<RwLock<HashMap<Uuid, Instant>>>,
 }
 
 #[tokio::main]
@@ -70,6 +115,18 @@ async fn main() {
     let config = AppConfig::load();
     let config = Arc::new(config);
 
+    // `yankcord-server migrate-storage ...` copies media between storage
+    // backends and exits instead of serving.
+    let storage_migration = (std::env::args().nth(1).as_deref() == Some(storage::migrate::COMMAND))
+        .then(|| {
+            storage::migrate::MigrationOptions::parse(std::env::args().skip(2)).unwrap_or_else(
+                |message| {
+                    eprintln!("{message}");
+                    std::process::exit(2);
+                },
+            )
+        });
+
     let pool = PgPoolOptions::new()
         .max_connections(20)
         .connect(&config.database.url)
@@ -85,24 +142,40 @@ async fn main() {
         .await
         .expect("Failed to seed default channel");
 
-    let media_service = media::MediaService::new(
-        config.media.worker_count,
-        config.media.webrtc_listen_ip.clone(),
-        config.media.announced_ip.clone(),
-        config.media.native_rtp_listen_ip.clone(),
-        config.media.native_rtp_announced_ip.clone(),
-    )
-    .await;
+    if let Some(options) = storage_migration {
+        match storage::migrate::run(&pool, &config.storage, &options).await {
+            Ok(report) if report.failed == 0 && report.left_behind == 0 => return,
+            Ok(_) => std::process::exit(1),
+            Err(error) => {
+                tracing::error!(error = ?error, "Storage migration failed");
+                std::process::exit(2);
+            }
+        }
+    }
 
-    let storage_backend = storage::create_storage_backend(&config.storage)
+    let media_service = media::MediaService::new(&config.media).await;
+
+    let _turn_server = turn_server::start(&config)
         .await
-        .expect("Failed to initialize storage backend");
+        .expect("Failed to start embedded TURN server");
 
-    let upload_service = uploads::UploadService::new(
-        pool.clone(),
-        storage_backend.clone(),
-        config.storage.max_upload_bytes,
-    );
+    let storage_backend_name = storage::migrate::active_backend_name(&pool, &config.storage)
+        .await
+        .expect("Failed to resolve storage backend");
+    if !storage_backend_name.eq_ignore_ascii_case(&config.storage.backend) {
+        tracing::warn!(
+            configured = %config.storage.backend,
+            active = %storage_backend_name,
+            "Using the storage backend a finished migration switched to; update STORAGE_BACKEND to match"
+        );
+    }
+    let storage_backend =
+        storage::create_named_storage_backend(&storage_backend_name, &config.storage)
+            .await
+            .expect("Failed to initialize storage backend");
+
+    let upload_service =
+        uploads::UploadService::new(pool.clone(), storage_backend.clone(), &config.storage);
 
     let state = AppState {
         db: pool,
@@ -122,9 +195,20 @@ async fn main() {
         voice_members_by_channel: Arc::new(RwLock::new(HashMap::new())),
         voice_mute_state_by_username: Arc::new(RwLock::new(HashMap::new())),
         media_signal_rate_by_connection: Arc::new(RwLock::new(HashMap::new())),
+        whip_sessions: Arc::new(RwLock::new(HashMap::new())),
+        whep_sessions: Arc::new(RwLock::new(HashMap::new())),
+        restreams: Arc::new(RwLock::new(HashMap::new())),
+        soundboard_playbacks: Arc::new(RwLock::new(HashMap::new())),
+        soundboard_last_played: Arc::new(RwLock::new(HashMap::new())),
     };
 
     start_derivative_cleanup_job(state.clone());
+    start_media_worker_supervisor(state.clone());
+    start_network_quality_reporter(state.clone());
+    start_whip_session_reaper(state.clone());
+    start_whep_session_reaper(state.clone());
+    start_restream_reaper(state.clone());
+    start_quarantine_notifier(state.clone());
 
     let cors = build_cors_layer(&config);
     tracing::info!(
@@ -135,12 +219,16 @@ async fn main() {
     );
 
     let app = Router::new()
+        .nest("/api", routes::admin_routes::router())
         .nest("/api", routes::auth_routes::router())
         .nest("/api", routes::channel_routes::router())
         .nest("/api", routes::dm_routes::router())
         .nest(
             "/api",
-            routes::media_routes::router(config.storage.max_upload_bytes),
+            routes::media_routes::router(
+                config.storage.max_upload_bytes,
+                config.storage.max_file_upload_bytes,
+            ),
         )
         .nest("/api", routes::embed_routes::router())
         .nest("/api", routes::emoji_routes::router())
@@ -148,6 +236,12 @@ async fn main() {
         .nest("/api", routes::invite_routes::router())
         .nest("/api", routes::reaction_routes::router())
         .nest("/api", routes::settings_routes::router())
+        .nest("/api", routes::soundboard_routes::router())
+        .nest("/api", routes::token_routes::router())
+        .nest("/api", routes::turn_routes::router())
+        .nest("/api", routes::tus_routes::router())
+        .nest("/api", routes::whep_routes::router())
+        .nest("/api", routes::whip_routes::router())
         .nest("/api", routes::user_routes::router())
         .route("/ws", axum::routing::get(ws::ws_upgrade))
         .layer(cors)
@@ -168,7 +262,13 @@ async fn main() {
 fn build_cors_layer(config: &AppConfig) -> CorsLayer {
     let server_config = &config.server;
 
-    let cors = CorsLayer::new();
+    // WHIP/WHEP clients read the session URL from `Location`, and tus
+    // clients read upload state from their protocol headers.
+    let cors = CorsLayer::new().expose_headers(
+        std::iter::once(axum::http::header::LOCATION)
+            .chain(routes::tus_routes::TUS_RESPONSE_HEADERS)
+            .collect::<Vec<_>>(),
+    );
 
     let cors = if is_wildcard(&server_config.cors_allowed_origins) {
         cors.allow_origin(Any)
@@ -210,6 +310,7 @@ fn build_cors_layer(config: &AppConfig) -> CorsLayer {
                 HeaderName::from_str(name)
                     .unwrap_or_else(|_| panic!("Invalid CORS header configured: {name}"))
             })
+            .chain(routes::tus_routes::TUS_REQUEST_HEADERS)
             .collect();
 
         cors.allow_headers(tower_http::cors::AllowHeaders::list(allowed_headers))
@@ -258,6 +359,133 @@ fn start_derivative_cleanup_job(state: AppState) {
                     tracing::warn!(error = ?error, "Orphan message upload cleanup iteration failed");
                 }
             }
+
+            match state.uploads.cleanup_unreferenced_blobs().await {
+                Ok(deleted) if deleted > 0 => {
+                    tracing::info!(deleted, "Media blob cleanup removed unreferenced objects");
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    tracing::warn!(error = ?error, "Media blob cleanup iteration failed");
+                }
+            }
+
+            match state.uploads.cleanup_expired_resumable_uploads().await {
+                Ok(deleted) if deleted > 0 => {
+                    tracing::info!(deleted, "Resumable upload cleanup removed expired uploads");
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    tracing::warn!(error = ?error, "Resumable upload cleanup iteration failed");
+                }
+            }
+        }
+    });
+}
+
+/// Tells connected operators and admins about uploads the malware scanner
+/// quarantined.
+fn start_quarantine_notifier(state: AppState) {
+    let mut events = state.uploads.subscribe_quarantined();
+    tokio::spawn(async move {
+        loop {
+            let upload = match events.recv().await {
+                Ok(upload) => upload,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "Dropped quarantine notifications");
+                    continue;
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            };
+
+            let recipients: Result<Vec<Uuid>, sqlx::Error> =
+                sqlx::query_scalar("SELECT id FROM users WHERE role IN ('operator', 'admin')")
+                    .fetch_all(&state.db)
+                    .await;
+            let username: Result<Option<String>, sqlx::Error> =
+                sqlx::query_scalar("SELECT username FROM users WHERE id = $1")
+                    .bind(upload.owner_id)
+                    .fetch_optional(&state.db)
+                    .await;
+            let (recipients, username) = match (recipients, username) {
+                (Ok(recipients), Ok(username)) => (recipients, username),
+                (Err(error), _) | (_, Err(error)) => {
+                    tracing::warn!(error = ?error, "Failed to notify admins of quarantined upload");
+                    continue;
+                }
+            };
+
+            ws::broadcast::broadcast_user_ids_message(
+                &state,
+                &recipients,
+                ws::messages::ServerMessage::UploadQuarantined {
+                    media_id: upload.media_id,
+                    username: username.unwrap_or_default(),
+                    filename: upload.filename,
+                    signature: upload.signature,
+                },
+                None,
+            )
+            .await;
+        }
+    });
+}
+
+fn start_media_worker_supervisor(state: AppState) {
+    tokio::spawn(async move {
+        while let Some(worker_id) = state.media.next_dead_worker().await {
+            let recovered = state.media.recover_dead_worker(worker_id).await;
+
+            tracing::warn!(
+                worker_id = %worker_id,
+                channels = recovered.channel_ids.len(),
+                connections = recovered.connection_ids.len(),
+                "Recovered voice channels from dead mediasoup worker"
+            );
+
+            ws::voice::request_media_renegotiation(
+                &state,
+                &recovered.channel_ids,
+                &recovered.closed_producers,
+                "media_worker_restarted",
+            )
+            .await;
+        }
+    });
+}
+
+fn start_network_quality_reporter(state: AppState) {
+    tokio::spawn(async move {
+        let mut ticker = interval(NETWORK_QUALITY_REPORT_INTERVAL);
+        let mut last_levels = ws::voice::NetworkQualityLevels::new();
+
+        loop {
+            ticker.tick().await;
+            ws::voice::broadcast_network_quality(&state, &mut last_levels).await;
+        }
+    });
+}
+
+fn start_whip_session_reaper(state: AppState) {
+    tokio::spawn(async move {
+        while let Some(session_id) = state.media.next_ended_whip_session().await {
+            routes::whip_routes::end_whip_session(&state, session_id, "transport_closed").await;
+        }
+    });
+}
+
+fn start_whep_session_reaper(state: AppState) {
+    tokio::spawn(async move {
+        while let Some(session_id) = state.media.next_ended_whep_session().await {
+            routes::whep_routes::end_whep_session(&state, session_id, "stream_ended").await;
+        }
+    });
+}
+
+fn start_restream_reaper(state: AppState) {
+    tokio::spawn(async move {
+        while let Some(session_id) = state.media.next_ended_restream().await {
+            restream::stop_restream(&state, session_id, "stream_ended").await;
         }
     });
 }
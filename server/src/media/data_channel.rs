@@ -0,0 +1,441 @@
+use mediasoup::prelude::{
+    DataConsumer, DataConsumerOptions, DataProducer, DataProducerId, DataProducerOptions,
+    DirectTransportOptions, SctpStreamParameters, Transport,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::transport::ConnectionMediaState;
+use super::MediaService;
+
+/// Largest SCTP message a call participant may send, enforced by mediasoup
+/// on the WebRTC transports.
+pub const MAX_DATA_MESSAGE_BYTES: u32 = 16 * 1024;
+pub const MAX_DATA_PRODUCERS_PER_CONNECTION: usize = 4;
+pub const MAX_DATA_CHANNEL_LABEL_CHARS: usize = 64;
+pub const MAX_DATA_CHANNEL_PROTOCOL_CHARS: usize = 64;
+const DATA_MESSAGE_RATE_WINDOW: Duration = Duration::from_secs(1);
+const MAX_DATA_MESSAGES_PER_WINDOW: u32 = 60;
+
+/// A participant's data producer as the rest of the channel sees it.
+///
+/// Messages from the client's producer are tapped through the channel's
+/// `DirectTransport`, rate limited, and re-sent through `relay`, which is the
+/// producer other participants consume.
+#[derive(Debug)]
+pub(crate) struct DataProducerEntry {
+    /// Held so the client's producer and its tap live as long as the entry.
+    _producer: DataProducer,
+    _tap: DataConsumer,
+    pub relay: DataProducer,
+    pub label: String,
+    pub protocol: String,
+}
+
+/// Per-connection budget for relayed data messages, shared by all of the
+/// connection's data producers.
+#[derive(Debug)]
+pub(crate) struct DataMessageRateState {
+    window_started_at: Instant,
+    messages_in_window: u32,
+    dropped_in_window: u32,
+}
+
+impl Default for DataMessageRateState {
+    fn default() -> Self {
+        Self {
+            window_started_at: Instant::now(),
+            messages_in_window: 0,
+            dropped_in_window: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DataMessageVerdict {
+    Relay,
+    /// First message dropped in the current window.
+    StartDropping,
+    Drop,
+}
+
+impl DataMessageRateState {
+    fn check(&mut self, now: Instant) -> DataMessageVerdict {
+        if now.duration_since(self.window_started_at) >= DATA_MESSAGE_RATE_WINDOW {
+            self.window_started_at = now;
+            self.messages_in_window = 0;
+            self.dropped_in_window = 0;
+        }
+
+        if self.messages_in_window < MAX_DATA_MESSAGES_PER_WINDOW {
+            self.messages_in_window += 1;
+            return DataMessageVerdict::Relay;
+        }
+
+        self.dropped_in_window += 1;
+        if self.dropped_in_window == 1 {
+            DataMessageVerdict::StartDropping
+        } else {
+            DataMessageVerdict::Drop
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PublishedDataProducer {
+    /// Id other participants consume; this is the relay, not the client's
+    /// own producer.
+    pub data_producer_id: String,
+    pub label: String,
+    pub protocol: String,
+    #[serde(skip)]
+    pub owner_connection_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedDataProducer {
+    /// The client's own producer id, which it uses to close the producer.
+    pub id: String,
+    pub published: PublishedDataProducer,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedDataConsumer {
+    pub id: String,
+    pub data_producer_id: String,
+    pub sctp_stream_parameters: Option<SctpStreamParameters>,
+    pub label: String,
+    pub protocol: String,
+}
+
+impl MediaService {
+    pub async fn create_data_producer_for_connection(
+        &self,
+        connection_id: Uuid,
+        channel_id: Uuid,
+        sctp_stream_parameters: SctpStreamParameters,
+        label: String,
+        protocol: String,
+    ) -> Result<CreatedDataProducer, String> {
+        let (send_transport, direct_transport, rate_state) = {
+            let media_state_lock = self.connection_media();
+            let media_state = media_state_lock.lock().await;
+            let entry = data_channel_entry(&media_state, connection_id, channel_id)?;
+
+            let Some(send_transport_id) = entry.send_transport_id.as_ref() else {
+                return Err("Send transport has not been created".into());
+            };
+
+            let Some(transport) = entry.transports.get(send_transport_id) else {
+                return Err("Send transport not found".into());
+            };
+
+            if entry.data_producers.len() >= MAX_DATA_PRODUCERS_PER_CONNECTION {
+                return Err("Too many data producers for this connection".into());
+            }
+
+            (
+                transport.clone(),
+                entry.direct_transport.clone(),
+                Arc::clone(&entry.data_message_rate),
+            )
+        };
+
+        let direct_transport = match direct_transport {
+            Some(direct_transport) => direct_transport,
+            None => send_transport
+                .router()
+                .create_direct_transport(DirectTransportOptions::default())
+                .await
+                .map_err(|error| format!("Failed to create data relay transport: {error}"))?,
+        };
+
+        let mut producer_options = DataProducerOptions::new_sctp(sctp_stream_parameters);
+        producer_options.label = label.clone();
+        producer_options.protocol = protocol.clone();
+        let producer = send_transport
+            .produce_data(producer_options)
+            .await
+            .map_err(|error| format!("Failed to create data producer: {error}"))?;
+
+        let mut relay_options = DataProducerOptions::new_direct();
+        relay_options.label = label.clone();
+        relay_options.protocol = protocol.clone();
+        let relay = direct_transport
+            .produce_data(relay_options)
+            .await
+            .map_err(|error| format!("Failed to create data relay: {error}"))?;
+
+        let tap = direct_transport
+            .consume_data(DataConsumerOptions::new_direct(producer.id(), None))
+            .await
+            .map_err(|error| format!("Failed to tap data producer: {error}"))?;
+
+        let (DataConsumer::Direct(_), DataProducer::Direct(direct_relay)) = (&tap, &relay) else {
+            return Err("Data relay transport returned a non-direct endpoint".into());
+        };
+
+        // Messages are only delivered to direct data consumers, but the
+        // callback is registered through the `DataConsumer` handle.
+        let relay_sender = direct_relay.clone();
+        tap.on_message(move |message| {
+            let verdict = rate_state
+                .lock()
+                .map(|mut rate| rate.check(Instant::now()))
+                .unwrap_or(DataMessageVerdict::Drop);
+            match verdict {
+                DataMessageVerdict::Relay => {
+                    if let Err(error) = relay_sender.send(message.clone(), None, None) {
+                        tracing::debug!(
+                            connection_id = %connection_id,
+                            error = %error,
+                            "Failed to relay data channel message"
+                        );
+                    }
+                }
+                DataMessageVerdict::StartDropping => {
+                    tracing::warn!(
+                        connection_id = %connection_id,
+                        limit = MAX_DATA_MESSAGES_PER_WINDOW,
+                        "Data channel rate limit exceeded; dropping messages"
+                    );
+                }
+                DataMessageVerdict::Drop => {}
+            }
+        })
+        .detach();
+
+        let created = CreatedDataProducer {
+            id: producer.id().to_string(),
+            published: PublishedDataProducer {
+                data_producer_id: relay.id().to_string(),
+                label: label.clone(),
+                protocol: protocol.clone(),
+                owner_connection_id: connection_id,
+            },
+        };
+
+        {
+            let media_state_lock = self.connection_media();
+            let mut media_state = media_state_lock.lock().await;
+            let Some(entry) = media_state.get_mut(&connection_id) else {
+                return Err("Media session was closed while creating data producer".into());
+            };
+
+            if entry.channel_id != channel_id {
+                return Err("Media session moved to a different channel".into());
+            }
+
+            if entry.data_producers.len() >= MAX_DATA_PRODUCERS_PER_CONNECTION {
+                return Err("Too many data producers for this connection".into());
+            }
+
+            entry.direct_transport.get_or_insert(direct_transport);
+            entry.data_producers.insert(
+                created.id.clone(),
+                DataProducerEntry {
+                    _producer: producer,
+                    _tap: tap,
+                    relay,
+                    label,
+                    protocol,
+                },
+            );
+        }
+
+        Ok(created)
+    }
+
+    pub async fn list_channel_data_producers(
+        &self,
+        channel_id: Uuid,
+        exclude_connection_id: Option<Uuid>,
+    ) -> Vec<PublishedDataProducer> {
+        let media_state_lock = self.connection_media();
+        let media_state = media_state_lock.lock().await;
+
+        media_state
+            .iter()
+            .filter(|(connection_id, entry)| {
+                entry.channel_id == channel_id && exclude_connection_id != Some(**connection_id)
+            })
+            .flat_map(|(connection_id, entry)| {
+                entry
+                    .data_producers
+                    .values()
+                    .map(|data_producer| PublishedDataProducer {
+                        data_producer_id: data_producer.relay.id().to_string(),
+                        label: data_producer.label.clone(),
+                        protocol: data_producer.protocol.clone(),
+                        owner_connection_id: *connection_id,
+                    })
+            })
+            .collect()
+    }
+
+    pub async fn create_data_consumer_for_connection(
+        &self,
+        connection_id: Uuid,
+        channel_id: Uuid,
+        data_producer_id: &str,
+    ) -> Result<CreatedDataConsumer, String> {
+        let relay_id = data_producer_id
+            .parse::<DataProducerId>()
+            .map_err(|_| "Invalid data producer id".to_string())?;
+
+        let (recv_transport, label, protocol) = {
+            let media_state_lock = self.connection_media();
+            let media_state = media_state_lock.lock().await;
+            let entry = data_channel_entry(&media_state, connection_id, channel_id)?;
+
+            let Some(recv_transport_id) = entry.recv_transport_id.as_ref() else {
+                return Err("Recv transport has not been created".into());
+            };
+
+            let Some(transport) = entry.transports.get(recv_transport_id) else {
+                return Err("Recv transport not found".into());
+            };
+
+            let Some((owner_connection_id, data_producer)) =
+                media_state.iter().find_map(|(owner_connection_id, state)| {
+                    if state.channel_id != channel_id {
+                        return None;
+                    }
+                    state
+                        .data_producers
+                        .values()
+                        .find(|data_producer| data_producer.relay.id() == relay_id)
+                        .map(|data_producer| (*owner_connection_id, data_producer))
+                })
+            else {
+                return Err("Data producer does not belong to this voice channel".into());
+            };
+
+            if owner_connection_id == connection_id {
+                return Err("Cannot consume your own data producer".into());
+            }
+
+            (
+                transport.clone(),
+                data_producer.label.clone(),
+                data_producer.protocol.clone(),
+            )
+        };
+
+        let data_consumer = recv_transport
+            .consume_data(DataConsumerOptions::new_sctp(relay_id))
+            .await
+            .map_err(|error| format!("Failed to create data consumer: {error}"))?;
+
+        let created = CreatedDataConsumer {
+            id: data_consumer.id().to_string(),
+            data_producer_id: relay_id.to_string(),
+            sctp_stream_parameters: data_consumer.sctp_stream_parameters(),
+            label,
+            protocol,
+        };
+
+        {
+            let media_state_lock = self.connection_media();
+            let mut media_state = media_state_lock.lock().await;
+            let Some(entry) = media_state.get_mut(&connection_id) else {
+                return Err("Media session was closed while creating data consumer".into());
+            };
+
+            if entry.channel_id != channel_id {
+                return Err("Media session moved to a different channel".into());
+            }
+
+            entry
+                .data_consumers
+                .insert(created.id.clone(), data_consumer);
+        }
+
+        Ok(created)
+    }
+
+    /// Closes one of the connection's own data producers, identified by the
+    /// id the client got back from `data_produce`. Returns the relay id the
+    /// rest of the channel knows it by.
+    pub async fn close_data_producer_for_connection(
+        &self,
+        connection_id: Uuid,
+        channel_id: Uuid,
+        data_producer_id: &str,
+    ) -> Result<String, String> {
+        let media_state_lock = self.connection_media();
+        let mut media_state = media_state_lock.lock().await;
+
+        let Some(entry) = media_state.get_mut(&connection_id) else {
+            return Err("No media session exists for this connection".into());
+        };
+
+        if entry.channel_id != channel_id {
+            return Err("Data producer does not belong to this voice channel".into());
+        }
+
+        let closed = entry
+            .data_producers
+            .remove(data_producer_id)
+            .ok_or_else(|| "Data producer not found for this connection".to_string())?;
+        let relay_id = closed.relay.id();
+
+        for (other_conn_id, other_entry) in media_state.iter_mut() {
+            if *other_conn_id == connection_id || other_entry.channel_id != channel_id {
+                continue;
+            }
+            other_entry
+                .data_consumers
+                .retain(|_id, data_consumer| data_consumer.data_producer_id() != relay_id);
+        }
+
+        Ok(relay_id.to_string())
+    }
+}
+
+fn data_channel_entry(
+    media_state: &HashMap<Uuid, ConnectionMediaState>,
+    connection_id: Uuid,
+    channel_id: Uuid,
+) -> Result<&ConnectionMediaState, String> {
+    let Some(entry) = media_state.get(&connection_id) else {
+        return Err("No media session exists for this connection".into());
+    };
+
+    if entry.channel_id != channel_id {
+        return Err("Transport does not belong to this voice channel".into());
+    }
+
+    Ok(entry)
+}
+
+pub(crate) fn new_data_message_rate() -> Arc<StdMutex<DataMessageRateState>> {
+    Arc::new(StdMutex::new(DataMessageRateState::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limit_drops_messages_past_the_window_budget() {
+        let start = Instant::now();
+        let mut rate = DataMessageRateState {
+            window_started_at: start,
+            messages_in_window: 0,
+            dropped_in_window: 0,
+        };
+
+        for _ in 0..MAX_DATA_MESSAGES_PER_WINDOW {
+            assert_eq!(rate.check(start), DataMessageVerdict::Relay);
+        }
+        assert_eq!(rate.check(start), DataMessageVerdict::StartDropping);
+        assert_eq!(rate.check(start), DataMessageVerdict::Drop);
+
+        let next_window = start + DATA_MESSAGE_RATE_WINDOW;
+        assert_eq!(rate.check(next_window), DataMessageVerdict::Relay);
+    }
+}
@@ -1,125 +1,280 @@
 pub mod consumer;
+pub mod data_channel;
 mod native_codec;
 pub mod producer;
+pub mod restream;
 pub mod router;
+pub mod sdp;
+pub mod soundboard;
+pub mod stats;
 pub mod transport;
+pub mod whep;
+pub mod whip;
+pub mod worker;
 
 use mediasoup::prelude::*;
+use mediasoup::worker::WorkerId;
 use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr};
+use std::ops::RangeInclusive;
 use std::str::FromStr;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
 use uuid::Uuid;
 
+use crate::config::MediaConfig;
+
+struct ChannelRouter {
+    router: Router,
+    worker_id: WorkerId,
+    webrtc_server: Option<WebRtcServer>,
+    opus_config: router::OpusConfig,
+}
+
+#[derive(Debug, Clone)]
+struct WebRtcListenSettings {
+    listen_ip: IpAddr,
+    announced_ip: Option<String>,
+    enable_tcp: bool,
+    /// Base port of the per-worker `WebRtcServer`s, when enabled.
+    server_port: Option<u16>,
+    port_range: Option<RangeInclusive<u16>>,
+}
+
+impl WebRtcListenSettings {
+    fn listen_info(&self, protocol: Protocol, port: Option<u16>) -> ListenInfo {
+        ListenInfo {
+            protocol,
+            ip: self.listen_ip,
+            announced_address: self.announced_ip.clone(),
+            expose_internal_ip: false,
+            port,
+            port_range: if port.is_some() {
+                None
+            } else {
+                self.port_range.clone()
+            },
+            flags: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+        }
+    }
+
+    fn transport_options(&self, webrtc_server: Option<WebRtcServer>) -> WebRtcTransportOptions {
+        let mut options = match webrtc_server {
+            Some(webrtc_server) => WebRtcTransportOptions::new_with_server(webrtc_server),
+            None => {
+                let mut listen_infos =
+                    WebRtcTransportListenInfos::new(self.listen_info(Protocol::Udp, None));
+                if self.enable_tcp {
+                    listen_infos = listen_infos.insert(self.listen_info(Protocol::Tcp, None));
+                }
+                WebRtcTransportOptions::new(listen_infos)
+            }
+        };
+
+        options.enable_udp = true;
+        options.enable_tcp = self.enable_tcp;
+        options.prefer_udp = true;
+        options
+    }
+}
+
 pub struct MediaService {
-    workers: Vec<Worker>,
-    routers: Arc<Mutex<HashMap<Uuid, Router>>>,
+    worker_manager: WorkerManager,
+    workers: Arc<Mutex<Vec<worker::WorkerSlot>>>,
+    dead_workers_tx: mpsc::UnboundedSender<WorkerId>,
+    dead_workers_rx: Mutex<mpsc::UnboundedReceiver<WorkerId>>,
+    ended_whip_sessions_tx: mpsc::UnboundedSender<Uuid>,
+    ended_whip_sessions_rx: Mutex<mpsc::UnboundedReceiver<Uuid>>,
+    ended_whep_sessions_tx: mpsc::UnboundedSender<Uuid>,
+    ended_whep_sessions_rx: Mutex<mpsc::UnboundedReceiver<Uuid>>,
+    ended_restreams_tx: mpsc::UnboundedSender<Uuid>,
+    ended_restreams_rx: Mutex<mpsc::UnboundedReceiver<Uuid>>,
+    routers: Arc<Mutex<HashMap<Uuid, ChannelRouter>>>,
     connection_media: Arc<Mutex<HashMap<Uuid, transport::ConnectionMediaState>>>,
-    webrtc_listen_ip: IpAddr,
-    announced_ip: Option<String>,
+    webrtc: WebRtcListenSettings,
     native_rtp_listen_ip: IpAddr,
     native_rtp_announced_ip: Option<IpAddr>,
+    rtc_port_range: Option<RangeInclusive<u16>>,
 }
 
 impl MediaService {
-    pub async fn new(
-        worker_count: usize,
-        webrtc_listen_ip: String,
-        announced_ip: Option<String>,
-        native_rtp_listen_ip: String,
-        native_rtp_announced_ip: Option<String>,
-    ) -> Self {
-        let mut workers = Vec::new();
-
-        let worker_manager = WorkerManager::new();
-
-        for _ in 0..worker_count {
-            let worker = worker_manager
-                .create_worker(WorkerSettings::default())
-                .await
-                .expect("Failed to create mediasoup worker");
-            workers.push(worker);
-        }
-
-        let parsed_webrtc_listen_ip = IpAddr::from_str(&webrtc_listen_ip).unwrap_or_else(|error| {
-            tracing::warn!(
-                "Invalid WEBRTC_LISTEN_IP '{}': {}. Falling back to 127.0.0.1",
-                webrtc_listen_ip,
-                error
-            );
-            IpAddr::V4(Ipv4Addr::LOCALHOST)
-        });
+    pub async fn new(config: &MediaConfig) -> Self {
+        let parsed_webrtc_listen_ip =
+            IpAddr::from_str(&config.webrtc_listen_ip).unwrap_or_else(|error| {
+                tracing::warn!(
+                    "Invalid WEBRTC_LISTEN_IP '{}': {}. Falling back to 127.0.0.1",
+                    config.webrtc_listen_ip,
+                    error
+                );
+                IpAddr::V4(Ipv4Addr::LOCALHOST)
+            });
 
-        let parsed_native_rtp_listen_ip =
-            IpAddr::from_str(&native_rtp_listen_ip).unwrap_or_else(|error| {
+        let parsed_native_rtp_listen_ip = IpAddr::from_str(&config.native_rtp_listen_ip)
+            .unwrap_or_else(|error| {
                 tracing::warn!(
                     "Invalid NATIVE_RTP_LISTEN_IP '{}': {}. Falling back to 127.0.0.1",
-                    native_rtp_listen_ip,
+                    config.native_rtp_listen_ip,
                     error
                 );
                 IpAddr::V4(Ipv4Addr::LOCALHOST)
             });
 
-        let parsed_native_rtp_announced_ip = native_rtp_announced_ip.and_then(|value| {
-            let trimmed = value.trim();
-            if trimmed.is_empty() {
-                return None;
-            }
+        let rtc_port_range = match (config.rtc_min_port, config.rtc_max_port) {
+            (Some(min), Some(max)) => Some(min..=max),
+            _ => None,
+        };
+
+        let webrtc = WebRtcListenSettings {
+            listen_ip: parsed_webrtc_listen_ip,
+            announced_ip: config.announced_ip.clone(),
+            enable_tcp: config.webrtc_enable_tcp,
+            server_port: config
+                .webrtc_server_enabled
+                .then_some(config.webrtc_server_port),
+            port_range: rtc_port_range.clone(),
+        };
 
-            match IpAddr::from_str(trimmed) {
-                Ok(ip) => Some(ip),
-                Err(error) => {
-                    tracing::warn!(
-                        "Invalid NATIVE_RTP_ANNOUNCED_IP '{}': {}. Falling back to listen IP",
-                        trimmed,
-                        error
-                    );
-                    None
+        let mut workers = Vec::new();
+
+        let worker_manager = WorkerManager::new();
+        let (dead_workers_tx, dead_workers_rx) = mpsc::unbounded_channel();
+        let (ended_whip_sessions_tx, ended_whip_sessions_rx) = mpsc::unbounded_channel();
+        let (ended_whep_sessions_tx, ended_whep_sessions_rx) = mpsc::unbounded_channel();
+        let (ended_restreams_tx, ended_restreams_rx) = mpsc::unbounded_channel();
+
+        for index in 0..config.worker_count {
+            let slot =
+                worker::spawn_worker(&worker_manager, dead_workers_tx.clone(), &webrtc, index)
+                    .await
+                    .expect("Failed to create mediasoup worker");
+            workers.push(slot);
+        }
+
+        let parsed_native_rtp_announced_ip =
+            config.native_rtp_announced_ip.clone().and_then(|value| {
+                let trimmed = value.trim();
+                if trimmed.is_empty() {
+                    return None;
                 }
-            }
-        });
+
+                match IpAddr::from_str(trimmed) {
+                    Ok(ip) => Some(ip),
+                    Err(error) => {
+                        tracing::warn!(
+                            "Invalid NATIVE_RTP_ANNOUNCED_IP '{}': {}. Falling back to listen IP",
+                            trimmed,
+                            error
+                        );
+                        None
+                    }
+                }
+            });
 
         MediaService {
-            workers,
+            worker_manager,
+            workers: Arc::new(Mutex::new(workers)),
+            dead_workers_tx,
+            dead_workers_rx: Mutex::new(dead_workers_rx),
+            ended_whip_sessions_tx,
+            ended_whip_sessions_rx: Mutex::new(ended_whip_sessions_rx),
+            ended_whep_sessions_tx,
+            ended_whep_sessions_rx: Mutex::new(ended_whep_sessions_rx),
+            ended_restreams_tx,
+            ended_restreams_rx: Mutex::new(ended_restreams_rx),
             routers: Arc::new(Mutex::new(HashMap::new())),
             connection_media: Arc::new(Mutex::new(HashMap::new())),
-            webrtc_listen_ip: parsed_webrtc_listen_ip,
-            announced_ip,
+            webrtc,
             native_rtp_listen_ip: parsed_native_rtp_listen_ip,
             native_rtp_announced_ip: parsed_native_rtp_announced_ip,
+            rtc_port_range,
         }
     }
 
     /// Gets an existing router for the channel or creates a new one.
     ///
-    /// NOTE: Routers are cached by channel_id. The `opus_config` is only applied
-    /// when creating a new router. If a channel's codec settings are modified
-    /// after the router is created, the cached router will retain its original
-    /// settings until the server restarts or the router is otherwise evicted.
-    /// For now this is acceptable since codec settings are set at channel creation
-    /// and are not expected to change frequently.
+    /// Routers are cached by channel_id and the `opus_config` is only applied
+    /// when creating a new router. Codec changes on a live channel go through
+    /// `replace_router`, which swaps in a fresh router so that members can
+    /// renegotiate onto it.
     pub async fn get_or_create_router(
         &self,
         channel_id: Uuid,
         opus_config: router::OpusConfig,
-    ) -> Router {
+    ) -> Result<Router, String> {
+        self.get_or_create_router_with_server(channel_id, opus_config)
+            .await
+            .map(|(router, _)| router)
+    }
+
+    /// Same as `get_or_create_router`, but also returns the `WebRtcServer` of
+    /// the worker hosting the router when single-port mode is enabled.
+    pub(super) async fn get_or_create_router_with_server(
+        &self,
+        channel_id: Uuid,
+        opus_config: router::OpusConfig,
+    ) -> Result<(Router, Option<WebRtcServer>), String> {
         let mut routers = self.routers.lock().await;
 
-        if let Some(router) = routers.get(&channel_id) {
-            return router.clone();
+        if let Some(channel_router) = routers.get(&channel_id) {
+            return Ok((
+                channel_router.router.clone(),
+                channel_router.webrtc_server.clone(),
+            ));
         }
 
-        let worker = &self.workers[channel_id.as_bytes()[0] as usize % self.workers.len()];
-        let media_codecs = router::media_codecs(Some(&opus_config));
+        // Fails while every worker is dead or respawning; callers surface
+        // the error so the client can retry once a worker is back.
+        let channel_router = self.create_router(&routers, &opus_config).await?;
 
-        let router = worker
-            .create_router(RouterOptions::new(media_codecs))
-            .await
-            .expect("Failed to create router");
+        let router = channel_router.router.clone();
+        let webrtc_server = channel_router.webrtc_server.clone();
+        routers.insert(channel_id, channel_router);
+        Ok((router, webrtc_server))
+    }
+
+    /// Returns the channel's router and `WebRtcServer` without creating a
+    /// router when none is cached.
+    pub(super) async fn cached_router_with_server(
+        &self,
+        channel_id: Uuid,
+    ) -> Option<(Router, Option<WebRtcServer>)> {
+        let routers = self.routers.lock().await;
+        routers.get(&channel_id).map(|channel_router| {
+            (
+                channel_router.router.clone(),
+                channel_router.webrtc_server.clone(),
+            )
+        })
+    }
+
+    /// Replaces the cached router for a channel with one built from
+    /// `opus_config` and returns the previous router and its replacement.
+    ///
+    /// Returns `None` when no router was cached, in which case there is
+    /// nothing to migrate and the next `get_or_create_router` call will pick
+    /// up the new settings, or when the cached router already uses
+    /// `opus_config`.
+    pub(super) async fn replace_router(
+        &self,
+        channel_id: Uuid,
+        opus_config: &router::OpusConfig,
+    ) -> Result<Option<(Router, Router)>, String> {
+        let mut routers = self.routers.lock().await;
 
-        routers.insert(channel_id, router.clone());
-        router
+        let Some(previous) = routers.get(&channel_id) else {
+            return Ok(None);
+        };
+        if previous.opus_config == *opus_config {
+            return Ok(None);
+        }
+        let previous_router = previous.router.clone();
+
+        let channel_router = self.create_router(&routers, opus_config).await?;
+        let router = channel_router.router.clone();
+        routers.insert(channel_id, channel_router);
+        Ok(Some((previous_router, router)))
     }
 
     /// Removes the cached router for a channel, allowing it to be recreated
@@ -129,18 +284,35 @@ impl MediaService {
         routers.remove(&channel_id);
     }
 
-    pub(super) fn webrtc_listen_info(&self) -> ListenInfo {
-        ListenInfo {
-            protocol: Protocol::Udp,
-            ip: self.webrtc_listen_ip,
-            announced_address: self.announced_ip.clone(),
-            expose_internal_ip: false,
-            port: None,
-            port_range: None,
-            flags: None,
-            send_buffer_size: None,
-            recv_buffer_size: None,
-        }
+    async fn create_router(
+        &self,
+        routers: &HashMap<Uuid, ChannelRouter>,
+        opus_config: &router::OpusConfig,
+    ) -> Result<ChannelRouter, String> {
+        let slot = self.least_loaded_worker(routers).await?;
+        let media_codecs = router::media_codecs(Some(opus_config));
+
+        let router = slot
+            .worker
+            .create_router(RouterOptions::new(media_codecs))
+            .await
+            .map_err(|error| format!("Failed to create router: {error}"))?;
+
+        Ok(ChannelRouter {
+            router,
+            worker_id: slot.worker.id(),
+            webrtc_server: slot.webrtc_server,
+            opus_config: opus_config.clone(),
+        })
+    }
+
+    /// Builds WebRTC transport options, sharing the worker's `WebRtcServer`
+    /// port when one is running and otherwise binding a socket per transport.
+    pub(super) fn webrtc_transport_options(
+        &self,
+        webrtc_server: Option<WebRtcServer>,
+    ) -> WebRtcTransportOptions {
+        self.webrtc.transport_options(webrtc_server)
     }
 
     pub(super) fn connection_media(
@@ -156,7 +328,7 @@ impl MediaService {
             announced_address: self.native_rtp_announced_ip.map(|ip| ip.to_string()),
             expose_internal_ip: false,
             port: None,
-            port_range: None,
+            port_range: self.rtc_port_range.clone(),
             flags: None,
             send_buffer_size: None,
             recv_buffer_size: None,
@@ -175,3 +347,58 @@ impl MediaService {
         std::net::SocketAddr::new(target_ip, local_port).to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mediasoup::webrtc_transport::WebRtcTransportListen;
+
+    fn listen_settings(enable_tcp: bool) -> WebRtcListenSettings {
+        WebRtcListenSettings {
+            listen_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            announced_ip: Some("203.0.113.5".into()),
+            enable_tcp,
+            server_port: None,
+            port_range: Some(40000..=40100),
+        }
+    }
+
+    fn listen_protocols(options: &WebRtcTransportOptions) -> Vec<Protocol> {
+        match &options.listen {
+            WebRtcTransportListen::Individual { listen_infos } => {
+                listen_infos.iter().map(|info| info.protocol).collect()
+            }
+            WebRtcTransportListen::Server { .. } => panic!("expected per-transport sockets"),
+        }
+    }
+
+    #[test]
+    fn transport_options_listen_on_udp_only_by_default() {
+        let options = listen_settings(false).transport_options(None);
+
+        assert_eq!(listen_protocols(&options), vec![Protocol::Udp]);
+        assert!(options.enable_udp);
+        assert!(!options.enable_tcp);
+        assert!(options.prefer_udp);
+    }
+
+    #[test]
+    fn transport_options_add_tcp_and_keep_the_port_range() {
+        let options = listen_settings(true).transport_options(None);
+
+        assert_eq!(
+            listen_protocols(&options),
+            vec![Protocol::Udp, Protocol::Tcp]
+        );
+        assert!(options.enable_tcp);
+        assert!(options.prefer_udp);
+        let WebRtcTransportListen::Individual { listen_infos } = &options.listen else {
+            unreachable!();
+        };
+        for info in listen_infos.iter() {
+            assert_eq!(info.port, None);
+            assert_eq!(info.port_range, Some(40000..=40100));
+            assert_eq!(info.announced_address.as_deref(), Some("203.0.113.5"));
+        }
+    }
+}
@@ -1,7 +1,10 @@
+use base64::Engine;
 use mediasoup::prelude::{
-    MimeTypeVideo, RtcpFeedback, RtcpParameters, RtpCodecParameters, RtpCodecParametersParameters,
-    RtpEncodingParameters, RtpParameters,
+    MimeTypeAudio, MimeTypeVideo, RtcpFeedback, RtcpParameters, RtpCodecParameters,
+    RtpCodecParametersParameters, RtpEncodingParameters, RtpEncodingParametersRtx,
+    RtpHeaderExtensionParameters, RtpHeaderExtensionUri, RtpParameters, SrtpCryptoSuite,
 };
+use mediasoup::types::srtp_parameters::SrtpParameters;
 use serde::Serialize;
 use uuid::Uuid;
 
@@ -15,6 +18,18 @@ const NATIVE_VP9_CLOCK_RATE: u32 = 90_000;
 const NATIVE_VP9_PT: u8 = 100;
 const NATIVE_AV1_CLOCK_RATE: u32 = 90_000;
 const NATIVE_AV1_PT: u8 = 102;
+/// One-byte header extension ids the native sender stamps on video so the
+/// router can send transport-cc feedback and REMB back to it.
+pub(super) const NATIVE_ABS_SEND_TIME_EXT_ID: u8 = 2;
+pub(super) const NATIVE_TRANSPORT_CC_EXT_ID: u8 = 3;
+/// The suite the native sender implements: AES-128 counter mode with an
+/// 80-bit HMAC-SHA1 tag, keyed by a 16-byte master key and 14-byte salt.
+pub(super) const NATIVE_SRTP_CRYPTO_SUITE: SrtpCryptoSuite = SrtpCryptoSuite::AesCm128HmacSha180;
+const NATIVE_SRTP_CRYPTO_SUITE_NAME: &str = "AES_CM_128_HMAC_SHA1_80";
+const NATIVE_SRTP_MASTER_KEY_LEN: usize = 30;
+const NATIVE_OPUS_PT: u8 = 111;
+const NATIVE_OPUS_CLOCK_RATE: u32 = 48_000;
+const NATIVE_OPUS_CHANNELS: u8 = 2;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -99,6 +114,11 @@ impl NativeVideoCodec {
         }
     }
 
+    /// Every video payload type leaves the next one free for its RTX.
+    pub fn rtx_payload_type(self) -> u8 {
+        self.payload_type() + 1
+    }
+
     pub fn clock_rate(self) -> u32 {
         match self {
             Self::H264 => NATIVE_H264_CLOCK_RATE,
@@ -155,9 +175,97 @@ pub struct NativeSenderSession {
     pub profile_level_id: String,
     pub codec: NativeCodecDescriptor,
     pub available_codecs: Vec<NativeCodecDescriptor>,
+    pub rtx: NativeRtxSession,
+    pub transport_cc_extension_id: u8,
+    pub abs_send_time_extension_id: u8,
+    /// Present when the client asked to send the shared screen's audio too.
+    pub audio: Option<NativeAudioSession>,
+    /// Present when the client asked for an encrypted session.
+    pub srtp: Option<NativeSrtpSession>,
     pub owner_connection_id: Uuid,
 }
 
+/// Retransmission stream (RFC 4588) the client answers NACKs on.
+#[derive(Debug, Clone, Serialize)]
+pub struct NativeRtxSession {
+    pub payload_type: u8,
+    pub ssrc: u32,
+}
+
+impl NativeRtxSession {
+    pub(super) fn new(codec: NativeVideoCodec, video_ssrc: u32) -> Self {
+        Self {
+            payload_type: codec.rtx_payload_type(),
+            ssrc: native_rtx_ssrc(video_ssrc),
+        }
+    }
+}
+
+/// Opus producer that shares a native sender's plain transport, so the
+/// client sends it from the same socket as the video.
+#[derive(Debug, Clone, Serialize)]
+pub struct NativeAudioSession {
+    pub producer_id: String,
+    pub payload_type: u8,
+    pub ssrc: u32,
+    pub mime_type: String,
+    pub clock_rate: u32,
+    pub channels: u8,
+}
+
+impl NativeAudioSession {
+    pub(super) fn new(producer_id: String, ssrc: u32) -> Self {
+        Self {
+            producer_id,
+            payload_type: NATIVE_OPUS_PT,
+            ssrc,
+            mime_type: "audio/opus".to_string(),
+            clock_rate: NATIVE_OPUS_CLOCK_RATE,
+            channels: NATIVE_OPUS_CHANNELS,
+        }
+    }
+}
+
+/// SRTP keys for a native sender session. Each direction has its own master
+/// key: the client protects its RTP and RTCP with `key_base64`, and checks
+/// the router's RTCP feedback with `remote_key_base64`. Both only ever
+/// travel over the authenticated media signal.
+#[derive(Debug, Clone, Serialize)]
+pub struct NativeSrtpSession {
+    pub crypto_suite: String,
+    pub key_base64: String,
+    pub remote_key_base64: String,
+}
+
+impl NativeSrtpSession {
+    pub(super) fn new(client_key: &SrtpParameters, router_key: &SrtpParameters) -> Self {
+        Self {
+            crypto_suite: NATIVE_SRTP_CRYPTO_SUITE_NAME.to_string(),
+            key_base64: client_key.key_base64.clone(),
+            remote_key_base64: router_key.key_base64.clone(),
+        }
+    }
+}
+
+/// A fresh master key and salt for the client's side of the session, drawn
+/// from the operating system's CSPRNG.
+pub(super) fn generate_native_srtp_parameters() -> Result<SrtpParameters, String> {
+    let mut key = [0u8; NATIVE_SRTP_MASTER_KEY_LEN];
+    getrandom::getrandom(&mut key)
+        .map_err(|error| format!("Failed to generate SRTP master key: {error}"))?;
+    Ok(SrtpParameters {
+        crypto_suite: NATIVE_SRTP_CRYPTO_SUITE,
+        key_base64: base64::engine::general_purpose::STANDARD.encode(key),
+    })
+}
+
+pub(super) fn native_rtx_ssrc(video_ssrc: u32) -> u32 {
+    match video_ssrc.wrapping_add(2) {
+        0 => 2,
+        ssrc => ssrc,
+    }
+}
+
 pub(super) fn canonical_native_ssrc(connection_id: Uuid) -> u32 {
     let bytes = connection_id.as_bytes();
     let mut seed = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
@@ -167,6 +275,63 @@ pub(super) fn canonical_native_ssrc(connection_id: Uuid) -> u32 {
     seed
 }
 
+/// SSRC of a native sender's audio, next to its video SSRC.
+pub(super) fn native_audio_ssrc(video_ssrc: u32) -> u32 {
+    match video_ssrc.wrapping_add(1) {
+        0 => 1,
+        ssrc => ssrc,
+    }
+}
+
+/// RTP parameters for a single 48 kHz stereo Opus stream.
+pub(super) fn opus_rtp_parameters(
+    mid: &str,
+    payload_type: u8,
+    ssrc: u32,
+    cname: String,
+) -> RtpParameters {
+    RtpParameters {
+        mid: Some(mid.to_string()),
+        codecs: vec![RtpCodecParameters::Audio {
+            mime_type: MimeTypeAudio::Opus,
+            payload_type,
+            clock_rate: NATIVE_OPUS_CLOCK_RATE.try_into().unwrap(),
+            channels: NATIVE_OPUS_CHANNELS.try_into().unwrap(),
+            parameters: RtpCodecParametersParameters::default(),
+            rtcp_feedback: vec![],
+        }],
+        header_extensions: vec![],
+        encodings: vec![RtpEncodingParameters {
+            ssrc: Some(ssrc),
+            rid: None,
+            codec_payload_type: Some(payload_type),
+            rtx: None,
+            dtx: None,
+            scalability_mode: Default::default(),
+            max_bitrate: None,
+        }],
+        rtcp: RtcpParameters {
+            cname: Some(cname),
+            reduced_size: true,
+        },
+    }
+}
+
+/// Opus parameters for a native sender's audio. The CNAME matches the
+/// video's so receivers treat both as one synchronized stream.
+pub(super) fn native_audio_rtp_parameters(video_ssrc: u32) -> RtpParameters {
+    opus_rtp_parameters(
+        "native-audio",
+        NATIVE_OPUS_PT,
+        native_audio_ssrc(video_ssrc),
+        native_cname(video_ssrc),
+    )
+}
+
+fn native_cname(video_ssrc: u32) -> String {
+    format!("native-{video_ssrc:x}")
+}
+
 pub(super) fn native_rtp_parameters(codec: NativeVideoCodec, ssrc: u32) -> RtpParameters {
     let mut parameters = RtpCodecParametersParameters::default();
     if let Some(packetization_mode) = codec.packetization_mode() {
@@ -186,29 +351,124 @@ pub(super) fn native_rtp_parameters(codec: NativeVideoCodec, ssrc: u32) -> RtpPa
     };
     let payload_type = codec.payload_type();
     let clock_rate = codec.clock_rate();
+    let rtx = NativeRtxSession::new(codec, ssrc);
+    let mut rtx_parameters = RtpCodecParametersParameters::default();
+    rtx_parameters.insert("apt", u32::from(payload_type));
 
     RtpParameters {
         mid: Some("native-screen".to_string()),
-        codecs: vec![RtpCodecParameters::Video {
-            mime_type,
-            payload_type,
-            clock_rate: clock_rate.try_into().unwrap(),
-            parameters,
-            rtcp_feedback: vec![RtcpFeedback::NackPli, RtcpFeedback::CcmFir],
-        }],
-        header_extensions: vec![],
+        codecs: vec![
+            RtpCodecParameters::Video {
+                mime_type,
+                payload_type,
+                clock_rate: clock_rate.try_into().unwrap(),
+                parameters,
+                rtcp_feedback: vec![
+                    RtcpFeedback::Nack,
+                    RtcpFeedback::NackPli,
+                    RtcpFeedback::CcmFir,
+                    RtcpFeedback::GoogRemb,
+                    RtcpFeedback::TransportCc,
+                ],
+            },
+            RtpCodecParameters::Video {
+                mime_type: MimeTypeVideo::Rtx,
+                payload_type: rtx.payload_type,
+                clock_rate: clock_rate.try_into().unwrap(),
+                parameters: rtx_parameters,
+                rtcp_feedback: vec![],
+            },
+        ],
+        header_extensions: vec![
+            RtpHeaderExtensionParameters {
+                uri: RtpHeaderExtensionUri::AbsSendTime,
+                id: NATIVE_ABS_SEND_TIME_EXT_ID.into(),
+                encrypt: false,
+            },
+            RtpHeaderExtensionParameters {
+                uri: RtpHeaderExtensionUri::TransportWideCcDraft01,
+                id: NATIVE_TRANSPORT_CC_EXT_ID.into(),
+                encrypt: false,
+            },
+        ],
         encodings: vec![RtpEncodingParameters {
             ssrc: Some(ssrc),
             rid: None,
             codec_payload_type: Some(payload_type),
-            rtx: None,
+            rtx: Some(RtpEncodingParametersRtx { ssrc: rtx.ssrc }),
             dtx: None,
             scalability_mode: Default::default(),
             max_bitrate: None,
         }],
         rtcp: RtcpParameters {
-            cname: Some(format!("native-{ssrc:x}")),
+            cname: Some(native_cname(ssrc)),
             reduced_size: true,
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn native_audio_shares_the_video_cname_on_its_own_ssrc() {
+        let video = native_rtp_parameters(NativeVideoCodec::H264, 0xFFFF_FFFF);
+        let audio = native_audio_rtp_parameters(0xFFFF_FFFF);
+
+        assert_eq!(audio.rtcp.cname, video.rtcp.cname);
+        assert_eq!(audio.encodings[0].ssrc, Some(1));
+        assert_eq!(native_audio_ssrc(41), 42);
+    }
+
+    #[test]
+    fn native_video_declares_rtx_next_to_its_codec() {
+        let parameters = native_rtp_parameters(NativeVideoCodec::Vp8, 40);
+
+        let RtpCodecParameters::Video {
+            mime_type,
+            payload_type,
+            parameters: rtx_parameters,
+            ..
+        } = &parameters.codecs[1]
+        else {
+            panic!("expected a video RTX codec");
+        };
+        assert_eq!(*mime_type, MimeTypeVideo::Rtx);
+        assert_eq!(*payload_type, NATIVE_VP8_PT + 1);
+        assert!(rtx_parameters.get("apt").is_some());
+        assert_eq!(
+            parameters.encodings[0].rtx.as_ref().map(|rtx| rtx.ssrc),
+            Some(42)
+        );
+        assert_ne!(native_rtx_ssrc(40), native_audio_ssrc(40));
+    }
+
+    #[test]
+    fn native_srtp_keys_are_fresh_master_keys_and_salts() {
+        let first = generate_native_srtp_parameters().unwrap();
+        let second = generate_native_srtp_parameters().unwrap();
+
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(&first.key_base64)
+            .expect("valid base64");
+        assert_eq!(decoded.len(), NATIVE_SRTP_MASTER_KEY_LEN);
+        assert_ne!(first.key_base64, second.key_base64);
+        assert_eq!(first.crypto_suite, NATIVE_SRTP_CRYPTO_SUITE);
+    }
+
+    #[test]
+    fn native_video_negotiates_congestion_control_feedback() {
+        let parameters = native_rtp_parameters(NativeVideoCodec::H264, 40);
+
+        let RtpCodecParameters::Video { rtcp_feedback, .. } = &parameters.codecs[0] else {
+            panic!("expected a video codec");
+        };
+        assert!(rtcp_feedback.contains(&RtcpFeedback::GoogRemb));
+        assert!(rtcp_feedback.contains(&RtcpFeedback::TransportCc));
+        assert!(parameters.header_extensions.iter().any(|extension| {
+            extension.uri == RtpHeaderExtensionUri::TransportWideCcDraft01
+                && extension.id == u16::from(NATIVE_TRANSPORT_CC_EXT_ID)
+        }));
+    }
+}
@@ -0,0 +1,381 @@
+use mediasoup::prelude::{
+    ConsumerOptions, ListenInfo, MediaKind, PlainTransportOptions, PlainTransportRemoteParameters,
+    Protocol, RtpCapabilities, Transport,
+};
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use uuid::Uuid;
+
+use super::sdp::{write_codec, SdpCodec};
+use super::transport::{media_kind_as_str, ConnectionMediaState, MediaSessionKind};
+use super::whep::{answer_codecs, WatchableStream};
+use super::MediaService;
+
+const FFMPEG_PORT_PAIR_ATTEMPTS: usize = 32;
+
+/// What ffmpeg needs to read a restream: an SDP file describing the RTP the
+/// session's consumers send to it.
+#[derive(Debug)]
+pub struct RestreamInput {
+    pub sdp: String,
+    /// Codec name of the video track (e.g. `"H264"`, `"VP8"`), if any.
+    pub video_codec: Option<String>,
+    pub has_audio: bool,
+}
+
+/// One consumed track and the loopback port pair ffmpeg reads it on.
+struct RestreamTrack {
+    kind: MediaKind,
+    rtp_port: u16,
+    codec: SdpCodec,
+}
+
+/// Router capabilities stripped down to what a plain RTP reader copes with:
+/// no RTX, no RTCP feedback and no header extensions.
+fn restream_rtp_capabilities(router_capabilities: &Value) -> Result<RtpCapabilities, String> {
+    let codecs: Vec<Value> = router_capabilities["codecs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|codec| {
+            !codec["mimeType"]
+                .as_str()
+                .is_some_and(|mime_type| mime_type.eq_ignore_ascii_case("video/rtx"))
+        })
+        .map(|codec| {
+            let mut codec = codec.clone();
+            codec["rtcpFeedback"] = json!([]);
+            codec
+        })
+        .collect();
+
+    serde_json::from_value(json!({
+        "codecs": codecs,
+        "headerExtensions": [],
+    }))
+    .map_err(|error| format!("Failed to build restream capabilities: {error}"))
+}
+
+/// Writes the SDP file ffmpeg opens to receive the restream tracks.
+fn ffmpeg_input_sdp(session_id: Uuid, tracks: &[RestreamTrack]) -> String {
+    let mut sdp = String::new();
+    let _ = write!(
+        sdp,
+        "v=0\r\no=- 0 0 IN IP4 127.0.0.1\r\ns=restream {session_id}\r\nc=IN IP4 127.0.0.1\r\nt=0 0\r\n"
+    );
+
+    for track in tracks {
+        let _ = write!(
+            sdp,
+            "m={} {} RTP/AVP {}\r\n",
+            media_kind_as_str(track.kind),
+            track.rtp_port,
+            track.codec.payload_type
+        );
+        write_codec(&mut sdp, &track.codec);
+        sdp.push_str("a=recvonly\r\n");
+    }
+
+    sdp
+}
+
+/// Picks `count` free loopback port pairs (even RTP port, RTCP on the next
+/// one) for ffmpeg. The sockets are only held while choosing so that pairs
+/// don't collide; ffmpeg binds the ports itself afterwards.
+fn reserve_ffmpeg_port_pairs(count: usize) -> Result<Vec<u16>, String> {
+    let mut held = Vec::new();
+    let mut ports = Vec::with_capacity(count);
+
+    for _ in 0..FFMPEG_PORT_PAIR_ATTEMPTS {
+        if ports.len() == count {
+            break;
+        }
+
+        let rtp = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))
+            .map_err(|error| format!("Failed to reserve restream port: {error}"))?;
+        let port = rtp
+            .local_addr()
+            .map_err(|error| format!("Failed to reserve restream port: {error}"))?
+            .port();
+        if port % 2 != 0 || port == u16::MAX {
+            held.push(rtp);
+            continue;
+        }
+        let Ok(rtcp) = UdpSocket::bind((Ipv4Addr::LOCALHOST, port + 1)) else {
+            held.push(rtp);
+            continue;
+        };
+
+        ports.push(port);
+        held.push(rtp);
+        held.push(rtcp);
+    }
+
+    if ports.len() < count {
+        return Err("No free UDP port pair for the restream input".into());
+    }
+    Ok(ports)
+}
+
+impl MediaService {
+    /// Consumes a watchable stream over plain RTP towards local ffmpeg ports.
+    ///
+    /// Consumers start paused; call `resume_restream` once ffmpeg listens on
+    /// the ports in the returned SDP. The session is stored under
+    /// `session_id` and reported by `next_ended_restream` when the stream's
+    /// producer or one of the transports closes.
+    pub async fn create_restream_session(
+        &self,
+        session_id: Uuid,
+        stream: &WatchableStream,
+    ) -> Result<RestreamInput, String> {
+        let Some((router, _)) = self.cached_router_with_server(stream.channel_id).await else {
+            return Err("Stream is no longer live".into());
+        };
+        let router_capabilities = serde_json::to_value(router.rtp_capabilities())
+            .map_err(|error| format!("Failed to read router capabilities: {error}"))?;
+        let rtp_capabilities = restream_rtp_capabilities(&router_capabilities)?;
+
+        let ports = reserve_ffmpeg_port_pairs(stream.tracks.len())?;
+        let mut state = ConnectionMediaState::new(stream.channel_id);
+        state.kind = MediaSessionKind::Restream;
+        let mut tracks = Vec::with_capacity(stream.tracks.len());
+
+        for ((kind, producer_id), rtp_port) in stream.tracks.iter().zip(ports) {
+            if !router.can_consume(producer_id, &rtp_capabilities) {
+                continue;
+            }
+
+            let mut transport_options = PlainTransportOptions::new(ListenInfo {
+                protocol: Protocol::Udp,
+                ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                announced_address: None,
+                expose_internal_ip: false,
+                port: None,
+                port_range: None,
+                flags: None,
+                send_buffer_size: None,
+                recv_buffer_size: None,
+            });
+            transport_options.rtcp_mux = false;
+            transport_options.comedia = false;
+
+            let transport = router
+                .create_plain_transport(transport_options)
+                .await
+                .map_err(|error| format!("Failed to create restream transport: {error}"))?;
+            transport
+                .connect(PlainTransportRemoteParameters {
+                    ip: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+                    port: Some(rtp_port),
+                    rtcp_port: Some(rtp_port + 1),
+                    srtp_parameters: None,
+                })
+                .await
+                .map_err(|error| format!("Failed to connect restream transport: {error}"))?;
+
+            let close_tx = self.ended_restreams_tx.clone();
+            transport
+                .on_close(Box::new(move || {
+                    let _ = close_tx.send(session_id);
+                }))
+                .detach();
+
+            let mut consumer_options = ConsumerOptions::new(*producer_id, rtp_capabilities.clone());
+            consumer_options.paused = true;
+            let consumer = transport
+                .consume(consumer_options)
+                .await
+                .map_err(|error| format!("Failed to create restream consumer: {error}"))?;
+
+            let ended_tx = self.ended_restreams_tx.clone();
+            consumer
+                .on_producer_close(move || {
+                    let _ = ended_tx.send(session_id);
+                })
+                .detach();
+
+            let rtp_parameters =
+                serde_json::to_value(consumer.rtp_parameters()).unwrap_or_default();
+            let Some(codec) = answer_codecs(&rtp_parameters).into_iter().next() else {
+                return Err("Restream consumer has no codec".into());
+            };
+
+            tracks.push(RestreamTrack {
+                kind: *kind,
+                rtp_port,
+                codec,
+            });
+            state
+                .native_transports_by_producer
+                .insert(producer_id.to_string(), transport);
+            state.consumers.insert(consumer.id().to_string(), consumer);
+        }
+
+        if tracks.is_empty() {
+            return Err("Stream has no tracks that can be restreamed".into());
+        }
+
+        let input = RestreamInput {
+            sdp: ffmpeg_input_sdp(session_id, &tracks),
+            video_codec: tracks
+                .iter()
+                .find(|track| track.kind == MediaKind::Video)
+                .map(|track| track.codec.name.clone()),
+            has_audio: tracks.iter().any(|track| track.kind == MediaKind::Audio),
+        };
+
+        {
+            let media_state_lock = self.connection_media();
+            let mut media_state = media_state_lock.lock().await;
+            media_state.insert(session_id, state);
+        }
+
+        Ok(input)
+    }
+
+    /// Starts sending a restream's RTP and asks for a key frame so that ffmpeg
+    /// can begin decoding right away.
+    pub async fn resume_restream(&self, session_id: Uuid) -> Result<(), String> {
+        let consumers: Vec<_> = {
+            let media_state_lock = self.connection_media();
+            let media_state = media_state_lock.lock().await;
+            let Some(entry) = media_state.get(&session_id) else {
+                return Err("Restream session not found".into());
+            };
+            entry.consumers.values().cloned().collect()
+        };
+
+        for consumer in &consumers {
+            consumer
+                .resume()
+                .await
+                .map_err(|error| format!("Failed to resume restream consumer: {error}"))?;
+        }
+        self.request_restream_key_frame(session_id).await;
+        Ok(())
+    }
+
+    /// Asks the restreamed video producer for a key frame. ffmpeg can only
+    /// cut a copied video track into segments at key frames.
+    pub async fn request_restream_key_frame(&self, session_id: Uuid) {
+        let consumers: Vec<_> = {
+            let media_state_lock = self.connection_media();
+            let media_state = media_state_lock.lock().await;
+            let Some(entry) = media_state.get(&session_id) else {
+                return;
+            };
+            entry
+                .consumers
+                .values()
+                .filter(|consumer| consumer.kind() == MediaKind::Video)
+                .cloned()
+                .collect()
+        };
+
+        for consumer in consumers {
+            if let Err(error) = consumer.request_key_frame().await {
+                tracing::debug!(
+                    session_id = %session_id,
+                    error = %error,
+                    "Failed to request restream key frame"
+                );
+            }
+        }
+    }
+
+    pub async fn close_restream_session(&self, session_id: Uuid) {
+        self.cleanup_connection_media(session_id).await;
+    }
+
+    /// Waits for the next restream whose producer or transport went away.
+    pub async fn next_ended_restream(&self) -> Option<Uuid> {
+        let mut ended_restreams_rx = self.ended_restreams_rx.lock().await;
+        ended_restreams_rx.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capabilities_drop_rtx_feedback_and_extensions() {
+        let router_capabilities = json!({
+            "codecs": [
+                {
+                    "kind": "video",
+                    "mimeType": "video/VP8",
+                    "preferredPayloadType": 101,
+                    "clockRate": 90000,
+                    "parameters": {},
+                    "rtcpFeedback": [{ "type": "nack", "parameter": "" }]
+                },
+                {
+                    "kind": "video",
+                    "mimeType": "video/rtx",
+                    "preferredPayloadType": 102,
+                    "clockRate": 90000,
+                    "parameters": { "apt": 101 },
+                    "rtcpFeedback": []
+                }
+            ],
+            "headerExtensions": [
+                {
+                    "kind": "video",
+                    "uri": "urn:ietf:params:rtp-hdrext:sdes:mid",
+                    "preferredId": 1,
+                    "preferredEncrypt": false,
+                    "direction": "sendrecv"
+                }
+            ]
+        });
+
+        let capabilities = restream_rtp_capabilities(&router_capabilities).unwrap();
+        let capabilities = serde_json::to_value(capabilities).unwrap();
+
+        assert_eq!(capabilities["codecs"].as_array().unwrap().len(), 1);
+        assert_eq!(capabilities["codecs"][0]["mimeType"], "video/VP8");
+        assert_eq!(capabilities["codecs"][0]["rtcpFeedback"], json!([]));
+        assert_eq!(capabilities["headerExtensions"], json!([]));
+    }
+
+    #[test]
+    fn input_sdp_lists_each_track_on_its_port() {
+        let tracks = [
+            RestreamTrack {
+                kind: MediaKind::Video,
+                rtp_port: 40000,
+                codec: SdpCodec {
+                    payload_type: 102,
+                    name: "H264".into(),
+                    clock_rate: 90000,
+                    channels: None,
+                    fmtp: vec![("packetization-mode".into(), "1".into())],
+                    rtcp_fb: Vec::new(),
+                },
+            },
+            RestreamTrack {
+                kind: MediaKind::Audio,
+                rtp_port: 40002,
+                codec: SdpCodec {
+                    payload_type: 100,
+                    name: "opus".into(),
+                    clock_rate: 48000,
+                    channels: Some(2),
+                    fmtp: Vec::new(),
+                    rtcp_fb: Vec::new(),
+                },
+            },
+        ];
+
+        let sdp = ffmpeg_input_sdp(Uuid::nil(), &tracks);
+
+        assert!(sdp.contains("m=video 40000 RTP/AVP 102\r\n"));
+        assert!(sdp.contains("a=rtpmap:102 H264/90000\r\n"));
+        assert!(sdp.contains("a=fmtp:102 packetization-mode=1\r\n"));
+        assert!(sdp.contains("m=audio 40002 RTP/AVP 100\r\n"));
+        assert!(sdp.contains("a=rtpmap:100 opus/48000/2\r\n"));
+    }
+}
@@ -2,13 +2,27 @@ use mediasoup::prelude::{
     MimeTypeAudio, MimeTypeVideo, RtcpFeedback, RtpCodecCapability, RtpCodecParametersParameters,
 };
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct OpusConfig {
     pub bitrate: Option<u32>,
     pub dtx: Option<bool>,
     pub fec: Option<bool>,
 }
 
+impl OpusConfig {
+    pub fn from_channel_columns(
+        bitrate: Option<i32>,
+        dtx: Option<bool>,
+        fec: Option<bool>,
+    ) -> Self {
+        Self {
+            bitrate: bitrate.map(|b| b as u32),
+            dtx,
+            fec,
+        }
+    }
+}
+
 fn build_opus_parameters(config: &OpusConfig) -> RtpCodecParametersParameters {
     let mut params = RtpCodecParametersParameters::default();
 
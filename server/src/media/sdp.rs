@@ -0,0 +1,413 @@
+//! Minimal SDP offer parsing and answer writing shared by the WHIP and WHEP
+//! endpoints. Only the attributes needed to bridge a single bundled,
+//! rtcp-muxed WebRTC peer onto a mediasoup transport are understood.
+
+use mediasoup::prelude::{DtlsParameters, RtpHeaderExtensionUri, WebRtcTransport};
+use std::fmt::Write as _;
+use uuid::Uuid;
+
+/// Upper bound on the SDP offer size accepted from WHIP and WHEP clients.
+pub const MAX_SDP_OFFER_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Default)]
+pub(super) struct SdpOffer {
+    pub(super) ice_ufrag: Option<String>,
+    pub(super) ice_pwd: Option<String>,
+    pub(super) fingerprint: Option<(String, String)>,
+    pub(super) setup: Option<String>,
+    pub(super) media: Vec<SdpMedia>,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct SdpMedia {
+    pub(super) kind: String,
+    pub(super) protocol: String,
+    pub(super) payload_types: Vec<u8>,
+    pub(super) mid: Option<String>,
+    pub(super) ice_ufrag: Option<String>,
+    pub(super) ice_pwd: Option<String>,
+    pub(super) fingerprint: Option<(String, String)>,
+    pub(super) setup: Option<String>,
+    pub(super) codecs: Vec<SdpCodec>,
+    pub(super) extmaps: Vec<(u16, String)>,
+    pub(super) ssrcs: Vec<u32>,
+    pub(super) fid_group: Option<(u32, u32)>,
+    pub(super) cname: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(super) struct SdpCodec {
+    pub(super) payload_type: u8,
+    pub(super) name: String,
+    pub(super) clock_rate: u32,
+    pub(super) channels: Option<u8>,
+    pub(super) fmtp: Vec<(String, String)>,
+    pub(super) rtcp_fb: Vec<String>,
+}
+
+impl SdpCodec {
+    pub(super) fn fmtp_value(&self, key: &str) -> Option<&str> {
+        self.fmtp
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub(super) fn parse_offer(sdp: &str) -> Result<SdpOffer, String> {
+    let mut offer = SdpOffer::default();
+
+    for line in sdp.lines().map(str::trim_end) {
+        if let Some(media_line) = line.strip_prefix("m=") {
+            let mut parts = media_line.split_whitespace();
+            let kind = parts.next().unwrap_or_default().to_string();
+            let _port = parts.next();
+            let protocol = parts.next().unwrap_or_default().to_string();
+            let payload_types = parts.filter_map(|value| value.parse().ok()).collect();
+            offer.media.push(SdpMedia {
+                kind,
+                protocol,
+                payload_types,
+                ..SdpMedia::default()
+            });
+            continue;
+        }
+
+        let Some(attribute) = line.strip_prefix("a=") else {
+            continue;
+        };
+        let (name, value) = attribute.split_once(':').unwrap_or((attribute, ""));
+
+        let Some(media) = offer.media.last_mut() else {
+            match name {
+                "ice-ufrag" => offer.ice_ufrag = Some(value.to_string()),
+                "ice-pwd" => offer.ice_pwd = Some(value.to_string()),
+                "fingerprint" => offer.fingerprint = parse_fingerprint(value),
+                "setup" => offer.setup = Some(value.to_string()),
+                _ => {}
+            }
+            continue;
+        };
+
+        match name {
+            "mid" => media.mid = Some(value.to_string()),
+            "ice-ufrag" => media.ice_ufrag = Some(value.to_string()),
+            "ice-pwd" => media.ice_pwd = Some(value.to_string()),
+            "fingerprint" => media.fingerprint = parse_fingerprint(value),
+            "setup" => media.setup = Some(value.to_string()),
+            "rtpmap" => {
+                let Some((payload_type, encoding)) = value.split_once(' ') else {
+                    continue;
+                };
+                let Ok(payload_type) = payload_type.parse() else {
+                    continue;
+                };
+                let mut encoding_parts = encoding.split('/');
+                let codec_name = encoding_parts.next().unwrap_or_default().to_string();
+                let clock_rate = encoding_parts
+                    .next()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or_default();
+                let channels = encoding_parts.next().and_then(|value| value.parse().ok());
+                let codec = codec_entry(media, payload_type);
+                codec.name = codec_name;
+                codec.clock_rate = clock_rate;
+                codec.channels = channels;
+            }
+            "fmtp" => {
+                let Some((payload_type, parameters)) = value.split_once(' ') else {
+                    continue;
+                };
+                let Ok(payload_type) = payload_type.parse() else {
+                    continue;
+                };
+                codec_entry(media, payload_type).fmtp = parameters
+                    .split(';')
+                    .filter_map(|parameter| {
+                        let (key, value) = parameter.trim().split_once('=')?;
+                        Some((key.trim().to_string(), value.trim().to_string()))
+                    })
+                    .collect();
+            }
+            "rtcp-fb" => {
+                let Some((payload_type, feedback)) = value.split_once(' ') else {
+                    continue;
+                };
+                if payload_type == "*" {
+                    for codec in &mut media.codecs {
+                        codec.rtcp_fb.push(feedback.to_string());
+                    }
+                } else if let Ok(payload_type) = payload_type.parse() {
+                    codec_entry(media, payload_type)
+                        .rtcp_fb
+                        .push(feedback.to_string());
+                }
+            }
+            "extmap" => {
+                let mut parts = value.split_whitespace();
+                let id = parts
+                    .next()
+                    .and_then(|id| id.split('/').next())
+                    .and_then(|id| id.parse().ok());
+                if let (Some(id), Some(uri)) = (id, parts.next()) {
+                    media.extmaps.push((id, uri.to_string()));
+                }
+            }
+            "ssrc" => {
+                let mut parts = value.splitn(2, ' ');
+                let Some(ssrc) = parts.next().and_then(|ssrc| ssrc.parse().ok()) else {
+                    continue;
+                };
+                if !media.ssrcs.contains(&ssrc) {
+                    media.ssrcs.push(ssrc);
+                }
+                if let Some(cname) = parts.next().and_then(|rest| rest.strip_prefix("cname:")) {
+                    media.cname.get_or_insert_with(|| cname.to_string());
+                }
+            }
+            "ssrc-group" => {
+                let mut parts = value.split_whitespace();
+                if parts.next() == Some("FID") {
+                    let primary = parts.next().and_then(|ssrc| ssrc.parse().ok());
+                    let rtx = parts.next().and_then(|ssrc| ssrc.parse().ok());
+                    if let (Some(primary), Some(rtx)) = (primary, rtx) {
+                        media.fid_group = Some((primary, rtx));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if offer.media.is_empty() {
+        return Err("SDP offer has no media sections".into());
+    }
+
+    Ok(offer)
+}
+
+fn codec_entry(media: &mut SdpMedia, payload_type: u8) -> &mut SdpCodec {
+    let index = match media
+        .codecs
+        .iter()
+        .position(|codec| codec.payload_type == payload_type)
+    {
+        Some(index) => index,
+        None => {
+            media.codecs.push(SdpCodec {
+                payload_type,
+                ..SdpCodec::default()
+            });
+            media.codecs.len() - 1
+        }
+    };
+    &mut media.codecs[index]
+}
+
+fn parse_fingerprint(value: &str) -> Option<(String, String)> {
+    let (algorithm, fingerprint) = value.split_once(' ')?;
+    Some((algorithm.to_lowercase(), fingerprint.trim().to_string()))
+}
+
+pub(super) fn header_extension_uri(uri: &str) -> Option<RtpHeaderExtensionUri> {
+    match uri {
+        "urn:ietf:params:rtp-hdrext:sdes:mid" => Some(RtpHeaderExtensionUri::Mid),
+        "urn:ietf:params:rtp-hdrext:ssrc-audio-level" => Some(RtpHeaderExtensionUri::AudioLevel),
+        "urn:ietf:params:rtp-hdrext:toffset" => Some(RtpHeaderExtensionUri::TimeOffset),
+        "urn:3gpp:video-orientation" => Some(RtpHeaderExtensionUri::VideoOrientation),
+        "http://www.webrtc.org/experiments/rtp-hdrext/abs-send-time" => {
+            Some(RtpHeaderExtensionUri::AbsSendTime)
+        }
+        "http://www.ietf.org/id/draft-holmer-rmcat-transport-wide-cc-extensions-01" => {
+            Some(RtpHeaderExtensionUri::TransportWideCcDraft01)
+        }
+        _ => None,
+    }
+}
+
+/// Converts the offer's DTLS attributes into mediasoup parameters, taking
+/// the opposite role of the one the remote peer picked.
+pub(super) fn remote_dtls_parameters(
+    offer: &SdpOffer,
+) -> Result<(DtlsParameters, &'static str), String> {
+    let first_media = offer.media.first();
+    let (algorithm, value) = first_media
+        .and_then(|media| media.fingerprint.clone())
+        .or_else(|| offer.fingerprint.clone())
+        .ok_or_else(|| "SDP offer is missing a DTLS fingerprint".to_string())?;
+    let setup = first_media
+        .and_then(|media| media.setup.clone())
+        .or_else(|| offer.setup.clone())
+        .unwrap_or_else(|| "actpass".to_string());
+
+    let (remote_role, local_setup) = match setup.as_str() {
+        "passive" => ("server", "active"),
+        _ => ("client", "passive"),
+    };
+
+    let dtls_parameters = serde_json::from_value(serde_json::json!({
+        "role": remote_role,
+        "fingerprints": [{ "algorithm": algorithm, "value": value }],
+    }))
+    .map_err(|error| format!("Invalid DTLS fingerprint in SDP offer: {error}"))?;
+
+    Ok((dtls_parameters, local_setup))
+}
+
+impl SdpOffer {
+    /// Whether a media section carries ICE credentials, either itself or
+    /// through session-level attributes.
+    pub(super) fn has_ice_credentials(&self, media: &SdpMedia) -> bool {
+        media
+            .ice_ufrag
+            .as_ref()
+            .or(self.ice_ufrag.as_ref())
+            .is_some()
+            && media.ice_pwd.as_ref().or(self.ice_pwd.as_ref()).is_some()
+    }
+}
+
+/// The ICE, DTLS and candidate attributes of a local transport, repeated in
+/// every bundled media section of an answer.
+pub(super) struct LocalTransportSdp {
+    ice_ufrag: String,
+    ice_pwd: String,
+    fingerprint: String,
+    candidates: Vec<String>,
+}
+
+impl LocalTransportSdp {
+    pub(super) fn new(transport: &WebRtcTransport) -> Self {
+        let ice_parameters = transport.ice_parameters();
+        let fingerprints =
+            serde_json::to_value(transport.dtls_parameters().fingerprints).unwrap_or_default();
+        let fingerprint = fingerprints
+            .as_array()
+            .and_then(|fingerprints| {
+                fingerprints
+                    .iter()
+                    .find(|fingerprint| fingerprint["algorithm"] == "sha-256")
+                    .or_else(|| fingerprints.first())
+            })
+            .map(|fingerprint| {
+                format!(
+                    "{} {}",
+                    fingerprint["algorithm"].as_str().unwrap_or_default(),
+                    fingerprint["value"].as_str().unwrap_or_default()
+                )
+            })
+            .unwrap_or_default();
+        let candidates = transport
+            .ice_candidates()
+            .iter()
+            .filter_map(|candidate| serde_json::to_value(candidate).ok())
+            .map(|candidate| {
+                let address = candidate
+                    .get("address")
+                    .or_else(|| candidate.get("ip"))
+                    .and_then(|value| value.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let protocol = candidate["protocol"].as_str().unwrap_or("udp");
+                let mut line = format!(
+                    "a=candidate:{} 1 {} {} {} {} typ host",
+                    candidate["foundation"].as_str().unwrap_or("0"),
+                    protocol,
+                    candidate["priority"].as_u64().unwrap_or_default(),
+                    address,
+                    candidate["port"].as_u64().unwrap_or_default(),
+                );
+                if protocol == "tcp" {
+                    let tcp_type = candidate["tcpType"].as_str().unwrap_or("passive");
+                    let _ = write!(line, " tcptype {tcp_type}");
+                }
+                line
+            })
+            .collect();
+
+        Self {
+            ice_ufrag: ice_parameters.username_fragment.clone(),
+            ice_pwd: ice_parameters.password.clone(),
+            fingerprint,
+            candidates,
+        }
+    }
+
+    /// Writes the `m=` line and transport attributes of an accepted section.
+    pub(super) fn write_media_start(
+        &self,
+        sdp: &mut String,
+        kind: &str,
+        payload_types: &[u8],
+        mid: &str,
+        local_setup: &str,
+        direction: &str,
+    ) {
+        let payload_types: Vec<String> = payload_types.iter().map(u8::to_string).collect();
+        let _ = write!(
+            sdp,
+            "m={kind} 9 UDP/TLS/RTP/SAVPF {}\r\nc=IN IP4 0.0.0.0\r\na=rtcp:9 IN IP4 0.0.0.0\r\n\
+             a=ice-ufrag:{}\r\na=ice-pwd:{}\r\na=fingerprint:{}\r\na=setup:{local_setup}\r\n\
+             a=mid:{mid}\r\na={direction}\r\na=rtcp-mux\r\na=rtcp-rsize\r\n",
+            payload_types.join(" "),
+            self.ice_ufrag,
+            self.ice_pwd,
+            self.fingerprint,
+        );
+    }
+
+    pub(super) fn write_candidates(&self, sdp: &mut String) {
+        for candidate in &self.candidates {
+            let _ = write!(sdp, "{candidate}\r\n");
+        }
+        sdp.push_str("a=end-of-candidates\r\n");
+    }
+}
+
+/// Writes the session-level lines of an ice-lite answer bundling `mids`.
+pub(super) fn write_session_header(sdp: &mut String, session_id: Uuid, mids: &[&str]) {
+    let _ = write!(
+        sdp,
+        "v=0\r\no=- {} 2 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\na=ice-lite\r\na=group:BUNDLE {}\r\na=msid-semantic: WMS\r\n",
+        (session_id.as_u128() as u64) >> 1,
+        mids.join(" ")
+    );
+}
+
+/// Writes the `rtpmap`, `fmtp` and `rtcp-fb` lines of a codec.
+pub(super) fn write_codec(sdp: &mut String, codec: &SdpCodec) {
+    let _ = write!(
+        sdp,
+        "a=rtpmap:{} {}/{}",
+        codec.payload_type, codec.name, codec.clock_rate
+    );
+    if let Some(channels) = codec.channels {
+        let _ = write!(sdp, "/{channels}");
+    }
+    sdp.push_str("\r\n");
+
+    if !codec.fmtp.is_empty() {
+        let fmtp: Vec<String> = codec
+            .fmtp
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        let _ = write!(sdp, "a=fmtp:{} {}\r\n", codec.payload_type, fmtp.join(";"));
+    }
+
+    for feedback in &codec.rtcp_fb {
+        let _ = write!(sdp, "a=rtcp-fb:{} {feedback}\r\n", codec.payload_type);
+    }
+}
+
+/// Writes a rejected (port zero) section mirroring an offered one.
+pub(super) fn write_rejected_media(sdp: &mut String, media: &SdpMedia) {
+    let payload_type = media.payload_types.first().copied().unwrap_or_default();
+    let _ = write!(
+        sdp,
+        "m={} 0 {} {payload_type}\r\nc=IN IP4 0.0.0.0\r\na=mid:{}\r\na=inactive\r\n",
+        media.kind,
+        media.protocol,
+        media.mid.as_deref().unwrap_or_default()
+    );
+}
@@ -0,0 +1,109 @@
+use mediasoup::prelude::{
+    ListenInfo, MediaKind, PlainTransportOptions, ProducerOptions, Protocol, Transport,
+};
+use std::net::{IpAddr, Ipv4Addr};
+use uuid::Uuid;
+
+use super::native_codec::{canonical_native_ssrc, opus_rtp_parameters};
+use super::transport::{
+    ClosedProducer, ConnectionMediaState, MediaSessionKind, ProducerEntry, ProducerSource,
+    RoutingMode,
+};
+use super::MediaService;
+
+pub const SOUNDBOARD_OPUS_PT: u8 = 100;
+
+/// Where ffmpeg sends a soundboard clip's RTP so that it plays in the
+/// voice channel.
+#[derive(Debug, Clone)]
+pub struct SoundboardInjection {
+    pub producer_id: String,
+    /// Loopback port of the plain transport; RTCP is muxed onto it.
+    pub rtp_port: u16,
+    pub ssrc: u32,
+    pub payload_type: u8,
+}
+
+impl MediaService {
+    /// Opens a loopback plain transport with an Opus producer on the voice
+    /// channel's router for one soundboard playback.
+    ///
+    /// The playback is stored under `session_id` like a connection, so the
+    /// channel's members see and consume it like any other audio producer.
+    /// Fails if nobody is in the channel's voice call.
+    pub async fn create_soundboard_injection(
+        &self,
+        session_id: Uuid,
+        channel_id: Uuid,
+    ) -> Result<SoundboardInjection, String> {
+        let Some((router, _)) = self.cached_router_with_server(channel_id).await else {
+            return Err("Nobody is in this voice channel".into());
+        };
+
+        let mut transport_options = PlainTransportOptions::new(ListenInfo {
+            protocol: Protocol::Udp,
+            ip: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            announced_address: None,
+            expose_internal_ip: false,
+            port: None,
+            port_range: None,
+            flags: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+        });
+        transport_options.comedia = true;
+        transport_options.rtcp_mux = true;
+
+        let transport = router
+            .create_plain_transport(transport_options)
+            .await
+            .map_err(|error| format!("Failed to create soundboard transport: {error}"))?;
+
+        let ssrc = canonical_native_ssrc(session_id);
+        let producer = transport
+            .produce(ProducerOptions::new(
+                MediaKind::Audio,
+                opus_rtp_parameters(
+                    "soundboard",
+                    SOUNDBOARD_OPUS_PT,
+                    ssrc,
+                    format!("soundboard-{ssrc:x}"),
+                ),
+            ))
+            .await
+            .map_err(|error| format!("Failed to create soundboard producer: {error}"))?;
+
+        let injection = SoundboardInjection {
+            producer_id: producer.id().to_string(),
+            rtp_port: transport.tuple().local_port(),
+            ssrc,
+            payload_type: SOUNDBOARD_OPUS_PT,
+        };
+
+        let mut state = ConnectionMediaState::new(channel_id);
+        state.kind = MediaSessionKind::Soundboard;
+        state
+            .native_transports_by_producer
+            .insert(injection.producer_id.clone(), transport);
+        state.producers.insert(
+            injection.producer_id.clone(),
+            ProducerEntry {
+                producer,
+                source: ProducerSource::Soundboard,
+                routing_mode: RoutingMode::Sfu,
+            },
+        );
+
+        {
+            let media_state_lock = self.connection_media();
+            let mut media_state = media_state_lock.lock().await;
+            media_state.insert(session_id, state);
+        }
+
+        Ok(injection)
+    }
+
+    pub async fn close_soundboard_injection(&self, session_id: Uuid) -> Vec<ClosedProducer> {
+        self.cleanup_connection_media(session_id).await
+    }
+}
@@ -0,0 +1,363 @@
+use mediasoup::prelude::{Consumer, Transport, TransportGeneric, WebRtcTransport};
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::transport::{media_kind_as_str, ProducerEntry};
+use super::MediaService;
+
+const GOOD_QUALITY_MIN_SCORE: u8 = 7;
+const FAIR_QUALITY_MIN_SCORE: u8 = 4;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransportStats {
+    pub transport_id: String,
+    pub direction: String,
+    pub ice_state: String,
+    pub recv_bitrate: u32,
+    pub send_bitrate: u32,
+    pub available_outgoing_bitrate: Option<u32>,
+    pub available_incoming_bitrate: Option<u32>,
+    pub packet_loss_received: Option<f64>,
+    pub packet_loss_sent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RtpStreamStats {
+    pub id: String,
+    pub kind: String,
+    pub source: Option<String>,
+    pub score: u8,
+    pub bitrate: u32,
+    /// Fraction of packets lost in the last reporting interval (0.0 - 1.0).
+    pub packet_loss: f64,
+    pub rtt_ms: Option<f64>,
+    /// Interarrival jitter in RTP timestamp units; only reported for
+    /// incoming streams.
+    pub jitter: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NetworkQuality {
+    /// 0 (unusable) to 10 (perfect), on the same scale as mediasoup scores.
+    pub score: u8,
+    pub level: &'static str,
+    pub rtt_ms: Option<f64>,
+    pub packet_loss: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionMediaStats {
+    pub transports: Vec<TransportStats>,
+    pub producers: Vec<RtpStreamStats>,
+    pub consumers: Vec<RtpStreamStats>,
+    pub quality: NetworkQuality,
+}
+
+struct StatsHandles {
+    send_transport_id: Option<String>,
+    transports: Vec<WebRtcTransport>,
+    producers: Vec<(String, ProducerEntry)>,
+    consumers: Vec<(String, Consumer)>,
+}
+
+impl MediaService {
+    /// Collects mediasoup stats for the caller's transports, producers and
+    /// consumers in `channel_id`.
+    pub async fn connection_stats(
+        &self,
+        connection_id: Uuid,
+        channel_id: Uuid,
+    ) -> Result<ConnectionMediaStats, String> {
+        let handles = self
+            .stats_handles(connection_id, channel_id)
+            .await
+            .ok_or_else(|| "No media session for this channel".to_string())?;
+
+        Ok(collect_stats(handles).await)
+    }
+
+    /// Computes a network quality score for every media session in
+    /// `channel_id`, keyed by connection id.
+    ///
+    /// Consumers are scored from the score mediasoup already pushed to us
+    /// rather than a stats request each, so the cost grows with members
+    /// instead of members squared.
+    pub async fn channel_network_quality(&self, channel_id: Uuid) -> HashMap<Uuid, NetworkQuality> {
+        let connection_ids: Vec<Uuid> = {
+            let media_state_lock = self.connection_media();
+            let media_state = media_state_lock.lock().await;
+            media_state
+                .iter()
+                .filter_map(|(connection_id, entry)| {
+                    (entry.channel_id == channel_id).then_some(*connection_id)
+                })
+                .collect()
+        };
+
+        let mut quality_by_connection = HashMap::with_capacity(connection_ids.len());
+        for connection_id in connection_ids {
+            if let Some(handles) = self.stats_handles(connection_id, channel_id).await {
+                quality_by_connection.insert(connection_id, collect_quality(&handles).await);
+            }
+        }
+        quality_by_connection
+    }
+
+    async fn stats_handles(&self, connection_id: Uuid, channel_id: Uuid) -> Option<StatsHandles> {
+        let media_state_lock = self.connection_media();
+        let media_state = media_state_lock.lock().await;
+        let entry = media_state.get(&connection_id)?;
+        if entry.channel_id != channel_id {
+            return None;
+        }
+
+        Some(StatsHandles {
+            send_transport_id: entry.send_transport_id.clone(),
+            transports: entry.transports.values().cloned().collect(),
+            producers: entry
+                .producers
+                .iter()
+                .map(|(producer_id, producer)| (producer_id.clone(), producer.clone()))
+                .collect(),
+            consumers: entry
+                .consumers
+                .iter()
+                .map(|(consumer_id, consumer)| (consumer_id.clone(), consumer.clone()))
+                .collect(),
+        })
+    }
+}
+
+async fn collect_stats(handles: StatsHandles) -> ConnectionMediaStats {
+    let transports = transport_stats(&handles).await;
+    let producers = producer_stats(&handles).await;
+    let consumers = consumer_stats(&handles).await;
+    let quality = network_quality(producers.iter().chain(consumers.iter()), &transports);
+
+    ConnectionMediaStats {
+        transports,
+        producers,
+        consumers,
+        quality,
+    }
+}
+
+async fn collect_quality(handles: &StatsHandles) -> NetworkQuality {
+    let transports = transport_stats(handles).await;
+    let producers = producer_stats(handles).await;
+    let consumers = cached_consumer_scores(handles);
+    network_quality(producers.iter().chain(consumers.iter()), &transports)
+}
+
+async fn transport_stats(handles: &StatsHandles) -> Vec<TransportStats> {
+    let mut transports = Vec::with_capacity(handles.transports.len());
+    for transport in &handles.transports {
+        let transport_id = transport.id().to_string();
+        let direction = if handles.send_transport_id.as_deref() == Some(transport_id.as_str()) {
+            "send"
+        } else {
+            "recv"
+        };
+
+        match transport.get_stats().await {
+            Ok(stats) => {
+                transports.extend(stats.into_iter().map(|stat| TransportStats {
+                    transport_id: transport_id.clone(),
+                    direction: direction.to_string(),
+                    ice_state: format!("{:?}", stat.ice_state).to_lowercase(),
+                    recv_bitrate: stat.recv_bitrate,
+                    send_bitrate: stat.send_bitrate,
+                    available_outgoing_bitrate: stat.available_outgoing_bitrate,
+                    available_incoming_bitrate: stat.available_incoming_bitrate,
+                    packet_loss_received: stat.rtp_packet_loss_received,
+                    packet_loss_sent: stat.rtp_packet_loss_sent,
+                }));
+            }
+            Err(error) => {
+                tracing::debug!(transport_id = %transport_id, error = %error, "Failed to read transport stats");
+            }
+        }
+    }
+    transports
+}
+
+async fn producer_stats(handles: &StatsHandles) -> Vec<RtpStreamStats> {
+    let mut producers = Vec::with_capacity(handles.producers.len());
+    for (producer_id, entry) in &handles.producers {
+        match entry.producer.get_stats().await {
+            Ok(stats) => {
+                producers.extend(stats.into_iter().map(|stat| RtpStreamStats {
+                    id: producer_id.clone(),
+                    kind: media_kind_as_str(stat.kind).to_string(),
+                    source: Some(entry.source.as_str().to_string()),
+                    score: stat.score,
+                    bitrate: stat.bitrate,
+                    packet_loss: fraction_lost(stat.fraction_lost),
+                    rtt_ms: stat.round_trip_time.map(f64::from),
+                    jitter: Some(stat.jitter),
+                }));
+            }
+            Err(error) => {
+                tracing::debug!(producer_id = %producer_id, error = %error, "Failed to read producer stats");
+            }
+        }
+    }
+    producers
+}
+
+async fn consumer_stats(handles: &StatsHandles) -> Vec<RtpStreamStats> {
+    let mut consumers = Vec::with_capacity(handles.consumers.len());
+    for (consumer_id, consumer) in &handles.consumers {
+        match consumer.get_stats().await {
+            Ok(stats) => {
+                let stat = stats.consumer_stats();
+                consumers.push(RtpStreamStats {
+                    id: consumer_id.clone(),
+                    kind: media_kind_as_str(stat.kind).to_string(),
+                    source: None,
+                    score: stat.score,
+                    bitrate: stat.bitrate,
+                    packet_loss: fraction_lost(stat.fraction_lost),
+                    rtt_ms: stat.round_trip_time.map(f64::from),
+                    jitter: None,
+                });
+            }
+            Err(error) => {
+                tracing::debug!(consumer_id = %consumer_id, error = %error, "Failed to read consumer stats");
+            }
+        }
+    }
+    consumers
+}
+
+/// Consumer scores as last reported by the worker, without a round trip.
+fn cached_consumer_scores(handles: &StatsHandles) -> Vec<RtpStreamStats> {
+    handles
+        .consumers
+        .iter()
+        .map(|(consumer_id, consumer)| RtpStreamStats {
+            id: consumer_id.clone(),
+            kind: media_kind_as_str(consumer.kind()).to_string(),
+            source: None,
+            score: consumer.score().score,
+            bitrate: 0,
+            packet_loss: 0.0,
+            rtt_ms: None,
+            jitter: None,
+        })
+        .collect()
+}
+
+/// RTCP reports loss as a fixed-point fraction of 256.
+fn fraction_lost(value: u8) -> f64 {
+    f64::from(value) / 256.0
+}
+
+/// Folds stream scores, round-trip time and packet loss into one 0-10 score.
+///
+/// The result is the worst of the average mediasoup stream score and the
+/// scores implied by RTT and loss, so a single bad metric is enough to flag
+/// a connection.
+fn network_quality<'a>(
+    streams: impl Iterator<Item = &'a RtpStreamStats>,
+    transports: &[TransportStats],
+) -> NetworkQuality {
+    let mut score_sum = 0u32;
+    let mut score_count = 0u32;
+    let mut rtt_ms: Option<f64> = None;
+    let mut packet_loss: Option<f64> = None;
+
+    for stream in streams {
+        score_sum += u32::from(stream.score);
+        score_count += 1;
+        if let Some(rtt) = stream.rtt_ms {
+            rtt_ms = Some(rtt_ms.map_or(rtt, |current| current.max(rtt)));
+        }
+        packet_loss = Some(packet_loss.map_or(stream.packet_loss, |current: f64| {
+            current.max(stream.packet_loss)
+        }));
+    }
+
+    for transport in transports {
+        for loss in [transport.packet_loss_received, transport.packet_loss_sent]
+            .into_iter()
+            .flatten()
+        {
+            packet_loss = Some(packet_loss.map_or(loss, |current| current.max(loss)));
+        }
+    }
+
+    let stream_score = score_sum
+        .checked_div(score_count)
+        .map_or(10, |average| u8::try_from(average).unwrap_or(10));
+    let rtt_score = match rtt_ms {
+        Some(rtt) if rtt >= 500.0 => 2,
+        Some(rtt) if rtt >= 300.0 => 4,
+        Some(rtt) if rtt >= 150.0 => 7,
+        _ => 10,
+    };
+    let loss_score = match packet_loss {
+        Some(loss) if loss >= 0.10 => 2,
+        Some(loss) if loss >= 0.05 => 4,
+        Some(loss) if loss >= 0.02 => 7,
+        _ => 10,
+    };
+
+    let score = stream_score.min(rtt_score).min(loss_score);
+    let level = if score >= GOOD_QUALITY_MIN_SCORE {
+        "good"
+    } else if score >= FAIR_QUALITY_MIN_SCORE {
+        "fair"
+    } else {
+        "poor"
+    };
+
+    NetworkQuality {
+        score,
+        level,
+        rtt_ms,
+        packet_loss,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(score: u8, packet_loss: f64, rtt_ms: Option<f64>) -> RtpStreamStats {
+        RtpStreamStats {
+            id: "stream".to_string(),
+            kind: "audio".to_string(),
+            source: None,
+            score,
+            bitrate: 32_000,
+            packet_loss,
+            rtt_ms,
+            jitter: None,
+        }
+    }
+
+    #[test]
+    fn idle_connection_is_good() {
+        let quality = network_quality(std::iter::empty(), &[]);
+
+        assert_eq!(quality.score, 10);
+        assert_eq!(quality.level, "good");
+        assert_eq!(quality.rtt_ms, None);
+    }
+
+    #[test]
+    fn worst_metric_drives_the_score() {
+        let streams = [stream(10, 0.0, Some(40.0)), stream(9, 0.06, Some(80.0))];
+        let quality = network_quality(streams.iter(), &[]);
+
+        assert_eq!(quality.score, 4);
+        assert_eq!(quality.level, "fair");
+        assert_eq!(quality.rtt_ms, Some(80.0));
+        assert_eq!(quality.packet_loss, Some(0.06));
+
+        let streams = [stream(10, 0.0, Some(650.0))];
+        assert_eq!(network_quality(streams.iter(), &[]).level, "poor");
+    }
+}
@@ -1,20 +1,34 @@
 use mediasoup::prelude::{
-    Consumer, ConsumerId, ConsumerOptions, DtlsParameters, IceCandidate, IceParameters, MediaKind,
-    PlainTransport, PlainTransportOptions, Producer, ProducerId, ProducerOptions, RtpCapabilities,
-    RtpCapabilitiesFinalized, RtpParameters, Transport, WebRtcTransport,
-    WebRtcTransportListenInfos, WebRtcTransportOptions, WebRtcTransportRemoteParameters,
+    Consumer, ConsumerId, ConsumerOptions, DataConsumer, DirectTransport, DtlsParameters,
+    IceCandidate, IceParameters, MediaKind, PipeToRouterOptions, PlainTransport,
+    PlainTransportOptions, PlainTransportRemoteParameters, Producer, ProducerId, ProducerOptions,
+    RtpCapabilities, RtpCapabilitiesFinalized, RtpParameters, Transport, WebRtcTransport,
+    WebRtcTransportRemoteParameters,
 };
+use mediasoup::types::sctp_parameters::SctpParameters;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
 use uuid::Uuid;
 
+use super::data_channel::{
+    new_data_message_rate, DataMessageRateState, DataProducerEntry, MAX_DATA_MESSAGE_BYTES,
+};
+
 use super::native_codec::{
-    canonical_native_ssrc, native_rtp_parameters, NativeSenderSession, NativeVideoCodec,
-    NATIVE_H264_PACKETIZATION_MODE, NATIVE_H264_PROFILE_LEVEL_ID,
+    canonical_native_ssrc, generate_native_srtp_parameters, native_audio_rtp_parameters,
+    native_audio_ssrc, native_rtp_parameters, NativeAudioSession, NativeRtxSession,
+    NativeSenderSession, NativeSrtpSession, NativeVideoCodec, NATIVE_ABS_SEND_TIME_EXT_ID,
+    NATIVE_H264_PACKETIZATION_MODE, NATIVE_H264_PROFILE_LEVEL_ID, NATIVE_SRTP_CRYPTO_SUITE,
+    NATIVE_TRANSPORT_CC_EXT_ID,
 };
 use super::router::OpusConfig;
 use super::MediaService;
 
+/// `ClosedProducer::source` for data producers, which are announced and
+/// closed with their own actions.
+pub const DATA_PRODUCER_SOURCE: &str = "data";
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TransportDirection {
     Send,
@@ -27,6 +41,8 @@ pub enum ProducerSource {
     Microphone,
     Camera,
     Screen,
+    /// A soundboard clip played into the channel by the server.
+    Soundboard,
 }
 
 impl ProducerSource {
@@ -35,6 +51,7 @@ impl ProducerSource {
             Self::Microphone => "microphone",
             Self::Camera => "camera",
             Self::Screen => "screen",
+            Self::Soundboard => "soundboard",
         }
     }
 }
@@ -53,13 +70,21 @@ impl RoutingMode {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct ProducerEntry {
     pub producer: Producer,
     pub source: ProducerSource,
     pub routing_mode: RoutingMode,
 }
 
+impl ProducerEntry {
+    /// The video of a screen share. Its audio, if any, is a separate
+    /// `ProducerSource::Screen` producer of kind audio.
+    pub fn is_screen_video(&self) -> bool {
+        self.source == ProducerSource::Screen && self.producer.kind() == MediaKind::Video
+    }
+}
+
 impl TransportDirection {
     pub fn as_str(self) -> &'static str {
         match self {
@@ -75,29 +100,58 @@ pub struct CreatedWebRtcTransport {
     pub ice_parameters: IceParameters,
     pub ice_candidates: Vec<IceCandidate>,
     pub dtls_parameters: DtlsParameters,
+    pub sctp_parameters: Option<SctpParameters>,
+}
+
+/// Who a `ConnectionMediaState` belongs to: a voice member's WebSocket
+/// connection, or one of the server-side sessions stored alongside them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MediaSessionKind {
+    Connection,
+    Whip,
+    Soundboard,
+    Whep,
+    Restream,
 }
 
 #[derive(Debug)]
 pub(crate) struct ConnectionMediaState {
     pub channel_id: Uuid,
+    pub kind: MediaSessionKind,
     pub send_transport_id: Option<String>,
     pub recv_transport_id: Option<String>,
     pub transports: HashMap<String, WebRtcTransport>,
     pub native_transports_by_producer: HashMap<String, PlainTransport>,
+    /// Audio producer sent alongside a native screen producer, keyed by the
+    /// video producer's id; it closes together with the video.
+    pub native_audio_by_producer: HashMap<String, String>,
     pub producers: HashMap<String, ProducerEntry>,
     pub consumers: HashMap<String, Consumer>,
+    /// Server-side end of the channel's data relays, created with the first
+    /// data producer.
+    pub direct_transport: Option<DirectTransport>,
+    /// Keyed by the client's data producer id.
+    pub data_producers: HashMap<String, DataProducerEntry>,
+    pub data_consumers: HashMap<String, DataConsumer>,
+    pub data_message_rate: Arc<StdMutex<DataMessageRateState>>,
 }
 
 impl ConnectionMediaState {
     pub(super) fn new(channel_id: Uuid) -> Self {
         Self {
             channel_id,
+            kind: MediaSessionKind::Connection,
             send_transport_id: None,
             recv_transport_id: None,
             transports: HashMap::new(),
             native_transports_by_producer: HashMap::new(),
+            native_audio_by_producer: HashMap::new(),
             producers: HashMap::new(),
             consumers: HashMap::new(),
+            direct_transport: None,
+            data_producers: HashMap::new(),
+            data_consumers: HashMap::new(),
+            data_message_rate: new_data_message_rate(),
         }
     }
 }
@@ -127,7 +181,70 @@ pub struct ClosedProducer {
     pub routing_mode: String,
 }
 
-fn media_kind_as_str(kind: MediaKind) -> &'static str {
+#[derive(Debug, Clone, Default)]
+pub struct RenegotiatedChannelMedia {
+    pub connection_ids: Vec<Uuid>,
+    pub closed_producers: Vec<ClosedProducer>,
+}
+
+/// What moving a channel onto a new router does to each of its sessions.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct RouterMove {
+    /// Member connections, dropped so they renegotiate from scratch.
+    renegotiate: Vec<Uuid>,
+    /// Producers of WHIP and soundboard sessions, which keep running on the
+    /// previous router and are piped into the new one.
+    pipe: Vec<ProducerId>,
+}
+
+/// WHEP viewers and restreams are left alone: they consume a producer on
+/// the previous router and end with it, or keep watching a piped one.
+fn plan_router_move(
+    media_state: &HashMap<Uuid, ConnectionMediaState>,
+    channel_id: Uuid,
+) -> RouterMove {
+    let mut router_move = RouterMove::default();
+    for (connection_id, entry) in media_state {
+        if entry.channel_id != channel_id {
+            continue;
+        }
+        match entry.kind {
+            MediaSessionKind::Connection => router_move.renegotiate.push(*connection_id),
+            MediaSessionKind::Whip | MediaSessionKind::Soundboard => router_move
+                .pipe
+                .extend(entry.producers.values().map(|entry| entry.producer.id())),
+            MediaSessionKind::Whep | MediaSessionKind::Restream => {}
+        }
+    }
+    router_move.renegotiate.sort();
+    router_move
+}
+
+pub(super) fn closed_producers_for(state: &ConnectionMediaState) -> Vec<ClosedProducer> {
+    state
+        .producers
+        .iter()
+        .map(|(producer_id, producer)| ClosedProducer {
+            channel_id: state.channel_id,
+            producer_id: producer_id.clone(),
+            source: producer.source.as_str().to_string(),
+            routing_mode: producer.routing_mode.as_str().to_string(),
+        })
+        .chain(
+            state
+                .data_producers
+                .values()
+                .map(|data_producer| ClosedProducer {
+                    channel_id: state.channel_id,
+                    producer_id: data_producer.relay.id().to_string(),
+                    source: DATA_PRODUCER_SOURCE.to_string(),
+                    routing_mode: RoutingMode::Sfu.as_str().to_string(),
+                }),
+        )
+        .collect()
+}
+
+pub(super) fn media_kind_as_str(kind: MediaKind) -> &'static str {
     match kind {
         MediaKind::Audio => "audio",
         MediaKind::Video => "video",
@@ -140,7 +257,7 @@ impl MediaService {
         channel_id: Uuid,
         opus_config: OpusConfig,
     ) -> Result<RtpCapabilitiesFinalized, String> {
-        let router = self.get_or_create_router(channel_id, opus_config).await;
+        let router = self.get_or_create_router(channel_id, opus_config).await?;
         Ok(router.rtp_capabilities())
     }
 
@@ -161,9 +278,12 @@ impl MediaService {
             }
         }
 
-        let router = self.get_or_create_router(channel_id, opus_config).await;
-        let listen_infos = WebRtcTransportListenInfos::new(self.webrtc_listen_info());
-        let transport_options = WebRtcTransportOptions::new(listen_infos);
+        let (router, webrtc_server) = self
+            .get_or_create_router_with_server(channel_id, opus_config)
+            .await?;
+        let mut transport_options = self.webrtc_transport_options(webrtc_server);
+        transport_options.enable_sctp = true;
+        transport_options.max_sctp_message_size = MAX_DATA_MESSAGE_BYTES;
 
         let transport = router
             .create_webrtc_transport(transport_options)
@@ -176,6 +296,7 @@ impl MediaService {
             ice_parameters: transport.ice_parameters().clone(),
             ice_candidates: transport.ice_candidates().clone(),
             dtls_parameters: transport.dtls_parameters(),
+            sctp_parameters: transport.sctp_parameters(),
         };
 
         {
@@ -273,10 +394,7 @@ impl MediaService {
             }
 
             if source == ProducerSource::Screen
-                && entry
-                    .producers
-                    .values()
-                    .any(|producer| producer.source == ProducerSource::Screen)
+                && entry.producers.values().any(ProducerEntry::is_screen_video)
             {
                 return Err("Only one active screen producer is allowed per connection".into());
             }
@@ -312,10 +430,7 @@ impl MediaService {
             }
 
             if source == ProducerSource::Screen
-                && entry
-                    .producers
-                    .values()
-                    .any(|existing| existing.source == ProducerSource::Screen)
+                && entry.producers.values().any(ProducerEntry::is_screen_video)
             {
                 return Err("Only one active screen producer is allowed per connection".into());
             }
@@ -344,6 +459,8 @@ impl MediaService {
         connection_id: Uuid,
         channel_id: Uuid,
         preferred_codecs: Option<Vec<String>>,
+        include_audio: bool,
+        enable_srtp: bool,
         opus_config: OpusConfig,
     ) -> Result<NativeSenderSession, String> {
         {
@@ -357,27 +474,46 @@ impl MediaService {
                 return Err("Native sender does not belong to this voice channel".into());
             }
 
-            if entry
-                .producers
-                .values()
-                .any(|producer| producer.source == ProducerSource::Screen)
-            {
+            if entry.producers.values().any(ProducerEntry::is_screen_video) {
                 return Err("Only one active screen producer is allowed per connection".into());
             }
         }
 
-        let router = self.get_or_create_router(channel_id, opus_config).await;
+        let router = self.get_or_create_router(channel_id, opus_config).await?;
         let listen_info = self.native_rtp_listen_info();
 
         let mut plain_transport_options = PlainTransportOptions::new(listen_info);
         plain_transport_options.comedia = true;
         plain_transport_options.rtcp_mux = true;
+        plain_transport_options.enable_srtp = enable_srtp;
+        plain_transport_options.srtp_crypto_suite = NATIVE_SRTP_CRYPTO_SUITE;
 
         let plain_transport = router
             .create_plain_transport(plain_transport_options)
             .await
             .map_err(|error| format!("Failed to create native sender transport: {error}"))?;
 
+        // With comedia the remote address is still learnt from the first
+        // packet; connecting only hands the router the client's key.
+        let srtp = if enable_srtp {
+            let client_key = generate_native_srtp_parameters()?;
+            plain_transport
+                .connect(PlainTransportRemoteParameters {
+                    ip: None,
+                    port: None,
+                    rtcp_port: None,
+                    srtp_parameters: Some(client_key.clone()),
+                })
+                .await
+                .map_err(|error| format!("Failed to key native sender transport: {error}"))?;
+            let router_key = plain_transport
+                .srtp_parameters()
+                .ok_or_else(|| "Native sender transport has no SRTP parameters".to_string())?;
+            Some(NativeSrtpSession::new(&client_key, &router_key))
+        } else {
+            None
+        };
+
         let tuple = plain_transport.tuple();
         let rtp_target = self.native_rtp_target_for_port(tuple.local_port());
         let ssrc = canonical_native_ssrc(connection_id);
@@ -394,6 +530,24 @@ impl MediaService {
 
         let producer_id = producer.id().to_string();
 
+        let audio_producer = if include_audio {
+            let audio_producer = plain_transport
+                .produce(ProducerOptions::new(
+                    MediaKind::Audio,
+                    native_audio_rtp_parameters(ssrc),
+                ))
+                .await
+                .map_err(|error| {
+                    format!("Failed to create native sender audio producer: {error}")
+                })?;
+            Some(audio_producer)
+        } else {
+            None
+        };
+        let audio = audio_producer.as_ref().map(|audio_producer| {
+            NativeAudioSession::new(audio_producer.id().to_string(), native_audio_ssrc(ssrc))
+        });
+
         {
             let media_state_lock = self.connection_media();
             let mut media_state = media_state_lock.lock().await;
@@ -405,14 +559,25 @@ impl MediaService {
                 return Err("Media session moved to a different channel".into());
             }
 
-            if entry
-                .producers
-                .values()
-                .any(|existing| existing.source == ProducerSource::Screen)
-            {
+            if entry.producers.values().any(ProducerEntry::is_screen_video) {
                 return Err("Only one active screen producer is allowed per connection".into());
             }
 
+            if let Some(audio_producer) = audio_producer {
+                let audio_producer_id = audio_producer.id().to_string();
+                entry.producers.insert(
+                    audio_producer_id.clone(),
+                    ProducerEntry {
+                        producer: audio_producer,
+                        source: ProducerSource::Screen,
+                        routing_mode: RoutingMode::Sfu,
+                    },
+                );
+                entry
+                    .native_audio_by_producer
+                    .insert(producer_id.clone(), audio_producer_id);
+            }
+
             entry.producers.insert(
                 producer_id.clone(),
                 ProducerEntry {
@@ -448,6 +613,11 @@ impl MediaService {
                 .iter()
                 .map(|codec| codec.descriptor())
                 .collect(),
+            rtx: NativeRtxSession::new(codec, ssrc),
+            transport_cc_extension_id: NATIVE_TRANSPORT_CC_EXT_ID,
+            abs_send_time_extension_id: NATIVE_ABS_SEND_TIME_EXT_ID,
+            audio,
+            srtp,
             owner_connection_id: connection_id,
         })
     }
@@ -601,12 +771,14 @@ impl MediaService {
             .map_err(|error| format!("Failed to resume consumer: {error}"))
     }
 
+    /// Closes one of the connection's producers, together with the audio a
+    /// native screen producer carries. The requested producer comes first.
     pub async fn close_producer_for_connection(
         &self,
         connection_id: Uuid,
         channel_id: Uuid,
         producer_id: &str,
-    ) -> Result<ClosedProducer, String> {
+    ) -> Result<Vec<ClosedProducer>, String> {
         let media_state_lock = self.connection_media();
         let mut media_state = media_state_lock.lock().await;
 
@@ -622,6 +794,16 @@ impl MediaService {
             .producers
             .remove(producer_id)
             .ok_or_else(|| "Producer not found for this connection".to_string())?;
+        let mut closed_producers = vec![(producer_id.to_string(), closed)];
+
+        if let Some(audio_producer_id) = entry.native_audio_by_producer.remove(producer_id) {
+            if let Some(audio) = entry.producers.remove(&audio_producer_id) {
+                closed_producers.push((audio_producer_id, audio));
+            }
+        }
+        entry
+            .native_audio_by_producer
+            .retain(|_, audio_producer_id| audio_producer_id != producer_id);
 
         entry.native_transports_by_producer.remove(producer_id);
 
@@ -630,16 +812,20 @@ impl MediaService {
                 continue;
             }
             other_entry.consumers.retain(|_cid, consumer| {
-                consumer.producer_id().to_string() != producer_id
+                let consumed = consumer.producer_id().to_string();
+                !closed_producers.iter().any(|(id, _)| *id == consumed)
             });
         }
 
-        Ok(ClosedProducer {
-            channel_id,
-            producer_id: producer_id.to_string(),
-            source: closed.source.as_str().to_string(),
-            routing_mode: closed.routing_mode.as_str().to_string(),
-        })
+        Ok(closed_producers
+            .into_iter()
+            .map(|(producer_id, closed)| ClosedProducer {
+                channel_id,
+                producer_id,
+                source: closed.source.as_str().to_string(),
+                routing_mode: closed.routing_mode.as_str().to_string(),
+            })
+            .collect())
     }
 
     pub async fn cleanup_connection_media(&self, connection_id: Uuid) -> Vec<ClosedProducer> {
@@ -652,8 +838,13 @@ impl MediaService {
         };
 
         let closed_producer_ids: Vec<String> = removed.producers.keys().cloned().collect();
+        let closed_relay_ids: Vec<_> = removed
+            .data_producers
+            .values()
+            .map(|data_producer| data_producer.relay.id())
+            .collect();
 
-        if !closed_producer_ids.is_empty() {
+        if !closed_producer_ids.is_empty() || !closed_relay_ids.is_empty() {
             for entry in media_state.values_mut() {
                 if entry.channel_id != removed.channel_id {
                     continue;
@@ -661,18 +852,120 @@ impl MediaService {
                 entry.consumers.retain(|_cid, consumer| {
                     !closed_producer_ids.contains(&consumer.producer_id().to_string())
                 });
+                entry.data_consumers.retain(|_id, data_consumer| {
+                    !closed_relay_ids.contains(&data_consumer.data_producer_id())
+                });
             }
         }
 
-        removed
-            .producers
-            .iter()
-            .map(|(producer_id, producer)| ClosedProducer {
-                channel_id: removed.channel_id,
-                producer_id: producer_id.clone(),
-                source: producer.source.as_str().to_string(),
-                routing_mode: producer.routing_mode.as_str().to_string(),
-            })
-            .collect()
+        closed_producers_for(&removed)
+    }
+
+    /// Moves a voice channel onto a router built from `opus_config`.
+    ///
+    /// Member connections lose their transports, producers and consumers on
+    /// the previous router and are returned so they can renegotiate against
+    /// the new one; a screen share has to be started again. Server-side
+    /// sessions stay up (see `plan_router_move`). Nothing happens when the
+    /// channel has no router or its router already uses `opus_config`.
+    /// Voice membership is left untouched.
+    pub async fn renegotiate_channel_media(
+        &self,
+        channel_id: Uuid,
+        opus_config: OpusConfig,
+    ) -> Result<RenegotiatedChannelMedia, String> {
+        let Some((previous_router, router)) = self.replace_router(channel_id, &opus_config).await?
+        else {
+            return Ok(RenegotiatedChannelMedia::default());
+        };
+
+        let (connection_ids, closed_producers, piped) = {
+            let media_state_lock = self.connection_media();
+            let mut media_state = media_state_lock.lock().await;
+            let router_move = plan_router_move(&media_state, channel_id);
+
+            let mut closed_producers = Vec::new();
+            for connection_id in &router_move.renegotiate {
+                if let Some(removed) = media_state.remove(connection_id) {
+                    closed_producers.extend(closed_producers_for(&removed));
+                }
+            }
+            (router_move.renegotiate, closed_producers, router_move.pipe)
+        };
+
+        // Piped producers keep their ids and close with their source, so
+        // members consume them on the new router as before.
+        for producer_id in piped {
+            if let Err(error) = previous_router
+                .pipe_producer_to_router(producer_id, PipeToRouterOptions::new(router.clone()))
+                .await
+            {
+                tracing::warn!(
+                    channel_id = %channel_id,
+                    producer_id = %producer_id,
+                    error = %error,
+                    "Failed to pipe producer onto the channel's new router"
+                );
+            }
+        }
+
+        Ok(RenegotiatedChannelMedia {
+            connection_ids,
+            closed_producers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(channel_id: Uuid, kind: MediaSessionKind) -> ConnectionMediaState {
+        let mut state = ConnectionMediaState::new(channel_id);
+        state.kind = kind;
+        state
+    }
+
+    #[test]
+    fn router_move_drops_only_member_connections_of_the_channel() {
+        let channel_id = Uuid::new_v4();
+        let other_channel_id = Uuid::new_v4();
+        let mut members = vec![Uuid::new_v4(), Uuid::new_v4()];
+        members.sort();
+
+        let mut media_state = HashMap::new();
+        for member in &members {
+            media_state.insert(*member, session(channel_id, MediaSessionKind::Connection));
+        }
+        media_state.insert(
+            Uuid::new_v4(),
+            session(other_channel_id, MediaSessionKind::Connection),
+        );
+        for kind in [
+            MediaSessionKind::Whip,
+            MediaSessionKind::Soundboard,
+            MediaSessionKind::Whep,
+            MediaSessionKind::Restream,
+        ] {
+            media_state.insert(Uuid::new_v4(), session(channel_id, kind));
+        }
+
+        let router_move = plan_router_move(&media_state, channel_id);
+        assert_eq!(router_move.renegotiate, members);
+        assert!(router_move.pipe.is_empty());
+    }
+
+    #[test]
+    fn router_move_of_an_empty_channel_does_nothing() {
+        let mut media_state = HashMap::new();
+        media_state.insert(
+            Uuid::new_v4(),
+            session(Uuid::new_v4(), MediaSessionKind::Connection),
+        );
+
+        assert_eq!(
+            plan_router_move(&media_state, Uuid::new_v4()),
+            RouterMove::default()
+        );
     }
 }
@@ -0,0 +1,484 @@
+use mediasoup::prelude::{
+    ConsumerOptions, MediaKind, ProducerId, RtpCapabilities, RtpParameters, Transport,
+    WebRtcTransportRemoteParameters,
+};
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+use uuid::Uuid;
+
+use super::sdp::{
+    header_extension_uri, parse_offer, remote_dtls_parameters, write_codec, write_rejected_media,
+    write_session_header, LocalTransportSdp, SdpCodec, SdpMedia,
+};
+use super::transport::{media_kind_as_str, ConnectionMediaState, MediaSessionKind, ProducerSource};
+use super::whip::watch_session_transport;
+use super::MediaService;
+
+#[derive(Debug)]
+pub struct WhepPlayback {
+    pub answer_sdp: String,
+    pub channel_id: Uuid,
+}
+
+/// A screen share or camera that WHEP viewers can watch.
+#[derive(Debug, Clone)]
+pub struct WatchableStream {
+    pub channel_id: Uuid,
+    /// Connection (or WHIP session) that publishes the stream.
+    pub owner_connection_id: Uuid,
+    /// The requested producer first, then the screen audio track that goes
+    /// with a screen share video (or the reverse), if there is one.
+    pub(super) tracks: Vec<(MediaKind, ProducerId)>,
+}
+
+/// A section we answered with a consumer, plus what the answer needs to
+/// describe the outgoing stream.
+struct ConsumedMedia {
+    kind: MediaKind,
+    consumer_id: String,
+    rtp_parameters: RtpParameters,
+}
+
+const RTCP_FEEDBACK_TYPES: [&str; 5] = ["nack", "nack pli", "ccm fir", "goog-remb", "transport-cc"];
+
+fn canonical_mime_type(kind: MediaKind, codec_name: &str) -> Option<&'static str> {
+    match (kind, codec_name.to_ascii_lowercase().as_str()) {
+        (MediaKind::Audio, "opus") => Some("audio/opus"),
+        (MediaKind::Video, "vp8") => Some("video/VP8"),
+        (MediaKind::Video, "vp9") => Some("video/VP9"),
+        (MediaKind::Video, "h264") => Some("video/H264"),
+        (MediaKind::Video, "av1") => Some("video/AV1"),
+        (MediaKind::Video, "rtx") => Some("video/rtx"),
+        _ => None,
+    }
+}
+
+fn fmtp_json(codec: &SdpCodec) -> Value {
+    codec
+        .fmtp
+        .iter()
+        .map(|(key, value)| {
+            let value = match value.parse::<u32>() {
+                Ok(number) => json!(number),
+                Err(_) => json!(value),
+            };
+            (key.clone(), value)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Describes what a viewer's media section can receive as mediasoup RTP
+/// capabilities, so that the router can pick a codec the viewer decodes.
+fn section_rtp_capabilities(media: &SdpMedia, kind: MediaKind) -> Result<RtpCapabilities, String> {
+    let kind_name = media_kind_as_str(kind);
+    let codecs: Vec<Value> = media
+        .payload_types
+        .iter()
+        .filter_map(|payload_type| {
+            media
+                .codecs
+                .iter()
+                .find(|codec| codec.payload_type == *payload_type)
+        })
+        .filter(|codec| codec.clock_rate > 0)
+        .filter_map(|codec| {
+            let mime_type = canonical_mime_type(kind, &codec.name)?;
+            let rtcp_feedback: Vec<Value> = codec
+                .rtcp_fb
+                .iter()
+                .filter(|feedback| RTCP_FEEDBACK_TYPES.contains(&feedback.as_str()))
+                .map(|feedback| {
+                    let (feedback_type, parameter) =
+                        feedback.split_once(' ').unwrap_or((feedback, ""));
+                    json!({ "type": feedback_type, "parameter": parameter })
+                })
+                .collect();
+            let mut capability = json!({
+                "kind": kind_name,
+                "mimeType": mime_type,
+                "preferredPayloadType": codec.payload_type,
+                "clockRate": codec.clock_rate,
+                "parameters": fmtp_json(codec),
+                "rtcpFeedback": rtcp_feedback,
+            });
+            if kind == MediaKind::Audio {
+                capability["channels"] = json!(codec.channels.unwrap_or(2));
+            }
+            Some(capability)
+        })
+        .collect();
+
+    let header_extensions: Vec<Value> = media
+        .extmaps
+        .iter()
+        .filter(|(_, uri)| header_extension_uri(uri).is_some())
+        .map(|(id, uri)| {
+            json!({
+                "kind": kind_name,
+                "uri": uri,
+                "preferredId": id,
+                "preferredEncrypt": false,
+                "direction": "sendrecv",
+            })
+        })
+        .collect();
+
+    serde_json::from_value(json!({
+        "codecs": codecs,
+        "headerExtensions": header_extensions,
+    }))
+    .map_err(|error| format!("Unsupported codecs in SDP offer: {error}"))
+}
+
+/// Turns a consumer's codecs back into SDP codec entries.
+pub(super) fn answer_codecs(rtp_parameters: &Value) -> Vec<SdpCodec> {
+    rtp_parameters["codecs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|codec| SdpCodec {
+            payload_type: codec["payloadType"].as_u64().unwrap_or_default() as u8,
+            name: codec["mimeType"]
+                .as_str()
+                .and_then(|mime_type| mime_type.split_once('/'))
+                .map(|(_, name)| name.to_string())
+                .unwrap_or_default(),
+            clock_rate: codec["clockRate"].as_u64().unwrap_or_default() as u32,
+            channels: codec["channels"].as_u64().map(|channels| channels as u8),
+            fmtp: codec["parameters"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::String(value) => value.clone(),
+                        other => other.to_string(),
+                    };
+                    (key.clone(), value)
+                })
+                .collect(),
+            rtcp_fb: codec["rtcpFeedback"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|feedback| {
+                    let feedback_type = feedback["type"].as_str()?;
+                    Some(match feedback["parameter"].as_str() {
+                        Some(parameter) if !parameter.is_empty() => {
+                            format!("{feedback_type} {parameter}")
+                        }
+                        _ => feedback_type.to_string(),
+                    })
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn build_answer(
+    session_id: Uuid,
+    offer_media: &[SdpMedia],
+    consumed: &[Option<ConsumedMedia>],
+    local: &LocalTransportSdp,
+    local_setup: &str,
+) -> String {
+    let bundle: Vec<&str> = offer_media
+        .iter()
+        .zip(consumed)
+        .filter(|(_, consumed)| consumed.is_some())
+        .filter_map(|(media, _)| media.mid.as_deref())
+        .collect();
+
+    let mut sdp = String::new();
+    write_session_header(&mut sdp, session_id, &bundle);
+
+    for (media, consumed) in offer_media.iter().zip(consumed) {
+        let Some(consumed) = consumed else {
+            write_rejected_media(&mut sdp, media);
+            continue;
+        };
+
+        // mediasoup consumers keep the router's payload types and header
+        // extension ids, so the answer describes the stream with those
+        // rather than echoing the numbers from the offer.
+        let rtp_parameters = serde_json::to_value(&consumed.rtp_parameters).unwrap_or_default();
+        let codecs = answer_codecs(&rtp_parameters);
+        let payload_types: Vec<u8> = codecs.iter().map(|codec| codec.payload_type).collect();
+        local.write_media_start(
+            &mut sdp,
+            media_kind_as_str(consumed.kind),
+            &payload_types,
+            media.mid.as_deref().unwrap_or_default(),
+            local_setup,
+            "sendonly",
+        );
+
+        for extension in rtp_parameters["headerExtensions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            if let (Some(id), Some(uri)) = (extension["id"].as_u64(), extension["uri"].as_str()) {
+                let _ = write!(sdp, "a=extmap:{id} {uri}\r\n");
+            }
+        }
+        for codec in &codecs {
+            write_codec(&mut sdp, codec);
+        }
+
+        let encoding = &rtp_parameters["encodings"][0];
+        let cname = rtp_parameters["rtcp"]["cname"]
+            .as_str()
+            .unwrap_or("yankcord");
+        let _ = write!(sdp, "a=msid:{session_id} {}\r\n", consumed.consumer_id);
+        if let Some(ssrc) = encoding["ssrc"].as_u64() {
+            let rtx_ssrc = encoding["rtx"]["ssrc"].as_u64();
+            if let Some(rtx_ssrc) = rtx_ssrc {
+                let _ = write!(sdp, "a=ssrc-group:FID {ssrc} {rtx_ssrc}\r\n");
+            }
+            for ssrc in std::iter::once(ssrc).chain(rtx_ssrc) {
+                let _ = write!(
+                    sdp,
+                    "a=ssrc:{ssrc} cname:{cname}\r\na=ssrc:{ssrc} msid:{session_id} {}\r\n",
+                    consumed.consumer_id
+                );
+            }
+        }
+        local.write_candidates(&mut sdp);
+    }
+
+    sdp
+}
+
+impl MediaService {
+    /// Looks up a screen share or camera producer for WHEP playback.
+    ///
+    /// Microphones are never watchable on their own; screen audio is only
+    /// served together with its screen share.
+    pub async fn watchable_stream(&self, producer_id: &str) -> Option<WatchableStream> {
+        let media_state_lock = self.connection_media();
+        let media_state = media_state_lock.lock().await;
+
+        let (owner_connection_id, entry, requested) =
+            media_state.iter().find_map(|(connection_id, entry)| {
+                let producer = entry.producers.get(producer_id)?;
+                Some((*connection_id, entry, producer))
+            })?;
+        if requested.source == ProducerSource::Microphone {
+            return None;
+        }
+
+        let mut tracks = vec![(requested.producer.kind(), requested.producer.id())];
+        if requested.source == ProducerSource::Screen {
+            let companion = entry.producers.values().find(|producer| {
+                producer.source == ProducerSource::Screen
+                    && producer.producer.kind() != requested.producer.kind()
+            });
+            if let Some(companion) = companion {
+                tracks.push((companion.producer.kind(), companion.producer.id()));
+            }
+        }
+
+        Some(WatchableStream {
+            channel_id: entry.channel_id,
+            owner_connection_id,
+            tracks,
+        })
+    }
+
+    /// Creates a send-only WebRTC session for a WHEP viewer.
+    ///
+    /// Each audio/video section of the offer consumes the matching track of
+    /// `stream`. The session is tracked like a websocket connection's media
+    /// state under `session_id`, so it goes away with the channel's router,
+    /// and is reported by `next_ended_whep_session` once its transport drops
+    /// or the producer it watches closes.
+    pub async fn create_whep_session(
+        &self,
+        session_id: Uuid,
+        stream: &WatchableStream,
+        offer_sdp: &str,
+    ) -> Result<WhepPlayback, String> {
+        let offer = parse_offer(offer_sdp)?;
+        let (dtls_parameters, local_setup) = remote_dtls_parameters(&offer)?;
+
+        let Some((router, webrtc_server)) = self.cached_router_with_server(stream.channel_id).await
+        else {
+            return Err("Stream is no longer live".into());
+        };
+        let transport = router
+            .create_webrtc_transport(self.webrtc_transport_options(webrtc_server))
+            .await
+            .map_err(|error| format!("Failed to create WebRTC transport: {error}"))?;
+
+        let mut consumers = Vec::new();
+        let mut consumed = Vec::with_capacity(offer.media.len());
+        for media in &offer.media {
+            let kind = match media.kind.as_str() {
+                "audio" => MediaKind::Audio,
+                "video" => MediaKind::Video,
+                _ => {
+                    consumed.push(None);
+                    continue;
+                }
+            };
+            let track = stream
+                .tracks
+                .iter()
+                .find(|(track_kind, _)| *track_kind == kind)
+                .filter(|_| {
+                    !consumers
+                        .iter()
+                        .any(|(consumed_kind, _)| *consumed_kind == kind)
+                });
+            let (Some((_, producer_id)), Some(mid)) = (track, media.mid.clone()) else {
+                consumed.push(None);
+                continue;
+            };
+            if !offer.has_ice_credentials(media) {
+                return Err("SDP offer is missing ICE credentials".into());
+            }
+
+            let rtp_capabilities = section_rtp_capabilities(media, kind)?;
+            if !router.can_consume(producer_id, &rtp_capabilities) {
+                consumed.push(None);
+                continue;
+            }
+
+            let mut consumer_options = ConsumerOptions::new(*producer_id, rtp_capabilities);
+            consumer_options.mid = Some(mid);
+            let consumer = transport
+                .consume(consumer_options)
+                .await
+                .map_err(|error| format!("Failed to create consumer: {error}"))?;
+
+            let ended_tx = self.ended_whep_sessions_tx.clone();
+            consumer
+                .on_producer_close(move || {
+                    let _ = ended_tx.send(session_id);
+                })
+                .detach();
+
+            consumed.push(Some(ConsumedMedia {
+                kind,
+                consumer_id: consumer.id().to_string(),
+                rtp_parameters: consumer.rtp_parameters().clone(),
+            }));
+            consumers.push((kind, consumer));
+        }
+        if consumers.is_empty() {
+            return Err("SDP offer has no audio or video the stream can be played with".into());
+        }
+
+        transport
+            .connect(WebRtcTransportRemoteParameters { dtls_parameters })
+            .await
+            .map_err(|error| format!("Failed to connect WebRTC transport: {error}"))?;
+
+        let answer_sdp = build_answer(
+            session_id,
+            &offer.media,
+            &consumed,
+            &LocalTransportSdp::new(&transport),
+            local_setup,
+        );
+        watch_session_transport(&self.ended_whep_sessions_tx, session_id, &transport);
+
+        let transport_id = transport.id().to_string();
+        let mut state = ConnectionMediaState::new(stream.channel_id);
+        state.kind = MediaSessionKind::Whep;
+        state.recv_transport_id = Some(transport_id.clone());
+        state.transports.insert(transport_id, transport);
+        state.consumers = consumers
+            .into_iter()
+            .map(|(_, consumer)| (consumer.id().to_string(), consumer))
+            .collect();
+
+        {
+            let media_state_lock = self.connection_media();
+            let mut media_state = media_state_lock.lock().await;
+            media_state.insert(session_id, state);
+        }
+
+        Ok(WhepPlayback {
+            answer_sdp,
+            channel_id: stream.channel_id,
+        })
+    }
+
+    /// Tears down a WHEP viewer's transport and consumers.
+    pub async fn close_whep_session(&self, session_id: Uuid) {
+        self.cleanup_connection_media(session_id).await;
+    }
+
+    /// Waits for the next WHEP session whose transport went away or whose
+    /// watched producer closed without the viewer sending a DELETE.
+    pub async fn next_ended_whep_session(&self) -> Option<Uuid> {
+        let mut ended_whep_sessions_rx = self.ended_whep_sessions_rx.lock().await;
+        ended_whep_sessions_rx.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BROWSER_OFFER: &str = "v=0\r\n\
+o=- 1 2 IN IP4 127.0.0.1\r\n\
+s=-\r\n\
+t=0 0\r\n\
+a=group:BUNDLE 0\r\n\
+m=video 9 UDP/TLS/RTP/SAVPF 96 97 98\r\n\
+c=IN IP4 0.0.0.0\r\n\
+a=ice-ufrag:abcd\r\n\
+a=ice-pwd:0123456789abcdef01234567\r\n\
+a=fingerprint:sha-256 AA:BB:CC\r\n\
+a=setup:actpass\r\n\
+a=mid:0\r\n\
+a=recvonly\r\n\
+a=extmap:4 urn:ietf:params:rtp-hdrext:sdes:mid\r\n\
+a=extmap:9 urn:example:unsupported\r\n\
+a=rtpmap:96 VP8/90000\r\n\
+a=rtcp-fb:96 nack\r\n\
+a=rtcp-fb:96 nack pli\r\n\
+a=rtcp-fb:96 goog-lntf\r\n\
+a=rtpmap:97 rtx/90000\r\n\
+a=fmtp:97 apt=96\r\n\
+a=rtpmap:98 red/90000\r\n";
+
+    #[test]
+    fn builds_capabilities_from_supported_codecs() {
+        let offer = parse_offer(BROWSER_OFFER).unwrap();
+        let capabilities = section_rtp_capabilities(&offer.media[0], MediaKind::Video).unwrap();
+        let capabilities = serde_json::to_value(capabilities).unwrap();
+
+        let codecs = capabilities["codecs"].as_array().unwrap();
+        assert_eq!(codecs.len(), 2);
+        assert_eq!(codecs[0]["mimeType"], "video/VP8");
+        assert_eq!(codecs[0]["rtcpFeedback"].as_array().unwrap().len(), 2);
+        assert_eq!(codecs[1]["parameters"]["apt"], 96);
+        assert_eq!(
+            capabilities["headerExtensions"].as_array().unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn answer_codecs_round_trip_feedback_and_parameters() {
+        let codecs = answer_codecs(&json!({
+            "codecs": [{
+                "mimeType": "video/H264",
+                "payloadType": 125,
+                "clockRate": 90000,
+                "parameters": { "packetization-mode": 1, "profile-level-id": "42e01f" },
+                "rtcpFeedback": [{ "type": "nack", "parameter": "" }, { "type": "nack", "parameter": "pli" }],
+            }],
+        }));
+
+        assert_eq!(codecs[0].name, "H264");
+        assert_eq!(codecs[0].fmtp_value("packetization-mode"), Some("1"));
+        assert_eq!(codecs[0].fmtp_value("profile-level-id"), Some("42e01f"));
+        assert_eq!(codecs[0].rtcp_fb, vec!["nack", "nack pli"]);
+    }
+}
@@ -0,0 +1,537 @@
+use mediasoup::prelude::{
+    MediaKind, MimeTypeAudio, MimeTypeVideo, ProducerOptions, RtcpFeedback, RtcpParameters,
+    RtpCodecParameters, RtpCodecParametersParameters, RtpEncodingParameters,
+    RtpEncodingParametersRtx, RtpHeaderExtensionParameters, RtpParameters, Transport,
+    WebRtcTransport, WebRtcTransportRemoteParameters,
+};
+use mediasoup::types::data_structures::{DtlsState, IceState};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::num::NonZeroU8;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use super::router::OpusConfig;
+use super::sdp::{
+    header_extension_uri, parse_offer, remote_dtls_parameters, write_codec, write_rejected_media,
+    write_session_header, LocalTransportSdp, SdpCodec, SdpMedia, SdpOffer,
+};
+use super::transport::{
+    media_kind_as_str, ClosedProducer, ConnectionMediaState, MediaSessionKind, ProducerEntry,
+    ProducerSource, PublishedProducer, RoutingMode,
+};
+use super::MediaService;
+
+/// How long a WHIP or WHEP peer has to complete ICE and DTLS.
+const SESSION_CONNECT_DEADLINE: Duration = Duration::from_secs(30);
+
+const H264_CONSTRAINED_BASELINE_PREFIXES: [&str; 2] = ["42e0", "4200"];
+
+#[derive(Debug)]
+pub struct WhipPublication {
+    pub answer_sdp: String,
+    pub producers: Vec<PublishedProducer>,
+}
+
+/// A media section we accepted, with the codecs and header extensions that
+/// go into both the producer and the SDP answer.
+struct AcceptedMedia {
+    kind: MediaKind,
+    codec: SdpCodec,
+    rtx: Option<SdpCodec>,
+    extmaps: Vec<(u16, String)>,
+    rtp_parameters: RtpParameters,
+}
+
+/// Picks the codec mediasoup can route for this section, preferring
+/// constrained-baseline H264 so that native and browser viewers can decode
+/// it, then VP8, VP9 and AV1.
+fn select_codec(media: &SdpMedia, kind: MediaKind) -> Option<SdpCodec> {
+    let offered = || {
+        media
+            .payload_types
+            .iter()
+            .filter_map(|payload_type| {
+                media
+                    .codecs
+                    .iter()
+                    .find(|codec| codec.payload_type == *payload_type)
+            })
+            .filter(|codec| codec.clock_rate > 0)
+    };
+
+    match kind {
+        MediaKind::Audio => offered()
+            .find(|codec| codec.name.eq_ignore_ascii_case("opus") && codec.clock_rate == 48000)
+            .cloned(),
+        MediaKind::Video => {
+            let h264 = offered().find(|codec| {
+                codec.name.eq_ignore_ascii_case("H264")
+                    && codec.fmtp_value("packetization-mode") == Some("1")
+                    && codec.fmtp_value("profile-level-id").is_some_and(|profile| {
+                        H264_CONSTRAINED_BASELINE_PREFIXES
+                            .iter()
+                            .any(|prefix| profile.to_lowercase().starts_with(prefix))
+                    })
+            });
+
+            h264.or_else(|| {
+                ["VP8", "VP9", "AV1"]
+                    .iter()
+                    .find_map(|name| offered().find(|codec| codec.name.eq_ignore_ascii_case(name)))
+            })
+            .cloned()
+        }
+    }
+}
+
+fn rtx_for(media: &SdpMedia, payload_type: u8) -> Option<SdpCodec> {
+    let apt = payload_type.to_string();
+    media
+        .codecs
+        .iter()
+        .find(|codec| {
+            codec.name.eq_ignore_ascii_case("rtx") && codec.fmtp_value("apt") == Some(apt.as_str())
+        })
+        .cloned()
+}
+
+fn rtcp_feedback(codec: &SdpCodec, kind: MediaKind) -> Vec<RtcpFeedback> {
+    codec
+        .rtcp_fb
+        .iter()
+        .filter_map(|feedback| match (kind, feedback.as_str()) {
+            (MediaKind::Video, "nack") => Some(RtcpFeedback::Nack),
+            (MediaKind::Video, "nack pli") => Some(RtcpFeedback::NackPli),
+            (MediaKind::Video, "ccm fir") => Some(RtcpFeedback::CcmFir),
+            (MediaKind::Video, "goog-remb") => Some(RtcpFeedback::GoogRemb),
+            (_, "transport-cc") => Some(RtcpFeedback::TransportCc),
+            _ => None,
+        })
+        .collect()
+}
+
+fn codec_parameters(codec: &SdpCodec) -> RtpCodecParametersParameters {
+    let mut parameters = RtpCodecParametersParameters::default();
+    for (key, value) in &codec.fmtp {
+        match value.parse::<u32>() {
+            Ok(number) => parameters.insert(key.clone(), number),
+            Err(_) => parameters.insert(key.clone(), value.clone()),
+        };
+    }
+    parameters
+}
+
+fn rtp_codec_parameters(codec: &SdpCodec, kind: MediaKind) -> Result<RtpCodecParameters, String> {
+    let clock_rate = codec
+        .clock_rate
+        .try_into()
+        .map_err(|_| format!("Codec {} has an invalid clock rate", codec.name))?;
+
+    match kind {
+        MediaKind::Audio => Ok(RtpCodecParameters::Audio {
+            mime_type: MimeTypeAudio::Opus,
+            payload_type: codec.payload_type,
+            clock_rate,
+            channels: NonZeroU8::new(codec.channels.unwrap_or(2))
+                .ok_or_else(|| format!("Codec {} has an invalid channel count", codec.name))?,
+            parameters: codec_parameters(codec),
+            rtcp_feedback: rtcp_feedback(codec, kind),
+        }),
+        MediaKind::Video => {
+            let mime_type = match codec.name.to_ascii_uppercase().as_str() {
+                "H264" => MimeTypeVideo::H264,
+                "VP8" => MimeTypeVideo::Vp8,
+                "VP9" => MimeTypeVideo::Vp9,
+                "AV1" => MimeTypeVideo::AV1,
+                "RTX" => MimeTypeVideo::Rtx,
+                other => return Err(format!("Unsupported video codec {other}")),
+            };
+            Ok(RtpCodecParameters::Video {
+                mime_type,
+                payload_type: codec.payload_type,
+                clock_rate,
+                parameters: codec_parameters(codec),
+                rtcp_feedback: rtcp_feedback(codec, kind),
+            })
+        }
+    }
+}
+
+fn accept_media(media: &SdpMedia) -> Result<Option<AcceptedMedia>, String> {
+    let kind = match media.kind.as_str() {
+        "audio" => MediaKind::Audio,
+        "video" => MediaKind::Video,
+        _ => return Ok(None),
+    };
+
+    let Some(mid) = media.mid.clone() else {
+        return Err("Every SDP media section must have a mid".into());
+    };
+
+    let Some(codec) = select_codec(media, kind) else {
+        return Ok(None);
+    };
+    let rtx = match kind {
+        MediaKind::Video => rtx_for(media, codec.payload_type),
+        MediaKind::Audio => None,
+    };
+
+    let extmaps: Vec<(u16, String)> = media
+        .extmaps
+        .iter()
+        .filter(|(_, uri)| header_extension_uri(uri).is_some())
+        .cloned()
+        .collect();
+
+    let mut codecs = vec![rtp_codec_parameters(&codec, kind)?];
+    if let Some(rtx) = &rtx {
+        codecs.push(rtp_codec_parameters(rtx, kind)?);
+    }
+
+    let (ssrc, rtx_ssrc) = match media.fid_group {
+        Some((primary, rtx_ssrc)) => (Some(primary), Some(rtx_ssrc)),
+        None => (media.ssrcs.first().copied(), None),
+    };
+
+    let rtp_parameters = RtpParameters {
+        mid: Some(mid),
+        codecs,
+        header_extensions: extmaps
+            .iter()
+            .filter_map(|(id, uri)| {
+                Some(RtpHeaderExtensionParameters {
+                    uri: header_extension_uri(uri)?,
+                    id: *id,
+                    encrypt: false,
+                })
+            })
+            .collect(),
+        encodings: vec![RtpEncodingParameters {
+            ssrc,
+            rid: None,
+            codec_payload_type: Some(codec.payload_type),
+            rtx: rtx
+                .as_ref()
+                .and(rtx_ssrc)
+                .map(|ssrc| RtpEncodingParametersRtx { ssrc }),
+            dtx: None,
+            scalability_mode: Default::default(),
+            max_bitrate: None,
+        }],
+        rtcp: RtcpParameters {
+            cname: media.cname.clone(),
+            reduced_size: true,
+        },
+    };
+
+    Ok(Some(AcceptedMedia {
+        kind,
+        codec,
+        rtx,
+        extmaps,
+        rtp_parameters,
+    }))
+}
+
+fn build_answer(
+    session_id: Uuid,
+    offer: &SdpOffer,
+    accepted: &[Option<AcceptedMedia>],
+    transport: &WebRtcTransport,
+    local_setup: &str,
+) -> String {
+    let local = LocalTransportSdp::new(transport);
+    let bundle: Vec<&str> = offer
+        .media
+        .iter()
+        .zip(accepted)
+        .filter(|(_, accepted)| accepted.is_some())
+        .filter_map(|(media, _)| media.mid.as_deref())
+        .collect();
+
+    let mut sdp = String::new();
+    write_session_header(&mut sdp, session_id, &bundle);
+
+    for (media, accepted) in offer.media.iter().zip(accepted) {
+        let Some(accepted) = accepted else {
+            write_rejected_media(&mut sdp, media);
+            continue;
+        };
+
+        let mut payload_types = vec![accepted.codec.payload_type];
+        if let Some(rtx) = &accepted.rtx {
+            payload_types.push(rtx.payload_type);
+        }
+        local.write_media_start(
+            &mut sdp,
+            media_kind_as_str(accepted.kind),
+            &payload_types,
+            media.mid.as_deref().unwrap_or_default(),
+            local_setup,
+            "recvonly",
+        );
+
+        for (id, uri) in &accepted.extmaps {
+            let _ = write!(sdp, "a=extmap:{id} {uri}\r\n");
+        }
+        for codec in std::iter::once(&accepted.codec).chain(accepted.rtx.as_ref()) {
+            write_codec(&mut sdp, codec);
+        }
+        local.write_candidates(&mut sdp);
+    }
+
+    sdp
+}
+
+impl MediaService {
+    /// Creates a receive-only WebRTC session for a WHIP publisher.
+    ///
+    /// Each accepted audio/video section of the offer becomes a
+    /// `ProducerSource::Screen` producer on the channel router. The session is
+    /// tracked like a websocket connection's media state under `session_id`
+    /// and reported by `next_ended_whip_session` once its transport goes away.
+    pub async fn create_whip_session(
+        &self,
+        session_id: Uuid,
+        channel_id: Uuid,
+        opus_config: OpusConfig,
+        offer_sdp: &str,
+    ) -> Result<WhipPublication, String> {
+        let offer = parse_offer(offer_sdp)?;
+        let accepted = offer
+            .media
+            .iter()
+            .map(accept_media)
+            .collect::<Result<Vec<_>, _>>()?;
+        if accepted.iter().all(Option::is_none) {
+            return Err("SDP offer has no audio or video the server can receive".into());
+        }
+        if offer
+            .media
+            .iter()
+            .zip(&accepted)
+            .any(|(media, accepted)| accepted.is_some() && !offer.has_ice_credentials(media))
+        {
+            return Err("SDP offer is missing ICE credentials".into());
+        }
+        let (dtls_parameters, local_setup) = remote_dtls_parameters(&offer)?;
+
+        let (router, webrtc_server) = self
+            .get_or_create_router_with_server(channel_id, opus_config)
+            .await?;
+        let transport = router
+            .create_webrtc_transport(self.webrtc_transport_options(webrtc_server))
+            .await
+            .map_err(|error| format!("Failed to create WebRTC transport: {error}"))?;
+
+        let mut producers = HashMap::new();
+        let mut published = Vec::new();
+        for accepted_media in accepted.iter().flatten() {
+            let producer = transport
+                .produce(ProducerOptions::new(
+                    accepted_media.kind,
+                    accepted_media.rtp_parameters.clone(),
+                ))
+                .await
+                .map_err(|error| format!("Failed to create producer: {error}"))?;
+
+            let producer_id = producer.id().to_string();
+            published.push(PublishedProducer {
+                producer_id: producer_id.clone(),
+                kind: media_kind_as_str(accepted_media.kind).to_string(),
+                source: ProducerSource::Screen.as_str().to_string(),
+                routing_mode: RoutingMode::Sfu.as_str().to_string(),
+                owner_connection_id: session_id,
+            });
+            producers.insert(
+                producer_id,
+                ProducerEntry {
+                    producer,
+                    source: ProducerSource::Screen,
+                    routing_mode: RoutingMode::Sfu,
+                },
+            );
+        }
+
+        transport
+            .connect(WebRtcTransportRemoteParameters { dtls_parameters })
+            .await
+            .map_err(|error| format!("Failed to connect WebRTC transport: {error}"))?;
+
+        let answer_sdp = build_answer(session_id, &offer, &accepted, &transport, local_setup);
+        watch_session_transport(&self.ended_whip_sessions_tx, session_id, &transport);
+
+        let transport_id = transport.id().to_string();
+        let mut state = ConnectionMediaState::new(channel_id);
+        state.kind = MediaSessionKind::Whip;
+        state.send_transport_id = Some(transport_id.clone());
+        state.transports.insert(transport_id, transport);
+        state.producers = producers;
+
+        {
+            let media_state_lock = self.connection_media();
+            let mut media_state = media_state_lock.lock().await;
+            media_state.insert(session_id, state);
+        }
+
+        Ok(WhipPublication {
+            answer_sdp,
+            producers: published,
+        })
+    }
+
+    /// Tears down a WHIP session and returns the producers that were closed.
+    pub async fn close_whip_session(&self, session_id: Uuid) -> Vec<ClosedProducer> {
+        self.cleanup_connection_media(session_id).await
+    }
+
+    /// Waits for the next WHIP session whose transport disconnected or
+    /// closed without the publisher sending a DELETE.
+    pub async fn next_ended_whip_session(&self) -> Option<Uuid> {
+        let mut ended_whip_sessions_rx = self.ended_whip_sessions_rx.lock().await;
+        ended_whip_sessions_rx.recv().await
+    }
+}
+
+/// Reports `session_id` on `ended_tx` once the transport's ICE connection
+/// drops, DTLS fails or the transport closes, or when DTLS has not connected
+/// within `SESSION_CONNECT_DEADLINE`. Reports may repeat, so the receiving
+/// side has to tolerate sessions that already ended.
+pub(super) fn watch_session_transport(
+    ended_tx: &mpsc::UnboundedSender<Uuid>,
+    session_id: Uuid,
+    transport: &WebRtcTransport,
+) {
+    let ice_tx = ended_tx.clone();
+    transport
+        .on_ice_state_change(move |ice_state| {
+            if ice_state == IceState::Disconnected {
+                let _ = ice_tx.send(session_id);
+            }
+        })
+        .detach();
+
+    let connected = Arc::new(AtomicBool::new(false));
+    let dtls_connected = connected.clone();
+    let dtls_tx = ended_tx.clone();
+    transport
+        .on_dtls_state_change(move |dtls_state| match dtls_state {
+            DtlsState::Connected => dtls_connected.store(true, Ordering::Relaxed),
+            DtlsState::Failed | DtlsState::Closed => {
+                let _ = dtls_tx.send(session_id);
+            }
+            _ => {}
+        })
+        .detach();
+
+    let close_tx = ended_tx.clone();
+    transport
+        .on_close(Box::new(move || {
+            let _ = close_tx.send(session_id);
+        }))
+        .detach();
+
+    // A peer that never completes ICE and DTLS raises none of the events
+    // above, and would otherwise hold its session forever.
+    let deadline_tx = ended_tx.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(SESSION_CONNECT_DEADLINE).await;
+        if !connected.load(Ordering::Relaxed) {
+            tracing::info!(
+                session_id = %session_id,
+                "Ending session that did not connect in time"
+            );
+            let _ = deadline_tx.send(session_id);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OBS_OFFER: &str = "v=0\r\n\
+o=- 1 2 IN IP4 127.0.0.1\r\n\
+s=-\r\n\
+t=0 0\r\n\
+a=group:BUNDLE 0 1\r\n\
+m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+c=IN IP4 0.0.0.0\r\n\
+a=ice-ufrag:abcd\r\n\
+a=ice-pwd:0123456789abcdef01234567\r\n\
+a=fingerprint:sha-256 AA:BB:CC\r\n\
+a=setup:actpass\r\n\
+a=mid:0\r\n\
+a=sendonly\r\n\
+a=extmap:1 urn:ietf:params:rtp-hdrext:sdes:mid\r\n\
+a=rtpmap:111 opus/48000/2\r\n\
+a=fmtp:111 minptime=10;useinbandfec=1\r\n\
+a=ssrc:1111 cname:obs\r\n\
+m=video 9 UDP/TLS/RTP/SAVPF 96 97 102\r\n\
+c=IN IP4 0.0.0.0\r\n\
+a=mid:1\r\n\
+a=sendonly\r\n\
+a=extmap:1 urn:ietf:params:rtp-hdrext:sdes:mid\r\n\
+a=extmap:9 urn:example:unsupported\r\n\
+a=rtpmap:96 VP8/90000\r\n\
+a=rtpmap:102 H264/90000\r\n\
+a=fmtp:102 level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f\r\n\
+a=rtcp-fb:102 nack\r\n\
+a=rtcp-fb:102 nack pli\r\n\
+a=rtpmap:97 rtx/90000\r\n\
+a=fmtp:97 apt=102\r\n\
+a=ssrc-group:FID 2222 3333\r\n\
+a=ssrc:2222 cname:obs\r\n\
+a=ssrc:3333 cname:obs\r\n";
+
+    #[test]
+    fn parses_media_sections_and_session_attributes() {
+        let offer = parse_offer(OBS_OFFER).unwrap();
+
+        assert_eq!(offer.media.len(), 2);
+        assert_eq!(offer.media[0].kind, "audio");
+        assert_eq!(offer.media[0].ice_ufrag.as_deref(), Some("abcd"));
+        assert_eq!(
+            offer.media[0].fingerprint,
+            Some(("sha-256".to_string(), "AA:BB:CC".to_string()))
+        );
+        assert_eq!(offer.media[1].fid_group, Some((2222, 3333)));
+        assert_eq!(offer.media[1].cname.as_deref(), Some("obs"));
+    }
+
+    #[test]
+    fn prefers_h264_and_pairs_rtx() {
+        let offer = parse_offer(OBS_OFFER).unwrap();
+        let video = accept_media(&offer.media[1]).unwrap().unwrap();
+
+        assert_eq!(video.codec.payload_type, 102);
+        assert_eq!(video.rtx.as_ref().map(|rtx| rtx.payload_type), Some(97));
+        assert_eq!(video.extmaps.len(), 1);
+        assert_eq!(video.rtp_parameters.encodings[0].ssrc, Some(2222));
+        assert_eq!(
+            video.rtp_parameters.encodings[0]
+                .rtx
+                .as_ref()
+                .map(|rtx| rtx.ssrc),
+            Some(3333)
+        );
+    }
+
+    #[test]
+    fn rejects_sections_without_mid() {
+        let offer = parse_offer(&OBS_OFFER.replace("a=mid:0\r\n", "")).unwrap();
+
+        assert!(accept_media(&offer.media[0]).is_err());
+    }
+
+    #[test]
+    fn rejects_zero_channel_audio() {
+        let offer = parse_offer(&OBS_OFFER.replace("opus/48000/2", "opus/48000/0")).unwrap();
+
+        assert!(accept_media(&offer.media[0]).is_err());
+    }
+}
@@ -0,0 +1,456 @@
+use mediasoup::prelude::{
+    Protocol, WebRtcServer, WebRtcServerListenInfos, WebRtcServerOptions, Worker, WorkerManager,
+    WorkerSettings,
+};
+use mediasoup::worker::WorkerId;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use super::transport::{closed_producers_for, ClosedProducer, ConnectionMediaState};
+use super::{MediaService, WebRtcListenSettings};
+
+const RESPAWN_ATTEMPTS: u32 = 5;
+const RESPAWN_BACKOFF_INITIAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone)]
+pub(super) struct WorkerSlot {
+    pub worker: Worker,
+    pub webrtc_server: Option<WebRtcServer>,
+    pub restarts: u32,
+}
+
+/// What the worker itself reports holding. Workers run as threads inside
+/// the server process, so there is no per-worker CPU or memory figure.
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaWorkerDump {
+    pub router_count: usize,
+    pub webrtc_server_count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaWorkerStats {
+    pub index: usize,
+    pub worker_id: String,
+    pub closed: bool,
+    pub restarts: u32,
+    pub webrtc_server_port: Option<u16>,
+    pub router_count: usize,
+    pub transport_count: usize,
+    pub channel_ids: Vec<Uuid>,
+    pub dump: Option<MediaWorkerDump>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RecoveredWorker {
+    pub channel_ids: Vec<Uuid>,
+    pub connection_ids: Vec<Uuid>,
+    pub closed_producers: Vec<ClosedProducer>,
+}
+
+/// Spawns the mediasoup worker for slot `index` and reports its id on
+/// `dead_workers_tx` if the worker process exits unexpectedly.
+///
+/// With single-port mode enabled the worker also gets a `WebRtcServer` on
+/// `server_port + index`, listening on UDP and, optionally, TCP.
+pub(super) async fn spawn_worker(
+    worker_manager: &WorkerManager,
+    dead_workers_tx: mpsc::UnboundedSender<WorkerId>,
+    webrtc: &WebRtcListenSettings,
+    index: usize,
+) -> Result<WorkerSlot, String> {
+    let worker = worker_manager
+        .create_worker(WorkerSettings::default())
+        .await
+        .map_err(|error| format!("Failed to create mediasoup worker: {error}"))?;
+
+    let worker_id = worker.id();
+    worker
+        .on_dead(move |reason| {
+            tracing::error!(worker_id = %worker_id, reason = ?reason, "Mediasoup worker died");
+            let _ = dead_workers_tx.send(worker_id);
+        })
+        .detach();
+
+    let webrtc_server = match webrtc_server_port(webrtc, index)? {
+        Some(port) => {
+            let mut listen_infos =
+                WebRtcServerListenInfos::new(webrtc.listen_info(Protocol::Udp, Some(port)));
+            if webrtc.enable_tcp {
+                listen_infos = listen_infos.insert(webrtc.listen_info(Protocol::Tcp, Some(port)));
+            }
+
+            let webrtc_server = worker
+                .create_webrtc_server(WebRtcServerOptions::new(listen_infos))
+                .await
+                .map_err(|error| {
+                    format!("Failed to create WebRTC server on port {port}: {error}")
+                })?;
+
+            tracing::info!(
+                worker_id = %worker.id(),
+                port,
+                tcp = webrtc.enable_tcp,
+                "Started mediasoup WebRTC server"
+            );
+            Some(webrtc_server)
+        }
+        None => None,
+    };
+
+    Ok(WorkerSlot {
+        worker,
+        webrtc_server,
+        restarts: 0,
+    })
+}
+
+/// Load per worker: each router counts as one unit and each transport on
+/// it adds one more.
+fn worker_loads(
+    router_workers: impl IntoIterator<Item = (Uuid, WorkerId)>,
+    transports_by_channel: &HashMap<Uuid, usize>,
+) -> HashMap<WorkerId, usize> {
+    let mut loads = HashMap::new();
+    for (channel_id, worker_id) in router_workers {
+        *loads.entry(worker_id).or_insert(0) += 1 + transports_by_channel
+            .get(&channel_id)
+            .copied()
+            .unwrap_or_default();
+    }
+    loads
+}
+
+fn channels_on_worker(
+    router_workers: impl IntoIterator<Item = (Uuid, WorkerId)>,
+    worker_id: WorkerId,
+) -> Vec<Uuid> {
+    let mut channel_ids: Vec<Uuid> = router_workers
+        .into_iter()
+        .filter_map(|(channel_id, router_worker_id)| {
+            (router_worker_id == worker_id).then_some(channel_id)
+        })
+        .collect();
+    channel_ids.sort();
+    channel_ids
+}
+
+/// Removes every media session of `channel_ids` and returns the removed
+/// connection ids and the producers that closed with them.
+fn remove_channel_media(
+    media_state: &mut HashMap<Uuid, ConnectionMediaState>,
+    channel_ids: &[Uuid],
+) -> (Vec<Uuid>, Vec<ClosedProducer>) {
+    let mut connection_ids: Vec<Uuid> = media_state
+        .iter()
+        .filter_map(|(connection_id, entry)| {
+            channel_ids
+                .contains(&entry.channel_id)
+                .then_some(*connection_id)
+        })
+        .collect();
+    connection_ids.sort();
+
+    let mut closed_producers = Vec::new();
+    for connection_id in &connection_ids {
+        if let Some(removed) = media_state.remove(connection_id) {
+            closed_producers.extend(closed_producers_for(&removed));
+        }
+    }
+    (connection_ids, closed_producers)
+}
+
+/// Port of the `WebRtcServer` for worker `index`, if single-port mode is
+/// enabled. A port past 65535 is an error rather than a worker without one.
+fn webrtc_server_port(webrtc: &WebRtcListenSettings, index: usize) -> Result<Option<u16>, String> {
+    let Some(base_port) = webrtc.server_port else {
+        return Ok(None);
+    };
+    u16::try_from(usize::from(base_port) + index)
+        .map(Some)
+        .map_err(|_| format!("WebRTC server port {base_port} + worker index {index} exceeds 65535"))
+}
+
+impl MediaService {
+    /// Picks the live worker with the fewest routers and transports.
+    ///
+    /// Each router counts as one unit of load and each transport on a router
+    /// hosted by the worker adds one more, so busy channels weigh more than
+    /// idle ones.
+    pub(super) async fn least_loaded_worker(
+        &self,
+        routers: &HashMap<Uuid, super::ChannelRouter>,
+    ) -> Result<WorkerSlot, String> {
+        let transports_by_channel = self.transport_counts_by_channel().await;
+        let loads = worker_loads(
+            routers
+                .iter()
+                .map(|(channel_id, channel_router)| (*channel_id, channel_router.worker_id)),
+            &transports_by_channel,
+        );
+        let workers = self.workers.lock().await;
+
+        workers
+            .iter()
+            .filter(|slot| !slot.worker.closed())
+            .min_by_key(|slot| loads.get(&slot.worker.id()).copied().unwrap_or_default())
+            .cloned()
+            .ok_or_else(|| "No mediasoup worker is available".to_string())
+    }
+
+    async fn transport_counts_by_channel(&self) -> HashMap<Uuid, usize> {
+        let media_state_lock = self.connection_media();
+        let media_state = media_state_lock.lock().await;
+
+        let mut counts = HashMap::new();
+        for entry in media_state.values() {
+            *counts.entry(entry.channel_id).or_insert(0) +=
+                entry.transports.len() + entry.native_transports_by_producer.len();
+        }
+        counts
+    }
+
+    /// Waits for the next worker that died unexpectedly.
+    pub async fn next_dead_worker(&self) -> Option<WorkerId> {
+        let mut dead_workers_rx = self.dead_workers_rx.lock().await;
+        dead_workers_rx.recv().await
+    }
+
+    /// Drops every router and media session that was hosted on a dead
+    /// worker, then replaces the worker.
+    ///
+    /// The returned connections have lost their transports and need to
+    /// renegotiate; the next `get_or_create_router` call for each channel
+    /// places a fresh router on the least loaded live worker. Cleanup never
+    /// waits on the respawn, which is retried with backoff and only logged
+    /// when it keeps failing; the slot then stays closed and is skipped.
+    pub async fn recover_dead_worker(&self, worker_id: WorkerId) -> RecoveredWorker {
+        let Some(index) = self
+            .workers
+            .lock()
+            .await
+            .iter()
+            .position(|slot| slot.worker.id() == worker_id)
+        else {
+            return RecoveredWorker::default();
+        };
+
+        let channel_ids = {
+            let mut routers = self.routers.lock().await;
+            let channel_ids = channels_on_worker(
+                routers
+                    .iter()
+                    .map(|(channel_id, channel_router)| (*channel_id, channel_router.worker_id)),
+                worker_id,
+            );
+            for channel_id in &channel_ids {
+                routers.remove(channel_id);
+            }
+            channel_ids
+        };
+
+        let (connection_ids, closed_producers) = {
+            let media_state_lock = self.connection_media();
+            let mut media_state = media_state_lock.lock().await;
+            remove_channel_media(&mut media_state, &channel_ids)
+        };
+
+        self.respawn_worker(worker_id, index).await;
+
+        RecoveredWorker {
+            channel_ids,
+            connection_ids,
+            closed_producers,
+        }
+    }
+
+    async fn respawn_worker(&self, dead_worker_id: WorkerId, index: usize) {
+        let mut backoff = RESPAWN_BACKOFF_INITIAL;
+        for attempt in 1..=RESPAWN_ATTEMPTS {
+            match spawn_worker(
+                &self.worker_manager,
+                self.dead_workers_tx.clone(),
+                &self.webrtc,
+                index,
+            )
+            .await
+            {
+                Ok(mut replacement) => {
+                    let mut workers = self.workers.lock().await;
+                    let Some(slot) = workers.get_mut(index) else {
+                        return;
+                    };
+                    replacement.restarts = slot.restarts + 1;
+                    *slot = replacement;
+                    tracing::info!(
+                        dead_worker_id = %dead_worker_id,
+                        worker_id = %slot.worker.id(),
+                        restarts = slot.restarts,
+                        "Respawned mediasoup worker"
+                    );
+                    return;
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        dead_worker_id = %dead_worker_id,
+                        attempt,
+                        error = %error,
+                        "Failed to respawn mediasoup worker"
+                    );
+                }
+            }
+
+            if attempt < RESPAWN_ATTEMPTS {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+
+        tracing::error!(
+            dead_worker_id = %dead_worker_id,
+            index,
+            "Giving up on respawning mediasoup worker"
+        );
+    }
+
+    pub async fn worker_stats(&self) -> Vec<MediaWorkerStats> {
+        let transports_by_channel = self.transport_counts_by_channel().await;
+        let channels_by_worker: HashMap<WorkerId, Vec<Uuid>> = {
+            let routers = self.routers.lock().await;
+            let mut channels_by_worker: HashMap<WorkerId, Vec<Uuid>> = HashMap::new();
+            for (channel_id, channel_router) in routers.iter() {
+                channels_by_worker
+                    .entry(channel_router.worker_id)
+                    .or_default()
+                    .push(*channel_id);
+            }
+            channels_by_worker
+        };
+
+        let slots: Vec<WorkerSlot> = self.workers.lock().await.clone();
+
+        let mut stats = Vec::with_capacity(slots.len());
+        for (index, slot) in slots.into_iter().enumerate() {
+            let worker = slot.worker;
+            let channel_ids = channels_by_worker
+                .get(&worker.id())
+                .cloned()
+                .unwrap_or_default();
+            let transport_count = channel_ids
+                .iter()
+                .map(|channel_id| {
+                    transports_by_channel
+                        .get(channel_id)
+                        .copied()
+                        .unwrap_or_default()
+                })
+                .sum();
+
+            let dump = match worker.dump().await {
+                Ok(dump) => Some(MediaWorkerDump {
+                    router_count: dump.router_ids.len(),
+                    webrtc_server_count: dump.webrtc_server_ids.len(),
+                }),
+                Err(error) => {
+                    tracing::warn!(
+                        worker_id = %worker.id(),
+                        error = %error,
+                        "Failed to dump mediasoup worker"
+                    );
+                    None
+                }
+            };
+
+            stats.push(MediaWorkerStats {
+                index,
+                worker_id: worker.id().to_string(),
+                closed: worker.closed(),
+                restarts: slot.restarts,
+                webrtc_server_port: slot
+                    .webrtc_server
+                    .is_some()
+                    .then(|| webrtc_server_port(&self.webrtc, index).ok().flatten())
+                    .flatten(),
+                router_count: channel_ids.len(),
+                transport_count,
+                channel_ids,
+                dump,
+            });
+        }
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker_id() -> WorkerId {
+        Uuid::new_v4().to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn worker_load_counts_routers_and_their_transports() {
+        let (busy, idle, empty) = (worker_id(), worker_id(), worker_id());
+        let (large, small, quiet) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let transports_by_channel = HashMap::from([(large, 6), (small, 1)]);
+
+        let loads = worker_loads(
+            [(large, busy), (small, idle), (quiet, idle)],
+            &transports_by_channel,
+        );
+
+        assert_eq!(loads.get(&busy), Some(&7));
+        assert_eq!(loads.get(&idle), Some(&3));
+        assert_eq!(loads.get(&empty), None);
+    }
+
+    #[test]
+    fn webrtc_server_port_overflow_is_an_error() {
+        let mut webrtc = WebRtcListenSettings {
+            listen_ip: std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+            announced_ip: None,
+            enable_tcp: false,
+            server_port: None,
+            port_range: None,
+        };
+        assert_eq!(webrtc_server_port(&webrtc, 3), Ok(None));
+
+        webrtc.server_port = Some(u16::MAX - 1);
+        assert_eq!(webrtc_server_port(&webrtc, 0), Ok(Some(u16::MAX - 1)));
+        assert_eq!(webrtc_server_port(&webrtc, 1), Ok(Some(u16::MAX)));
+        assert!(webrtc_server_port(&webrtc, 2).is_err());
+    }
+
+    #[test]
+    fn recovery_drops_only_the_dead_workers_channels() {
+        let (dead, alive) = (worker_id(), worker_id());
+        let (lost, other_lost, kept) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+
+        let mut expected_channels = vec![lost, other_lost];
+        expected_channels.sort();
+        let channel_ids =
+            channels_on_worker([(lost, dead), (kept, alive), (other_lost, dead)], dead);
+        assert_eq!(channel_ids, expected_channels);
+
+        let mut media_state = HashMap::new();
+        let mut expected_connections = Vec::new();
+        for channel_id in [lost, other_lost] {
+            let connection_id = Uuid::new_v4();
+            media_state.insert(connection_id, ConnectionMediaState::new(channel_id));
+            expected_connections.push(connection_id);
+        }
+        expected_connections.sort();
+        let survivor = Uuid::new_v4();
+        media_state.insert(survivor, ConnectionMediaState::new(kept));
+
+        let (connection_ids, closed_producers) =
+            remove_channel_media(&mut media_state, &channel_ids);
+        assert_eq!(connection_ids, expected_connections);
+        assert!(closed_producers.is_empty());
+        assert_eq!(media_state.keys().collect::<Vec<_>>(), vec![&survivor]);
+    }
+}
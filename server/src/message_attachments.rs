@@ -6,6 +6,7 @@ use sqlx::{Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::errors::AppError;
+use crate::uploads::THUMBNAIL_KIND_PREFIX;
 use crate::AppState;
 
 const MAX_ATTACHMENTS_PER_MESSAGE: usize = 10;
@@ -17,21 +18,39 @@ pub struct ResolvedMessageAttachment {
     pub bytes: i64,
     pub width: Option<i32>,
     pub height: Option<i32>,
+    pub filename: Option<String>,
 }
 
 #[derive(Debug, Serialize, Clone)]
 pub struct MessageAttachmentPayload {
     pub media_id: Uuid,
     pub mime_type: String,
+    /// `image`, `video`, `audio` or `file`, from the MIME type.
+    pub kind: &'static str,
+    /// Original name of files uploaded through `/api/media/files`.
+    pub filename: Option<String>,
     pub bytes: i64,
     pub width: Option<i32>,
     pub height: Option<i32>,
+    /// Playback length of processed videos.
+    pub duration_ms: Option<i32>,
     pub status: String,
+    /// Base64 ThumbHash to draw while the image or video poster loads.
+    pub placeholder: Option<String>,
     pub thumbnail_url: Option<String>,
+    /// Width-bounded renditions of processed images, narrowest first.
+    pub thumbnails: Vec<AttachmentThumbnail>,
     pub display_url: Option<String>,
     pub original_url: String,
 }
 
+#[derive(Debug, Serialize, Clone)]
+pub struct AttachmentThumbnail {
+    pub url: String,
+    pub width: i32,
+    pub height: i32,
+}
+
 #[derive(sqlx::FromRow)]
 struct CandidateMediaAsset {
     id: Uuid,
@@ -39,6 +58,7 @@ struct CandidateMediaAsset {
     bytes: i64,
     width: Option<i32>,
     height: Option<i32>,
+    original_filename: Option<String>,
 }
 
 #[derive(sqlx::FromRow)]
@@ -49,7 +69,13 @@ struct StoredAttachmentRow {
     bytes: i64,
     width: Option<i32>,
     height: Option<i32>,
+    filename: Option<String>,
+    duration_ms: Option<i32>,
     status: String,
+    placeholder: Option<String>,
+    derivative_kinds: Vec<String>,
+    derivative_widths: Vec<i32>,
+    derivative_heights: Vec<i32>,
 }
 
 pub async fn resolve_uploads_for_message(
@@ -76,7 +102,7 @@ pub async fn resolve_uploads_for_message(
     }
 
     let records: Vec<CandidateMediaAsset> = sqlx::query_as(
-        "SELECT id, mime_type, bytes, width, height
+        "SELECT id, mime_type, bytes, width, height, original_filename
          FROM media_assets
          WHERE id = ANY($1)
            AND owner_id = $2
@@ -99,18 +125,13 @@ pub async fn resolve_uploads_for_message(
             AppError::BadRequest("One or more attachments are unavailable for this user".into())
         })?;
 
-        if !record.mime_type.starts_with("image/") {
-            return Err(AppError::BadRequest(
-                "Only image uploads can be attached to messages".into(),
-            ));
-        }
-
         resolved.push(ResolvedMessageAttachment {
             media_id,
             mime_type: record.mime_type.clone(),
             bytes: record.bytes,
             width: record.width,
             height: record.height,
+            filename: record.original_filename.clone(),
         });
     }
 
@@ -124,8 +145,8 @@ pub async fn persist_message_attachments_in_tx(
 ) -> Result<(), AppError> {
     for attachment in attachments {
         sqlx::query(
-            "INSERT INTO message_attachments (id, message_id, media_id, mime_type, bytes, width, height)
-             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            "INSERT INTO message_attachments (id, message_id, media_id, mime_type, bytes, width, height, filename)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
         )
         .bind(Uuid::new_v4())
         .bind(message_id)
@@ -134,6 +155,7 @@ pub async fn persist_message_attachments_in_tx(
         .bind(attachment.bytes)
         .bind(attachment.width)
         .bind(attachment.height)
+        .bind(&attachment.filename)
         .execute(&mut **tx)
         .await?;
     }
@@ -154,11 +176,26 @@ pub async fn load_message_attachments_by_message(
                 ma.media_id,
                 ma.mime_type,
                 ma.bytes,
-                ma.width,
-                ma.height,
-                media.status
+                COALESCE(ma.width, media.width) AS width,
+                COALESCE(ma.height, media.height) AS height,
+                ma.filename,
+                media.duration_ms,
+                media.status,
+                media.placeholder,
+                COALESCE(derivatives.kinds, '{}') AS derivative_kinds,
+                COALESCE(derivatives.widths, '{}') AS derivative_widths,
+                COALESCE(derivatives.heights, '{}') AS derivative_heights
          FROM message_attachments ma
          JOIN media_assets media ON media.id = ma.media_id
+         LEFT JOIN LATERAL (
+             SELECT ARRAY_AGG(d.derivative_kind ORDER BY d.width, d.derivative_kind) AS kinds,
+                    ARRAY_AGG(COALESCE(d.width, 0) ORDER BY d.width, d.derivative_kind) AS widths,
+                    ARRAY_AGG(COALESCE(d.height, 0) ORDER BY d.width, d.derivative_kind) AS heights
+             FROM media_assets d
+             WHERE d.parent_id = ma.media_id
+               AND d.derivative_kind IS NOT NULL
+               AND d.status = 'ready'
+         ) derivatives ON TRUE
          WHERE ma.message_id = ANY($1)
          ORDER BY ma.created_at ASC",
     )
@@ -168,8 +205,47 @@ pub async fn load_message_attachments_by_message(
 
     let mut by_message = HashMap::<Uuid, Vec<MessageAttachmentPayload>>::new();
     for row in rows {
+        let kind = attachment_kind(&row.mime_type);
+        let derivative_url = |derivative_kind: &str| {
+            row.derivative_kinds
+                .iter()
+                .any(|existing| existing == derivative_kind)
+                .then(|| format!("/api/media/{}/{derivative_kind}", row.media_id))
+        };
+        // Derivatives come back narrowest first, so the first sized
+        // thumbnail is the smallest.
+        let thumbnails: Vec<AttachmentThumbnail> = if row.status == "ready" {
+            row.derivative_kinds
+                .iter()
+                .zip(&row.derivative_widths)
+                .zip(&row.derivative_heights)
+                .filter(|((derivative_kind, _), _)| {
+                    derivative_kind.starts_with(THUMBNAIL_KIND_PREFIX)
+                })
+                .map(|((derivative_kind, width), height)| AttachmentThumbnail {
+                    url: format!("/api/media/{}/{derivative_kind}", row.media_id),
+                    width: *width,
+                    height: *height,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        // Images shared as plain files have no thumbnail and show as-is;
+        // videos get a poster and, when transcoded, a web-safe variant.
+        // Images processed before sized thumbnails keep a single
+        // `thumbnail`.
         let (thumbnail_url, display_url) = if row.status == "ready" {
-            (Some(format!("/api/media/{}/thumbnail", row.media_id)), None)
+            let thumbnail_url = thumbnails
+                .first()
+                .map(|thumbnail| thumbnail.url.clone())
+                .or_else(|| derivative_url("thumbnail"))
+                .or_else(|| derivative_url("poster"));
+            let display_url = derivative_url("web").or_else(|| {
+                (kind == "image" && thumbnail_url.is_none())
+                    .then(|| format!("/api/media/{}/original", row.media_id))
+            });
+            (thumbnail_url, display_url)
         } else {
             (None, None)
         };
@@ -179,12 +255,17 @@ pub async fn load_message_attachments_by_message(
             .or_default()
             .push(MessageAttachmentPayload {
                 media_id: row.media_id,
+                kind,
+                filename: row.filename,
                 mime_type: row.mime_type,
                 bytes: row.bytes,
                 width: row.width,
                 height: row.height,
+                duration_ms: row.duration_ms,
                 status: row.status,
+                placeholder: row.placeholder,
                 thumbnail_url,
+                thumbnails,
                 display_url,
                 original_url: format!("/api/media/{}/original", row.media_id),
             });
@@ -192,3 +273,14 @@ pub async fn load_message_attachments_by_message(
 
     Ok(by_message)
 }
+
+/// Groups a MIME type into what clients render: inline images, players for
+/// video and audio, and a download card for everything else.
+fn attachment_kind(mime_type: &str) -> &'static str {
+    match mime_type.split_once('/') {
+        Some(("image", _)) => "image",
+        Some(("video", _)) => "video",
+        Some(("audio", _)) => "audio",
+        _ => "file",
+    }
+}
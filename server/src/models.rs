@@ -143,6 +143,18 @@ pub struct Emoji {
     pub created_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct SoundboardClip {
+    pub id: Uuid,
+    pub name: String,
+    pub media_id: Uuid,
+    pub duration_ms: i32,
+    pub uploaded_by: Uuid,
+    pub approved_by: Option<Uuid>,
+    pub approved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, sqlx::FromRow)]
 pub struct Reaction {
     pub id: Uuid,
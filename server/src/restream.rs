@@ -0,0 +1,530 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc;
+use tokio::time::{interval, timeout, Duration};
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::media::restream::RestreamInput;
+use crate::media::whep::WatchableStream;
+use crate::uploads::MAX_STREAM_RECORDING_BYTES;
+use crate::{AppState, RestreamSession};
+
+pub const HLS_PLAYLIST_NAME: &str = "index.m3u8";
+const HLS_SEGMENT_SECONDS: u64 = 4;
+const HLS_LIVE_PLAYLIST_SEGMENTS: u32 = 6;
+const INPUT_SDP_NAME: &str = "input.sdp";
+const RECORDING_NAME: &str = "recording.mp4";
+const STORAGE_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+const FFMPEG_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Recordings are cut off after this long.
+const MAX_RECORDING_SECONDS: u64 = 6 * 60 * 60;
+/// ffmpeg finishes the packet that crosses `-fs` and then writes the index,
+/// so the remux stops this far below the stored size limit.
+const RECORDING_SIZE_HEADROOM_BYTES: u64 = 64 * 1024 * 1024;
+
+pub fn hls_storage_key(session_id: Uuid, file_name: &str) -> String {
+    format!("hls/{session_id}/{file_name}")
+}
+
+/// Content type of a file ffmpeg writes for a restream, or `None` for names
+/// that are not part of the HLS output and must not be served.
+pub fn hls_content_type(file_name: &str) -> Option<&'static str> {
+    if file_name == HLS_PLAYLIST_NAME {
+        return Some("application/vnd.apple.mpegurl");
+    }
+
+    let index = file_name.strip_prefix("segment_")?.strip_suffix(".ts")?;
+    (!index.is_empty() && index.bytes().all(|byte| byte.is_ascii_digit())).then_some("video/mp2t")
+}
+
+fn work_dir_for(session_id: Uuid) -> PathBuf {
+    std::env::temp_dir()
+        .join("yankcord-restreams")
+        .join(session_id.to_string())
+}
+
+/// ffmpeg arguments that read the restream SDP and write HLS into the
+/// working directory.
+///
+/// H.264 is copied as-is; other video codecs can't go into MPEG-TS segments
+/// and are transcoded with key frames forced on segment boundaries. Opus is
+/// always transcoded to AAC for the same reason. Recordings keep every
+/// segment in an event playlist so the whole stream can be remuxed at the
+/// end.
+fn ffmpeg_hls_args(input: &RestreamInput, record: bool) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-nostats",
+        "-protocol_whitelist",
+        "file,udp,rtp",
+        "-fflags",
+        "+genpts",
+        "-i",
+        INPUT_SDP_NAME,
+        "-map",
+        "0",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect();
+
+    match input.video_codec.as_deref() {
+        Some(codec) if codec.eq_ignore_ascii_case("H264") => {
+            args.extend(["-c:v", "copy"].map(String::from));
+        }
+        Some(_) => {
+            args.extend(
+                [
+                    "-c:v",
+                    "libx264",
+                    "-preset",
+                    "veryfast",
+                    "-tune",
+                    "zerolatency",
+                    "-pix_fmt",
+                    "yuv420p",
+                    "-force_key_frames",
+                ]
+                .map(String::from),
+            );
+            args.push(format!("expr:gte(t,n_forced*{HLS_SEGMENT_SECONDS})"));
+        }
+        None => {}
+    }
+    if input.has_audio {
+        args.extend(["-c:a", "aac", "-b:a", "128k"].map(String::from));
+    }
+
+    args.extend(["-f", "hls", "-hls_time"].map(String::from));
+    args.push(HLS_SEGMENT_SECONDS.to_string());
+    args.extend(["-hls_segment_filename", "segment_%05d.ts"].map(String::from));
+    if record {
+        args.extend(
+            [
+                "-hls_list_size",
+                "0",
+                "-hls_playlist_type",
+                "event",
+                "-hls_flags",
+                "independent_segments+temp_file",
+            ]
+            .map(String::from),
+        );
+    } else {
+        args.push("-hls_list_size".into());
+        args.push(HLS_LIVE_PLAYLIST_SEGMENTS.to_string());
+        args.extend(
+            [
+                "-hls_flags",
+                "delete_segments+independent_segments+temp_file",
+            ]
+            .map(String::from),
+        );
+    }
+    args.push(HLS_PLAYLIST_NAME.into());
+    args
+}
+
+/// Starts restreaming `stream` into HLS for a session already registered in
+/// `AppState::restreams`. The session runs until a reason arrives on
+/// `stop_rx`, then uploads the recording if one was asked for and removes
+/// itself from `AppState::restreams`.
+pub async fn start_restream(
+    state: &AppState,
+    session_id: Uuid,
+    stream: &WatchableStream,
+    record: bool,
+    stop_rx: mpsc::UnboundedReceiver<&'static str>,
+) -> Result<(), String> {
+    let input = state
+        .media
+        .create_restream_session(session_id, stream)
+        .await?;
+
+    let work_dir = work_dir_for(session_id);
+    let ffmpeg = match spawn_ffmpeg(state, session_id, &work_dir, &input, record).await {
+        Ok(ffmpeg) => ffmpeg,
+        Err(error) => {
+            state.media.close_restream_session(session_id).await;
+            let _ = tokio::fs::remove_dir_all(&work_dir).await;
+            return Err(error);
+        }
+    };
+
+    if let Err(error) = state.media.resume_restream(session_id).await {
+        tracing::warn!(
+            session_id = %session_id,
+            error = %error,
+            "Failed to resume restream consumers"
+        );
+    }
+
+    let copies_video = input
+        .video_codec
+        .as_deref()
+        .is_some_and(|codec| codec.eq_ignore_ascii_case("H264"));
+    tokio::spawn(run_restream(
+        state.clone(),
+        session_id,
+        ffmpeg,
+        work_dir,
+        copies_video,
+        stop_rx,
+    ));
+    Ok(())
+}
+
+/// Asks a running restream to stop. Returns `false` when it is not running.
+pub async fn stop_restream(state: &AppState, session_id: Uuid, reason: &'static str) -> bool {
+    let restreams = state.restreams.read().await;
+    let Some(session) = restreams.get(&session_id) else {
+        return false;
+    };
+    let _ = session.stop_tx.send(reason);
+    true
+}
+
+async fn spawn_ffmpeg(
+    state: &AppState,
+    session_id: Uuid,
+    work_dir: &Path,
+    input: &RestreamInput,
+    record: bool,
+) -> Result<Child, String> {
+    tokio::fs::create_dir_all(work_dir)
+        .await
+        .map_err(|error| format!("Failed to create restream directory: {error}"))?;
+    tokio::fs::write(work_dir.join(INPUT_SDP_NAME), &input.sdp)
+        .await
+        .map_err(|error| format!("Failed to write restream SDP: {error}"))?;
+
+    let mut ffmpeg = Command::new(&state.config.media.ffmpeg_bin)
+        .args(ffmpeg_hls_args(input, record))
+        .current_dir(work_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|error| format!("Failed to start ffmpeg: {error}"))?;
+
+    if let Some(stderr) = ffmpeg.stderr.take() {
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::warn!(session_id = %session_id, output = %line, "ffmpeg restream error");
+            }
+        });
+    }
+
+    Ok(ffmpeg)
+}
+
+async fn run_restream(
+    state: AppState,
+    session_id: Uuid,
+    mut ffmpeg: Child,
+    work_dir: PathBuf,
+    copies_video: bool,
+    mut stop_rx: mpsc::UnboundedReceiver<&'static str>,
+) {
+    let mut uploaded = HashSet::new();
+    let mut playlist = None;
+    let mut sync_ticker = interval(STORAGE_SYNC_INTERVAL);
+    let mut key_frame_ticker = interval(Duration::from_secs(HLS_SEGMENT_SECONDS));
+
+    let reason = loop {
+        tokio::select! {
+            reason = stop_rx.recv() => break reason.unwrap_or("stopped"),
+            status = ffmpeg.wait() => {
+                tracing::warn!(session_id = %session_id, status = ?status, "ffmpeg restream exited");
+                break "ffmpeg_exited";
+            }
+            _ = sync_ticker.tick() => {
+                sync_hls_output(&state, session_id, &work_dir, &mut uploaded, &mut playlist).await;
+            }
+            // Copied video can only be cut at key frames, and transcoded
+            // video needs one to start decoding.
+            _ = key_frame_ticker.tick(), if copies_video || playlist.is_none() => {
+                state.media.request_restream_key_frame(session_id).await;
+            }
+        }
+    };
+
+    stop_ffmpeg(session_id, &mut ffmpeg).await;
+    state.media.close_restream_session(session_id).await;
+    sync_hls_output(&state, session_id, &work_dir, &mut uploaded, &mut playlist).await;
+
+    let session = state.restreams.write().await.remove(&session_id);
+    let recording = match session {
+        Some(session) if session.record && playlist.is_some() => {
+            match save_recording(&state, &session, &work_dir).await {
+                Ok(media_id) => Some(media_id),
+                Err(error) => {
+                    tracing::error!(
+                        session_id = %session_id,
+                        error = ?error,
+                        "Failed to save restream recording"
+                    );
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    if playlist.is_some() {
+        uploaded.insert(HLS_PLAYLIST_NAME.to_string());
+    }
+    for file_name in &uploaded {
+        if let Err(error) = state
+            .storage
+            .delete(&hls_storage_key(session_id, file_name))
+            .await
+        {
+            tracing::warn!(
+                session_id = %session_id,
+                file_name = %file_name,
+                error = ?error,
+                "Failed to delete restream object"
+            );
+        }
+    }
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+
+    tracing::info!(
+        session_id = %session_id,
+        reason = %reason,
+        recording_media_id = ?recording,
+        "Ended HLS restream"
+    );
+}
+
+/// Lets ffmpeg finish the playlist by quitting through stdin, and kills it
+/// if it doesn't exit in time.
+async fn stop_ffmpeg(session_id: Uuid, ffmpeg: &mut Child) {
+    if let Some(mut stdin) = ffmpeg.stdin.take() {
+        let _ = stdin.write_all(b"q").await;
+    }
+
+    if timeout(FFMPEG_STOP_TIMEOUT, ffmpeg.wait()).await.is_err() {
+        tracing::warn!(session_id = %session_id, "ffmpeg restream did not stop, killing it");
+        let _ = ffmpeg.kill().await;
+    }
+}
+
+/// Mirrors ffmpeg's finished segments and playlist into storage.
+///
+/// Segments go up before the playlist that references them, and segments
+/// ffmpeg has dropped from a live playlist are deleted again.
+async fn sync_hls_output(
+    state: &AppState,
+    session_id: Uuid,
+    work_dir: &Path,
+    uploaded: &mut HashSet<String>,
+    playlist: &mut Option<Vec<u8>>,
+) {
+    let mut on_disk = HashSet::new();
+    if let Ok(mut entries) = tokio::fs::read_dir(work_dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(file_name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if file_name != HLS_PLAYLIST_NAME && hls_content_type(&file_name).is_some() {
+                on_disk.insert(file_name);
+            }
+        }
+    }
+
+    let mut new_segments: Vec<String> = on_disk.difference(uploaded).cloned().collect();
+    new_segments.sort();
+    for file_name in new_segments {
+        match upload_hls_file(state, session_id, work_dir, &file_name).await {
+            Ok(()) => {
+                uploaded.insert(file_name);
+            }
+            Err(error) => {
+                tracing::warn!(
+                    session_id = %session_id,
+                    file_name = %file_name,
+                    error = ?error,
+                    "Failed to upload restream segment"
+                );
+                return;
+            }
+        }
+    }
+
+    let dropped: Vec<String> = uploaded.difference(&on_disk).cloned().collect();
+    for file_name in dropped {
+        let _ = state
+            .storage
+            .delete(&hls_storage_key(session_id, &file_name))
+            .await;
+        uploaded.remove(&file_name);
+    }
+
+    let Ok(bytes) = tokio::fs::read(work_dir.join(HLS_PLAYLIST_NAME)).await else {
+        return;
+    };
+    if playlist.as_ref() == Some(&bytes) {
+        return;
+    }
+    let first_playlist = playlist.is_none();
+    if let Err(error) = state
+        .storage
+        .put(
+            &hls_storage_key(session_id, HLS_PLAYLIST_NAME),
+            bytes.clone(),
+            "application/vnd.apple.mpegurl",
+        )
+        .await
+    {
+        tracing::warn!(
+            session_id = %session_id,
+            error = ?error,
+            "Failed to upload restream playlist"
+        );
+        return;
+    }
+    *playlist = Some(bytes);
+
+    if first_playlist {
+        if let Some(session) = state.restreams.write().await.get_mut(&session_id) {
+            session.live = true;
+        }
+        tracing::info!(session_id = %session_id, "HLS restream is live");
+    }
+}
+
+async fn upload_hls_file(
+    state: &AppState,
+    session_id: Uuid,
+    work_dir: &Path,
+    file_name: &str,
+) -> Result<(), AppError> {
+    let bytes = tokio::fs::read(work_dir.join(file_name))
+        .await
+        .map_err(|error| AppError::Internal(format!("Failed to read HLS output: {error}")))?;
+    state
+        .storage
+        .put(
+            &hls_storage_key(session_id, file_name),
+            bytes,
+            hls_content_type(file_name).unwrap_or("application/octet-stream"),
+        )
+        .await
+}
+
+/// Remuxes the event playlist into a single MP4 and stores it as a media
+/// asset owned by whoever started the restream. The MP4 is truncated to
+/// `MAX_RECORDING_SECONDS` and to just under the stored size limit.
+async fn save_recording(
+    state: &AppState,
+    session: &RestreamSession,
+    work_dir: &Path,
+) -> Result<Uuid, AppError> {
+    let max_seconds = MAX_RECORDING_SECONDS.to_string();
+    let max_bytes = (MAX_STREAM_RECORDING_BYTES - RECORDING_SIZE_HEADROOM_BYTES).to_string();
+    let output = Command::new(&state.config.media.ffmpeg_bin)
+        .args([
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-nostats",
+            "-i",
+            HLS_PLAYLIST_NAME,
+            "-t",
+            &max_seconds,
+            "-fs",
+            &max_bytes,
+            "-c",
+            "copy",
+            "-bsf:a",
+            "aac_adtstoasc",
+            "-movflags",
+            "+faststart",
+            RECORDING_NAME,
+        ])
+        .current_dir(work_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .await
+        .map_err(|error| AppError::Internal(format!("Failed to start ffmpeg: {error}")))?;
+    if !output.status.success() {
+        return Err(AppError::Internal(format!(
+            "ffmpeg failed to remux recording: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let uploaded = state
+        .uploads
+        .store_stream_recording(
+            session.started_by,
+            session.channel_id,
+            session.started_at,
+            &work_dir.join(RECORDING_NAME),
+        )
+        .await?;
+    Ok(uploaded.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(video_codec: Option<&str>, has_audio: bool) -> RestreamInput {
+        RestreamInput {
+            sdp: String::new(),
+            video_codec: video_codec.map(String::from),
+            has_audio,
+        }
+    }
+
+    #[test]
+    fn serves_only_playlist_and_segments() {
+        assert_eq!(
+            hls_content_type("index.m3u8"),
+            Some("application/vnd.apple.mpegurl")
+        );
+        assert_eq!(hls_content_type("segment_00042.ts"), Some("video/mp2t"));
+        assert_eq!(hls_content_type("segment_.ts"), None);
+        assert_eq!(hls_content_type("segment_00042.ts.tmp"), None);
+        assert_eq!(hls_content_type("input.sdp"), None);
+        assert_eq!(hls_content_type("../index.m3u8"), None);
+    }
+
+    #[test]
+    fn copies_h264_and_transcodes_other_video() {
+        let copied = ffmpeg_hls_args(&input(Some("H264"), true), false);
+        assert!(copied.windows(2).any(|pair| pair == ["-c:v", "copy"]));
+        assert!(copied.windows(2).any(|pair| pair == ["-c:a", "aac"]));
+        assert!(copied.windows(2).any(|pair| pair
+            == [
+                "-hls_flags",
+                "delete_segments+independent_segments+temp_file"
+            ]));
+
+        let transcoded = ffmpeg_hls_args(&input(Some("VP8"), false), true);
+        assert!(transcoded
+            .windows(2)
+            .any(|pair| pair == ["-c:v", "libx264"]));
+        assert!(!transcoded.iter().any(|arg| arg == "-c:a"));
+        assert!(transcoded
+            .windows(2)
+            .any(|pair| pair == ["-hls_playlist_type", "event"]));
+        assert_eq!(
+            transcoded.last().map(String::as_str),
+            Some(HLS_PLAYLIST_NAME)
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::{extract_claims, require_operator_or_admin};
+use crate::errors::AppError;
+use crate::media::worker::MediaWorkerStats;
+use crate::uploads::{QuarantinedAsset, StorageConsumer};
+use crate::ws::broadcast::broadcast_global_message;
+use crate::ws::messages::ServerMessage;
+use crate::AppState;
+
+type PurgedUserRow = (
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+#[derive(Deserialize)]
+pub struct StorageQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct StorageOverviewResponse {
+    pub total_used_bytes: u64,
+    pub total_quota_bytes: Option<u64>,
+    pub user_quota_bytes: Option<u64>,
+    pub top_users: Vec<StorageConsumer>,
+}
+
+#[derive(Serialize)]
+pub struct PurgeUserMediaResponse {
+    pub deleted: u64,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/admin/media/workers", get(get_media_workers))
+        .route("/admin/storage", get(get_storage_overview))
+        .route("/admin/users/{user_id}/media", delete(purge_user_media))
+        .route("/admin/quarantine", get(list_quarantined_uploads))
+        .route(
+            "/admin/quarantine/{media_id}",
+            delete(delete_quarantined_upload),
+        )
+}
+
+async fn get_media_workers(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<MediaWorkerStats>>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+    require_operator_or_admin(&claims, "view media worker stats")?;
+
+    Ok(Json(state.media.worker_stats().await))
+}
+
+async fn get_storage_overview(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Query(query): Query<StorageQuery>,
+) -> Result<Json<StorageOverviewResponse>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+    require_operator_or_admin(&claims, "view storage usage")?;
+
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let quota = state.uploads.storage_quota();
+
+    Ok(Json(StorageOverviewResponse {
+        total_used_bytes: state.uploads.total_storage_bytes().await?,
+        total_quota_bytes: quota.total_bytes,
+        user_quota_bytes: quota.user_bytes,
+        top_users: state.uploads.top_storage_consumers(limit).await?,
+    }))
+}
+
+async fn purge_user_media(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<PurgeUserMediaResponse>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+    require_operator_or_admin(&claims, "purge user media")?;
+
+    let user: Option<PurgedUserRow> = sqlx::query_as(
+        "SELECT
+               username,
+               COALESCE(display_name, username) AS display_name,
+               avatar_url,
+               profile_description,
+               profile_status
+             FROM users
+             WHERE id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await?;
+    let (username, display_name, avatar_url, profile_description, profile_status) =
+        user.ok_or_else(|| AppError::NotFound("User not found".into()))?;
+
+    let deleted = state.uploads.purge_user_media(user_id).await?;
+    tracing::info!(
+        actor = %claims.username,
+        user_id = %user_id,
+        username = %username,
+        deleted,
+        "Purged user media"
+    );
+
+    if avatar_url.is_some() {
+        broadcast_global_message(
+            &state,
+            ServerMessage::UserProfileUpdated {
+                username,
+                display_name,
+                avatar_url: None,
+                profile_description,
+                profile_status,
+            },
+            None,
+        )
+        .await;
+    }
+
+    Ok(Json(PurgeUserMediaResponse { deleted }))
+}
+
+async fn list_quarantined_uploads(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<QuarantinedAsset>>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+    require_operator_or_admin(&claims, "view quarantined uploads")?;
+
+    Ok(Json(state.uploads.quarantined_uploads().await?))
+}
+
+async fn delete_quarantined_upload(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Path(media_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+    require_operator_or_admin(&claims, "delete quarantined uploads")?;
+
+    if !state.uploads.delete_quarantined_upload(media_id).await? {
+        return Err(AppError::NotFound("Quarantined upload not found".into()));
+    }
+    tracing::info!(
+        actor = %claims.username,
+        media_id = %media_id,
+        "Deleted quarantined upload"
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
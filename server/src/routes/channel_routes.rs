@@ -9,6 +9,7 @@ use uuid::Uuid;
 
 use crate::auth::extract_claims;
 use crate::errors::AppError;
+use crate::media::router::OpusConfig;
 use crate::message_attachments::{
     load_message_attachments_by_message, persist_message_attachments_in_tx,
     resolve_uploads_for_message, MessageAttachmentPayload,
@@ -19,8 +20,19 @@ use crate::ws::broadcast::{
     broadcast_channel_message, broadcast_global_message, remove_channel_subscribers,
 };
 use crate::ws::messages::ServerMessage;
+use crate::ws::voice::renegotiate_voice_channel_media;
 use crate::AppState;
 
+type ChannelOpusRow = (ChannelKind, Option<i32>, Option<bool>, Option<bool>);
+
+/// The channel's new Opus settings, if an update changed them. Media is
+/// only renegotiated in that case.
+fn changed_opus_config(previous: OpusConfig, channel: &Channel) -> Option<OpusConfig> {
+    let updated =
+        OpusConfig::from_channel_columns(channel.opus_bitrate, channel.opus_dtx, channel.opus_fec);
+    (updated != previous).then_some(updated)
+}
+
 #[derive(Deserialize)]
 pub struct CreateChannelRequest {
     pub name: String,
@@ -36,6 +48,8 @@ pub struct UpdateChannelRequest {
     pub name: String,
     pub description: Option<String>,
     pub opus_bitrate: Option<i32>,
+    pub opus_dtx: Option<bool>,
+    pub opus_fec: Option<bool>,
 }
 
 #[derive(Deserialize)]
@@ -315,19 +329,21 @@ async fn update_channel(
         _ => None,
     };
 
-    let existing: Option<(ChannelKind,)> =
-        sqlx::query_as("SELECT kind FROM channels WHERE id = $1")
+    let existing: Option<ChannelOpusRow> =
+        sqlx::query_as("SELECT kind, opus_bitrate, opus_dtx, opus_fec FROM channels WHERE id = $1")
             .bind(channel_id)
             .fetch_optional(&state.db)
             .await?;
 
-    let Some((channel_kind,)) = existing else {
+    let Some((channel_kind, previous_bitrate, previous_dtx, previous_fec)) = existing else {
         return Err(AppError::NotFound("Channel not found".into()));
     };
 
-    if channel_kind == ChannelKind::Text && body.opus_bitrate.is_some() {
+    if channel_kind == ChannelKind::Text
+        && (body.opus_bitrate.is_some() || body.opus_dtx.is_some() || body.opus_fec.is_some())
+    {
         return Err(AppError::BadRequest(
-            "opus_bitrate can only be set for voice channels".into(),
+            "Opus settings can only be set for voice channels".into(),
         ));
     }
 
@@ -339,19 +355,35 @@ async fn update_channel(
         }
     }
 
+    // DTX and FEC are only changed when provided, so older clients that only
+    // send the bitrate do not reset them.
     let channel: Channel = sqlx::query_as(
-        "UPDATE channels SET name = $1, description = $2, opus_bitrate = $3 WHERE id = $4 RETURNING *",
+        "UPDATE channels
+         SET name = $1,
+             description = $2,
+             opus_bitrate = $3,
+             opus_dtx = COALESCE($4, opus_dtx),
+             opus_fec = COALESCE($5, opus_fec)
+         WHERE id = $6
+         RETURNING *",
     )
     .bind(trimmed_name)
     .bind(description)
     .bind(body.opus_bitrate)
+    .bind(body.opus_dtx)
+    .bind(body.opus_fec)
     .bind(channel_id)
     .fetch_optional(&state.db)
     .await?
     .ok_or_else(|| AppError::NotFound("Channel not found".into()))?;
 
     if channel_kind == ChannelKind::Voice {
-        state.media.invalidate_router(channel_id).await;
+        let previous_opus_config =
+            OpusConfig::from_channel_columns(previous_bitrate, previous_dtx, previous_fec);
+        if let Some(opus_config) = changed_opus_config(previous_opus_config, &channel) {
+            renegotiate_voice_channel_media(&state, channel_id, opus_config, "opus_config_changed")
+                .await;
+        }
     }
 
     broadcast_global_message(
@@ -797,3 +829,56 @@ async fn delete_message(
 
     Ok(Json(serde_json::json!({ "deleted": true })))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn voice_channel(bitrate: Option<i32>, dtx: Option<bool>, fec: Option<bool>) -> Channel {
+        Channel {
+            id: Uuid::new_v4(),
+            name: "voice".into(),
+            description: None,
+            kind: ChannelKind::Voice,
+            position: 0,
+            created_at: Utc::now(),
+            opus_bitrate: bitrate,
+            opus_dtx: dtx,
+            opus_fec: fec,
+        }
+    }
+
+    #[test]
+    fn unchanged_opus_config_does_not_renegotiate() {
+        let previous = OpusConfig::from_channel_columns(Some(64_000), Some(true), None);
+        let channel = voice_channel(Some(64_000), Some(true), None);
+        assert_eq!(changed_opus_config(previous, &channel), None);
+
+        let channel = voice_channel(None, None, None);
+        assert_eq!(changed_opus_config(OpusConfig::default(), &channel), None);
+    }
+
+    #[test]
+    fn changed_opus_config_renegotiates_with_new_settings() {
+        let previous = OpusConfig::from_channel_columns(Some(64_000), None, None);
+        let channel = voice_channel(Some(96_000), None, Some(true));
+        assert_eq!(
+            changed_opus_config(previous, &channel),
+            Some(OpusConfig::from_channel_columns(
+                Some(96_000),
+                None,
+                Some(true)
+            ))
+        );
+
+        // Clearing an explicit setting falls back to the default and counts
+        // as a change.
+        let previous = OpusConfig::from_channel_columns(None, Some(false), None);
+        let channel = voice_channel(None, None, None);
+        assert_eq!(
+            changed_opus_config(previous, &channel),
+            Some(OpusConfig::default())
+        );
+    }
+}
@@ -4,16 +4,22 @@ use axum::{
     extract::Multipart,
     http::{header, HeaderValue, StatusCode},
     response::Response,
-    routing::{get, post},
+    routing::{delete, get, post},
     Json, Router,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use std::time::Instant;
+use tokio::sync::mpsc;
 use uuid::Uuid;
 
-use crate::auth::extract_claims;
+use crate::auth::{extract_claims, is_operator_or_admin_role};
 use crate::errors::AppError;
-use crate::AppState;
+use crate::restream::{hls_content_type, hls_storage_key, HLS_PLAYLIST_NAME};
+use crate::uploads::content_disposition;
+use crate::{AppState, RestreamSession};
+
+const MAX_ACTIVE_RESTREAMS: usize = 4;
 
 #[derive(Debug, Serialize)]
 struct UploadMediaResponse {
@@ -21,11 +27,84 @@ struct UploadMediaResponse {
     status: String,
 }
 
-type MediaFetchRow = (Uuid, Option<Uuid>, Uuid, Option<String>, String, String);
+#[derive(Debug, Deserialize)]
+struct StartRestreamRequest {
+    producer_id: String,
+    #[serde(default)]
+    record: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct RestreamResponse {
+    id: Uuid,
+    channel_id: Uuid,
+    producer_id: String,
+    record: bool,
+    live: bool,
+    playlist_path: String,
+}
+
+impl RestreamResponse {
+    fn new(id: Uuid, session: &RestreamSession) -> Self {
+        Self {
+            id,
+            channel_id: session.channel_id,
+            producer_id: session.producer_id.clone(),
+            record: session.record,
+            live: session.live,
+            playlist_path: format!("/api/media/hls/{id}/{HLS_PLAYLIST_NAME}"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct StreamRecordingResponse {
+    media_id: Uuid,
+    channel_id: Uuid,
+    bytes: i64,
+    started_at: chrono::DateTime<chrono::Utc>,
+    ended_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(sqlx::FromRow)]
+struct MediaFetchRow {
+    parent_id: Option<Uuid>,
+    owner_id: Uuid,
+    derivative_kind: Option<String>,
+    storage_key: String,
+    mime_type: String,
+    original_filename: Option<String>,
+    checksum: String,
+}
+
+/// A stored object and the headers it is served with.
+struct ServedObject {
+    storage_key: String,
+    mime_type: String,
+    content_disposition: Option<String>,
+    etag: Option<String>,
+    cache_control: &'static str,
+}
+
+/// Outcome of a `Range` header checked against an object's length.
+#[derive(Debug, PartialEq, Eq)]
+enum RangeRequest {
+    Full,
+    Partial(Range<u64>),
+    Unsatisfiable,
+}
 
-pub fn router(max_upload_bytes: usize) -> Router<AppState> {
+pub fn router(max_upload_bytes: usize, max_file_upload_bytes: usize) -> Router<AppState> {
     Router::new()
         .route("/media/upload", post(upload_media))
+        .route(
+            "/media/files",
+            post(upload_file).layer(DefaultBodyLimit::max(max_file_upload_bytes)),
+        )
+        .route("/media/restreams", post(start_restream))
+        .route("/media/restreams/{restream_id}", delete(stop_restream))
+        .route("/media/hls/{restream_id}/{file_name}", get(get_hls_file))
+        .route("/media/recordings", get(list_stream_recordings))
         .route("/media/{media_id}/{variant}", get(get_media_asset))
         .layer(DefaultBodyLimit::max(max_upload_bytes))
 }
@@ -39,7 +118,7 @@ async fn get_media_asset(
     let record_query_started = Instant::now();
     let record: Option<MediaFetchRow> = if variant == "original" {
         sqlx::query_as(
-            "SELECT id, parent_id, owner_id, derivative_kind, storage_key, mime_type
+            "SELECT parent_id, owner_id, derivative_kind, storage_key, mime_type, original_filename, checksum
              FROM media_assets
              WHERE id = $1 AND derivative_kind IS NULL AND status = 'ready'",
         )
@@ -48,7 +127,7 @@ async fn get_media_asset(
         .await?
     } else {
         sqlx::query_as(
-            "SELECT id, parent_id, owner_id, derivative_kind, storage_key, mime_type
+            "SELECT parent_id, owner_id, derivative_kind, storage_key, mime_type, original_filename, checksum
              FROM media_assets
              WHERE parent_id = $1 AND derivative_kind = $2 AND status = 'ready'",
         )
@@ -62,12 +141,11 @@ async fn get_media_asset(
         record_query_started.elapsed(),
     );
 
-    let (_asset_id, parent_id, owner_id, derivative_kind, storage_key, mime_type) =
-        record.ok_or_else(|| AppError::NotFound("Media asset not found".into()))?;
+    let record = record.ok_or_else(|| AppError::NotFound("Media asset not found".into()))?;
 
-    let root_media_id = parent_id.unwrap_or(media_id);
+    let root_media_id = record.parent_id.unwrap_or(media_id);
     let allow_public_derivative = matches!(
-        derivative_kind.as_deref(),
+        record.derivative_kind.as_deref(),
         Some("avatar_64") | Some("avatar_256")
     );
 
@@ -83,11 +161,25 @@ async fn get_media_asset(
             .fetch_one(&state.db)
             .await?;
 
-    let requester_can_access = if allow_public_derivative || linked_to_message || linked_to_emoji {
+    // Approved soundboard clips are public; pending ones only reach their
+    // uploader and the operators and admins who review them.
+    let soundboard_clip_approved: Option<bool> = sqlx::query_scalar(
+        "SELECT approved_at IS NOT NULL FROM soundboard_clips WHERE media_id = $1",
+    )
+    .bind(root_media_id)
+    .fetch_optional(&state.db)
+    .await?;
+
+    let is_public = allow_public_derivative
+        || linked_to_message
+        || linked_to_emoji
+        || soundboard_clip_approved == Some(true);
+    let requester_can_access = if is_public {
         true
     } else {
         let claims = extract_claims(&headers, &state.config.jwt.secret)?;
-        claims.user_id == owner_id
+        claims.user_id == record.owner_id
+            || (soundboard_clip_approved.is_some() && is_operator_or_admin_role(&claims.role))
     };
 
     if !requester_can_access {
@@ -97,31 +189,371 @@ async fn get_media_asset(
         ));
     }
 
-    serve_media_asset(state, storage_key, mime_type).await
+    // A ready asset never changes under its URL, so anything public can sit
+    // in shared caches for good; assets that need a token stay private.
+    let cache_control = if is_public {
+        "public, max-age=31536000, immutable"
+    } else {
+        "private, max-age=300"
+    };
+    let content_disposition =
+        content_disposition(&record.mime_type, record.original_filename.as_deref());
+    serve_media_asset(
+        &state,
+        &headers,
+        ServedObject {
+            storage_key: record.storage_key,
+            mime_type: record.mime_type,
+            content_disposition,
+            etag: Some(format!("\"{}\"", record.checksum)),
+            cache_control,
+        },
+    )
+    .await
 }
 
+/// Streams a stored object, answering `If-None-Match` with 304 and a single
+/// `Range` with 206 so video players can seek without downloading it all.
 async fn serve_media_asset(
-    state: AppState,
-    storage_key: String,
-    mime_type: String,
+    state: &AppState,
+    headers: &axum::http::HeaderMap,
+    object: ServedObject,
 ) -> Result<Response, AppError> {
-    let bytes = state.storage.read(&storage_key).await?;
-
-    let mut response = Response::new(Body::from(bytes));
-    *response.status_mut() = StatusCode::OK;
-    response.headers_mut().insert(
+    let mut response = Response::new(Body::empty());
+    let response_headers = response.headers_mut();
+    response_headers.insert(
         header::CONTENT_TYPE,
-        HeaderValue::from_str(&mime_type)
+        HeaderValue::from_str(&object.mime_type)
             .map_err(|_| AppError::Internal("Invalid content type for media response".into()))?,
     );
-    response.headers_mut().insert(
+    response_headers.insert(
         header::CACHE_CONTROL,
-        HeaderValue::from_static("public, max-age=300"),
+        HeaderValue::from_static(object.cache_control),
+    );
+    response_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
     );
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Some(content_disposition) = &object.content_disposition {
+        response_headers.insert(
+            header::CONTENT_DISPOSITION,
+            HeaderValue::from_str(content_disposition).map_err(|_| {
+                AppError::Internal("Invalid content disposition for media response".into())
+            })?,
+        );
+    }
+    if let Some(etag) = &object.etag {
+        response_headers.insert(
+            header::ETAG,
+            HeaderValue::from_str(etag)
+                .map_err(|_| AppError::Internal("Invalid ETag for media response".into()))?,
+        );
+
+        let if_none_match = headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok());
+        if if_none_match.is_some_and(|value| etag_matches(value, etag)) {
+            *response.status_mut() = StatusCode::NOT_MODIFIED;
+            return Ok(response);
+        }
+    }
+
+    let total_len = state.storage.size(&object.storage_key).await?;
+
+    // A stale `If-Range` means the client's partial copy is outdated, so it
+    // gets the whole object instead of a range of the new one.
+    let range_allowed = match headers.get(header::IF_RANGE) {
+        Some(if_range) => object
+            .etag
+            .as_deref()
+            .is_some_and(|etag| if_range.as_bytes() == etag.as_bytes()),
+        None => true,
+    };
+    let range_request = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| range_allowed)
+        .map_or(RangeRequest::Full, |value| parse_range(value, total_len));
+
+    let range = match range_request {
+        RangeRequest::Full => None,
+        RangeRequest::Partial(range) => Some(range),
+        RangeRequest::Unsatisfiable => {
+            *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+            response.headers_mut().insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes */{total_len}"))
+                    .map_err(|_| AppError::Internal("Invalid content range".into()))?,
+            );
+            return Ok(response);
+        }
+    };
+
+    let body_len = match &range {
+        Some(range) => {
+            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            response.headers_mut().insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!(
+                    "bytes {}-{}/{total_len}",
+                    range.start,
+                    range.end - 1
+                ))
+                .map_err(|_| AppError::Internal("Invalid content range".into()))?,
+            );
+            range.end - range.start
+        }
+        None => total_len,
+    };
+    response
+        .headers_mut()
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(body_len));
+
+    let stream = state
+        .storage
+        .read_stream(&object.storage_key, range)
+        .await?;
+    *response.body_mut() = Body::from_stream(stream);
 
     Ok(response)
 }
 
+/// Parses a single-range `Range` header. Multiple ranges and malformed
+/// values are ignored, which per RFC 9110 means serving the full object.
+fn parse_range(value: &str, total_len: u64) -> RangeRequest {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return RangeRequest::Full;
+    };
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = if start.is_empty() {
+        let Ok(suffix_len) = end.parse::<u64>() else {
+            return RangeRequest::Full;
+        };
+        total_len.saturating_sub(suffix_len)..total_len
+    } else {
+        let Ok(start) = start.parse::<u64>() else {
+            return RangeRequest::Full;
+        };
+        let end = if end.is_empty() {
+            total_len
+        } else {
+            let Ok(end) = end.parse::<u64>() else {
+                return RangeRequest::Full;
+            };
+            if end < start {
+                return RangeRequest::Full;
+            }
+            end.saturating_add(1).min(total_len)
+        };
+        start..end
+    };
+
+    if range.start >= range.end {
+        RangeRequest::Unsatisfiable
+    } else {
+        RangeRequest::Partial(range)
+    }
+}
+
+/// `If-None-Match` uses weak comparison: `W/` prefixes are ignored and `*`
+/// matches anything.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Starts restreaming a screen share or camera into HLS, optionally keeping
+/// a recording once it ends. Only the streamer, operators and admins can
+/// start one; asking again for the same stream returns the running restream.
+async fn start_restream(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(payload): Json<StartRestreamRequest>,
+) -> Result<(StatusCode, Json<RestreamResponse>), AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    let Some(stream) = state.media.watchable_stream(&payload.producer_id).await else {
+        return Err(AppError::NotFound("Stream not found".into()));
+    };
+
+    let streamer_user_id = match state
+        .connection_user_ids
+        .read()
+        .await
+        .get(&stream.owner_connection_id)
+    {
+        Some(user_id) => Some(*user_id),
+        None => state
+            .whip_sessions
+            .read()
+            .await
+            .get(&stream.owner_connection_id)
+            .map(|session| session.user_id),
+    };
+    if streamer_user_id != Some(claims.user_id) && !is_operator_or_admin_role(&claims.role) {
+        return Err(AppError::Unauthorized(
+            "Only the streamer, operators and admins can restream this stream".into(),
+        ));
+    }
+
+    // Register the session first so a producer that closes right away is
+    // still picked up by the restream reaper.
+    let session_id = Uuid::new_v4();
+    let (stop_tx, stop_rx) = mpsc::unbounded_channel();
+    let session = RestreamSession {
+        channel_id: stream.channel_id,
+        producer_id: payload.producer_id.clone(),
+        started_by: claims.user_id,
+        started_at: chrono::Utc::now(),
+        record: payload.record,
+        live: false,
+        stop_tx,
+    };
+    {
+        let mut restreams = state.restreams.write().await;
+        if let Some((existing_id, existing)) = restreams
+            .iter()
+            .find(|(_, existing)| existing.producer_id == payload.producer_id)
+        {
+            if payload.record && !existing.record {
+                return Err(AppError::Conflict(
+                    "This stream is already being restreamed without a recording".into(),
+                ));
+            }
+            return Ok((
+                StatusCode::OK,
+                Json(RestreamResponse::new(*existing_id, existing)),
+            ));
+        }
+        if restreams.len() >= MAX_ACTIVE_RESTREAMS {
+            return Err(AppError::TooManyRequests(
+                "Too many streams are being restreamed right now".into(),
+            ));
+        }
+        restreams.insert(session_id, session.clone());
+    }
+
+    if let Err(error) =
+        crate::restream::start_restream(&state, session_id, &stream, payload.record, stop_rx).await
+    {
+        state.restreams.write().await.remove(&session_id);
+        return Err(AppError::BadRequest(error));
+    }
+
+    tracing::info!(
+        session_id = %session_id,
+        channel_id = %stream.channel_id,
+        producer_id = %payload.producer_id,
+        username = %claims.username,
+        record = payload.record,
+        "Started HLS restream"
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(RestreamResponse::new(session_id, &session)),
+    ))
+}
+
+async fn stop_restream(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Path(restream_id): axum::extract::Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    let started_by = state
+        .restreams
+        .read()
+        .await
+        .get(&restream_id)
+        .map(|session| session.started_by)
+        .ok_or_else(|| AppError::NotFound("Restream not found".into()))?;
+    if started_by != claims.user_id && !is_operator_or_admin_role(&claims.role) {
+        return Err(AppError::Unauthorized(
+            "Only whoever started a restream, operators and admins can stop it".into(),
+        ));
+    }
+
+    crate::restream::stop_restream(&state, restream_id, "stopped").await;
+    Ok(StatusCode::OK)
+}
+
+/// Serves a live restream's playlist and segments. Like WHEP session URLs,
+/// the restream id is only handed out to people allowed to start it or that
+/// it was shared with, so it is enough to read the stream while it runs.
+#[tracing::instrument(skip(state, headers), fields(restream_id = %restream_id, file_name = %file_name))]
+async fn get_hls_file(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    headers: axum::http::HeaderMap,
+    axum::extract::Path((restream_id, file_name)): axum::extract::Path<(Uuid, String)>,
+) -> Result<Response, AppError> {
+    let Some(content_type) = hls_content_type(&file_name) else {
+        return Err(AppError::NotFound("HLS file not found".into()));
+    };
+
+    let live = state
+        .restreams
+        .read()
+        .await
+        .get(&restream_id)
+        .map(|session| session.live)
+        .ok_or_else(|| AppError::NotFound("Restream not found".into()))?;
+    if !live {
+        return Err(AppError::NotFound("Restream is still starting".into()));
+    }
+
+    // The playlist changes with every segment; segments never change.
+    let cache_control = if file_name == HLS_PLAYLIST_NAME {
+        "no-cache"
+    } else {
+        "public, max-age=300"
+    };
+    serve_media_asset(
+        &state,
+        &headers,
+        ServedObject {
+            storage_key: hls_storage_key(restream_id, &file_name),
+            mime_type: content_type.to_string(),
+            content_disposition: None,
+            etag: None,
+            cache_control,
+        },
+    )
+    .await
+}
+
+async fn list_stream_recordings(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<StreamRecordingResponse>>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    let recordings: Vec<StreamRecordingResponse> = sqlx::query_as(
+        "SELECT sr.media_id, sr.channel_id, ma.bytes, sr.started_at, sr.ended_at
+         FROM stream_recordings sr
+         JOIN media_assets ma ON ma.id = sr.media_id
+         WHERE sr.started_by = $1 AND ma.status = 'ready'
+         ORDER BY sr.ended_at DESC",
+    )
+    .bind(claims.user_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(recordings))
+}
+
 async fn upload_media(
     axum::extract::State(state): axum::extract::State<AppState>,
     headers: axum::http::HeaderMap,
@@ -173,3 +605,110 @@ async fn upload_media(
         status: uploaded.status,
     }))
 }
+
+/// Uploads a generic file attachment (documents, archives, logs, audio,
+/// video). Images sent here are stored as-is, without derivatives; videos
+/// are processed in the background.
+async fn upload_file(
+    axum::extract::State(state): axum::extract::State<AppState>,
+    headers: axum::http::HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Json<UploadMediaResponse>, AppError> {
+    let username = extract_claims(&headers, &state.config.jwt.secret)?.username;
+
+    let user_id: Uuid = sqlx::query_scalar("SELECT id FROM users WHERE username = $1")
+        .bind(&username)
+        .fetch_optional(&state.db)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("User not found".into()))?;
+
+    let mut uploaded = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|error| AppError::BadRequest(format!("Invalid multipart payload: {error}")))?
+    {
+        if field.name() != Some("file") {
+            continue;
+        }
+
+        let filename = field.file_name().map(ToOwned::to_owned);
+        let bytes = field.bytes().await.map_err(|error| {
+            AppError::BadRequest(format!("Failed to read upload field: {error}"))
+        })?;
+
+        uploaded = Some(
+            state
+                .uploads
+                .upload_file(
+                    user_id,
+                    filename.as_deref(),
+                    bytes.to_vec(),
+                    &state.config.media.ffmpeg_bin,
+                    &state.config.media.ffprobe_bin,
+                )
+                .await?,
+        );
+        break;
+    }
+
+    let uploaded = uploaded
+        .ok_or_else(|| AppError::BadRequest("Multipart form must include 'file'".into()))?;
+
+    Ok(Json(UploadMediaResponse {
+        id: uploaded.id,
+        status: uploaded.status,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_byte_ranges() {
+        assert_eq!(
+            parse_range("bytes=0-99", 1000),
+            RangeRequest::Partial(0..100)
+        );
+        assert_eq!(
+            parse_range("bytes=900-", 1000),
+            RangeRequest::Partial(900..1000)
+        );
+        assert_eq!(
+            parse_range("bytes=-100", 1000),
+            RangeRequest::Partial(900..1000)
+        );
+        assert_eq!(
+            parse_range("bytes=500-5000", 1000),
+            RangeRequest::Partial(500..1000)
+        );
+        assert_eq!(
+            parse_range("bytes=-5000", 1000),
+            RangeRequest::Partial(0..1000)
+        );
+    }
+
+    #[test]
+    fn ignores_malformed_or_multiple_ranges_and_rejects_out_of_bounds() {
+        assert_eq!(parse_range("bytes=0-1,5-9", 1000), RangeRequest::Full);
+        assert_eq!(parse_range("items=0-1", 1000), RangeRequest::Full);
+        assert_eq!(parse_range("bytes=9-2", 1000), RangeRequest::Full);
+        assert_eq!(parse_range("bytes=abc-", 1000), RangeRequest::Full);
+        assert_eq!(
+            parse_range("bytes=1000-", 1000),
+            RangeRequest::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=-0", 1000), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        assert!(etag_matches("\"abc\"", "\"abc\""));
+        assert!(etag_matches("W/\"abc\", \"def\"", "\"abc\""));
+        assert!(etag_matches("*", "\"abc\""));
+        assert!(!etag_matches("\"abcd\"", "\"abc\""));
+    }
+}
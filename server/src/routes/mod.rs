@@ -1,3 +1,4 @@
+pub mod admin_routes;
 pub mod auth_routes;
 pub mod channel_routes;
 pub mod dm_routes;
@@ -7,4 +8,11 @@ pub mod gif_routes;
 pub mod invite_routes;
 pub mod media_routes;
 pub mod reaction_routes;
+pub mod settings_routes;
+pub mod soundboard_routes;
+pub mod token_routes;
+pub mod turn_routes;
+pub mod tus_routes;
 pub mod user_routes;
+pub mod whep_routes;
+pub mod whip_routes;
@@ -0,0 +1,370 @@
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::auth::{extract_claims, is_operator_or_admin_role, require_operator_or_admin};
+use crate::errors::AppError;
+use crate::models::SoundboardClip;
+use crate::soundboard::{
+    start_playback, PlayableClip, MAX_SOUNDBOARD_PLAYBACKS_PER_CHANNEL, SOUNDBOARD_COOLDOWN,
+};
+use crate::ws::broadcast::broadcast_global_message;
+use crate::ws::messages::ServerMessage;
+use crate::{AppState, SoundboardPlayback};
+
+const SOUNDBOARD_UPLOAD_BODY_LIMIT: usize = 3 * 1024 * 1024;
+const MAX_PENDING_CLIPS_PER_USER: i64 = 5;
+const MAX_CLIP_NAME_CHARS: usize = 32;
+
+#[derive(Serialize)]
+pub struct SoundboardClipResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub url: String,
+    pub duration_ms: i32,
+    pub uploaded_by: Uuid,
+    pub approved: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<SoundboardClip> for SoundboardClipResponse {
+    fn from(clip: SoundboardClip) -> Self {
+        Self {
+            id: clip.id,
+            url: format!("/api/media/{}/original", clip.media_id),
+            name: clip.name,
+            duration_ms: clip.duration_ms,
+            uploaded_by: clip.uploaded_by,
+            approved: clip.approved_at.is_some(),
+            created_at: clip.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PlayClipRequest {
+    pub channel_id: Uuid,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/soundboard/clips",
+            get(list_clips)
+                .post(upload_clip)
+                .layer(DefaultBodyLimit::max(SOUNDBOARD_UPLOAD_BODY_LIMIT)),
+        )
+        .route("/soundboard/clips/{clip_id}", delete(delete_clip))
+        .route("/soundboard/clips/{clip_id}/approve", post(approve_clip))
+        .route("/soundboard/clips/{clip_id}/play", post(play_clip))
+}
+
+/// Lists the soundboard. Members see the approved clips and their own
+/// pending uploads; operators and admins also see everyone's pending clips.
+async fn list_clips(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<SoundboardClipResponse>>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    let clips: Vec<SoundboardClip> = sqlx::query_as(
+        "SELECT id, name, media_id, duration_ms, uploaded_by, approved_by, approved_at, created_at
+         FROM soundboard_clips
+         WHERE approved_at IS NOT NULL OR uploaded_by = $1 OR $2
+         ORDER BY approved_at IS NULL, lower(name)",
+    )
+    .bind(claims.user_id)
+    .bind(is_operator_or_admin_role(&claims.role))
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(clips.into_iter().map(Into::into).collect()))
+}
+
+/// Uploads a clip for the soundboard. Clips from members wait for an
+/// operator or admin to approve them; their own uploads are approved
+/// right away.
+async fn upload_clip(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<SoundboardClipResponse>), AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+    let curator = is_operator_or_admin_role(&claims.role);
+
+    let mut name: Option<String> = None;
+    let mut audio_bytes: Option<Vec<u8>> = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|error| AppError::BadRequest(format!("Invalid multipart payload: {error}")))?
+    {
+        let field_name = field.name().map(String::from);
+
+        match field_name.as_deref() {
+            Some("name") => {
+                let value = field.text().await.map_err(|error| {
+                    AppError::BadRequest(format!("Failed to read name: {error}"))
+                })?;
+                name = Some(value);
+            }
+            Some("file") => {
+                let bytes = field.bytes().await.map_err(|error| {
+                    AppError::BadRequest(format!("Failed to read file: {error}"))
+                })?;
+                audio_bytes = Some(bytes.to_vec());
+            }
+            _ => {}
+        }
+    }
+
+    let name = name
+        .map(|name| name.trim().to_string())
+        .ok_or_else(|| AppError::BadRequest("Missing 'name' field".into()))?;
+    let audio_bytes =
+        audio_bytes.ok_or_else(|| AppError::BadRequest("Missing 'file' field".into()))?;
+
+    if name.is_empty() || name.chars().count() > MAX_CLIP_NAME_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "Name must be between 1 and {MAX_CLIP_NAME_CHARS} characters"
+        )));
+    }
+
+    if !curator {
+        let pending: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM soundboard_clips WHERE uploaded_by = $1 AND approved_at IS NULL",
+        )
+        .bind(claims.user_id)
+        .fetch_one(&state.db)
+        .await?;
+        if pending >= MAX_PENDING_CLIPS_PER_USER {
+            return Err(AppError::TooManyRequests(
+                "You already have too many clips waiting for approval".into(),
+            ));
+        }
+    }
+
+    let upload = state
+        .uploads
+        .upload_sound_clip(claims.user_id, audio_bytes, &state.config.media.ffmpeg_bin)
+        .await?;
+
+    let clip: SoundboardClip = sqlx::query_as(
+        "INSERT INTO soundboard_clips (id, name, media_id, duration_ms, uploaded_by, approved_by, approved_at)
+         VALUES ($1, $2, $3, $4, $5, $6, CASE WHEN $6::uuid IS NULL THEN NULL ELSE now() END)
+         RETURNING id, name, media_id, duration_ms, uploaded_by, approved_by, approved_at, created_at",
+    )
+    .bind(Uuid::new_v4())
+    .bind(&name)
+    .bind(upload.id)
+    .bind(upload.duration_ms as i32)
+    .bind(claims.user_id)
+    .bind(curator.then_some(claims.user_id))
+    .fetch_one(&state.db)
+    .await?;
+
+    tracing::info!(
+        clip_id = %clip.id,
+        username = %claims.username,
+        duration_ms = upload.duration_ms,
+        approved = curator,
+        "Uploaded soundboard clip"
+    );
+
+    Ok((StatusCode::CREATED, Json(clip.into())))
+}
+
+async fn approve_clip(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(clip_id): Path<Uuid>,
+) -> Result<Json<SoundboardClipResponse>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+    require_operator_or_admin(&claims, "approve soundboard clips")?;
+
+    let clip: SoundboardClip = sqlx::query_as(
+        "UPDATE soundboard_clips
+         SET approved_by = $2, approved_at = COALESCE(approved_at, now())
+         WHERE id = $1
+         RETURNING id, name, media_id, duration_ms, uploaded_by, approved_by, approved_at, created_at",
+    )
+    .bind(clip_id)
+    .bind(claims.user_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Sound clip not found".into()))?;
+
+    Ok(Json(clip.into()))
+}
+
+/// Removes a clip. Operators and admins can remove any clip; members can
+/// withdraw their own clips while they are still pending.
+async fn delete_clip(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(clip_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    let clip: SoundboardClip = sqlx::query_as(
+        "SELECT id, name, media_id, duration_ms, uploaded_by, approved_by, approved_at, created_at
+         FROM soundboard_clips
+         WHERE id = $1",
+    )
+    .bind(clip_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Sound clip not found".into()))?;
+
+    let withdrawing_own_pending = clip.uploaded_by == claims.user_id && clip.approved_at.is_none();
+    if !withdrawing_own_pending && !is_operator_or_admin_role(&claims.role) {
+        return Err(AppError::Unauthorized(
+            "Only operators and admins can remove soundboard clips".into(),
+        ));
+    }
+
+    sqlx::query("DELETE FROM soundboard_clips WHERE id = $1")
+        .bind(clip_id)
+        .execute(&state.db)
+        .await?;
+
+    if let Err(error) = state.uploads.delete_media_family(clip.media_id).await {
+        tracing::warn!(
+            clip_id = %clip_id,
+            media_id = %clip.media_id,
+            error = ?error,
+            "Failed to delete soundboard clip media"
+        );
+    }
+
+    Ok(Json(serde_json::json!({"deleted": true})))
+}
+
+/// Plays an approved clip into a voice channel the caller is in.
+async fn play_clip(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(clip_id): Path<Uuid>,
+    Json(payload): Json<PlayClipRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+    let channel_id = payload.channel_id;
+
+    let in_channel = {
+        let connection_user_ids = state.connection_user_ids.read().await;
+        let voice_members_by_connection = state.voice_members_by_connection.read().await;
+        voice_members_by_connection
+            .iter()
+            .any(|(connection_id, voice_channel_id)| {
+                *voice_channel_id == channel_id
+                    && connection_user_ids.get(connection_id) == Some(&claims.user_id)
+            })
+    };
+    if !in_channel {
+        return Err(AppError::BadRequest(
+            "Join the voice channel to play sound clips".into(),
+        ));
+    }
+
+    let clip: (String, i32, String) = sqlx::query_as(
+        "SELECT sc.name, sc.duration_ms, ma.storage_key
+         FROM soundboard_clips sc
+         JOIN media_assets ma ON ma.id = sc.media_id
+         WHERE sc.id = $1 AND sc.approved_at IS NOT NULL AND ma.status = 'ready'",
+    )
+    .bind(clip_id)
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Sound clip not found".into()))?;
+    let (clip_name, duration_ms, storage_key) = clip;
+
+    {
+        let mut last_played = state.soundboard_last_played.write().await;
+        let now = Instant::now();
+        if let Some(played_at) = last_played.get(&claims.user_id) {
+            let elapsed = now.duration_since(*played_at);
+            if elapsed < SOUNDBOARD_COOLDOWN {
+                let wait_seconds = (SOUNDBOARD_COOLDOWN - elapsed).as_secs().max(1);
+                return Err(AppError::TooManyRequests(format!(
+                    "Wait {wait_seconds}s before playing another clip"
+                )));
+            }
+        }
+        last_played.retain(|_, played_at| now.duration_since(*played_at) < SOUNDBOARD_COOLDOWN);
+        last_played.insert(claims.user_id, now);
+    }
+
+    let session_id = Uuid::new_v4();
+    let playback = SoundboardPlayback {
+        channel_id,
+        username: claims.username.clone(),
+    };
+    let registered = {
+        let mut playbacks = state.soundboard_playbacks.write().await;
+        let playing_in_channel = playbacks
+            .values()
+            .filter(|playback| playback.channel_id == channel_id)
+            .count();
+        let has_room = playing_in_channel < MAX_SOUNDBOARD_PLAYBACKS_PER_CHANNEL;
+        if has_room {
+            playbacks.insert(session_id, playback.clone());
+        }
+        has_room
+    };
+    if !registered {
+        state
+            .soundboard_last_played
+            .write()
+            .await
+            .remove(&claims.user_id);
+        return Err(AppError::TooManyRequests(
+            "Too many clips are playing in this channel".into(),
+        ));
+    }
+
+    let clip = PlayableClip {
+        storage_key,
+        duration_ms: duration_ms.max(0) as u64,
+    };
+    if let Err(error) = start_playback(&state, session_id, &playback, clip).await {
+        state.soundboard_playbacks.write().await.remove(&session_id);
+        state
+            .soundboard_last_played
+            .write()
+            .await
+            .remove(&claims.user_id);
+        return Err(AppError::BadRequest(error));
+    }
+
+    tracing::info!(
+        session_id = %session_id,
+        channel_id = %channel_id,
+        clip_id = %clip_id,
+        username = %claims.username,
+        "Playing soundboard clip"
+    );
+
+    broadcast_global_message(
+        &state,
+        ServerMessage::SoundboardClipPlayed {
+            channel_id,
+            clip_id,
+            clip_name,
+            username: claims.username,
+        },
+        None,
+    )
+    .await;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({"playing": true})),
+    ))
+}
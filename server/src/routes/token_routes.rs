@@ -0,0 +1,172 @@
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::{
+    extract_bearer_token, extract_claims, generate_personal_token, hash_personal_token,
+    PERSONAL_TOKEN_PREFIX,
+};
+use crate::errors::AppError;
+use crate::AppState;
+
+const MAX_TOKENS_PER_USER: i64 = 20;
+const MAX_TOKEN_NAME_CHARS: usize = 64;
+
+#[derive(Deserialize)]
+pub struct CreatePersonalTokenRequest {
+    pub name: String,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct PersonalTokenResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct CreatePersonalTokenResponse {
+    pub id: Uuid,
+    pub name: String,
+    /// Only returned once; the server keeps a hash.
+    pub token: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The user a personal access token was issued to.
+#[derive(Debug, Clone)]
+pub struct PersonalTokenOwner {
+    pub user_id: Uuid,
+    pub username: String,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/users/me/tokens",
+            get(list_personal_tokens).post(create_personal_token),
+        )
+        .route("/users/me/tokens/{token_id}", delete(revoke_personal_token))
+}
+
+/// Resolves the `Authorization: Bearer` personal token used by external
+/// tools such as WHIP publishers.
+pub async fn authenticate_personal_token(
+    state: &AppState,
+    headers: &axum::http::HeaderMap,
+) -> Result<PersonalTokenOwner, AppError> {
+    let token = extract_bearer_token(headers)?;
+    if !token.starts_with(PERSONAL_TOKEN_PREFIX) {
+        return Err(AppError::Unauthorized("Invalid personal token".into()));
+    }
+
+    let owner: Option<(Uuid, String)> = sqlx::query_as(
+        "UPDATE personal_access_tokens t SET last_used_at = now()
+         FROM users u
+         WHERE t.token_hash = $1 AND u.id = t.user_id
+         RETURNING u.id, u.username",
+    )
+    .bind(hash_personal_token(token))
+    .fetch_optional(&state.db)
+    .await?;
+
+    let (user_id, username) =
+        owner.ok_or_else(|| AppError::Unauthorized("Invalid personal token".into()))?;
+
+    Ok(PersonalTokenOwner { user_id, username })
+}
+
+async fn list_personal_tokens(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<Vec<PersonalTokenResponse>>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    let tokens: Vec<PersonalTokenResponse> = sqlx::query_as(
+        "SELECT id, name, created_at, last_used_at
+         FROM personal_access_tokens
+         WHERE user_id = $1
+         ORDER BY created_at DESC",
+    )
+    .bind(claims.user_id)
+    .fetch_all(&state.db)
+    .await?;
+
+    Ok(Json(tokens))
+}
+
+async fn create_personal_token(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Json(body): Json<CreatePersonalTokenRequest>,
+) -> Result<Json<CreatePersonalTokenResponse>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    let name = body.name.trim().to_string();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("Token name is required".into()));
+    }
+    if name.chars().count() > MAX_TOKEN_NAME_CHARS {
+        return Err(AppError::BadRequest(format!(
+            "Token name must be at most {MAX_TOKEN_NAME_CHARS} characters"
+        )));
+    }
+
+    let (existing,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM personal_access_tokens WHERE user_id = $1")
+            .bind(claims.user_id)
+            .fetch_one(&state.db)
+            .await?;
+    if existing >= MAX_TOKENS_PER_USER {
+        return Err(AppError::Conflict(format!(
+            "You can have at most {MAX_TOKENS_PER_USER} personal tokens"
+        )));
+    }
+
+    let token_id = Uuid::new_v4();
+    let token = generate_personal_token();
+    let created_at = chrono::Utc::now();
+
+    sqlx::query(
+        "INSERT INTO personal_access_tokens (id, user_id, name, token_hash, created_at) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(token_id)
+    .bind(claims.user_id)
+    .bind(&name)
+    .bind(hash_personal_token(&token))
+    .bind(created_at)
+    .execute(&state.db)
+    .await?;
+
+    Ok(Json(CreatePersonalTokenResponse {
+        id: token_id,
+        name,
+        token,
+        created_at,
+    }))
+}
+
+async fn revoke_personal_token(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+    Path(token_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    let result = sqlx::query("DELETE FROM personal_access_tokens WHERE id = $1 AND user_id = $2")
+        .bind(token_id)
+        .bind(claims.user_id)
+        .execute(&state.db)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Personal token not found".into()));
+    }
+
+    Ok(Json(serde_json::json!({ "revoked": true })))
+}
@@ -0,0 +1,21 @@
+use axum::{extract::State, routing::get, Json, Router};
+
+use crate::auth::extract_claims;
+use crate::errors::AppError;
+use crate::turn_server::{mint_credentials, TurnCredentials};
+use crate::AppState;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/turn/credentials", get(get_turn_credentials))
+}
+
+async fn get_turn_credentials(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<TurnCredentials>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    mint_credentials(&state.config, claims.user_id)
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound("TURN server is not enabled".into()))
+}
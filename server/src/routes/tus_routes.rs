@@ -0,0 +1,354 @@
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{head, options},
+    Json, Router,
+};
+use futures_util::StreamExt;
+use uuid::Uuid;
+
+use crate::auth::extract_claims;
+use crate::errors::AppError;
+use crate::uploads::{
+    chunk_matches_checksum, parse_upload_checksum, parse_upload_metadata, ResumableUpload,
+};
+use crate::AppState;
+
+const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,expiration,checksum,termination";
+const TUS_CHECKSUM_ALGORITHMS: &str = "sha256";
+const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+const TUS_VERSION_HEADER: HeaderName = HeaderName::from_static("tus-version");
+const TUS_EXTENSION: HeaderName = HeaderName::from_static("tus-extension");
+const TUS_MAX_SIZE: HeaderName = HeaderName::from_static("tus-max-size");
+const TUS_CHECKSUM_ALGORITHM: HeaderName = HeaderName::from_static("tus-checksum-algorithm");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+const UPLOAD_CHECKSUM: HeaderName = HeaderName::from_static("upload-checksum");
+const UPLOAD_EXPIRES: HeaderName = HeaderName::from_static("upload-expires");
+const UPLOAD_DEFER_LENGTH: HeaderName = HeaderName::from_static("upload-defer-length");
+/// Media asset a finished upload became; not part of tus itself.
+pub const MEDIA_ID: HeaderName = HeaderName::from_static("x-media-id");
+
+/// Headers browsers must be allowed to send or read cross-origin for tus.
+pub const TUS_REQUEST_HEADERS: [HeaderName; 5] = [
+    TUS_RESUMABLE,
+    UPLOAD_LENGTH,
+    UPLOAD_OFFSET,
+    UPLOAD_METADATA,
+    UPLOAD_CHECKSUM,
+];
+pub const TUS_RESPONSE_HEADERS: [HeaderName; 9] = [
+    TUS_RESUMABLE,
+    TUS_VERSION_HEADER,
+    TUS_EXTENSION,
+    TUS_MAX_SIZE,
+    TUS_CHECKSUM_ALGORITHM,
+    UPLOAD_LENGTH,
+    UPLOAD_OFFSET,
+    UPLOAD_EXPIRES,
+    MEDIA_ID,
+];
+
+/// Resumable file uploads over tus 1.0 (https://tus.io/protocols/resumable-upload).
+///
+/// Clients create an upload with `POST /media/tus`, send the bytes with one
+/// or more `PATCH` requests to the returned `Location`, and use `HEAD` to
+/// find where to resume after a dropped connection. The finished file goes
+/// through the same pipeline as `POST /media/files`; its media id comes back
+/// in `X-Media-Id`.
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/media/tus", options(describe_server).post(create_upload))
+        .route(
+            "/media/tus/{upload_id}",
+            head(get_upload_offset)
+                .patch(append_upload)
+                .delete(terminate_upload),
+        )
+        .layer(middleware::map_response(with_tus_resumable))
+}
+
+async fn with_tus_resumable(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert(TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION));
+    response
+}
+
+async fn describe_server(State(state): State<AppState>) -> Response {
+    (
+        StatusCode::NO_CONTENT,
+        [
+            (TUS_VERSION_HEADER, TUS_VERSION.to_string()),
+            (TUS_EXTENSION, TUS_EXTENSIONS.to_string()),
+            (
+                TUS_MAX_SIZE,
+                state.config.storage.max_file_upload_bytes.to_string(),
+            ),
+            (TUS_CHECKSUM_ALGORITHM, TUS_CHECKSUM_ALGORITHMS.to_string()),
+        ],
+    )
+        .into_response()
+}
+
+async fn create_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if let Some(response) = reject_unsupported_version(&headers) {
+        return Ok(response);
+    }
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    if headers.contains_key(UPLOAD_DEFER_LENGTH) {
+        return Err(AppError::BadRequest(
+            "Upload-Defer-Length is not supported".into(),
+        ));
+    }
+    let upload_length = header_u64(&headers, &UPLOAD_LENGTH)?
+        .ok_or_else(|| AppError::BadRequest("Upload-Length is required".into()))?;
+    let metadata = match header_str(&headers, &UPLOAD_METADATA)? {
+        Some(value) => parse_upload_metadata(value)?,
+        None => Default::default(),
+    };
+
+    let upload = state
+        .uploads
+        .create_resumable_upload(claims.user_id, upload_length, &metadata)
+        .await?;
+
+    tracing::info!(
+        upload_id = %upload.id,
+        username = %claims.username,
+        upload_length,
+        "Created resumable upload"
+    );
+
+    let mut response = upload_state_response(StatusCode::CREATED, &upload);
+    response.headers_mut().insert(
+        header::LOCATION,
+        header_value(format!("/api/media/tus/{}", upload.id)),
+    );
+    Ok(response)
+}
+
+async fn get_upload_offset(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    if let Some(response) = reject_unsupported_version(&headers) {
+        return Ok(response);
+    }
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    let upload = state
+        .uploads
+        .resumable_upload(claims.user_id, upload_id)
+        .await?;
+
+    let mut response = upload_state_response(StatusCode::OK, &upload);
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    Ok(response)
+}
+
+async fn append_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+    body: Body,
+) -> Result<Response, AppError> {
+    if let Some(response) = reject_unsupported_version(&headers) {
+        return Ok(response);
+    }
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    if header_str(&headers, &header::CONTENT_TYPE)? != Some(OFFSET_OCTET_STREAM) {
+        return Ok((
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Json(serde_json::json!({
+                "error": format!("PATCH requests must use Content-Type: {OFFSET_OCTET_STREAM}")
+            })),
+        )
+            .into_response());
+    }
+    let offset = header_u64(&headers, &UPLOAD_OFFSET)?
+        .ok_or_else(|| AppError::BadRequest("Upload-Offset is required".into()))?;
+    let checksum = header_str(&headers, &UPLOAD_CHECKSUM)?
+        .map(parse_upload_checksum)
+        .transpose()?;
+
+    let upload = state
+        .uploads
+        .resumable_upload(claims.user_id, upload_id)
+        .await?;
+    if offset != upload.upload_offset as u64 {
+        return Err(AppError::Conflict(format!(
+            "Upload-Offset does not match the current offset {}",
+            upload.upload_offset
+        )));
+    }
+
+    let remaining = (upload.upload_length - upload.upload_offset) as usize;
+    let (bytes, interrupted) = read_chunk(body, remaining).await?;
+
+    if let Some(digest) = checksum {
+        if interrupted || !chunk_matches_checksum(&bytes, &digest) {
+            return Ok((
+                StatusCode::from_u16(460).expect("460 is a valid status code"),
+                Json(serde_json::json!({ "error": "Checksum Mismatch" })),
+            )
+                .into_response());
+        }
+    }
+
+    if interrupted {
+        tracing::debug!(
+            upload_id = %upload_id,
+            received = bytes.len(),
+            "Resumable upload connection dropped mid-chunk; keeping received bytes"
+        );
+    }
+
+    let upload = state
+        .uploads
+        .append_resumable_chunk(
+            claims.user_id,
+            upload_id,
+            offset,
+            bytes,
+            &state.config.media.ffmpeg_bin,
+            &state.config.media.ffprobe_bin,
+        )
+        .await?;
+
+    if let Some(media_id) = upload.media_id {
+        tracing::info!(
+            upload_id = %upload_id,
+            media_id = %media_id,
+            username = %claims.username,
+            "Completed resumable upload"
+        );
+    }
+
+    Ok(upload_state_response(StatusCode::NO_CONTENT, &upload))
+}
+
+async fn terminate_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(upload_id): Path<Uuid>,
+) -> Result<Response, AppError> {
+    if let Some(response) = reject_unsupported_version(&headers) {
+        return Ok(response);
+    }
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    state
+        .uploads
+        .cancel_resumable_upload(claims.user_id, upload_id)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Answers `412` with the versions this server speaks when a request asks
+/// for a different tus version.
+fn reject_unsupported_version(headers: &HeaderMap) -> Option<Response> {
+    if headers
+        .get(TUS_RESUMABLE)
+        .is_some_and(|version| version == TUS_VERSION)
+    {
+        return None;
+    }
+
+    Some(
+        (
+            StatusCode::PRECONDITION_FAILED,
+            [(TUS_VERSION_HEADER, TUS_VERSION)],
+        )
+            .into_response(),
+    )
+}
+
+fn upload_state_response(status: StatusCode, upload: &ResumableUpload) -> Response {
+    let mut response = status.into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        UPLOAD_OFFSET,
+        header_value(upload.upload_offset.to_string()),
+    );
+    headers.insert(
+        UPLOAD_LENGTH,
+        header_value(upload.upload_length.to_string()),
+    );
+    headers.insert(
+        UPLOAD_EXPIRES,
+        header_value(
+            upload
+                .expires_at
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string(),
+        ),
+    );
+    if let Some(media_id) = upload.media_id {
+        headers.insert(MEDIA_ID, header_value(media_id.to_string()));
+    }
+    response
+}
+
+/// Reads at most `remaining` bytes of a PATCH body. If the connection drops
+/// partway, what did arrive is kept so the client resumes from there.
+async fn read_chunk(body: Body, remaining: usize) -> Result<(Vec<u8>, bool), AppError> {
+    let mut stream = body.into_data_stream();
+    let mut received = Vec::new();
+
+    while let Some(frame) = stream.next().await {
+        let Ok(data) = frame else {
+            return Ok((received, true));
+        };
+        if received.len() + data.len() > remaining {
+            return Err(AppError::BadRequest(
+                "Chunk extends past Upload-Length".into(),
+            ));
+        }
+        received.extend_from_slice(&data);
+    }
+
+    Ok((received, false))
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Result<Option<&'a str>, AppError> {
+    headers
+        .get(name)
+        .map(|value| {
+            value
+                .to_str()
+                .map_err(|_| AppError::BadRequest(format!("{name} header is not valid text")))
+        })
+        .transpose()
+}
+
+fn header_u64(headers: &HeaderMap, name: &HeaderName) -> Result<Option<u64>, AppError> {
+    header_str(headers, name)?
+        .map(|value| {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| AppError::BadRequest(format!("{name} must be a non-negative integer")))
+        })
+        .transpose()
+}
+
+fn header_value(value: String) -> HeaderValue {
+    HeaderValue::from_str(&value).expect("tus header values are ASCII")
+}
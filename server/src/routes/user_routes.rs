@@ -10,6 +10,7 @@ use uuid::Uuid;
 
 use crate::auth::{create_token, extract_claims};
 use crate::errors::AppError;
+use crate::uploads::StorageUsage;
 use crate::ws::broadcast::broadcast_global_message;
 use crate::ws::messages::ServerMessage;
 use crate::AppState;
@@ -90,6 +91,7 @@ pub fn router() -> Router<AppState> {
         )
         .route("/users/{username}", get(get_user_profile))
         .route("/users/me/avatar", post(upload_current_user_avatar))
+        .route("/users/me/storage", get(get_current_user_storage))
 }
 
 fn normalize_optional_profile_field(
@@ -330,3 +332,12 @@ async fn upload_current_user_avatar(
         avatar_url: format!("/api/media/{}/avatar_64", uploaded.id),
     }))
 }
+
+async fn get_current_user_storage(
+    State(state): State<AppState>,
+    headers: axum::http::HeaderMap,
+) -> Result<Json<StorageUsage>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    Ok(Json(state.uploads.storage_usage(claims.user_id).await?))
+}
@@ -0,0 +1,203 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, post},
+    Json, Router,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::auth::{
+    create_stream_viewer_token, extract_bearer_token, extract_claims, validate_stream_viewer_token,
+    validate_token, PERSONAL_TOKEN_PREFIX,
+};
+use crate::errors::AppError;
+use crate::routes::token_routes::authenticate_personal_token;
+use crate::routes::whip_routes::validate_sdp_offer;
+use crate::ws::voice::broadcast_channel_viewers;
+use crate::{AppState, WhepViewer};
+
+const MAX_WHEP_VIEWERS_PER_STREAM: usize = 50;
+const STREAM_VIEWER_LINK_TTL_HOURS: i64 = 24;
+
+#[derive(Serialize)]
+pub struct StreamViewerLinkResponse {
+    pub token: String,
+    pub watch_path: String,
+    pub whep_path: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/whep/producers/{producer_id}", post(create_whep_session))
+        .route(
+            "/whep/producers/{producer_id}/links",
+            post(create_stream_viewer_link),
+        )
+        .route("/whep/sessions/{session_id}", delete(delete_whep_session))
+}
+
+/// Resolves who is asking to watch `producer_id`.
+///
+/// Accepts a session JWT or personal token, which makes the viewer show up
+/// by name, or a stream-viewer link token for that producer, which makes an
+/// anonymous viewer.
+async fn authenticate_viewer(
+    state: &AppState,
+    headers: &HeaderMap,
+    producer_id: &str,
+) -> Result<Option<String>, AppError> {
+    let token = extract_bearer_token(headers)?;
+    if token.starts_with(PERSONAL_TOKEN_PREFIX) {
+        let owner = authenticate_personal_token(state, headers).await?;
+        return Ok(Some(owner.username));
+    }
+
+    let secret = &state.config.jwt.secret;
+    if let Ok(claims) = validate_token(token, secret) {
+        return Ok(Some(claims.username));
+    }
+
+    match validate_stream_viewer_token(token, secret) {
+        Ok(link_producer_id) if link_producer_id == producer_id => Ok(None),
+        _ => Err(AppError::Unauthorized("Invalid viewer token".into())),
+    }
+}
+
+/// WHEP playback endpoint.
+///
+/// Answers a receive-only SDP offer with a session that consumes the given
+/// screen share or camera (and the screen audio that goes with a screen
+/// share), without joining the voice channel.
+async fn create_whep_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(producer_id): Path<String>,
+    offer_sdp: String,
+) -> Result<Response, AppError> {
+    let username = authenticate_viewer(&state, &headers, &producer_id).await?;
+    validate_sdp_offer(&headers, &offer_sdp)?;
+
+    let Some(stream) = state.media.watchable_stream(&producer_id).await else {
+        return Err(AppError::NotFound("Stream not found".into()));
+    };
+
+    // Register the viewer first so a transport that fails right away is
+    // still reaped by the session reaper.
+    let session_id = Uuid::new_v4();
+    {
+        let mut whep_sessions = state.whep_sessions.write().await;
+        let viewers = whep_sessions
+            .values()
+            .filter(|viewer| viewer.producer_id == producer_id)
+            .count();
+        if viewers >= MAX_WHEP_VIEWERS_PER_STREAM {
+            return Err(AppError::Conflict(
+                "This stream has reached its viewer limit".into(),
+            ));
+        }
+        whep_sessions.insert(
+            session_id,
+            WhepViewer {
+                channel_id: stream.channel_id,
+                producer_id: producer_id.clone(),
+                username: username.clone(),
+            },
+        );
+    }
+
+    let playback = match state
+        .media
+        .create_whep_session(session_id, &stream, &offer_sdp)
+        .await
+    {
+        Ok(playback) => playback,
+        Err(error) => {
+            state.whep_sessions.write().await.remove(&session_id);
+            return Err(AppError::BadRequest(error));
+        }
+    };
+
+    tracing::info!(
+        session_id = %session_id,
+        channel_id = %playback.channel_id,
+        producer_id = %producer_id,
+        viewer = username.as_deref().unwrap_or("anonymous"),
+        "Started WHEP playback session"
+    );
+    broadcast_channel_viewers(&state, playback.channel_id).await;
+
+    Ok((
+        StatusCode::CREATED,
+        [
+            (header::CONTENT_TYPE, "application/sdp".to_string()),
+            (header::LOCATION, format!("/api/whep/sessions/{session_id}")),
+        ],
+        playback.answer_sdp,
+    )
+        .into_response())
+}
+
+/// Ends a WHEP session. The session URL is only handed to the viewer that
+/// created it, so knowing it is enough to tear the session down.
+async fn delete_whep_session(
+    State(state): State<AppState>,
+    Path(session_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    if !state.whep_sessions.read().await.contains_key(&session_id) {
+        return Err(AppError::NotFound("WHEP session not found".into()));
+    }
+
+    end_whep_session(&state, session_id, "deleted").await;
+    Ok(StatusCode::OK)
+}
+
+async fn create_stream_viewer_link(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(producer_id): Path<String>,
+) -> Result<Json<StreamViewerLinkResponse>, AppError> {
+    let claims = extract_claims(&headers, &state.config.jwt.secret)?;
+
+    if state.media.watchable_stream(&producer_id).await.is_none() {
+        return Err(AppError::NotFound("Stream not found".into()));
+    }
+
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(STREAM_VIEWER_LINK_TTL_HOURS);
+    let token = create_stream_viewer_token(&producer_id, &state.config.jwt.secret, expires_at)
+        .map_err(|error| AppError::Internal(format!("Failed to sign viewer link: {error}")))?;
+
+    tracing::info!(
+        producer_id = %producer_id,
+        username = %claims.username,
+        "Created stream viewer link"
+    );
+
+    Ok(Json(StreamViewerLinkResponse {
+        watch_path: format!("/watch/{producer_id}?token={token}"),
+        whep_path: format!("/api/whep/producers/{producer_id}"),
+        token,
+        expires_at,
+    }))
+}
+
+/// Closes a WHEP session's transport and updates the channel's viewer list.
+/// Safe to call more than once for the same session.
+pub async fn end_whep_session(state: &AppState, session_id: Uuid, reason: &str) {
+    let Some(viewer) = state.whep_sessions.write().await.remove(&session_id) else {
+        return;
+    };
+
+    state.media.close_whep_session(session_id).await;
+    broadcast_channel_viewers(state, viewer.channel_id).await;
+
+    tracing::info!(
+        session_id = %session_id,
+        channel_id = %viewer.channel_id,
+        producer_id = %viewer.producer_id,
+        reason = %reason,
+        "Ended WHEP playback session"
+    );
+}
@@ -0,0 +1,189 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, post},
+    Router,
+};
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::media::router::OpusConfig;
+use crate::media::sdp::MAX_SDP_OFFER_BYTES;
+use crate::routes::token_routes::authenticate_personal_token;
+use crate::ws::voice::{broadcast_closed_producers, broadcast_media_signal_to_voice_channel};
+use crate::{AppState, WhipSessionOwner};
+
+type ChannelOpusRow = (String, Option<i32>, Option<bool>, Option<bool>);
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/whip/channels/{channel_id}", post(create_whip_session))
+        .route("/whip/sessions/{session_id}", delete(delete_whip_session))
+}
+
+/// WHIP publish endpoint (RFC 9725).
+///
+/// Accepts an SDP offer from OBS, GStreamer and similar tools, authenticated
+/// with a personal token, and answers with a receive-only session whose
+/// tracks are announced to the voice channel as screen producers.
+async fn create_whip_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(channel_id): Path<Uuid>,
+    offer_sdp: String,
+) -> Result<Response, AppError> {
+    let owner = authenticate_personal_token(&state, &headers).await?;
+
+    validate_sdp_offer(&headers, &offer_sdp)?;
+
+    let channel: Option<ChannelOpusRow> = sqlx::query_as(
+        "SELECT kind::text, opus_bitrate, opus_dtx, opus_fec FROM channels WHERE id = $1",
+    )
+    .bind(channel_id)
+    .fetch_optional(&state.db)
+    .await?;
+    let Some((kind, opus_bitrate, opus_dtx, opus_fec)) = channel else {
+        return Err(AppError::NotFound("Channel not found".into()));
+    };
+    if kind != "voice" {
+        return Err(AppError::BadRequest(
+            "Selected channel is not voice-enabled".into(),
+        ));
+    }
+
+    // Register the owner first so a transport that fails right away is
+    // still reaped by the session reaper. Checking under the same lock keeps
+    // two concurrent offers from both getting in.
+    let session_id = Uuid::new_v4();
+    {
+        let mut whip_sessions = state.whip_sessions.write().await;
+        if whip_sessions
+            .values()
+            .any(|session| session.user_id == owner.user_id && session.channel_id == channel_id)
+        {
+            return Err(AppError::Conflict(
+                "You already have a WHIP stream in this channel".into(),
+            ));
+        }
+        whip_sessions.insert(
+            session_id,
+            WhipSessionOwner {
+                user_id: owner.user_id,
+                username: owner.username.clone(),
+                channel_id,
+            },
+        );
+    }
+
+    let publication = match state
+        .media
+        .create_whip_session(
+            session_id,
+            channel_id,
+            OpusConfig::from_channel_columns(opus_bitrate, opus_dtx, opus_fec),
+            &offer_sdp,
+        )
+        .await
+    {
+        Ok(publication) => publication,
+        Err(error) => {
+            state.whip_sessions.write().await.remove(&session_id);
+            return Err(AppError::BadRequest(error));
+        }
+    };
+
+    tracing::info!(
+        session_id = %session_id,
+        channel_id = %channel_id,
+        username = %owner.username,
+        producers = publication.producers.len(),
+        "Started WHIP ingest session"
+    );
+
+    for producer in &publication.producers {
+        broadcast_media_signal_to_voice_channel(
+            &state,
+            channel_id,
+            serde_json::json!({
+                "action": "new_producer",
+                "producer_id": producer.producer_id,
+                "kind": producer.kind,
+                "source": producer.source,
+                "routing_mode": producer.routing_mode,
+                "username": owner.username,
+            }),
+            None,
+        )
+        .await;
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        [
+            (header::CONTENT_TYPE, "application/sdp".to_string()),
+            (header::LOCATION, format!("/api/whip/sessions/{session_id}")),
+        ],
+        publication.answer_sdp,
+    )
+        .into_response())
+}
+
+/// Checks the Content-Type and size of a WHIP/WHEP offer body.
+pub(crate) fn validate_sdp_offer(headers: &HeaderMap, offer_sdp: &str) -> Result<(), AppError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    if !content_type.starts_with("application/sdp") {
+        return Err(AppError::BadRequest(
+            "SDP offers must use Content-Type: application/sdp".into(),
+        ));
+    }
+    if offer_sdp.is_empty() || offer_sdp.len() > MAX_SDP_OFFER_BYTES {
+        return Err(AppError::BadRequest(
+            "SDP offer is empty or too large".into(),
+        ));
+    }
+    Ok(())
+}
+
+async fn delete_whip_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<Uuid>,
+) -> Result<StatusCode, AppError> {
+    let owner = authenticate_personal_token(&state, &headers).await?;
+
+    let owns_session = state
+        .whip_sessions
+        .read()
+        .await
+        .get(&session_id)
+        .is_some_and(|session| session.user_id == owner.user_id);
+    if !owns_session {
+        return Err(AppError::NotFound("WHIP session not found".into()));
+    }
+
+    end_whip_session(&state, session_id, "deleted").await;
+    Ok(StatusCode::OK)
+}
+
+/// Closes a WHIP session's producers and tells the voice channel about it.
+/// Safe to call more than once for the same session.
+pub async fn end_whip_session(state: &AppState, session_id: Uuid, reason: &str) {
+    let Some(owner) = state.whip_sessions.write().await.remove(&session_id) else {
+        return;
+    };
+
+    let closed_producers = state.media.close_whip_session(session_id).await;
+    broadcast_closed_producers(state, &closed_producers, None).await;
+
+    tracing::info!(
+        session_id = %session_id,
+        channel_id = %owner.channel_id,
+        username = %owner.username,
+        reason = %reason,
+        "Ended WHIP ingest session"
+    );
+}
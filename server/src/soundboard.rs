@@ -0,0 +1,185 @@
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::time::{sleep, timeout, Duration};
+use uuid::Uuid;
+
+use crate::media::soundboard::SoundboardInjection;
+use crate::media::transport::ProducerSource;
+use crate::ws::voice::{broadcast_closed_producers, broadcast_media_signal_to_voice_channel};
+use crate::{AppState, SoundboardPlayback};
+
+pub const SOUNDBOARD_COOLDOWN: Duration = Duration::from_secs(8);
+pub const MAX_SOUNDBOARD_PLAYBACKS_PER_CHANNEL: usize = 3;
+/// Head start the channel's members get to consume a clip's producer before
+/// ffmpeg starts sending, so the first syllable isn't lost.
+const CONSUME_LEAD_TIME: Duration = Duration::from_millis(300);
+/// How long past the clip's own length ffmpeg may run before it is killed.
+const PLAYBACK_GRACE: Duration = Duration::from_secs(5);
+
+/// A stored, approved clip ready to be played.
+#[derive(Debug, Clone)]
+pub struct PlayableClip {
+    pub storage_key: String,
+    pub duration_ms: u64,
+}
+
+/// ffmpeg arguments that send an Ogg Opus clip from stdin, unchanged and in
+/// real time, as RTP to the soundboard producer's plain transport.
+fn ffmpeg_rtp_args(injection: &SoundboardInjection) -> Vec<String> {
+    let port = injection.rtp_port;
+    [
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-re",
+        "-i",
+        "pipe:0",
+        "-map",
+        "0:a:0",
+        "-c:a",
+        "copy",
+        "-payload_type",
+        &injection.payload_type.to_string(),
+        "-ssrc",
+        &injection.ssrc.to_string(),
+        "-f",
+        "rtp",
+        &format!("rtp://127.0.0.1:{port}?rtcpport={port}&pkt_size=1200"),
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+/// Starts playing a clip into `playback.channel_id`.
+///
+/// The playback must already be registered in `state.soundboard_playbacks`
+/// under `session_id`; it is removed again once the clip has finished.
+pub async fn start_playback(
+    state: &AppState,
+    session_id: Uuid,
+    playback: &SoundboardPlayback,
+    clip: PlayableClip,
+) -> Result<(), String> {
+    let bytes = state
+        .storage
+        .read(&clip.storage_key)
+        .await
+        .map_err(|error| format!("Failed to read sound clip: {error:?}"))?;
+
+    let injection = state
+        .media
+        .create_soundboard_injection(session_id, playback.channel_id)
+        .await?;
+
+    broadcast_media_signal_to_voice_channel(
+        state,
+        playback.channel_id,
+        serde_json::json!({
+            "action": "new_producer",
+            "producer_id": injection.producer_id,
+            "kind": "audio",
+            "source": ProducerSource::Soundboard.as_str(),
+            "routing_mode": "sfu",
+            "username": playback.username,
+        }),
+        None,
+    )
+    .await;
+
+    tokio::spawn(run_playback(
+        state.clone(),
+        session_id,
+        injection,
+        bytes,
+        Duration::from_millis(clip.duration_ms),
+    ));
+    Ok(())
+}
+
+async fn run_playback(
+    state: AppState,
+    session_id: Uuid,
+    injection: SoundboardInjection,
+    bytes: Vec<u8>,
+    duration: Duration,
+) {
+    sleep(CONSUME_LEAD_TIME).await;
+
+    if let Err(error) = send_clip(&state, session_id, &injection, bytes, duration).await {
+        tracing::warn!(
+            session_id = %session_id,
+            error = %error,
+            "Soundboard playback failed"
+        );
+    }
+
+    let closed_producers = state.media.close_soundboard_injection(session_id).await;
+    state.soundboard_playbacks.write().await.remove(&session_id);
+    broadcast_closed_producers(&state, &closed_producers, None).await;
+}
+
+async fn send_clip(
+    state: &AppState,
+    session_id: Uuid,
+    injection: &SoundboardInjection,
+    bytes: Vec<u8>,
+    duration: Duration,
+) -> Result<(), String> {
+    let mut ffmpeg = Command::new(&state.config.media.ffmpeg_bin)
+        .args(ffmpeg_rtp_args(injection))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|error| format!("Failed to start ffmpeg: {error}"))?;
+
+    if let Some(mut stdin) = ffmpeg.stdin.take() {
+        tokio::spawn(async move {
+            let _ = stdin.write_all(&bytes).await;
+        });
+    }
+
+    if let Some(stderr) = ffmpeg.stderr.take() {
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                tracing::warn!(session_id = %session_id, output = %line, "ffmpeg soundboard error");
+            }
+        });
+    }
+
+    match timeout(duration + PLAYBACK_GRACE, ffmpeg.wait()).await {
+        Ok(Ok(status)) if status.success() => Ok(()),
+        Ok(Ok(status)) => Err(format!("ffmpeg exited with {status}")),
+        Ok(Err(error)) => Err(format!("Failed to wait for ffmpeg: {error}")),
+        Err(_) => {
+            let _ = ffmpeg.kill().await;
+            Err("ffmpeg did not finish the clip in time".into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rtp_args_copy_opus_to_the_muxed_loopback_port() {
+        let injection = SoundboardInjection {
+            producer_id: "producer".into(),
+            rtp_port: 41000,
+            ssrc: 1234,
+            payload_type: 100,
+        };
+
+        let args = ffmpeg_rtp_args(&injection).join(" ");
+
+        assert!(args.contains("-re -i pipe:0"));
+        assert!(args.contains("-c:a copy"));
+        assert!(args.contains("-payload_type 100 -ssrc 1234"));
+        assert!(args.ends_with("rtp://127.0.0.1:41000?rtcpport=41000&pkt_size=1200"));
+    }
+}
@@ -1,9 +1,16 @@
 use async_trait::async_trait;
+use axum::body::Bytes;
+use futures_util::StreamExt;
+use std::io::SeekFrom;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
 
 use crate::errors::AppError;
 
-use super::StorageBackend;
+use super::{ByteStream, StorageBackend};
+
+const STREAM_CHUNK_BYTES: usize = 64 * 1024;
 
 #[derive(Debug, Clone)]
 pub struct LocalStorage {
@@ -65,6 +72,21 @@ impl StorageBackend for LocalStorage {
             .map_err(|error| AppError::Internal(format!("Failed to write storage object: {error}")))
     }
 
+    async fn put_file(&self, key: &str, path: &Path, _content_type: &str) -> Result<(), AppError> {
+        let destination = self.resolve_path(key)?;
+
+        if let Some(parent) = destination.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|error| {
+                AppError::Internal(format!("Failed to create storage directory: {error}"))
+            })?;
+        }
+
+        tokio::fs::copy(path, &destination)
+            .await
+            .map(|_| ())
+            .map_err(|error| AppError::Internal(format!("Failed to write storage object: {error}")))
+    }
+
     async fn read(&self, key: &str) -> Result<Vec<u8>, AppError> {
         let path = self.resolve_path(key)?;
         tokio::fs::read(&path).await.map_err(|error| {
@@ -75,6 +97,56 @@ impl StorageBackend for LocalStorage {
         })
     }
 
+    async fn size(&self, key: &str) -> Result<u64, AppError> {
+        let path = self.resolve_path(key)?;
+        let metadata = tokio::fs::metadata(&path).await.map_err(|error| {
+            AppError::Internal(format!(
+                "Failed to stat storage object (key: '{key}', path: '{}'): {error}",
+                path.display(),
+            ))
+        })?;
+        Ok(metadata.len())
+    }
+
+    async fn read_stream(
+        &self,
+        key: &str,
+        range: Option<Range<u64>>,
+    ) -> Result<ByteStream, AppError> {
+        let path = self.resolve_path(key)?;
+        let open_error = |error: std::io::Error| {
+            AppError::Internal(format!(
+                "Failed to open storage object (key: '{key}', path: '{}'): {error}",
+                path.display(),
+            ))
+        };
+
+        let mut file = tokio::fs::File::open(&path).await.map_err(open_error)?;
+        let len = match range {
+            Some(range) => {
+                file.seek(SeekFrom::Start(range.start))
+                    .await
+                    .map_err(open_error)?;
+                range.end.saturating_sub(range.start)
+            }
+            None => u64::MAX,
+        };
+
+        let reader = file.take(len);
+        Ok(
+            futures_util::stream::try_unfold(reader, |mut reader| async move {
+                let mut chunk = vec![0; STREAM_CHUNK_BYTES];
+                let read = reader.read(&mut chunk).await?;
+                if read == 0 {
+                    return Ok(None);
+                }
+                chunk.truncate(read);
+                Ok(Some((Bytes::from(chunk), reader)))
+            })
+            .boxed(),
+        )
+    }
+
     async fn delete(&self, key: &str) -> Result<(), AppError> {
         let path = self.resolve_path(key)?;
         match tokio::fs::remove_file(&path).await {
@@ -0,0 +1,476 @@
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::StorageConfig;
+use crate::errors::AppError;
+use crate::uploads::chunk_storage_key;
+
+use super::{create_named_storage_backend, StorageBackend};
+
+/// Subcommand that runs a migration instead of starting the server.
+pub const COMMAND: &str = "migrate-storage";
+
+const USAGE: &str =
+    "usage: yankcord-server migrate-storage --to <local|s3> [--dry-run] [--no-switch]";
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationOptions {
+    /// Backend to copy every stored object to.
+    pub target: String,
+    /// Only check the source and report what a real run would copy.
+    pub dry_run: bool,
+    /// Switch to the target once every object is copied and verified.
+    pub switch_backend: bool,
+}
+
+impl MigrationOptions {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut target = None;
+        let mut dry_run = false;
+        let mut switch_backend = true;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--to" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("--to needs a backend\n{USAGE}"))?;
+                    target = Some(value);
+                }
+                "--dry-run" => dry_run = true,
+                "--no-switch" => switch_backend = false,
+                other => {
+                    if let Some(value) = other.strip_prefix("--to=") {
+                        target = Some(value.to_string());
+                    } else {
+                        return Err(format!("Unknown argument '{other}'\n{USAGE}"));
+                    }
+                }
+            }
+        }
+
+        let target = target
+            .map(|value| value.trim().to_ascii_lowercase())
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| format!("--to is required\n{USAGE}"))?;
+
+        Ok(Self {
+            target,
+            dry_run,
+            switch_backend,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub objects: u64,
+    /// Copied and verified during this run.
+    pub copied: u64,
+    /// Copied by an earlier run and skipped.
+    pub already_copied: u64,
+    /// Would be copied; only counted on dry runs.
+    pub pending: u64,
+    pub failed: u64,
+    pub bytes_copied: u64,
+    pub bytes_pending: u64,
+    /// Stored while the copy ran and not copied yet; the backend is not
+    /// switched while any are left.
+    pub left_behind: u64,
+}
+
+impl MigrationReport {
+    fn is_complete(&self) -> bool {
+        self.failed == 0 && self.copied + self.already_copied == self.objects
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredObject {
+    storage_key: String,
+    /// Recorded SHA-256; staged tus chunks have none and are checked
+    /// against what was read from the source instead.
+    checksum: Option<String>,
+    bytes: i64,
+    mime_type: String,
+}
+
+impl StoredObject {
+    fn is_copied(&self, copied: &HashMap<String, String>) -> bool {
+        match (&self.checksum, copied.get(&self.storage_key)) {
+            (Some(checksum), Some(copied)) => checksum == copied,
+            (None, copied) => copied.is_some(),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Resolves which backend the server should use: the configured one,
+/// unless a finished migration switched away from it.
+pub async fn active_backend_name(db: &PgPool, config: &StorageConfig) -> Result<String, AppError> {
+    let configured = config.backend.to_ascii_lowercase();
+    let switched: Option<String> = sqlx::query_scalar(
+        "SELECT target_backend FROM storage_backend_switch WHERE source_backend = $1",
+    )
+    .bind(&configured)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(switched.unwrap_or(configured))
+}
+
+/// Copies every object referenced by `media_assets`, plus the staged chunks
+/// of unfinished tus uploads, from the active backend to `--to`, verifying
+/// each copy against its recorded SHA-256. Objects are recorded as they
+/// finish, so rerunning after an interruption or failures only copies what
+/// is left. Before switching, everything is checked again with uploads
+/// blocked, and the switch is refused if anything stored meanwhile is
+/// missing from the target. The server only picks up a switch when it
+/// restarts, so the run that switches must be made with it stopped.
+pub async fn run(
+    db: &PgPool,
+    config: &StorageConfig,
+    options: &MigrationOptions,
+) -> Result<MigrationReport, AppError> {
+    let source_name = active_backend_name(db, config).await?;
+    if source_name == options.target {
+        return Err(AppError::BadRequest(format!(
+            "Storage is already using the '{source_name}' backend"
+        )));
+    }
+    let source = create_named_storage_backend(&source_name, config).await?;
+    let target = create_named_storage_backend(&options.target, config).await?;
+
+    tracing::info!(
+        source = %source_name,
+        target = %options.target,
+        dry_run = options.dry_run,
+        "Starting storage migration"
+    );
+
+    let report = migrate_objects(db, source.as_ref(), target.as_ref(), options).await?;
+
+    tracing::info!(
+        source = %source_name,
+        target = %options.target,
+        objects = report.objects,
+        copied = report.copied,
+        already_copied = report.already_copied,
+        pending = report.pending,
+        failed = report.failed,
+        bytes_copied = report.bytes_copied,
+        bytes_pending = report.bytes_pending,
+        "Storage migration finished"
+    );
+
+    if options.dry_run || !options.switch_backend {
+        return Ok(report);
+    }
+    if !report.is_complete() {
+        tracing::warn!(
+            failed = report.failed,
+            "Not switching storage backend until every object is copied; run the migration again"
+        );
+        return Ok(report);
+    }
+
+    let mut report = report;
+    report.left_behind = switch_backend(db, config, &options.target).await?;
+    if report.left_behind > 0 {
+        tracing::warn!(
+            left_behind = report.left_behind,
+            "Not switching storage backend: objects were stored while copying; stop the server and run the migration again"
+        );
+        return Ok(report);
+    }
+    tracing::info!(
+        target = %options.target,
+        "Switched storage backend; restart the server and set STORAGE_BACKEND to match"
+    );
+
+    Ok(report)
+}
+
+async fn migrate_objects(
+    db: &PgPool,
+    source: &dyn StorageBackend,
+    target: &dyn StorageBackend,
+    options: &MigrationOptions,
+) -> Result<MigrationReport, AppError> {
+    let mut conn = db.acquire().await?;
+    let objects = stored_objects(&mut conn).await?;
+    let copied = copied_objects(&mut conn, &options.target).await?;
+    drop(conn);
+
+    let mut report = MigrationReport {
+        objects: objects.len() as u64,
+        ..Default::default()
+    };
+    let mut last_progress = Instant::now();
+
+    for (index, object) in objects.iter().enumerate() {
+        if last_progress.elapsed() >= PROGRESS_INTERVAL {
+            tracing::info!(
+                processed = index,
+                objects = report.objects,
+                copied = report.copied,
+                failed = report.failed,
+                bytes_copied = report.bytes_copied,
+                "Storage migration progress"
+            );
+            last_progress = Instant::now();
+        }
+
+        if object.is_copied(&copied) {
+            report.already_copied += 1;
+            continue;
+        }
+
+        if options.dry_run {
+            match source.size(&object.storage_key).await {
+                Ok(_) => {
+                    report.pending += 1;
+                    report.bytes_pending += object.bytes.max(0) as u64;
+                }
+                Err(error) => {
+                    report.failed += 1;
+                    tracing::warn!(
+                        storage_key = %object.storage_key,
+                        error = ?error,
+                        "Source object is unreadable"
+                    );
+                }
+            }
+            continue;
+        }
+
+        match copy_object(source, target, object).await {
+            Ok(checksum) => {
+                sqlx::query(
+                    "INSERT INTO storage_migration_objects (target_backend, storage_key, checksum, bytes)
+                     VALUES ($1, $2, $3, $4)
+                     ON CONFLICT (target_backend, storage_key)
+                     DO UPDATE SET checksum = EXCLUDED.checksum, bytes = EXCLUDED.bytes, copied_at = now()",
+                )
+                .bind(&options.target)
+                .bind(&object.storage_key)
+                .bind(&checksum)
+                .bind(object.bytes)
+                .execute(db)
+                .await?;
+                report.copied += 1;
+                report.bytes_copied += object.bytes.max(0) as u64;
+            }
+            Err(error) => {
+                report.failed += 1;
+                tracing::warn!(
+                    storage_key = %object.storage_key,
+                    error = ?error,
+                    "Failed to migrate storage object"
+                );
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Every object the target needs: the originals and derivatives in
+/// `media_assets`, and the chunks staged by unfinished tus uploads, which
+/// are resumed from the new backend once the server switches.
+async fn stored_objects(conn: &mut PgConnection) -> Result<Vec<StoredObject>, AppError> {
+    let mut objects: Vec<StoredObject> = sqlx::query_as(
+        "SELECT DISTINCT ON (storage_key) storage_key, checksum, bytes, mime_type
+         FROM media_assets
+         ORDER BY storage_key",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let uploads: Vec<(Uuid, i64, Vec<i64>)> = sqlx::query_as(
+        "SELECT id, upload_offset, chunk_offsets
+         FROM resumable_uploads
+         WHERE media_id IS NULL AND expires_at > now()
+         ORDER BY id",
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    for (upload_id, upload_offset, chunk_offsets) in uploads {
+        let chunk_ends = chunk_offsets
+            .iter()
+            .skip(1)
+            .copied()
+            .chain(std::iter::once(upload_offset));
+        for (offset, end) in chunk_offsets.iter().zip(chunk_ends) {
+            objects.push(StoredObject {
+                storage_key: chunk_storage_key(upload_id, *offset),
+                checksum: None,
+                bytes: end - offset,
+                mime_type: "application/octet-stream".into(),
+            });
+        }
+    }
+
+    Ok(objects)
+}
+
+async fn copied_objects(
+    conn: &mut PgConnection,
+    target: &str,
+) -> Result<HashMap<String, String>, AppError> {
+    Ok(sqlx::query_as::<_, (String, String)>(
+        "SELECT storage_key, checksum FROM storage_migration_objects WHERE target_backend = $1",
+    )
+    .bind(target)
+    .fetch_all(conn)
+    .await?
+    .into_iter()
+    .collect())
+}
+
+/// Copies one object and reads it back from the target, so a copy only
+/// counts once the target returns the exact bytes that were recorded.
+/// Returns the checksum of the copied bytes.
+async fn copy_object(
+    source: &dyn StorageBackend,
+    target: &dyn StorageBackend,
+    object: &StoredObject,
+) -> Result<String, AppError> {
+    let bytes = source.read(&object.storage_key).await?;
+    let checksum = sha256_hex(&bytes);
+    if object
+        .checksum
+        .as_ref()
+        .is_some_and(|recorded| *recorded != checksum)
+    {
+        return Err(AppError::Internal(
+            "Source object does not match its recorded checksum".into(),
+        ));
+    }
+
+    target
+        .put(&object.storage_key, bytes, &object.mime_type)
+        .await?;
+
+    let copied = target.read(&object.storage_key).await?;
+    if sha256_hex(&copied) != checksum {
+        return Err(AppError::Internal(
+            "Copied object does not match its recorded checksum".into(),
+        ));
+    }
+
+    Ok(checksum)
+}
+
+/// Switches to `target` unless something stored since the copy started is
+/// missing from it. The check and the switch share a transaction that
+/// blocks new uploads, so nothing can slip in between them. Returns how
+/// many objects were left behind; the backend only switches when none are.
+async fn switch_backend(
+    db: &PgPool,
+    config: &StorageConfig,
+    target: &str,
+) -> Result<u64, AppError> {
+    let mut tx = db.begin().await?;
+    sqlx::query("LOCK TABLE media_assets, resumable_uploads IN SHARE MODE")
+        .execute(&mut *tx)
+        .await?;
+
+    let copied = copied_objects(&mut tx, target).await?;
+    let left_behind = stored_objects(&mut tx)
+        .await?
+        .iter()
+        .filter(|object| !object.is_copied(&copied))
+        .count() as u64;
+    if left_behind > 0 {
+        return Ok(left_behind);
+    }
+
+    let configured = config.backend.to_ascii_lowercase();
+    if configured == target {
+        sqlx::query("DELETE FROM storage_backend_switch")
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        return Ok(0);
+    }
+
+    sqlx::query(
+        "INSERT INTO storage_backend_switch (singleton, source_backend, target_backend)
+         VALUES (TRUE, $1, $2)
+         ON CONFLICT (singleton)
+         DO UPDATE SET source_backend = EXCLUDED.source_backend,
+                       target_backend = EXCLUDED.target_backend,
+                       switched_at = now()",
+    )
+    .bind(configured)
+    .bind(target)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(0)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(bytes);
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_migration_options() {
+        assert_eq!(
+            MigrationOptions::parse(args(&["--to", "S3", "--dry-run"])).unwrap(),
+            MigrationOptions {
+                target: "s3".into(),
+                dry_run: true,
+                switch_backend: true,
+            }
+        );
+        assert_eq!(
+            MigrationOptions::parse(args(&["--no-switch", "--to=local"])).unwrap(),
+            MigrationOptions {
+                target: "local".into(),
+                dry_run: false,
+                switch_backend: false,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_missing_target_and_unknown_arguments() {
+        assert!(MigrationOptions::parse(args(&[])).is_err());
+        assert!(MigrationOptions::parse(args(&["--to"])).is_err());
+        assert!(MigrationOptions::parse(args(&["--to", "s3", "--force"])).is_err());
+    }
+
+    #[test]
+    fn staged_chunks_count_as_copied_without_a_checksum() {
+        let copied = HashMap::from([("blobs/ab".to_string(), "ab".to_string())]);
+        let object = |storage_key: &str, checksum: Option<&str>| StoredObject {
+            storage_key: storage_key.into(),
+            checksum: checksum.map(str::to_string),
+            bytes: 1,
+            mime_type: "application/octet-stream".into(),
+        };
+
+        assert!(object("blobs/ab", Some("ab")).is_copied(&copied));
+        assert!(!object("blobs/ab", Some("cd")).is_copied(&copied));
+        assert!(object("blobs/ab", None).is_copied(&copied));
+        assert!(!object("uploads/x/0", None).is_copied(&copied));
+    }
+}
@@ -1,7 +1,12 @@
 mod local;
+pub mod migrate;
 mod s3;
 
 use async_trait::async_trait;
+use axum::body::Bytes;
+use futures_util::stream::BoxStream;
+use std::ops::Range;
+use std::path::Path;
 use std::sync::Arc;
 
 use crate::config::StorageConfig;
@@ -10,17 +15,33 @@ use crate::errors::AppError;
 pub use local::LocalStorage;
 pub use s3::S3Storage;
 
+/// Chunks of a stored object, read as the response body is sent.
+pub type ByteStream = BoxStream<'static, std::io::Result<Bytes>>;
+
 #[async_trait]
 pub trait StorageBackend: Send + Sync {
     async fn put(&self, key: &str, bytes: Vec<u8>, content_type: &str) -> Result<(), AppError>;
+    /// Stores the file at `path` without reading it into memory.
+    async fn put_file(&self, key: &str, path: &Path, content_type: &str) -> Result<(), AppError>;
     async fn read(&self, key: &str) -> Result<Vec<u8>, AppError>;
+    /// Length of a stored object in bytes.
+    async fn size(&self, key: &str) -> Result<u64, AppError>;
+    /// Streams an object, or only the byte range `range` of it.
+    async fn read_stream(
+        &self,
+        key: &str,
+        range: Option<Range<u64>>,
+    ) -> Result<ByteStream, AppError>;
     async fn delete(&self, key: &str) -> Result<(), AppError>;
 }
 
-pub async fn create_storage_backend(
+/// Creates the backend named `backend` (`local` or `s3`) from the storage
+/// configuration, whichever backend that configuration selects.
+pub async fn create_named_storage_backend(
+    backend: &str,
     config: &StorageConfig,
 ) -> Result<Arc<dyn StorageBackend>, AppError> {
-    match config.backend.to_ascii_lowercase().as_str() {
+    match backend.to_ascii_lowercase().as_str() {
         "local" => {
             let backend = LocalStorage::new(config.local_root.clone()).await?;
             Ok(Arc::new(backend))
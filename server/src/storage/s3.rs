@@ -1,9 +1,11 @@
 use async_trait::async_trait;
+use std::ops::Range;
+use std::path::Path;
 
 use crate::config::StorageConfig;
 use crate::errors::AppError;
 
-use super::StorageBackend;
+use super::{ByteStream, StorageBackend};
 
 #[derive(Debug, Clone)]
 pub struct S3Storage {
@@ -37,10 +39,31 @@ impl StorageBackend for S3Storage {
         Err(self.not_implemented_error())
     }
 
+    async fn put_file(
+        &self,
+        _key: &str,
+        _path: &Path,
+        _content_type: &str,
+    ) -> Result<(), AppError> {
+        Err(self.not_implemented_error())
+    }
+
     async fn read(&self, _key: &str) -> Result<Vec<u8>, AppError> {
         Err(self.not_implemented_error())
     }
 
+    async fn size(&self, _key: &str) -> Result<u64, AppError> {
+        Err(self.not_implemented_error())
+    }
+
+    async fn read_stream(
+        &self,
+        _key: &str,
+        _range: Option<Range<u64>>,
+    ) -> Result<ByteStream, AppError> {
+        Err(self.not_implemented_error())
+    }
+
     async fn delete(&self, _key: &str) -> Result<(), AppError> {
         Err(self.not_implemented_error())
     }
@@ -0,0 +1,396 @@
+mod peer_policy;
+mod tcp;
+
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha1::Sha1;
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::Semaphore;
+use turn::auth::{generate_auth_key, AuthHandler};
+use turn::relay::relay_range::RelayAddressGeneratorRanges;
+use turn::relay::relay_static::RelayAddressGeneratorStatic;
+use turn::relay::RelayAddressGenerator;
+use turn::server::config::{ConnConfig, ServerConfig};
+use turn::server::Server;
+use uuid::Uuid;
+use webrtc_util::vnet::net::Net;
+
+use crate::config::AppConfig;
+
+use peer_policy::{PeerPolicy, PeerPolicyConn};
+
+type HmacSha1 = Hmac<Sha1>;
+
+const RELAY_PORT_MAX_RETRIES: u16 = 10;
+const ACCEPT_BACKOFF_MIN: Duration = Duration::from_millis(10);
+const ACCEPT_BACKOFF_MAX: Duration = Duration::from_secs(1);
+/// Ports mediasoup picks from when `RTC_MIN_PORT`/`RTC_MAX_PORT` are unset.
+const MEDIASOUP_DEFAULT_PORTS: RangeInclusive<u16> = 10000..=59999;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TurnCredentials {
+    pub username: String,
+    pub credential: String,
+    pub ttl_seconds: u64,
+    pub expires_at: u64,
+    pub ice_servers: Vec<IceServer>,
+}
+
+/// Keeps the embedded TURN listeners alive for the lifetime of the process.
+pub struct TurnServer {
+    _udp: Server,
+}
+
+/// Mints time-limited TURN credentials for a user.
+///
+/// Follows the TURN REST API convention: the username is
+/// `<expiry unix seconds>:<user id>` and the password is the base64
+/// HMAC-SHA1 of the username keyed with the JWT secret, so the TURN server
+/// can verify credentials without any shared state.
+pub fn mint_credentials(config: &AppConfig, user_id: Uuid) -> Option<TurnCredentials> {
+    if !config.turn.enabled {
+        return None;
+    }
+
+    let ttl_seconds = config.turn.credential_ttl_seconds;
+    let expires_at = unix_now() + ttl_seconds;
+    let username = format!("{expires_at}:{user_id}");
+    let credential = credential_for(&config.jwt.secret, &username);
+
+    let ice_servers = vec![
+        IceServer {
+            urls: vec![format!("stun:{}", public_address(config))],
+            username: None,
+            credential: None,
+        },
+        IceServer {
+            urls: turn_urls(config),
+            username: Some(username.clone()),
+            credential: Some(credential.clone()),
+        },
+    ];
+
+    Some(TurnCredentials {
+        username,
+        credential,
+        ttl_seconds,
+        expires_at,
+        ice_servers,
+    })
+}
+
+/// ICE servers to hand to a client alongside a new WebRTC transport. Empty
+/// when the embedded TURN server is disabled.
+pub fn ice_servers_for_user(config: &AppConfig, user_id: Uuid) -> Vec<IceServer> {
+    mint_credentials(config, user_id)
+        .map(|credentials| credentials.ice_servers)
+        .unwrap_or_default()
+}
+
+fn turn_urls(config: &AppConfig) -> Vec<String> {
+    let address = public_address(config);
+    let mut urls = vec![format!("turn:{address}?transport=udp")];
+    if config.turn.enable_tcp {
+        urls.push(format!("turn:{address}?transport=tcp"));
+    }
+    urls
+}
+
+fn public_address(config: &AppConfig) -> String {
+    let host = config
+        .turn
+        .public_host
+        .clone()
+        .or_else(|| config.media.announced_ip.clone())
+        .unwrap_or_else(|| config.turn.listen_ip.clone());
+
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{host}]:{}", config.turn.port)
+    } else {
+        format!("{host}:{}", config.turn.port)
+    }
+}
+
+fn credential_for(secret: &str, username: &str) -> String {
+    let mut mac =
+        HmacSha1::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(username.as_bytes());
+    base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+fn credential_expiry(username: &str) -> Option<u64> {
+    let (expires_at, _user_id) = username.split_once(':')?;
+    expires_at.parse().ok()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+struct TimeLimitedAuthHandler {
+    secret: String,
+}
+
+impl AuthHandler for TimeLimitedAuthHandler {
+    fn auth_handle(
+        &self,
+        username: &str,
+        realm: &str,
+        src_addr: SocketAddr,
+    ) -> Result<Vec<u8>, turn::Error> {
+        let Some(expires_at) = credential_expiry(username) else {
+            tracing::debug!(src_addr = %src_addr, "Rejected malformed TURN username");
+            return Err(turn::Error::Other("Malformed TURN username".into()));
+        };
+
+        if expires_at < unix_now() {
+            tracing::debug!(src_addr = %src_addr, "Rejected expired TURN credentials");
+            return Err(turn::Error::Other("TURN credentials expired".into()));
+        }
+
+        let password = credential_for(&self.secret, username);
+        Ok(generate_auth_key(username, realm, &password))
+    }
+}
+
+/// Starts the embedded TURN server on UDP and, optionally, TCP.
+///
+/// Returns `None` when TURN is disabled in the configuration.
+pub async fn start(config: &AppConfig) -> Result<Option<TurnServer>, String> {
+    if !config.turn.enabled {
+        return Ok(None);
+    }
+
+    let listen_ip = IpAddr::from_str(&config.turn.listen_ip).map_err(|error| {
+        format!(
+            "Invalid TURN_LISTEN_IP '{}': {error}",
+            config.turn.listen_ip
+        )
+    })?;
+    let listen_addr = SocketAddr::new(listen_ip, config.turn.port);
+    let relay_ip = relay_ip(config, listen_ip)?;
+    let auth_handler: Arc<dyn AuthHandler + Send + Sync> = Arc::new(TimeLimitedAuthHandler {
+        secret: config.jwt.secret.clone(),
+    });
+    let peer_policy = Arc::new(peer_policy(config, listen_ip, relay_ip));
+
+    let udp_socket = UdpSocket::bind(listen_addr)
+        .await
+        .map_err(|error| format!("Failed to bind TURN UDP socket on {listen_addr}: {error}"))?;
+
+    let udp = Server::new(ServerConfig {
+        conn_configs: vec![ConnConfig {
+            conn: Arc::new(PeerPolicyConn::new(
+                Arc::new(udp_socket),
+                peer_policy.clone(),
+            )),
+            relay_addr_generator: relay_address_generator(config, relay_ip),
+        }],
+        realm: config.turn.realm.clone(),
+        auth_handler: auth_handler.clone(),
+        channel_bind_timeout: Duration::from_secs(0),
+        alloc_close_notify: None,
+    })
+    .await
+    .map_err(|error| format!("Failed to start TURN UDP server: {error}"))?;
+
+    if config.turn.enable_tcp {
+        let listener = TcpListener::bind(listen_addr).await.map_err(|error| {
+            format!("Failed to bind TURN TCP listener on {listen_addr}: {error}")
+        })?;
+        let realm = config.turn.realm.clone();
+        let config = config.clone();
+        let connection_slots = Arc::new(Semaphore::new(config.turn.max_tcp_connections));
+
+        tokio::spawn(async move {
+            let mut accept_backoff = ACCEPT_BACKOFF_MIN;
+            loop {
+                let (stream, remote_addr) = match listener.accept().await {
+                    Ok(accepted) => {
+                        accept_backoff = ACCEPT_BACKOFF_MIN;
+                        accepted
+                    }
+                    Err(error) => {
+                        // Errors such as EMFILE persist until descriptors
+                        // free up, so retrying at once would spin.
+                        tracing::warn!(
+                            error = %error,
+                            retry_in_ms = accept_backoff.as_millis() as u64,
+                            "Failed to accept TURN TCP connection"
+                        );
+                        tokio::time::sleep(accept_backoff).await;
+                        accept_backoff = (accept_backoff * 2).min(ACCEPT_BACKOFF_MAX);
+                        continue;
+                    }
+                };
+
+                let Ok(slot) = connection_slots.clone().try_acquire_owned() else {
+                    tracing::warn!(
+                        remote_addr = %remote_addr,
+                        max_tcp_connections = config.turn.max_tcp_connections,
+                        "Rejected TURN TCP connection; too many open sessions"
+                    );
+                    continue;
+                };
+
+                tcp::serve_connection(
+                    stream,
+                    remote_addr,
+                    slot,
+                    realm.clone(),
+                    auth_handler.clone(),
+                    peer_policy.clone(),
+                    relay_address_generator(&config, relay_ip),
+                );
+            }
+        });
+    }
+
+    tracing::info!(
+        listen_addr = %listen_addr,
+        relay_ip = %relay_ip,
+        tcp = config.turn.enable_tcp,
+        "Embedded TURN server started"
+    );
+
+    Ok(Some(TurnServer { _udp: udp }))
+}
+
+fn relay_ip(config: &AppConfig, listen_ip: IpAddr) -> Result<IpAddr, String> {
+    let configured = config
+        .turn
+        .relay_ip
+        .as_deref()
+        .or(config.media.announced_ip.as_deref())
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+
+    match configured {
+        Some(value) => IpAddr::from_str(value)
+            .map_err(|error| format!("Invalid TURN relay IP '{value}': {error}")),
+        None => Ok(listen_ip),
+    }
+}
+
+/// The server's own addresses and the mediasoup ports TURN clients may
+/// relay to on them. Clients reach the SFU through the announced media IP,
+/// which may be private on LAN deployments.
+fn peer_policy(config: &AppConfig, listen_ip: IpAddr, relay_ip: IpAddr) -> PeerPolicy {
+    let configured = [
+        config.media.announced_ip.as_deref(),
+        config.media.native_rtp_announced_ip.as_deref(),
+        config.turn.public_host.as_deref(),
+        Some(config.media.webrtc_listen_ip.as_str()),
+    ];
+    let mut own_ips: Vec<IpAddr> = configured
+        .into_iter()
+        .flatten()
+        .filter_map(|value| IpAddr::from_str(value.trim()).ok())
+        .chain([listen_ip, relay_ip])
+        .filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+        .collect();
+    own_ips.sort_unstable();
+    own_ips.dedup();
+
+    let mut media_ports = vec![
+        match (config.media.rtc_min_port, config.media.rtc_max_port) {
+            (Some(min_port), Some(max_port)) => min_port..=max_port,
+            _ => MEDIASOUP_DEFAULT_PORTS,
+        },
+    ];
+    if config.media.webrtc_server_enabled {
+        let first = config.media.webrtc_server_port;
+        let last = first.saturating_add(config.media.worker_count.saturating_sub(1) as u16);
+        media_ports.push(first..=last);
+    }
+
+    PeerPolicy {
+        own_ips,
+        media_ports,
+    }
+}
+
+fn relay_address_generator(
+    config: &AppConfig,
+    relay_ip: IpAddr,
+) -> Box<dyn RelayAddressGenerator + Send + Sync> {
+    let net = Arc::new(Net::new(None));
+    let address = config.turn.listen_ip.clone();
+
+    match (config.turn.relay_min_port, config.turn.relay_max_port) {
+        (Some(min_port), Some(max_port)) => Box::new(RelayAddressGeneratorRanges {
+            relay_address: relay_ip,
+            min_port,
+            max_port,
+            max_retries: RELAY_PORT_MAX_RETRIES,
+            address,
+            net,
+        }),
+        _ => Box::new(RelayAddressGeneratorStatic {
+            relay_address: relay_ip,
+            address,
+            net,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credential_expiry_reads_unix_prefix() {
+        let user_id = Uuid::new_v4();
+
+        assert_eq!(
+            credential_expiry(&format!("1700000000:{user_id}")),
+            Some(1_700_000_000)
+        );
+        assert_eq!(credential_expiry("not-a-timestamp"), None);
+        assert_eq!(credential_expiry("soon:user"), None);
+    }
+
+    #[test]
+    fn credentials_are_bound_to_username_and_secret() {
+        let username = "1700000000:user";
+        let credential = credential_for("secret", username);
+
+        assert_eq!(credential, credential_for("secret", username));
+        assert_ne!(credential, credential_for("other-secret", username));
+        assert_ne!(credential, credential_for("secret", "1700000001:user"));
+    }
+
+    #[test]
+    fn expired_credentials_are_rejected() {
+        let handler = TimeLimitedAuthHandler {
+            secret: "secret".to_string(),
+        };
+        let src_addr: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let expired = format!("{}:user", unix_now() - 1);
+        let valid = format!("{}:user", unix_now() + 60);
+
+        assert!(handler.auth_handle(&expired, "yankcord", src_addr).is_err());
+        assert_eq!(
+            handler.auth_handle(&valid, "yankcord", src_addr).unwrap(),
+            generate_auth_key(&valid, "yankcord", &credential_for("secret", &valid))
+        );
+    }
+}
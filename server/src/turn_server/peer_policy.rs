@@ -0,0 +1,371 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use webrtc_util::Conn;
+
+const STUN_HEADER_LEN: usize = 20;
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+const METHOD_SEND: u16 = 0x006;
+const METHOD_CREATE_PERMISSION: u16 = 0x008;
+const METHOD_CHANNEL_BIND: u16 = 0x009;
+const ATTR_XOR_PEER_ADDRESS: u16 = 0x0012;
+
+/// Which peers TURN clients may relay to.
+#[derive(Debug, Clone, Default)]
+pub(super) struct PeerPolicy {
+    /// The server's own addresses (announced, relay and listen IPs). Only
+    /// the mediasoup ports on them are reachable, so that clients behind
+    /// restrictive networks can relay to the SFU but not to anything else
+    /// the server exposes.
+    pub own_ips: Vec<IpAddr>,
+    pub media_ports: Vec<RangeInclusive<u16>>,
+}
+
+impl PeerPolicy {
+    /// Whether a client may relay to `peer`. A permission (`port` is
+    /// `None`) covers every port of an IP, so for the server's own IPs the
+    /// port is checked on each ChannelBind and Send instead.
+    fn denies(&self, ip: IpAddr, port: Option<u16>) -> bool {
+        let ip = unmapped(ip);
+        if self.own_ips.iter().any(|own| unmapped(*own) == ip) {
+            return port
+                .is_some_and(|port| !self.media_ports.iter().any(|range| range.contains(&port)));
+        }
+        is_denied_peer(ip)
+    }
+}
+
+fn unmapped(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    }
+}
+
+/// Peers a TURN client may never relay to: loopback, RFC 1918, shared
+/// (CGNAT), link-local, "this network", multicast, broadcast, unique local,
+/// unspecified and NAT64 addresses. Without this any credentialed user
+/// could reach services bound on the server's loopback or LAN, such as the
+/// native sender's plain transports. IPv4-mapped IPv6 addresses are checked
+/// as the IPv4 address they carry.
+fn is_denied_peer(ip: IpAddr) -> bool {
+    match unmapped(ip) {
+        IpAddr::V4(v4) => {
+            let [first, second, ..] = v4.octets();
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_multicast()
+                || v4.is_broadcast()
+                || first == 0
+                || (first == 100 && second & 0xc0 == 64)
+        }
+        IpAddr::V6(v6) => {
+            let segments = v6.segments();
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || segments[0] & 0xfe00 == 0xfc00
+                || segments[0] & 0xffc0 == 0xfe80
+                || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+        }
+    }
+}
+
+/// Returns the first XOR-PEER-ADDRESS of a CreatePermission or ChannelBind
+/// request, or of a Send indication, that names a denied peer.
+fn denied_peer_in_request(packet: &[u8], policy: &PeerPolicy) -> Option<SocketAddr> {
+    if packet.len() < STUN_HEADER_LEN || packet[0] & 0xC0 != 0 {
+        return None;
+    }
+    if u32::from_be_bytes([packet[4], packet[5], packet[6], packet[7]]) != STUN_MAGIC_COOKIE {
+        return None;
+    }
+
+    let message_type = u16::from_be_bytes([packet[0], packet[1]]);
+    let class = message_type & 0x0110;
+    let method =
+        (message_type & 0x000F) | ((message_type & 0x00E0) >> 1) | ((message_type & 0x3E00) >> 2);
+    let checks_port = match (class, method) {
+        (0x0000, METHOD_CREATE_PERMISSION) => false,
+        (0x0000, METHOD_CHANNEL_BIND) | (0x0010, METHOD_SEND) => true,
+        _ => return None,
+    };
+
+    let length = usize::from(u16::from_be_bytes([packet[2], packet[3]]));
+    let end = (STUN_HEADER_LEN + length).min(packet.len());
+    let mut offset = STUN_HEADER_LEN;
+    while offset + 4 <= end {
+        let attr_type = u16::from_be_bytes([packet[offset], packet[offset + 1]]);
+        let attr_len = usize::from(u16::from_be_bytes([packet[offset + 2], packet[offset + 3]]));
+        let value_start = offset + 4;
+        let value_end = value_start + attr_len;
+        if value_end > end {
+            break;
+        }
+
+        if attr_type == ATTR_XOR_PEER_ADDRESS {
+            if let Some(peer) = xor_address(&packet[value_start..value_end], packet) {
+                if policy.denies(peer.ip(), checks_port.then_some(peer.port())) {
+                    return Some(peer);
+                }
+            }
+        }
+        offset = value_end.next_multiple_of(4);
+    }
+
+    None
+}
+
+fn xor_address(value: &[u8], packet: &[u8]) -> Option<SocketAddr> {
+    let cookie = STUN_MAGIC_COOKIE.to_be_bytes();
+    let port = u16::from_be_bytes([*value.get(2)?, *value.get(3)?]) ^ 0x2112;
+    let ip = match (value.get(1)?, value.len()) {
+        (0x01, 8) => {
+            let mut octets = [0u8; 4];
+            for (index, octet) in octets.iter_mut().enumerate() {
+                *octet = value[4 + index] ^ cookie[index];
+            }
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        (0x02, 20) => {
+            // IPv6 addresses are XORed with the cookie and transaction id,
+            // which sit back to back in the header.
+            let mut octets = [0u8; 16];
+            for (index, octet) in octets.iter_mut().enumerate() {
+                *octet = value[4 + index] ^ packet[4 + index];
+            }
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// Wraps a TURN listener and drops CreatePermission, ChannelBind and Send
+/// messages for denied peers before the TURN server sees them. Without a
+/// permission the server relays nothing to or from that peer.
+pub(super) struct PeerPolicyConn {
+    inner: Arc<dyn Conn + Send + Sync>,
+    policy: Arc<PeerPolicy>,
+}
+
+impl PeerPolicyConn {
+    pub(super) fn new(inner: Arc<dyn Conn + Send + Sync>, policy: Arc<PeerPolicy>) -> Self {
+        Self { inner, policy }
+    }
+}
+
+#[async_trait]
+impl Conn for PeerPolicyConn {
+    async fn connect(&self, addr: SocketAddr) -> webrtc_util::Result<()> {
+        self.inner.connect(addr).await
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> webrtc_util::Result<usize> {
+        let (len, _) = self.recv_from(buf).await?;
+        Ok(len)
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> webrtc_util::Result<(usize, SocketAddr)> {
+        loop {
+            let (len, src_addr) = self.inner.recv_from(buf).await?;
+            match denied_peer_in_request(&buf[..len], &self.policy) {
+                Some(peer) => tracing::debug!(
+                    src_addr = %src_addr,
+                    peer = %peer,
+                    "Dropped TURN request for a denied peer"
+                ),
+                None => return Ok((len, src_addr)),
+            }
+        }
+    }
+
+    async fn send(&self, buf: &[u8]) -> webrtc_util::Result<usize> {
+        self.inner.send(buf).await
+    }
+
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> webrtc_util::Result<usize> {
+        self.inner.send_to(buf, target).await
+    }
+
+    fn local_addr(&self) -> webrtc_util::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.inner.remote_addr()
+    }
+
+    async fn close(&self) -> webrtc_util::Result<()> {
+        self.inner.close().await
+    }
+
+    fn as_any(&self) -> &(dyn Any + Send + Sync) {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddrV4;
+
+    /// STUN message of `message_type` carrying one XOR-PEER-ADDRESS for
+    /// `peer`.
+    fn request(message_type: u16, peer: SocketAddrV4) -> Vec<u8> {
+        let mut packet = message_type.to_be_bytes().to_vec();
+        packet.extend_from_slice(&[0x00, 0x0C]);
+        packet.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+        packet.extend_from_slice(&[7; 12]);
+        packet.extend_from_slice(&ATTR_XOR_PEER_ADDRESS.to_be_bytes());
+        packet.extend_from_slice(&[0x00, 0x08, 0x00, 0x01]);
+        packet.extend_from_slice(&(peer.port() ^ 0x2112).to_be_bytes());
+        let cookie = STUN_MAGIC_COOKIE.to_be_bytes();
+        for (index, octet) in peer.ip().octets().iter().enumerate() {
+            packet.push(octet ^ cookie[index]);
+        }
+        packet
+    }
+
+    fn create_permission(peer: SocketAddrV4) -> Vec<u8> {
+        request(0x0008, peer)
+    }
+
+    fn channel_bind(peer: SocketAddrV4) -> Vec<u8> {
+        request(0x0009, peer)
+    }
+
+    fn send_indication(peer: SocketAddrV4) -> Vec<u8> {
+        request(0x0016, peer)
+    }
+
+    fn peer(addr: &str) -> SocketAddrV4 {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn denies_internal_and_special_purpose_peers() {
+        for (range, denied) in [
+            ("127.0.0.0/8", "127.0.0.1"),
+            ("10.0.0.0/8", "10.1.2.3"),
+            ("172.16.0.0/12", "172.20.0.5"),
+            ("192.168.0.0/16", "192.168.1.10"),
+            ("169.254.0.0/16", "169.254.169.254"),
+            ("100.64.0.0/10", "100.64.0.1"),
+            ("100.64.0.0/10", "100.127.255.254"),
+            ("0.0.0.0/8", "0.0.0.0"),
+            ("0.0.0.0/8", "0.1.2.3"),
+            ("224.0.0.0/4", "224.0.0.251"),
+            ("224.0.0.0/4", "239.255.255.250"),
+            ("255.255.255.255/32", "255.255.255.255"),
+            ("::1/128", "::1"),
+            ("::/128", "::"),
+            ("fc00::/7", "fd00::1"),
+            ("fe80::/10", "fe80::1"),
+            ("ff00::/8", "ff02::1"),
+            ("64:ff9b::/96", "64:ff9b::a00:1"),
+            ("64:ff9b::/96", "64:ff9b::cb00:710a"),
+            ("::ffff:0:0/96", "::ffff:127.0.0.1"),
+            ("::ffff:0:0/96", "::ffff:10.0.0.1"),
+            ("::ffff:0:0/96", "::ffff:100.64.0.1"),
+        ] {
+            assert!(
+                is_denied_peer(denied.parse().unwrap()),
+                "{denied} ({range})"
+            );
+        }
+
+        for allowed in [
+            "203.0.113.10",
+            "172.32.0.1",
+            "100.63.255.255",
+            "100.128.0.1",
+            "1.0.0.1",
+            "223.255.255.255",
+            "2001:db8::1",
+            "64:ff9b:1::a00:1",
+            "::ffff:203.0.113.10",
+        ] {
+            assert!(!is_denied_peer(allowed.parse().unwrap()), "{allowed}");
+        }
+    }
+
+    #[test]
+    fn own_ips_are_reachable_on_media_ports_only() {
+        let policy = PeerPolicy {
+            own_ips: vec![
+                "192.168.1.10".parse().unwrap(),
+                "203.0.113.5".parse().unwrap(),
+            ],
+            media_ports: vec![40000..=40100, 44444..=44447],
+        };
+
+        for own in ["192.168.1.10", "203.0.113.5", "::ffff:203.0.113.5"] {
+            let ip: IpAddr = own.parse().unwrap();
+            assert!(!policy.denies(ip, None), "{own}");
+            assert!(!policy.denies(ip, Some(40000)), "{own}");
+            assert!(!policy.denies(ip, Some(40100)), "{own}");
+            assert!(!policy.denies(ip, Some(44447)), "{own}");
+            assert!(policy.denies(ip, Some(39999)), "{own}");
+            assert!(policy.denies(ip, Some(22)), "{own}");
+            assert!(policy.denies(ip, Some(3478)), "{own}");
+        }
+
+        // Other private peers stay denied whatever the port.
+        assert!(policy.denies("192.168.1.11".parse().unwrap(), Some(40000)));
+        assert!(!policy.denies("198.51.100.1".parse().unwrap(), Some(22)));
+    }
+
+    #[test]
+    fn finds_denied_peer_in_permission_request() {
+        let policy = PeerPolicy::default();
+        let loopback = create_permission(peer("127.0.0.1:5000"));
+        assert_eq!(
+            denied_peer_in_request(&loopback, &policy),
+            Some("127.0.0.1:5000".parse().unwrap())
+        );
+
+        let public = create_permission(peer("203.0.113.10:5000"));
+        assert_eq!(denied_peer_in_request(&public, &policy), None);
+
+        // Allocate requests are not permission requests.
+        let mut allocate = loopback.clone();
+        allocate[1] = 0x03;
+        assert_eq!(denied_peer_in_request(&allocate, &policy), None);
+    }
+
+    #[test]
+    fn checks_ports_of_own_ips_on_channel_bind_and_send() {
+        let policy = PeerPolicy {
+            own_ips: vec!["203.0.113.5".parse().unwrap()],
+            media_ports: vec![40000..=40100],
+        };
+
+        let ssh = peer("203.0.113.5:22");
+        let media = peer("203.0.113.5:40050");
+        assert_eq!(
+            denied_peer_in_request(&create_permission(ssh), &policy),
+            None
+        );
+        assert_eq!(
+            denied_peer_in_request(&channel_bind(ssh), &policy),
+            Some(ssh.into())
+        );
+        assert_eq!(
+            denied_peer_in_request(&send_indication(ssh), &policy),
+            Some(ssh.into())
+        );
+        assert_eq!(denied_peer_in_request(&channel_bind(media), &policy), None);
+        assert_eq!(
+            denied_peer_in_request(&send_indication(media), &policy),
+            None
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use async_trait::async_trait;
+use std::any::Any;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{Mutex, Notify, OwnedSemaphorePermit};
+use turn::auth::AuthHandler;
+use turn::relay::RelayAddressGenerator;
+use turn::server::config::{ConnConfig, ServerConfig};
+use turn::server::Server;
+use webrtc_util::Conn;
+
+use super::peer_policy::{PeerPolicy, PeerPolicyConn};
+
+const STUN_HEADER_LEN: usize = 20;
+const CHANNEL_DATA_HEADER_LEN: usize = 4;
+
+/// Runs a dedicated TURN server instance for one TCP client.
+///
+/// TURN over TCP (RFC 6062 framing) carries STUN messages and ChannelData
+/// back to back on the stream; `FramedTcpConn` splits them into the
+/// datagrams the TURN server expects and closes the instance once the
+/// client disconnects. `slot` is held until then, which bounds how many
+/// sessions run at once.
+pub(super) fn serve_connection(
+    stream: TcpStream,
+    remote_addr: SocketAddr,
+    slot: OwnedSemaphorePermit,
+    realm: String,
+    auth_handler: Arc<dyn AuthHandler + Send + Sync>,
+    peer_policy: Arc<PeerPolicy>,
+    relay_addr_generator: Box<dyn RelayAddressGenerator + Send + Sync>,
+) {
+    tokio::spawn(async move {
+        let _slot = slot;
+        let local_addr = match stream.local_addr() {
+            Ok(addr) => addr,
+            Err(error) => {
+                tracing::warn!(error = %error, "Failed to read TURN TCP local address");
+                return;
+            }
+        };
+
+        let closed = Arc::new(Notify::new());
+        let (reader, writer) = stream.into_split();
+        let framed = Arc::new(FramedTcpConn {
+            reader: Mutex::new(reader),
+            writer: Mutex::new(writer),
+            local_addr,
+            remote_addr,
+            closed: closed.clone(),
+        });
+        let conn = Arc::new(PeerPolicyConn::new(framed, peer_policy));
+
+        let server = match Server::new(ServerConfig {
+            conn_configs: vec![ConnConfig {
+                conn,
+                relay_addr_generator,
+            }],
+            realm,
+            auth_handler,
+            channel_bind_timeout: Duration::from_secs(0),
+            alloc_close_notify: None,
+        })
+        .await
+        {
+            Ok(server) => server,
+            Err(error) => {
+                tracing::warn!(
+                    remote_addr = %remote_addr,
+                    error = %error,
+                    "Failed to start TURN TCP session"
+                );
+                return;
+            }
+        };
+
+        closed.notified().await;
+        if let Err(error) = server.close().await {
+            tracing::debug!(
+                remote_addr = %remote_addr,
+                error = %error,
+                "Failed to close TURN TCP session"
+            );
+        }
+    });
+}
+
+struct FramedTcpConn {
+    reader: Mutex<OwnedReadHalf>,
+    writer: Mutex<OwnedWriteHalf>,
+    local_addr: SocketAddr,
+    remote_addr: SocketAddr,
+    closed: Arc<Notify>,
+}
+
+impl FramedTcpConn {
+    async fn read_frame(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut reader = self.reader.lock().await;
+
+        let mut header = [0u8; CHANNEL_DATA_HEADER_LEN];
+        reader.read_exact(&mut header).await?;
+        let length = usize::from(u16::from_be_bytes([header[2], header[3]]));
+
+        // ChannelData messages start with 0b01; STUN messages with 0b00.
+        let (frame_len, padded_len) = if header[0] & 0xC0 == 0x40 {
+            let frame_len = CHANNEL_DATA_HEADER_LEN + length;
+            (frame_len, frame_len.next_multiple_of(4))
+        } else {
+            let frame_len = STUN_HEADER_LEN + length;
+            (frame_len, frame_len)
+        };
+
+        if padded_len > buf.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "TURN TCP frame exceeds receive buffer",
+            ));
+        }
+
+        buf[..CHANNEL_DATA_HEADER_LEN].copy_from_slice(&header);
+        reader
+            .read_exact(&mut buf[CHANNEL_DATA_HEADER_LEN..padded_len])
+            .await?;
+
+        Ok(frame_len)
+    }
+
+    async fn write_frame(&self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut writer = self.writer.lock().await;
+        writer.write_all(buf).await?;
+
+        let is_channel_data = buf.first().is_some_and(|byte| byte & 0xC0 == 0x40);
+        let padding = buf.len().next_multiple_of(4) - buf.len();
+        if is_channel_data && padding > 0 {
+            writer.write_all(&[0u8; 3][..padding]).await?;
+        }
+
+        Ok(buf.len())
+    }
+}
+
+#[async_trait]
+impl Conn for FramedTcpConn {
+    async fn connect(&self, _addr: SocketAddr) -> webrtc_util::Result<()> {
+        Err(std::io::Error::from(std::io::ErrorKind::Unsupported).into())
+    }
+
+    async fn recv(&self, buf: &mut [u8]) -> webrtc_util::Result<usize> {
+        let (len, _) = self.recv_from(buf).await?;
+        Ok(len)
+    }
+
+    async fn recv_from(&self, buf: &mut [u8]) -> webrtc_util::Result<(usize, SocketAddr)> {
+        match self.read_frame(buf).await {
+            Ok(len) => Ok((len, self.remote_addr)),
+            Err(error) => {
+                self.closed.notify_one();
+                Err(error.into())
+            }
+        }
+    }
+
+    async fn send(&self, buf: &[u8]) -> webrtc_util::Result<usize> {
+        Ok(self.write_frame(buf).await?)
+    }
+
+    async fn send_to(&self, buf: &[u8], _target: SocketAddr) -> webrtc_util::Result<usize> {
+        Ok(self.write_frame(buf).await?)
+    }
+
+    fn local_addr(&self) -> webrtc_util::Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(self.remote_addr)
+    }
+
+    async fn close(&self) -> webrtc_util::Result<()> {
+        let mut writer = self.writer.lock().await;
+        let _ = writer.shutdown().await;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &(dyn Any + Send + Sync) {
+        self
+    }
+}
@@ -0,0 +1,152 @@
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::errors::AppError;
+
+const TRANSCODE_TIMEOUT: Duration = Duration::from_secs(30);
+const OPUS_SAMPLE_RATE: u64 = 48_000;
+
+/// Recognizes the audio containers accepted for soundboard clips. Opus clips
+/// come in Ogg, so both report `audio/ogg`.
+pub(super) fn sniff_audio_mime_type(bytes: &[u8]) -> Result<&'static str, AppError> {
+    if bytes.starts_with(b"OggS") {
+        return Ok("audio/ogg");
+    }
+
+    if bytes.starts_with(b"ID3") {
+        return Ok("audio/mpeg");
+    }
+
+    // Bare MPEG audio frame sync. A zero layer field is ADTS (AAC), not MP3.
+    if bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xE0 == 0xE0 && bytes[1] & 0x06 != 0 {
+        return Ok("audio/mpeg");
+    }
+
+    Err(AppError::BadRequest(
+        "Unsupported audio payload. Allowed: Opus, OGG, MP3".into(),
+    ))
+}
+
+/// Re-encodes an uploaded clip as loudness-normalized 48 kHz stereo Opus in
+/// Ogg, dropping any cover art and metadata. The result is what gets stored
+/// and what the soundboard sends into voice channels unchanged.
+pub(super) async fn transcode_to_ogg_opus(
+    ffmpeg_bin: &str,
+    input: Vec<u8>,
+) -> Result<Vec<u8>, AppError> {
+    let mut ffmpeg = Command::new(ffmpeg_bin)
+        .args([
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-i",
+            "pipe:0",
+            "-map",
+            "0:a:0",
+            "-vn",
+            "-map_metadata",
+            "-1",
+            "-af",
+            "loudnorm=I=-16:TP=-1.5:LRA=11",
+            "-ac",
+            "2",
+            "-ar",
+            "48000",
+            "-c:a",
+            "libopus",
+            "-b:a",
+            "96k",
+            "-f",
+            "ogg",
+            "pipe:1",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|error| AppError::Internal(format!("Failed to start ffmpeg: {error}")))?;
+
+    let Some(mut stdin) = ffmpeg.stdin.take() else {
+        return Err(AppError::Internal("ffmpeg stdin was not captured".into()));
+    };
+    tokio::spawn(async move {
+        // ffmpeg closes its input early on undecodable data; the exit status
+        // reports that, so a broken pipe here is not an error of its own.
+        let _ = stdin.write_all(&input).await;
+    });
+
+    let output = tokio::time::timeout(TRANSCODE_TIMEOUT, ffmpeg.wait_with_output())
+        .await
+        .map_err(|_| AppError::BadRequest("Audio clip took too long to process".into()))?
+        .map_err(|error| AppError::Internal(format!("Failed to run ffmpeg: {error}")))?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        tracing::debug!(
+            status = ?output.status,
+            stderr = %String::from_utf8_lossy(&output.stderr),
+            "ffmpeg could not transcode audio clip"
+        );
+        return Err(AppError::BadRequest("Could not decode audio clip".into()));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Playback length of an Ogg Opus stream: the granule position of its last
+/// page minus the encoder pre-skip, at Opus's fixed 48 kHz.
+pub(super) fn ogg_opus_duration_ms(bytes: &[u8]) -> Option<u64> {
+    let head = bytes.windows(8).position(|window| window == b"OpusHead")?;
+    let pre_skip = u16::from_le_bytes([*bytes.get(head + 10)?, *bytes.get(head + 11)?]);
+
+    let last_page = bytes.windows(4).rposition(|window| window == b"OggS")?;
+    let granule: [u8; 8] = bytes.get(last_page + 6..last_page + 14)?.try_into().ok()?;
+    let granule = i64::from_le_bytes(granule);
+    if granule < 0 {
+        return None;
+    }
+
+    let samples = (granule as u64).checked_sub(u64::from(pre_skip))?;
+    Some(samples * 1000 / OPUS_SAMPLE_RATE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ogg_page(granule: i64, body: &[u8]) -> Vec<u8> {
+        let mut page = b"OggS".to_vec();
+        page.extend_from_slice(&[0, 0]);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&[0; 13]);
+        page.extend_from_slice(body);
+        page
+    }
+
+    #[test]
+    fn sniffs_ogg_and_mp3_but_not_aac() {
+        assert_eq!(sniff_audio_mime_type(b"OggS\0\x02").unwrap(), "audio/ogg");
+        assert_eq!(sniff_audio_mime_type(b"ID3\x04\0").unwrap(), "audio/mpeg");
+        assert_eq!(
+            sniff_audio_mime_type(&[0xFF, 0xFB, 0x90]).unwrap(),
+            "audio/mpeg"
+        );
+        assert!(sniff_audio_mime_type(&[0xFF, 0xF1, 0x50]).is_err());
+        assert!(sniff_audio_mime_type(b"RIFF....WAVE").is_err());
+    }
+
+    #[test]
+    fn duration_uses_last_granule_minus_pre_skip() {
+        let mut head = b"OpusHead\x01\x02".to_vec();
+        head.extend_from_slice(&312_u16.to_le_bytes());
+        let mut stream = ogg_page(0, &head);
+        stream.extend(ogg_page(0, b"OpusTags"));
+        stream.extend(ogg_page(48_000, &[0; 16]));
+        stream.extend(ogg_page(96_000 + 312, &[0; 16]));
+
+        assert_eq!(ogg_opus_duration_ms(&stream), Some(2000));
+        assert_eq!(ogg_opus_duration_ms(b"OggS not opus"), None);
+    }
+}
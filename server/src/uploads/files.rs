@@ -0,0 +1,295 @@
+use crate::config::StorageConfig;
+use crate::errors::AppError;
+
+const MAX_FILENAME_CHARS: usize = 255;
+const TEXT_SNIFF_BYTES: usize = 8192;
+
+/// MIME types served inline; everything else is sent as a download so an
+/// uploaded HTML, SVG or PDF never renders on the API origin.
+const INLINE_MIME_TYPES: &[&str] = &[
+    "image/jpeg",
+    "image/png",
+    "image/webp",
+    "image/gif",
+    "video/mp4",
+    "video/webm",
+    "audio/ogg",
+    "audio/mpeg",
+    "audio/wav",
+    "audio/flac",
+    "audio/mp4",
+];
+
+/// Which generic file attachments are accepted. Rules are MIME types
+/// (`application/pdf`), MIME families (`video/*`) or filename extensions
+/// (`.exe`); an empty allowlist accepts anything that is not denied.
+#[derive(Debug, Clone)]
+pub struct FileUploadPolicy {
+    pub max_bytes: usize,
+    allowed: Vec<String>,
+    denied: Vec<String>,
+}
+
+impl FileUploadPolicy {
+    pub fn from_config(config: &StorageConfig) -> Self {
+        let normalize = |rules: &[String]| {
+            rules
+                .iter()
+                .map(|rule| rule.trim().to_ascii_lowercase())
+                .filter(|rule| !rule.is_empty())
+                .collect()
+        };
+
+        Self {
+            max_bytes: config.max_file_upload_bytes,
+            allowed: normalize(&config.file_allowed_types),
+            denied: normalize(&config.file_denied_types),
+        }
+    }
+
+    pub fn check(&self, mime_type: &str, filename: &str) -> Result<(), AppError> {
+        let extension = filename
+            .rsplit_once('.')
+            .map(|(_, extension)| format!(".{}", extension.to_ascii_lowercase()));
+        let matches = |rule: &String| match rule.strip_suffix("/*") {
+            Some(family) => mime_type
+                .split_once('/')
+                .is_some_and(|(mime_family, _)| mime_family == family),
+            None if rule.starts_with('.') => extension.as_deref() == Some(rule.as_str()),
+            None => rule == mime_type,
+        };
+
+        if self.denied.iter().any(matches) {
+            return Err(AppError::BadRequest(format!(
+                "Files of type {mime_type} cannot be uploaded"
+            )));
+        }
+
+        if !self.allowed.is_empty() && !self.allowed.iter().any(matches) {
+            return Err(AppError::BadRequest(format!(
+                "Files of type {mime_type} are not allowed on this server"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Recognizes common document, archive, media and executable formats from
+/// their magic bytes. Unrecognized UTF-8 is `text/plain`; anything else is
+/// `application/octet-stream`.
+pub(super) fn sniff_file_mime_type(bytes: &[u8]) -> &'static str {
+    if let Ok(mime_type) = super::sniff_mime_type(bytes) {
+        return mime_type;
+    }
+
+    if let Ok(mime_type) = super::audio::sniff_audio_mime_type(bytes) {
+        return mime_type;
+    }
+
+    let starts_with = |magic: &[u8]| bytes.starts_with(magic);
+    if starts_with(b"%PDF-") {
+        return "application/pdf";
+    }
+    if starts_with(b"PK\x03\x04") || starts_with(b"PK\x05\x06") {
+        return "application/zip";
+    }
+    if starts_with(&[0x1F, 0x8B]) {
+        return "application/gzip";
+    }
+    if starts_with(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C]) {
+        return "application/x-7z-compressed";
+    }
+    if starts_with(b"Rar!\x1A\x07") {
+        return "application/vnd.rar";
+    }
+    if starts_with(b"fLaC") {
+        return "audio/flac";
+    }
+    if starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        return "video/webm";
+    }
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" {
+        match &bytes[8..12] {
+            b"WAVE" => return "audio/wav",
+            b"AVI " => return "video/x-msvideo",
+            _ => {}
+        }
+    }
+    if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        return match &bytes[8..12] {
+            b"M4A " => "audio/mp4",
+            b"qt  " => "video/quicktime",
+            _ => "video/mp4",
+        };
+    }
+    if starts_with(b"MZ") {
+        return "application/x-msdownload";
+    }
+    if starts_with(b"\x7FELF") {
+        return "application/x-executable";
+    }
+    if [
+        [0xFE, 0xED, 0xFA, 0xCE],
+        [0xFE, 0xED, 0xFA, 0xCF],
+        [0xCE, 0xFA, 0xED, 0xFE],
+        [0xCF, 0xFA, 0xED, 0xFE],
+    ]
+    .iter()
+    .any(|magic| starts_with(magic))
+    {
+        return "application/x-mach-binary";
+    }
+
+    // A multi-byte character cut off by the sniff window is still text.
+    let head = &bytes[..bytes.len().min(TEXT_SNIFF_BYTES)];
+    let is_text = match std::str::from_utf8(head) {
+        Ok(text) => !text.contains('\0'),
+        Err(error) => error.error_len().is_none() && !head[..error.valid_up_to()].contains(&0),
+    };
+    if is_text {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Keeps the last path component of a client-supplied filename, without
+/// control characters and at most 255 characters long.
+pub(super) fn sanitize_filename(filename: Option<&str>) -> String {
+    let base_name = filename
+        .unwrap_or_default()
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default();
+    let cleaned: String = base_name
+        .chars()
+        .filter(|character| !character.is_control())
+        .take(MAX_FILENAME_CHARS)
+        .collect();
+
+    let trimmed = cleaned.trim();
+    if trimmed.is_empty() || trimmed == "." || trimmed == ".." {
+        "file".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Builds the `Content-Disposition` value for a stored asset, or `None`
+/// for inline-safe types that have no filename to report.
+pub fn content_disposition(mime_type: &str, filename: Option<&str>) -> Option<String> {
+    let disposition = if INLINE_MIME_TYPES.contains(&mime_type) {
+        "inline"
+    } else {
+        "attachment"
+    };
+
+    let Some(filename) = filename else {
+        return (disposition == "attachment").then(|| disposition.to_string());
+    };
+
+    let ascii_fallback: String = filename
+        .chars()
+        .map(|character| match character {
+            ' '..='~' if character != '"' && character != '\\' => character,
+            _ => '_',
+        })
+        .collect();
+    let mut encoded = String::with_capacity(filename.len());
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    Some(format!(
+        "{disposition}; filename=\"{ascii_fallback}\"; filename*=UTF-8''{encoded}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed: &[&str], denied: &[&str]) -> FileUploadPolicy {
+        FileUploadPolicy::from_config(&StorageConfig {
+            file_allowed_types: allowed.iter().map(|rule| rule.to_string()).collect(),
+            file_denied_types: denied.iter().map(|rule| rule.to_string()).collect(),
+            ..StorageConfig::default()
+        })
+    }
+
+    #[test]
+    fn sniffs_documents_archives_executables_and_text() {
+        assert_eq!(sniff_file_mime_type(b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(sniff_file_mime_type(b"PK\x03\x04rest"), "application/zip");
+        assert_eq!(sniff_file_mime_type(b"\0\0\0\x18ftypisom"), "video/mp4");
+        assert_eq!(
+            sniff_file_mime_type(b"MZ\x90\0"),
+            "application/x-msdownload"
+        );
+        assert_eq!(
+            sniff_file_mime_type("log line é\n".as_bytes()),
+            "text/plain"
+        );
+        assert_eq!(sniff_file_mime_type(&"é".as_bytes()[..1]), "text/plain");
+        assert_eq!(
+            sniff_file_mime_type(b"\x01\x02\0\x03"),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn policy_matches_types_families_and_extensions() {
+        let defaults = FileUploadPolicy::from_config(&StorageConfig::default());
+        assert!(defaults.check("application/pdf", "report.pdf").is_ok());
+        assert!(defaults
+            .check("application/x-msdownload", "setup.exe")
+            .is_err());
+        assert!(defaults.check("text/plain", "run.BAT").is_err());
+
+        let media_only = policy(&["video/*", "audio/*"], &[]);
+        assert!(media_only.check("video/mp4", "clip.mp4").is_ok());
+        assert!(media_only.check("application/zip", "clip.zip").is_err());
+
+        let no_zips = policy(&[], &["application/zip"]);
+        assert!(no_zips.check("application/zip", "logs.zip").is_err());
+        assert!(no_zips.check("text/plain", "logs.txt").is_ok());
+    }
+
+    #[test]
+    fn filenames_lose_paths_and_control_characters() {
+        assert_eq!(
+            sanitize_filename(Some("C:\\Users\\me\\notes.txt")),
+            "notes.txt"
+        );
+        assert_eq!(sanitize_filename(Some("../../etc/pass\nwd")), "passwd");
+        assert_eq!(sanitize_filename(Some("..")), "file");
+        assert_eq!(sanitize_filename(None), "file");
+        assert_eq!(
+            sanitize_filename(Some(&"a".repeat(300))).len(),
+            MAX_FILENAME_CHARS
+        );
+    }
+
+    #[test]
+    fn disposition_downloads_unsafe_types_and_encodes_names() {
+        assert_eq!(content_disposition("image/webp", None), None);
+        assert_eq!(
+            content_disposition("text/html", None).as_deref(),
+            Some("attachment")
+        );
+        assert_eq!(
+            content_disposition("application/pdf", Some("résumé \"v2\".pdf")).as_deref(),
+            Some(
+                "attachment; filename=\"r_sum_ _v2_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9%20%22v2%22.pdf"
+            )
+        );
+        assert!(content_disposition("video/mp4", Some("clip.mp4"))
+            .unwrap()
+            .starts_with("inline; "));
+    }
+}
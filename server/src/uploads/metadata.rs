@@ -0,0 +1,443 @@
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::jpeg::JpegEncoder;
+use image::metadata::Orientation;
+use image::{
+    AnimationDecoder, DynamicImage, GenericImageView, ImageDecoder, ImageFormat, ImageReader,
+};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+use std::io::Cursor;
+
+use crate::errors::AppError;
+
+/// Quality for JPEGs re-encoded to drop their metadata; high enough that
+/// the second generation loss is hard to see.
+const JPEG_QUALITY: u8 = 90;
+
+/// An upload decoded for re-encoding: EXIF orientation applied, pixels in
+/// sRGB, and nothing else from the source file carried along.
+pub(super) struct NormalizedImage {
+    pub image: DynamicImage,
+    /// Whether the source had EXIF, XMP, IPTC or ICC data that re-encoding
+    /// drops.
+    pub had_metadata: bool,
+}
+
+pub(super) fn decode_normalized(bytes: &[u8]) -> Result<NormalizedImage, AppError> {
+    let invalid = |error: image::ImageError| {
+        AppError::BadRequest(format!("Unsupported image payload: {error}"))
+    };
+    let mut decoder = ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .map_err(|error| AppError::BadRequest(format!("Unsupported image payload: {error}")))?
+        .into_decoder()
+        .map_err(invalid)?;
+
+    // Unreadable metadata is dropped like any other, so errors count as none.
+    let exif = decoder.exif_metadata().ok().flatten();
+    let xmp = decoder.xmp_metadata().ok().flatten();
+    let iptc = decoder.iptc_metadata().ok().flatten();
+    let icc = decoder.icc_profile().ok().flatten();
+    let orientation = exif
+        .as_deref()
+        .and_then(Orientation::from_exif_chunk)
+        .unwrap_or(Orientation::NoTransforms);
+
+    let mut image = DynamicImage::from_decoder(decoder).map_err(invalid)?;
+    image.apply_orientation(orientation);
+    if let Some(icc) = icc.as_deref() {
+        image = convert_to_srgb(image, icc);
+    }
+
+    Ok(NormalizedImage {
+        image,
+        had_metadata: exif.is_some() || xmp.is_some() || iptc.is_some() || icc.is_some(),
+    })
+}
+
+/// An upload ready to be stored: the bytes hold no EXIF, XMP, IPTC, ICC,
+/// JPEG comment, PNG text or GIF comment data, whatever the source carried.
+pub(super) struct StrippedImage {
+    pub bytes: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub had_metadata: bool,
+}
+
+/// Strips an image upload before it is first written to storage. JPEG and
+/// PNG files are always re-encoded in their own format, since the decoder
+/// does not report JPEG comments or PNG text chunks; GIFs are always
+/// re-encoded frame by frame for the same reason, which keeps them
+/// animated. WebP files are re-encoded only when they carry metadata, which
+/// the decoder reports in full; clean ones are kept byte for byte.
+pub(super) fn strip(bytes: Vec<u8>, mime_type: &str) -> Result<StrippedImage, AppError> {
+    let NormalizedImage {
+        image,
+        had_metadata,
+    } = decode_normalized(&bytes)?;
+    let had_metadata = had_metadata || has_text_segments(&bytes, mime_type);
+    let (width, height) = image.dimensions();
+
+    let encode_failed = |error: image::ImageError| {
+        AppError::Internal(format!("Failed to strip image metadata: {error}"))
+    };
+    let bytes = match mime_type {
+        "image/gif" => reencode_gif(&bytes)?,
+        "image/webp" if !had_metadata => bytes,
+        "image/jpeg" => {
+            let mut output = Vec::new();
+            DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY))
+                .map_err(encode_failed)?;
+            output
+        }
+        "image/png" | "image/webp" => {
+            let format = if mime_type == "image/png" {
+                ImageFormat::Png
+            } else {
+                ImageFormat::WebP
+            };
+            let mut output = Cursor::new(Vec::new());
+            image.write_to(&mut output, format).map_err(encode_failed)?;
+            output.into_inner()
+        }
+        _ => {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported image payload: {mime_type}"
+            )))
+        }
+    };
+
+    Ok(StrippedImage {
+        bytes,
+        width,
+        height,
+        had_metadata,
+    })
+}
+
+/// Whether a JPEG has COM segments or a PNG has tEXt, zTXt, iTXt or eXIf
+/// chunks, which `decode_normalized` does not count as metadata.
+fn has_text_segments(bytes: &[u8], mime_type: &str) -> bool {
+    match mime_type {
+        "image/jpeg" => jpeg_segment_markers(bytes).any(|marker| marker == JPEG_COM),
+        "image/png" => {
+            png_chunk_types(bytes).any(|chunk_type| PNG_TEXT_CHUNKS.contains(&chunk_type))
+        }
+        _ => false,
+    }
+}
+
+const JPEG_COM: u8 = 0xFE;
+const JPEG_SOS: u8 = 0xDA;
+const JPEG_EOI: u8 = 0xD9;
+const PNG_SIGNATURE_LEN: usize = 8;
+const PNG_TEXT_CHUNKS: [&[u8; 4]; 4] = [b"tEXt", b"zTXt", b"iTXt", b"eXIf"];
+
+/// Markers of the JPEG segments before the scan data.
+fn jpeg_segment_markers(bytes: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    let mut offset = 2;
+    std::iter::from_fn(move || {
+        while bytes.get(offset) == Some(&0xFF) && bytes.get(offset + 1) == Some(&0xFF) {
+            offset += 1;
+        }
+        if bytes.get(offset) != Some(&0xFF) {
+            return None;
+        }
+        let marker = *bytes.get(offset + 1)?;
+        if marker == JPEG_SOS || marker == JPEG_EOI {
+            return None;
+        }
+        offset += 2;
+        // TEM and RSTn stand alone; every other marker has a length.
+        if marker != 0x01 && !(0xD0..=0xD7).contains(&marker) {
+            let length = u16::from_be_bytes([*bytes.get(offset)?, *bytes.get(offset + 1)?]);
+            offset += usize::from(length);
+        }
+        Some(marker)
+    })
+}
+
+fn png_chunk_types(bytes: &[u8]) -> impl Iterator<Item = &[u8; 4]> + '_ {
+    let mut offset = PNG_SIGNATURE_LEN;
+    std::iter::from_fn(move || {
+        let length = u32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?);
+        let chunk_type: &[u8; 4] = bytes.get(offset + 4..offset + 8)?.try_into().ok()?;
+        offset = offset
+            .checked_add(12)?
+            .checked_add(usize::try_from(length).ok()?)?;
+        Some(chunk_type)
+    })
+}
+
+/// Copies only the frames, with their delays, into a new looping GIF.
+fn reencode_gif(bytes: &[u8]) -> Result<Vec<u8>, AppError> {
+    let decoder = GifDecoder::new(Cursor::new(bytes))
+        .map_err(|error| AppError::BadRequest(format!("Unsupported image payload: {error}")))?;
+
+    let failed = |error: image::ImageError| {
+        AppError::BadRequest(format!("Failed to re-encode GIF: {error}"))
+    };
+    let mut output = Vec::new();
+    let mut encoder = GifEncoder::new(&mut output);
+    encoder.set_repeat(Repeat::Infinite).map_err(failed)?;
+    encoder
+        .try_encode_frames(decoder.into_frames())
+        .map_err(failed)?;
+    drop(encoder);
+    Ok(output)
+}
+
+/// Converts pixels described by an embedded ICC profile to sRGB so they
+/// look the same once the profile is gone. Non-RGB profiles and profiles
+/// that fail to parse leave the pixels as they are.
+fn convert_to_srgb(image: DynamicImage, icc: &[u8]) -> DynamicImage {
+    let Ok(profile) = ColorProfile::new_from_slice(icc) else {
+        return image;
+    };
+    if profile.color_space != DataColorSpace::Rgb {
+        return image;
+    }
+
+    let layout = if image.color().has_alpha() {
+        Layout::Rgba
+    } else {
+        Layout::Rgb
+    };
+    let Ok(transform) = profile.create_transform_8bit(
+        layout,
+        &ColorProfile::new_srgb(),
+        layout,
+        TransformOptions::default(),
+    ) else {
+        return image;
+    };
+
+    match layout {
+        Layout::Rgba => {
+            let source = image.to_rgba8();
+            let mut converted = source.clone();
+            match transform.transform(&source, &mut converted) {
+                Ok(()) => DynamicImage::ImageRgba8(converted),
+                Err(_) => image,
+            }
+        }
+        _ => {
+            let source = image.to_rgb8();
+            let mut converted = source.clone();
+            match transform.transform(&source, &mut converted) {
+                Ok(()) => DynamicImage::ImageRgb8(converted),
+                Err(_) => image,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageEncoder, Rgb, RgbImage};
+
+    /// A little-endian TIFF header with a single Orientation (0x0112) entry.
+    fn exif_with_orientation(orientation: u8) -> Vec<u8> {
+        let mut exif = b"II*\0\x08\0\0\0\x01\0\x12\x01\x03\0\x01\0\0\0".to_vec();
+        exif.extend_from_slice(&[orientation, 0, 0, 0, 0, 0, 0, 0]);
+        exif
+    }
+
+    #[test]
+    fn applies_exif_orientation_and_reports_metadata() {
+        let mut source = RgbImage::from_pixel(4, 2, Rgb([200, 40, 40]));
+        source.put_pixel(0, 0, Rgb([0, 0, 0]));
+
+        let mut jpeg = Vec::new();
+        let mut encoder = JpegEncoder::new_with_quality(&mut jpeg, 100);
+        encoder.set_exif_metadata(exif_with_orientation(6)).unwrap();
+        encoder
+            .write_image(source.as_raw(), 4, 2, image::ExtendedColorType::Rgb8)
+            .unwrap();
+
+        let normalized = decode_normalized(&jpeg).unwrap();
+        assert!(normalized.had_metadata);
+        assert_eq!(normalized.image.dimensions(), (2, 4));
+
+        let mut plain = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(source)
+            .write_to(&mut plain, image::ImageFormat::Png)
+            .unwrap();
+        let normalized = decode_normalized(plain.get_ref()).unwrap();
+        assert!(!normalized.had_metadata);
+        assert_eq!(normalized.image.dimensions(), (4, 2));
+    }
+
+    fn encode_jpeg(source: &RgbImage) -> Vec<u8> {
+        let mut jpeg = Vec::new();
+        JpegEncoder::new_with_quality(&mut jpeg, 100)
+            .write_image(source.as_raw(), 4, 2, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        jpeg
+    }
+
+    fn encode_png(source: &RgbImage) -> Vec<u8> {
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(source.clone())
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        png.into_inner()
+    }
+
+    /// Inserts a COM segment right after the JPEG's SOI marker.
+    fn with_jpeg_comment(jpeg: &[u8], comment: &[u8]) -> Vec<u8> {
+        let mut output = jpeg[..2].to_vec();
+        output.extend_from_slice(&[0xFF, JPEG_COM]);
+        output.extend_from_slice(&(comment.len() as u16 + 2).to_be_bytes());
+        output.extend_from_slice(comment);
+        output.extend_from_slice(&jpeg[2..]);
+        output
+    }
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0_u32;
+        for byte in bytes {
+            crc ^= u32::from(*byte);
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    /// Inserts a chunk right after the PNG's IHDR chunk.
+    fn with_png_chunk(png: &[u8], chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let ihdr_end = PNG_SIGNATURE_LEN + 12 + 13;
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+
+        let mut output = png[..ihdr_end].to_vec();
+        output.extend_from_slice(&chunk);
+        output.extend_from_slice(&png[ihdr_end..]);
+        output
+    }
+
+    /// Zlib stream holding `data` in a single stored block.
+    fn zlib_stored(data: &[u8]) -> Vec<u8> {
+        let mut output = vec![0x78, 0x01, 0x01];
+        let length = data.len() as u16;
+        output.extend_from_slice(&length.to_le_bytes());
+        output.extend_from_slice(&(!length).to_le_bytes());
+        output.extend_from_slice(data);
+        let (mut a, mut b) = (1_u32, 0_u32);
+        for byte in data {
+            a = (a + u32::from(*byte)) % 65521;
+            b = (b + a) % 65521;
+        }
+        output.extend_from_slice(&((b << 16) | a).to_be_bytes());
+        output
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    #[test]
+    fn strip_drops_jpeg_comments() {
+        let source = RgbImage::from_pixel(4, 2, Rgb([200, 40, 40]));
+        let commented = with_jpeg_comment(&encode_jpeg(&source), b"taken at 51.5N 0.1W");
+        assert!(has_text_segments(&commented, "image/jpeg"));
+
+        let stripped = strip(commented, "image/jpeg").unwrap();
+        assert!(stripped.had_metadata);
+        assert!(!contains(&stripped.bytes, b"51.5N"));
+        assert!(!has_text_segments(&stripped.bytes, "image/jpeg"));
+        assert_eq!((stripped.width, stripped.height), (4, 2));
+    }
+
+    #[test]
+    fn strip_drops_png_text_chunks() {
+        let source = RgbImage::from_pixel(4, 2, Rgb([200, 40, 40]));
+        let mut exif = b"MM\0*\0\0\0\x08\0\0".to_vec();
+        exif.extend_from_slice(b"\0\0\0\0");
+        let mut itxt = b"Comment\0\0\0\0\0".to_vec();
+        itxt.extend_from_slice(b"secret itxt");
+        let mut ztxt = b"Comment\0\0".to_vec();
+        ztxt.extend_from_slice(&zlib_stored(b"secret ztxt"));
+        let fixtures: [(&[u8; 4], Vec<u8>, &[u8]); 4] = [
+            (b"tEXt", b"Comment\0secret text".to_vec(), b"secret text"),
+            (b"zTXt", ztxt, b"Comment"),
+            (b"iTXt", itxt, b"secret itxt"),
+            (b"eXIf", exif, b"MM\0*"),
+        ];
+
+        for (chunk_type, data, marker) in fixtures {
+            let tagged = with_png_chunk(&encode_png(&source), chunk_type, &data);
+            assert!(has_text_segments(&tagged, "image/png"), "{chunk_type:?}");
+
+            let stripped = strip(tagged, "image/png").unwrap();
+            assert!(stripped.had_metadata, "{chunk_type:?}");
+            assert!(!contains(&stripped.bytes, marker), "{chunk_type:?}");
+            assert!(
+                png_chunk_types(&stripped.bytes).all(|found| !PNG_TEXT_CHUNKS.contains(&found)),
+                "{chunk_type:?}"
+            );
+            assert_eq!(
+                image::load_from_memory(&stripped.bytes).unwrap().to_rgb8(),
+                source,
+                "{chunk_type:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn strip_drops_metadata_and_reencodes_clean_uploads() {
+        let source = RgbImage::from_pixel(4, 2, Rgb([200, 40, 40]));
+
+        let mut jpeg = Vec::new();
+        let mut encoder = JpegEncoder::new_with_quality(&mut jpeg, 100);
+        encoder.set_exif_metadata(exif_with_orientation(6)).unwrap();
+        encoder
+            .write_image(source.as_raw(), 4, 2, image::ExtendedColorType::Rgb8)
+            .unwrap();
+
+        let stripped = strip(jpeg, "image/jpeg").unwrap();
+        assert!(stripped.had_metadata);
+        assert_eq!((stripped.width, stripped.height), (2, 4));
+        assert!(!decode_normalized(&stripped.bytes).unwrap().had_metadata);
+
+        let stripped = strip(encode_png(&source), "image/png").unwrap();
+        assert!(!stripped.had_metadata);
+        assert_eq!(
+            image::load_from_memory(&stripped.bytes).unwrap().to_rgb8(),
+            source
+        );
+
+        let stripped = strip(encode_jpeg(&source), "image/jpeg").unwrap();
+        assert!(!stripped.had_metadata);
+        assert_eq!((stripped.width, stripped.height), (4, 2));
+    }
+
+    #[test]
+    fn strip_reencodes_every_gif_frame() {
+        let mut gif = Vec::new();
+        let mut encoder = GifEncoder::new(&mut gif);
+        for shade in [0, 255] {
+            let frame = image::RgbaImage::from_pixel(3, 3, image::Rgba([shade, shade, shade, 255]));
+            encoder.encode_frame(image::Frame::new(frame)).unwrap();
+        }
+        drop(encoder);
+
+        let stripped = strip(gif, "image/gif").unwrap();
+        let frames = GifDecoder::new(Cursor::new(stripped.bytes))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+    }
+}
@@ -1,38 +1,83 @@
+mod audio;
+mod files;
+mod metadata;
+mod placeholder;
+mod quota;
+mod resumable;
+mod scan;
+mod video;
+
 use image::codecs::gif::GifEncoder;
 use image::GenericImageView;
 use image::ImageFormat;
 use sha2::{Digest, Sha256};
 use sqlx::PgPool;
 use std::io::Cursor;
+use std::path::Path;
 use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{broadcast, Semaphore};
 use uuid::Uuid;
 
+use crate::config::StorageConfig;
 use crate::errors::AppError;
 use crate::models::MediaAsset;
 use crate::storage::StorageBackend;
 
+pub use files::content_disposition;
+pub use quota::{StorageConsumer, StorageQuota, StorageUsage};
+pub use resumable::{
+    chunk_matches_checksum, chunk_storage_key, parse_upload_checksum, parse_upload_metadata,
+    ResumableUpload,
+};
+pub use scan::{QuarantinedAsset, QuarantinedUpload};
+pub use video::VideoTranscodeTarget;
+
 #[derive(Debug, serde::Serialize)]
 pub struct UploadResult {
     pub id: Uuid,
     pub status: String,
 }
 
+#[derive(Debug, serde::Serialize)]
+pub struct SoundClipUpload {
+    pub id: Uuid,
+    pub duration_ms: u64,
+}
+
 #[derive(Clone)]
 pub struct UploadService {
     db: PgPool,
     storage: Arc<dyn StorageBackend>,
     max_upload_bytes: usize,
+    file_policy: files::FileUploadPolicy,
+    quota: StorageQuota,
+    video_transcode: VideoTranscodeTarget,
+    video_jobs: Arc<Semaphore>,
+    scanner: Option<scan::ClamdScanner>,
+    quarantine_events: broadcast::Sender<QuarantinedUpload>,
 }
 
 impl UploadService {
-    pub fn new(db: PgPool, storage: Arc<dyn StorageBackend>, max_upload_bytes: usize) -> Self {
+    pub fn new(db: PgPool, storage: Arc<dyn StorageBackend>, config: &StorageConfig) -> Self {
         Self {
             db,
             storage,
-            max_upload_bytes,
+            max_upload_bytes: config.max_upload_bytes,
+            file_policy: files::FileUploadPolicy::from_config(config),
+            quota: StorageQuota::from_config(config),
+            video_transcode: VideoTranscodeTarget::from_config(&config.video_transcode),
+            video_jobs: Arc::new(Semaphore::new(MAX_CONCURRENT_VIDEO_JOBS)),
+            scanner: scan::ClamdScanner::from_config(config),
+            quarantine_events: broadcast::channel(QUARANTINE_EVENT_CAPACITY).0,
         }
     }
 
+    /// Uploads quarantined by the malware scanner from now on.
+    pub fn subscribe_quarantined(&self) -> broadcast::Receiver<QuarantinedUpload> {
+        self.quarantine_events.subscribe()
+    }
+
     pub async fn upload_image(
         &self,
         owner_id: Uuid,
@@ -53,37 +98,31 @@ impl UploadService {
             )));
         }
 
-        let (width, height) =
-            validate_image_dimensions(&bytes, MAX_IMAGE_UPLOAD_DIMENSION, "Image")?;
+        validate_image_dimensions(&bytes, MAX_IMAGE_UPLOAD_DIMENSION, "Image")?;
+        let stripped = metadata::strip(bytes, mime_type)?;
+        self.ensure_quota(owner_id, stripped.bytes.len()).await?;
 
         let media_id = Uuid::new_v4();
-        let storage_key = format!("original/{media_id}.{}", extension_for_mime(mime_type));
-        let checksum = sha256_hex(&bytes);
+        let checksum = sha256_hex(&stripped.bytes);
+        let byte_len = stripped.bytes.len() as i64;
+        let storage_key = self.put_blob(stripped.bytes, &checksum, mime_type).await?;
 
         sqlx::query(
-            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status, width, height)
-             VALUES ($1, $2, NULL, NULL, $3, $4, $5, $6, 'processing', $7, $8)",
+            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status, width, height, metadata_stripped)
+             VALUES ($1, $2, NULL, NULL, $3, $4, $5, $6, 'processing', $7, $8, $9)",
         )
         .bind(media_id)
         .bind(owner_id)
         .bind(mime_type)
-        .bind(bytes.len() as i64)
+        .bind(byte_len)
         .bind(&checksum)
         .bind(&storage_key)
-        .bind(width as i32)
-        .bind(height as i32)
+        .bind(stripped.width as i32)
+        .bind(stripped.height as i32)
+        .bind(stripped.had_metadata)
         .execute(&self.db)
         .await?;
 
-        if let Err(error) = self.storage.put(&storage_key, bytes, mime_type).await {
-            self.mark_failed(
-                media_id,
-                &format!("Failed to persist original upload: {error:?}"),
-            )
-            .await?;
-            return Err(error);
-        }
-
         let processor = self.clone();
         tokio::spawn(async move {
             if let Err(error) = processor.process_derivatives(media_id).await {
@@ -103,6 +142,161 @@ impl UploadService {
         })
     }
 
+    /// Stores any file as a message attachment under its original name. The
+    /// type is sniffed from the content rather than taken from the client,
+    /// then checked against the configured file policy and, when clamd is
+    /// configured, scanned for malware. JPEG and PNG images are stripped of
+    /// their metadata before they are stored. MP4, WebM and MOV videos stay
+    /// `processing` until their poster frame (and web-safe variant, when
+    /// needed) are ready.
+    pub async fn upload_file(
+        &self,
+        owner_id: Uuid,
+        filename: Option<&str>,
+        bytes: Vec<u8>,
+        ffmpeg_bin: &str,
+        ffprobe_bin: &str,
+    ) -> Result<UploadResult, AppError> {
+        if bytes.is_empty() {
+            return Err(AppError::BadRequest("Upload payload is empty".into()));
+        }
+
+        if bytes.len() > self.file_policy.max_bytes {
+            return Err(AppError::BadRequest(format!(
+                "File exceeds limit of {} bytes",
+                self.file_policy.max_bytes,
+            )));
+        }
+
+        let filename = files::sanitize_filename(filename);
+        let mime_type = files::sniff_file_mime_type(&bytes);
+        self.file_policy.check(mime_type, &filename)?;
+        self.ensure_quota(owner_id, bytes.len()).await?;
+
+        if let Some(scanner) = &self.scanner {
+            if let scan::ScanVerdict::Infected(signature) = scanner.scan(&bytes).await? {
+                self.quarantine_upload(owner_id, &filename, mime_type, bytes, &signature)
+                    .await?;
+                return Err(AppError::BadRequest(format!(
+                    "File was rejected by the malware scanner ({signature})"
+                )));
+            }
+        }
+
+        let (bytes, metadata_stripped) = match mime_type {
+            "image/jpeg" | "image/png" => {
+                // Probe the header first so a decompression bomb is rejected
+                // before it is decoded.
+                validate_image_dimensions(&bytes, MAX_IMAGE_UPLOAD_DIMENSION, "Image")?;
+                let stripped = metadata::strip(bytes, mime_type)?;
+                (stripped.bytes, stripped.had_metadata)
+            }
+            _ => (bytes, false),
+        };
+
+        let media_id = Uuid::new_v4();
+        let checksum = sha256_hex(&bytes);
+        let byte_len = bytes.len() as i64;
+        let storage_key = self.put_blob(bytes, &checksum, mime_type).await?;
+        let status = if video::is_processable_video(mime_type) {
+            "processing"
+        } else {
+            "ready"
+        };
+
+        sqlx::query(
+            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status, original_filename, metadata_stripped)
+             VALUES ($1, $2, NULL, NULL, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(media_id)
+        .bind(owner_id)
+        .bind(mime_type)
+        .bind(byte_len)
+        .bind(&checksum)
+        .bind(&storage_key)
+        .bind(status)
+        .bind(&filename)
+        .bind(metadata_stripped)
+        .execute(&self.db)
+        .await?;
+
+        if status == "processing" {
+            let processor = self.clone();
+            let ffmpeg_bin = ffmpeg_bin.to_string();
+            let ffprobe_bin = ffprobe_bin.to_string();
+            tokio::spawn(async move {
+                if let Err(error) = processor
+                    .process_video(media_id, &ffmpeg_bin, &ffprobe_bin)
+                    .await
+                {
+                    tracing::error!(media_id = %media_id, error = ?error, "Video processing failed");
+                    let _ = processor
+                        .mark_failed(media_id, &format!("Video processing failed: {error:?}"))
+                        .await;
+                }
+            });
+        }
+
+        Ok(UploadResult {
+            id: media_id,
+            status: status.to_string(),
+        })
+    }
+
+    /// Keeps an infected upload out of the shared blobs: it is written under
+    /// `quarantine/` and recorded as a failed asset, which is never served
+    /// and stays until an admin deletes it.
+    async fn quarantine_upload(
+        &self,
+        owner_id: Uuid,
+        filename: &str,
+        mime_type: &str,
+        bytes: Vec<u8>,
+        signature: &str,
+    ) -> Result<Uuid, AppError> {
+        let media_id = Uuid::new_v4();
+        let checksum = sha256_hex(&bytes);
+        let byte_len = bytes.len() as i64;
+        let storage_key = format!("quarantine/{media_id}");
+
+        self.storage
+            .put(&storage_key, bytes, "application/octet-stream")
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status, original_filename, error_message, quarantined_at)
+             VALUES ($1, $2, NULL, NULL, $3, $4, $5, $6, 'failed', $7, $8, now())",
+        )
+        .bind(media_id)
+        .bind(owner_id)
+        .bind(mime_type)
+        .bind(byte_len)
+        .bind(&checksum)
+        .bind(&storage_key)
+        .bind(filename)
+        .bind(format!("Malware detected: {signature}"))
+        .execute(&self.db)
+        .await?;
+
+        tracing::warn!(
+            media_id = %media_id,
+            owner_id = %owner_id,
+            filename = %filename,
+            signature = %signature,
+            "Quarantined infected upload"
+        );
+
+        // Nobody listening just means no admin gets a live notice.
+        let _ = self.quarantine_events.send(QuarantinedUpload {
+            media_id,
+            owner_id,
+            filename: filename.to_string(),
+            signature: signature.to_string(),
+        });
+
+        Ok(media_id)
+    }
+
     pub async fn upload_emoji(
         &self,
         owner_id: Uuid,
@@ -122,55 +316,49 @@ impl UploadService {
         let mime_type = sniff_mime_type(&bytes)?;
         validate_emoji_mime_type(mime_type)?;
 
-        let image = image::load_from_memory(&bytes)
-            .map_err(|error| AppError::BadRequest(format!("Invalid image file: {error}")))?;
+        let metadata::NormalizedImage {
+            image,
+            had_metadata,
+        } = metadata::decode_normalized(&bytes)?;
         let (width, height) = image.dimensions();
 
         let (processed_bytes, processed_width, processed_height) =
             if width > MAX_EMOJI_DIMENSION || height > MAX_EMOJI_DIMENSION {
-                let resized = image.thumbnail(MAX_EMOJI_DIMENSION, MAX_EMOJI_DIMENSION);
-                let (resized_width, resized_height) = resized.dimensions();
+                let output = image.thumbnail(MAX_EMOJI_DIMENSION, MAX_EMOJI_DIMENSION);
+                let (output_width, output_height) = output.dimensions();
                 (
-                    encode_emoji_in_source_format(&resized, mime_type)?,
-                    resized_width,
-                    resized_height,
+                    encode_emoji_in_source_format(&output, mime_type)?,
+                    output_width,
+                    output_height,
                 )
             } else {
-                (bytes, width, height)
+                // Small GIFs are re-encoded frame by frame so they stay animated.
+                let stripped = metadata::strip(bytes, mime_type)?;
+                (stripped.bytes, stripped.width, stripped.height)
             };
+        self.ensure_quota(owner_id, processed_bytes.len()).await?;
 
         let media_id = Uuid::new_v4();
-        let storage_key = format!("original/{media_id}.{}", extension_for_mime(mime_type));
         let checksum = sha256_hex(&processed_bytes);
+        let byte_len = processed_bytes.len() as i64;
+        let storage_key = self.put_blob(processed_bytes, &checksum, mime_type).await?;
 
         sqlx::query(
-            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status, width, height)
-             VALUES ($1, $2, NULL, NULL, $3, $4, $5, $6, 'ready', $7, $8)",
+            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status, width, height, metadata_stripped)
+             VALUES ($1, $2, NULL, NULL, $3, $4, $5, $6, 'ready', $7, $8, $9)",
         )
         .bind(media_id)
         .bind(owner_id)
         .bind(mime_type)
-        .bind(processed_bytes.len() as i64)
+        .bind(byte_len)
         .bind(&checksum)
         .bind(&storage_key)
         .bind(processed_width as i32)
         .bind(processed_height as i32)
+        .bind(had_metadata)
         .execute(&self.db)
         .await?;
 
-        if let Err(error) = self
-            .storage
-            .put(&storage_key, processed_bytes, mime_type)
-            .await
-        {
-            self.mark_failed(
-                media_id,
-                &format!("Failed to persist emoji upload: {error:?}"),
-            )
-            .await?;
-            return Err(error);
-        }
-
         Ok(UploadResult {
             id: media_id,
             status: "ready".to_string(),
@@ -197,37 +385,31 @@ impl UploadService {
             ));
         }
 
-        let (width, height) =
-            validate_image_dimensions(&bytes, MAX_AVATAR_UPLOAD_DIMENSION, "Avatar")?;
+        validate_image_dimensions(&bytes, MAX_AVATAR_UPLOAD_DIMENSION, "Avatar")?;
+        let stripped = metadata::strip(bytes, mime_type)?;
+        self.ensure_quota(owner_id, stripped.bytes.len()).await?;
 
         let media_id = Uuid::new_v4();
-        let storage_key = format!("original/{media_id}.{}", extension_for_mime(mime_type));
-        let checksum = sha256_hex(&bytes);
+        let checksum = sha256_hex(&stripped.bytes);
+        let byte_len = stripped.bytes.len() as i64;
+        let storage_key = self.put_blob(stripped.bytes, &checksum, mime_type).await?;
 
         sqlx::query(
-            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status, width, height)
-             VALUES ($1, $2, NULL, NULL, $3, $4, $5, $6, 'processing', $7, $8)",
+            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status, width, height, metadata_stripped)
+             VALUES ($1, $2, NULL, NULL, $3, $4, $5, $6, 'processing', $7, $8, $9)",
         )
         .bind(media_id)
         .bind(owner_id)
         .bind(mime_type)
-        .bind(bytes.len() as i64)
+        .bind(byte_len)
         .bind(&checksum)
         .bind(&storage_key)
-        .bind(width as i32)
-        .bind(height as i32)
+        .bind(stripped.width as i32)
+        .bind(stripped.height as i32)
+        .bind(stripped.had_metadata)
         .execute(&self.db)
         .await?;
 
-        if let Err(error) = self.storage.put(&storage_key, bytes, mime_type).await {
-            self.mark_failed(
-                media_id,
-                &format!("Failed to persist avatar upload: {error:?}"),
-            )
-            .await?;
-            return Err(error);
-        }
-
         if let Err(error) = self.process_avatar_derivatives(media_id).await {
             self.mark_failed(
                 media_id,
@@ -261,6 +443,260 @@ impl UploadService {
         })
     }
 
+    /// Validates a soundboard clip (Opus/OGG or MP3, at most
+    /// `MAX_SOUND_CLIP_DURATION_MS` long) and stores it transcoded to Ogg Opus.
+    pub async fn upload_sound_clip(
+        &self,
+        owner_id: Uuid,
+        bytes: Vec<u8>,
+        ffmpeg_bin: &str,
+    ) -> Result<SoundClipUpload, AppError> {
+        if bytes.is_empty() {
+            return Err(AppError::BadRequest("Upload payload is empty".into()));
+        }
+
+        if bytes.len() > MAX_SOUND_CLIP_UPLOAD_BYTES {
+            return Err(AppError::BadRequest(format!(
+                "Sound clip exceeds maximum size of {} KB",
+                MAX_SOUND_CLIP_UPLOAD_BYTES / 1024
+            )));
+        }
+
+        audio::sniff_audio_mime_type(&bytes)?;
+        let opus = audio::transcode_to_ogg_opus(ffmpeg_bin, bytes).await?;
+        let duration_ms = audio::ogg_opus_duration_ms(&opus)
+            .filter(|duration_ms| *duration_ms > 0)
+            .ok_or_else(|| AppError::BadRequest("Sound clip has no playable audio".into()))?;
+
+        if duration_ms > MAX_SOUND_CLIP_DURATION_MS {
+            return Err(AppError::BadRequest(format!(
+                "Sound clip must be at most {} seconds long",
+                MAX_SOUND_CLIP_DURATION_MS / 1000
+            )));
+        }
+        self.ensure_quota(owner_id, opus.len()).await?;
+
+        let media_id = Uuid::new_v4();
+        let checksum = sha256_hex(&opus);
+        let byte_len = opus.len() as i64;
+        let storage_key = self.put_blob(opus, &checksum, "audio/ogg").await?;
+
+        sqlx::query(
+            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status)
+             VALUES ($1, $2, NULL, NULL, 'audio/ogg', $3, $4, $5, 'ready')",
+        )
+        .bind(media_id)
+        .bind(owner_id)
+        .bind(byte_len)
+        .bind(&checksum)
+        .bind(&storage_key)
+        .execute(&self.db)
+        .await?;
+
+        Ok(SoundClipUpload {
+            id: media_id,
+            duration_ms,
+        })
+    }
+
+    /// Stores a finished stream recording (an MP4 remuxed from its HLS
+    /// restream, at `path`) as a ready media asset owned by `owner_id`.
+    pub async fn store_stream_recording(
+        &self,
+        owner_id: Uuid,
+        channel_id: Uuid,
+        started_at: chrono::DateTime<chrono::Utc>,
+        path: &Path,
+    ) -> Result<UploadResult, AppError> {
+        let byte_len = tokio::fs::metadata(path)
+            .await
+            .map_err(|error| AppError::Internal(format!("Failed to stat recording: {error}")))?
+            .len();
+        if byte_len == 0 {
+            return Err(AppError::Internal("Stream recording is empty".into()));
+        }
+        if byte_len > MAX_STREAM_RECORDING_BYTES {
+            return Err(AppError::BadRequest(format!(
+                "Stream recording exceeds maximum size of {} MB",
+                MAX_STREAM_RECORDING_BYTES / (1024 * 1024)
+            )));
+        }
+        self.ensure_quota(owner_id, byte_len as usize).await?;
+
+        let media_id = Uuid::new_v4();
+        let checksum = sha256_file_hex(path).await?;
+        let storage_key = self
+            .put_blob_file(path, byte_len, &checksum, "video/mp4")
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status)
+             VALUES ($1, $2, NULL, NULL, 'video/mp4', $3, $4, $5, 'ready')",
+        )
+        .bind(media_id)
+        .bind(owner_id)
+        .bind(byte_len as i64)
+        .bind(&checksum)
+        .bind(&storage_key)
+        .execute(&self.db)
+        .await?;
+
+        sqlx::query(
+            "INSERT INTO stream_recordings (media_id, channel_id, started_by, started_at)
+             VALUES ($1, $2, $3, $4)",
+        )
+        .bind(media_id)
+        .bind(channel_id)
+        .bind(owner_id)
+        .bind(started_at)
+        .execute(&self.db)
+        .await?;
+
+        Ok(UploadResult {
+            id: media_id,
+            status: "ready".to_string(),
+        })
+    }
+
+    /// Bytes and media rows owned by `owner_id`, derivatives included.
+    pub async fn storage_usage(&self, owner_id: Uuid) -> Result<StorageUsage, AppError> {
+        let (used_bytes, media_count): (i64, i64) = sqlx::query_as(
+            "SELECT COALESCE(SUM(bytes), 0)::BIGINT, COUNT(*)
+             FROM media_assets
+             WHERE owner_id = $1",
+        )
+        .bind(owner_id)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(StorageUsage {
+            used_bytes: used_bytes.max(0) as u64,
+            media_count: media_count.max(0) as u64,
+            quota_bytes: self.quota.user_bytes,
+        })
+    }
+
+    /// Bytes stored across the instance, counting each shared blob once.
+    pub async fn total_storage_bytes(&self) -> Result<u64, AppError> {
+        let total: i64 = sqlx::query_scalar(
+            "SELECT COALESCE(SUM(bytes), 0)::BIGINT
+             FROM (SELECT DISTINCT ON (storage_key) bytes FROM media_assets) stored",
+        )
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(total.max(0) as u64)
+    }
+
+    pub fn storage_quota(&self) -> StorageQuota {
+        self.quota
+    }
+
+    pub async fn top_storage_consumers(
+        &self,
+        limit: i64,
+    ) -> Result<Vec<StorageConsumer>, AppError> {
+        let consumers = sqlx::query_as(
+            "SELECT u.id AS user_id,
+                    u.username,
+                    COALESCE(SUM(m.bytes), 0)::BIGINT AS used_bytes,
+                    COUNT(*) AS media_count
+             FROM media_assets m
+             JOIN users u ON u.id = m.owner_id
+             GROUP BY u.id, u.username
+             ORDER BY used_bytes DESC, u.username ASC
+             LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(consumers)
+    }
+
+    /// Deletes every media family `owner_id` uploaded and clears their
+    /// avatar. Attachments, emojis, sound clips and recordings backed by
+    /// that media cascade away with it; messages keep their text.
+    pub async fn purge_user_media(&self, owner_id: Uuid) -> Result<u64, AppError> {
+        let parent_rows: Vec<(Uuid,)> = sqlx::query_as(
+            "SELECT id FROM media_assets WHERE owner_id = $1 AND derivative_kind IS NULL",
+        )
+        .bind(owner_id)
+        .fetch_all(&self.db)
+        .await?;
+
+        sqlx::query("UPDATE users SET avatar_url = NULL WHERE id = $1")
+            .bind(owner_id)
+            .execute(&self.db)
+            .await?;
+
+        let mut deleted_count = 0_u64;
+        for (parent_id,) in parent_rows {
+            if self.delete_media_family(parent_id).await? {
+                deleted_count += 1;
+            }
+        }
+
+        Ok(deleted_count)
+    }
+
+    pub async fn quarantined_uploads(&self) -> Result<Vec<QuarantinedAsset>, AppError> {
+        let assets = sqlx::query_as(
+            "SELECT m.id AS media_id,
+                    m.owner_id,
+                    u.username,
+                    m.original_filename AS filename,
+                    m.mime_type,
+                    m.bytes,
+                    m.error_message,
+                    m.quarantined_at
+             FROM media_assets m
+             LEFT JOIN users u ON u.id = m.owner_id
+             WHERE m.quarantined_at IS NOT NULL
+             ORDER BY m.quarantined_at DESC",
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(assets)
+    }
+
+    /// Deletes a quarantined upload and its object. Returns `false` when
+    /// `media_id` is not quarantined.
+    pub async fn delete_quarantined_upload(&self, media_id: Uuid) -> Result<bool, AppError> {
+        let quarantined: bool = sqlx::query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM media_assets WHERE id = $1 AND quarantined_at IS NOT NULL)",
+        )
+        .bind(media_id)
+        .fetch_one(&self.db)
+        .await?;
+        if !quarantined {
+            return Ok(false);
+        }
+
+        self.delete_media_family(media_id).await
+    }
+
+    /// Concurrent uploads can each pass this check, so a user may overshoot
+    /// their quota by at most a few in-flight uploads.
+    async fn ensure_quota(&self, owner_id: Uuid, incoming_bytes: usize) -> Result<(), AppError> {
+        if self.quota.is_unlimited() {
+            return Ok(());
+        }
+
+        let user_used = match self.quota.user_bytes {
+            Some(_) => self.storage_usage(owner_id).await?.used_bytes,
+            None => 0,
+        };
+        let total_used = match self.quota.total_bytes {
+            Some(_) => self.total_storage_bytes().await?,
+            None => 0,
+        };
+
+        self.quota
+            .check(user_used, total_used, incoming_bytes as u64)
+    }
+
     pub async fn cleanup_derivatives(&self, failed_retention_hours: i64) -> Result<u64, AppError> {
         let candidates: Vec<(Uuid, String)> = sqlx::query_as(
             "SELECT d.id, d.storage_key
@@ -317,9 +753,16 @@ impl UploadService {
                         AND p.updated_at < now() - make_interval(hours => ($1 * 4)::int)
                     )
                )
+               AND p.quarantined_at IS NULL
                AND NOT EXISTS (
                     SELECT 1 FROM message_attachments ma WHERE ma.media_id = p.id
                )
+               AND NOT EXISTS (
+                    SELECT 1 FROM stream_recordings sr WHERE sr.media_id = p.id
+               )
+               AND NOT EXISTS (
+                    SELECT 1 FROM soundboard_clips sc WHERE sc.media_id = p.id
+               )
                AND NOT EXISTS (
                     SELECT 1
                     FROM media_assets d
@@ -349,16 +792,31 @@ impl UploadService {
 
         let source_bytes = self.storage.read(&original.storage_key).await?;
 
-        let image = image::load_from_memory(&source_bytes)
-            .map_err(|error| AppError::BadRequest(format!("Unsupported image payload: {error}")))?;
-
-        self.write_derivative(
-            media_id,
-            original.owner_id,
-            "thumbnail",
-            image.thumbnail(320, 320),
-        )
-        .await?;
+        let metadata::NormalizedImage {
+            image,
+            had_metadata,
+        } = metadata::decode_normalized(&source_bytes)?;
+
+        let (width, _) = image.dimensions();
+        let mut placeholder = None;
+        for (index, bound) in THUMBNAIL_WIDTHS.into_iter().enumerate() {
+            // Larger sizes are only worth writing when they are smaller than
+            // the original; the narrowest one is always written.
+            if index > 0 && width <= bound {
+                break;
+            }
+            let thumbnail = fit_to_width(&image, bound);
+            if placeholder.is_none() {
+                placeholder = Some(placeholder::thumbhash_base64(&thumbnail));
+            }
+            self.write_derivative(
+                media_id,
+                original.owner_id,
+                &format!("{THUMBNAIL_KIND_PREFIX}{bound}"),
+                thumbnail,
+            )
+            .await?;
+        }
 
         let (width, height) = image.dimensions();
         let optimized_original = if width > 1920 || height > 1920 {
@@ -366,9 +824,51 @@ impl UploadService {
         } else {
             image
         };
-        self.rewrite_original_as_webp(&original, optimized_original)
+        self.rewrite_original_as_webp(&original, optimized_original, had_metadata)
+            .await?;
+
+        sqlx::query(
+            "UPDATE media_assets
+             SET status = 'ready', error_message = NULL, placeholder = $2, updated_at = now()
+             WHERE id = $1",
+        )
+        .bind(media_id)
+        .bind(placeholder)
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn process_video(
+        &self,
+        media_id: Uuid,
+        ffmpeg_bin: &str,
+        ffprobe_bin: &str,
+    ) -> Result<(), AppError> {
+        let _permit = self
+            .video_jobs
+            .acquire()
+            .await
+            .map_err(|_| AppError::Internal("Video processing queue closed".into()))?;
+
+        let original: MediaAsset = sqlx::query_as("SELECT * FROM media_assets WHERE id = $1")
+            .bind(media_id)
+            .fetch_one(&self.db)
             .await?;
 
+        let work_dir = video::work_dir_for(media_id);
+        tokio::fs::create_dir_all(&work_dir)
+            .await
+            .map_err(|error| {
+                AppError::Internal(format!("Failed to create video work directory: {error}"))
+            })?;
+        let result = self
+            .process_video_in(&original, &work_dir, ffmpeg_bin, ffprobe_bin)
+            .await;
+        let _ = tokio::fs::remove_dir_all(&work_dir).await;
+        result?;
+
         sqlx::query("UPDATE media_assets SET status = 'ready', error_message = NULL, updated_at = now() WHERE id = $1")
             .bind(media_id)
             .execute(&self.db)
@@ -377,19 +877,112 @@ impl UploadService {
         Ok(())
     }
 
+    /// Probes the upload, adds a web-safe variant when browsers cannot play
+    /// it as-is, and writes the poster frame last so a ready poster means
+    /// the video is about to turn ready.
+    async fn process_video_in(
+        &self,
+        original: &MediaAsset,
+        work_dir: &Path,
+        ffmpeg_bin: &str,
+        ffprobe_bin: &str,
+    ) -> Result<(), AppError> {
+        let source_bytes = self.storage.read(&original.storage_key).await?;
+        tokio::fs::write(work_dir.join(video::INPUT_NAME), source_bytes)
+            .await
+            .map_err(|error| {
+                AppError::Internal(format!("Failed to stage video upload: {error}"))
+            })?;
+
+        let probe = video::probe(ffprobe_bin, work_dir).await?;
+        sqlx::query(
+            "UPDATE media_assets SET width = $2, height = $3, duration_ms = $4, updated_at = now() WHERE id = $1",
+        )
+        .bind(original.id)
+        .bind(probe.width as i32)
+        .bind(probe.height as i32)
+        .bind(i32::try_from(probe.duration_ms).unwrap_or(i32::MAX))
+        .execute(&self.db)
+        .await?;
+
+        if self.video_transcode != VideoTranscodeTarget::Off
+            && !probe.is_web_safe(&original.mime_type)
+        {
+            let bytes = video::transcode(ffmpeg_bin, work_dir, self.video_transcode).await?;
+            self.write_video_derivative(original, "web", self.video_transcode, bytes, &probe)
+                .await?;
+        }
+
+        let poster = video::extract_poster(ffmpeg_bin, work_dir, &probe).await?;
+        let poster = image::load_from_memory(&poster)
+            .map_err(|error| AppError::Internal(format!("Invalid poster frame: {error}")))?;
+        let poster = poster.thumbnail(video::POSTER_MAX_DIMENSION, video::POSTER_MAX_DIMENSION);
+        sqlx::query("UPDATE media_assets SET placeholder = $2, updated_at = now() WHERE id = $1")
+            .bind(original.id)
+            .bind(placeholder::thumbhash_base64(&poster))
+            .execute(&self.db)
+            .await?;
+        self.write_derivative(original.id, original.owner_id, "poster", poster)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn write_video_derivative(
+        &self,
+        original: &MediaAsset,
+        derivative_kind: &str,
+        target: VideoTranscodeTarget,
+        bytes: Vec<u8>,
+        probe: &video::VideoProbe,
+    ) -> Result<(), AppError> {
+        let derivative_id = Uuid::new_v4();
+        let storage_key = format!(
+            "derivatives/{}/{derivative_kind}.{}",
+            original.id,
+            target.file_name().rsplit('.').next().unwrap_or("bin"),
+        );
+        let checksum = sha256_hex(&bytes);
+        let byte_len = bytes.len() as i64;
+
+        self.storage
+            .put(&storage_key, bytes, target.mime_type())
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO media_assets (id, owner_id, parent_id, derivative_kind, mime_type, bytes, checksum, storage_key, status, width, height, duration_ms)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'ready', $9, $10, $11)",
+        )
+        .bind(derivative_id)
+        .bind(original.owner_id)
+        .bind(original.id)
+        .bind(derivative_kind)
+        .bind(target.mime_type())
+        .bind(byte_len)
+        .bind(checksum)
+        .bind(storage_key)
+        .bind(probe.width as i32)
+        .bind(probe.height as i32)
+        .bind(i32::try_from(probe.duration_ms).unwrap_or(i32::MAX))
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Replaces the stored original with `image` re-encoded as WebP, which
+    /// leaves behind any EXIF, XMP, IPTC or ICC data the upload carried.
     async fn rewrite_original_as_webp(
         &self,
         original: &MediaAsset,
         image: image::DynamicImage,
+        metadata_stripped: bool,
     ) -> Result<(), AppError> {
         let (width, height) = image.dimensions();
         let bytes = encode_webp(&image)?;
-        let storage_key = format!("original/{}.webp", original.id);
         let checksum = sha256_hex(&bytes);
-
-        self.storage
-            .put(&storage_key, bytes.clone(), "image/webp")
-            .await?;
+        let byte_len = bytes.len() as i64;
+        let storage_key = self.put_blob(bytes, &checksum, "image/webp").await?;
 
         sqlx::query(
             "UPDATE media_assets
@@ -399,20 +992,22 @@ impl UploadService {
                  storage_key = $4,
                  width = $5,
                  height = $6,
+                 metadata_stripped = metadata_stripped OR $7,
                  updated_at = now()
              WHERE id = $1",
         )
         .bind(original.id)
-        .bind(bytes.len() as i64)
+        .bind(byte_len)
         .bind(checksum)
         .bind(&storage_key)
         .bind(width as i32)
         .bind(height as i32)
+        .bind(metadata_stripped)
         .execute(&self.db)
         .await?;
 
         if original.storage_key != storage_key {
-            if let Err(error) = self.storage.delete(&original.storage_key).await {
+            if let Err(error) = self.release_storage_key(&original.storage_key).await {
                 tracing::warn!(
                     media_id = %original.id,
                     old_storage_key = %original.storage_key,
@@ -433,8 +1028,8 @@ impl UploadService {
 
         let source_bytes = self.storage.read(&original.storage_key).await?;
 
-        let image = image::load_from_memory(&source_bytes)
-            .map_err(|error| AppError::BadRequest(format!("Unsupported image payload: {error}")))?;
+        // The original was stripped on upload and is kept as it is.
+        let metadata::NormalizedImage { image, .. } = metadata::decode_normalized(&source_bytes)?;
 
         let cropped = crop_square(&image);
 
@@ -552,8 +1147,13 @@ impl UploadService {
             return Ok(false);
         }
 
+        sqlx::query("DELETE FROM media_assets WHERE id = $1 OR parent_id = $1")
+            .bind(parent_id)
+            .execute(&self.db)
+            .await?;
+
         for (storage_key,) in assets {
-            if let Err(error) = self.storage.delete(&storage_key).await {
+            if let Err(error) = self.release_storage_key(&storage_key).await {
                 tracing::warn!(
                     parent_id = %parent_id,
                     storage_key = %storage_key,
@@ -563,12 +1163,141 @@ impl UploadService {
             }
         }
 
-        sqlx::query("DELETE FROM media_assets WHERE id = $1 OR parent_id = $1")
-            .bind(parent_id)
+        Ok(true)
+    }
+
+    /// Deletes blobs no media row has referenced for the claim grace period,
+    /// such as those left behind when accounts or channels cascade-delete
+    /// their uploads.
+    pub async fn cleanup_unreferenced_blobs(&self) -> Result<u64, AppError> {
+        let candidates: Vec<(String,)> = sqlx::query_as(
+            "SELECT storage_key
+             FROM media_blobs
+             WHERE ref_count <= 0
+               AND updated_at < now() - make_interval(mins => $1)",
+        )
+        .bind(BLOB_CLAIM_GRACE_MINUTES)
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut deleted_count = 0_u64;
+        for (storage_key,) in candidates {
+            match self.release_storage_key(&storage_key).await {
+                Ok(true) => deleted_count += 1,
+                Ok(false) => {}
+                Err(error) => {
+                    tracing::warn!(
+                        storage_key = %storage_key,
+                        error = ?error,
+                        "Failed to delete unreferenced blob during cleanup"
+                    );
+                }
+            }
+        }
+
+        Ok(deleted_count)
+    }
+
+    /// Stores `bytes` once per distinct SHA-256 and returns the shared
+    /// storage key. Reference counts follow `media_assets` through a
+    /// trigger, so callers only insert (or repoint) their row afterwards;
+    /// claiming the blob also restarts its grace period so cleanup cannot
+    /// remove it in between.
+    async fn put_blob(
+        &self,
+        bytes: Vec<u8>,
+        checksum: &str,
+        mime_type: &str,
+    ) -> Result<String, AppError> {
+        let (storage_key, created) = self.claim_blob(checksum, bytes.len() as u64).await?;
+        if created {
+            if let Err(error) = self.storage.put(&storage_key, bytes, mime_type).await {
+                self.forget_unstored_blob(&storage_key).await;
+                return Err(error);
+            }
+        }
+
+        Ok(storage_key)
+    }
+
+    /// `put_blob` for content too large to hold in memory, copied from the
+    /// file at `path`.
+    async fn put_blob_file(
+        &self,
+        path: &Path,
+        byte_len: u64,
+        checksum: &str,
+        mime_type: &str,
+    ) -> Result<String, AppError> {
+        let (storage_key, created) = self.claim_blob(checksum, byte_len).await?;
+        if created {
+            if let Err(error) = self.storage.put_file(&storage_key, path, mime_type).await {
+                self.forget_unstored_blob(&storage_key).await;
+                return Err(error);
+            }
+        }
+
+        Ok(storage_key)
+    }
+
+    /// Claims the blob for `checksum`, returning its storage key and whether
+    /// the row is new and its object still has to be stored.
+    async fn claim_blob(&self, checksum: &str, byte_len: u64) -> Result<(String, bool), AppError> {
+        let storage_key = blob_storage_key(checksum);
+        let (created,): (bool,) = sqlx::query_as(
+            "INSERT INTO media_blobs (storage_key, checksum, bytes)
+             VALUES ($1, $2, $3)
+             ON CONFLICT (storage_key) DO UPDATE SET updated_at = now()
+             RETURNING (xmax = 0)",
+        )
+        .bind(&storage_key)
+        .bind(checksum)
+        .bind(byte_len as i64)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok((storage_key, created))
+    }
+
+    async fn forget_unstored_blob(&self, storage_key: &str) {
+        let _ = sqlx::query("DELETE FROM media_blobs WHERE storage_key = $1 AND ref_count <= 0")
+            .bind(storage_key)
             .execute(&self.db)
-            .await?;
+            .await;
+    }
 
-        Ok(true)
+    /// Deletes the object behind `storage_key` unless it is a blob still
+    /// referenced, or claimed within the grace period, in which case it is
+    /// left for `cleanup_unreferenced_blobs`. Returns whether the object
+    /// was deleted.
+    async fn release_storage_key(&self, storage_key: &str) -> Result<bool, AppError> {
+        if !is_blob_storage_key(storage_key) {
+            self.storage.delete(storage_key).await?;
+            return Ok(true);
+        }
+
+        // The row lock taken by the delete makes a concurrent claim of the
+        // same blob wait until the object is gone, then store it afresh.
+        let mut tx = self.db.begin().await?;
+        let released = sqlx::query(
+            "DELETE FROM media_blobs
+             WHERE storage_key = $1
+               AND ref_count <= 0
+               AND updated_at < now() - make_interval(mins => $2)",
+        )
+        .bind(storage_key)
+        .bind(BLOB_CLAIM_GRACE_MINUTES)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if released {
+            self.storage.delete(storage_key).await?;
+        }
+        tx.commit().await?;
+
+        Ok(released)
     }
 }
 
@@ -599,16 +1328,6 @@ fn validate_avatar_mime_type(mime_type: &str) -> Result<(), AppError> {
     }
 }
 
-fn extension_for_mime(mime_type: &str) -> &'static str {
-    match mime_type {
-        "image/jpeg" => "jpg",
-        "image/png" => "png",
-        "image/webp" => "webp",
-        "image/gif" => "gif",
-        _ => "bin",
-    }
-}
-
 fn sniff_mime_type(bytes: &[u8]) -> Result<&'static str, AppError> {
     if bytes.len() >= 3 && bytes[0] == 0xFF && bytes[1] == 0xD8 && bytes[2] == 0xFF {
         return Ok("image/jpeg");
@@ -640,12 +1359,38 @@ fn sniff_mime_type(bytes: &[u8]) -> Result<&'static str, AppError> {
     ))
 }
 
+/// Content-addressed key for a blob, fanned out by the first byte of its
+/// checksum so no single directory grows unbounded.
+fn blob_storage_key(checksum: &str) -> String {
+    format!("blobs/{}/{checksum}", &checksum[..2])
+}
+
+fn is_blob_storage_key(storage_key: &str) -> bool {
+    storage_key.starts_with("blobs/")
+}
+
 fn sha256_hex(bytes: &[u8]) -> String {
     let mut hasher = Sha256::new();
     hasher.update(bytes);
     hex::encode(hasher.finalize())
 }
 
+async fn sha256_file_hex(path: &Path) -> Result<String, AppError> {
+    let hash_error =
+        |error: std::io::Error| AppError::Internal(format!("Failed to hash file: {error}"));
+    let mut file = tokio::fs::File::open(path).await.map_err(hash_error)?;
+    let mut hasher = Sha256::new();
+    let mut chunk = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut chunk).await.map_err(hash_error)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&chunk[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
 fn encode_webp(image: &image::DynamicImage) -> Result<Vec<u8>, AppError> {
     let mut cursor = Cursor::new(Vec::<u8>::new());
     image
@@ -702,6 +1447,33 @@ const MAX_IMAGE_UPLOAD_DIMENSION: u32 = 8192;
 const MAX_AVATAR_UPLOAD_DIMENSION: u32 = 4096;
 const MAX_EMOJI_DIMENSION: u32 = 128;
 const MAX_EMOJI_BYTES: usize = 512 * 1024;
+const MAX_SOUND_CLIP_UPLOAD_BYTES: usize = 2 * 1024 * 1024;
+const MAX_SOUND_CLIP_DURATION_MS: u64 = 10_000;
+/// Largest stream recording stored; the restream remux is capped below it.
+pub const MAX_STREAM_RECORDING_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+const MAX_CONCURRENT_VIDEO_JOBS: usize = 2;
+const QUARANTINE_EVENT_CAPACITY: usize = 16;
+/// Widths of the `thumb_<width>` derivatives written for processed images,
+/// narrowest first, so clients can pick one that fits their layout.
+const THUMBNAIL_WIDTHS: [u32; 3] = [320, 640, 1280];
+pub const THUMBNAIL_KIND_PREFIX: &str = "thumb_";
+/// Very tall images are scaled to fit this multiple of the width bound so
+/// their thumbnails do not grow without limit.
+const THUMBNAIL_MAX_ASPECT: u32 = 4;
+/// How long an unreferenced blob survives after its last claim, covering
+/// the gap between an upload claiming it and inserting its media row.
+const BLOB_CLAIM_GRACE_MINUTES: i32 = 15;
+
+/// Scales `image` down to at most `bound` pixels wide, keeping its aspect
+/// ratio. Images already that narrow are returned unscaled.
+fn fit_to_width(image: &image::DynamicImage, bound: u32) -> image::DynamicImage {
+    let max_height = bound.saturating_mul(THUMBNAIL_MAX_ASPECT);
+    let (width, height) = image.dimensions();
+    if width <= bound && height <= max_height {
+        return image.clone();
+    }
+    image.thumbnail(bound, max_height)
+}
 
 fn validate_image_dimensions(
     bytes: &[u8],
@@ -730,3 +1502,150 @@ fn validate_image_dimensions(
 
     Ok((width, height))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+
+    const PDF: &[u8] = b"%PDF-1.4\n% shared attachment\n";
+
+    async fn upload_service(db: PgPool) -> (UploadService, std::path::PathBuf) {
+        let root = std::env::temp_dir().join(format!("yankcord-blob-test-{}", Uuid::new_v4()));
+        let config: StorageConfig =
+            serde_json::from_value(serde_json::json!({ "local_root": root })).unwrap();
+        let storage = LocalStorage::new(config.local_root.clone()).await.unwrap();
+        (UploadService::new(db, Arc::new(storage), &config), root)
+    }
+
+    async fn create_user(db: &PgPool) -> Uuid {
+        let user_id = Uuid::new_v4();
+        sqlx::query("INSERT INTO users (id, username, password_hash) VALUES ($1, $2, '')")
+            .bind(user_id)
+            .bind(format!("user-{user_id}"))
+            .execute(db)
+            .await
+            .unwrap();
+        user_id
+    }
+
+    async fn upload_pdf(uploads: &UploadService, owner_id: Uuid) -> Uuid {
+        uploads
+            .upload_file(
+                owner_id,
+                Some("notes.pdf"),
+                PDF.to_vec(),
+                "ffmpeg",
+                "ffprobe",
+            )
+            .await
+            .unwrap()
+            .id
+    }
+
+    async fn storage_key(db: &PgPool, media_id: Uuid) -> String {
+        sqlx::query_scalar("SELECT storage_key FROM media_assets WHERE id = $1")
+            .bind(media_id)
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    async fn ref_count(db: &PgPool, storage_key: &str) -> Option<i32> {
+        sqlx::query_scalar("SELECT ref_count FROM media_blobs WHERE storage_key = $1")
+            .bind(storage_key)
+            .fetch_optional(db)
+            .await
+            .unwrap()
+    }
+
+    /// Moves a blob's last claim to just before the cleanup grace period.
+    async fn expire_claim(db: &PgPool, storage_key: &str) {
+        sqlx::query(
+            "UPDATE media_blobs
+             SET updated_at = now() - make_interval(mins => $2 + 1)
+             WHERE storage_key = $1",
+        )
+        .bind(storage_key)
+        .bind(BLOB_CLAIM_GRACE_MINUTES)
+        .execute(db)
+        .await
+        .unwrap();
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs DATABASE_URL pointing at a PostgreSQL server"]
+    async fn identical_uploads_share_one_blob(db: PgPool) {
+        let (uploads, root) = upload_service(db.clone()).await;
+        let (alice, bob) = (create_user(&db).await, create_user(&db).await);
+
+        let first = upload_pdf(&uploads, alice).await;
+        let second = upload_pdf(&uploads, bob).await;
+
+        let key = storage_key(&db, first).await;
+        assert_ne!(first, second);
+        assert_eq!(storage_key(&db, second).await, key);
+        assert_eq!(key, blob_storage_key(&sha256_hex(PDF)));
+        assert_eq!(ref_count(&db, &key).await, Some(2));
+        let blob_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM media_blobs")
+            .fetch_one(&db)
+            .await
+            .unwrap();
+        assert_eq!(blob_count, 1);
+        assert_eq!(std::fs::read(root.join(&key)).unwrap(), PDF);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs DATABASE_URL pointing at a PostgreSQL server"]
+    async fn blob_is_released_once_every_row_is_deleted(db: PgPool) {
+        let (uploads, root) = upload_service(db.clone()).await;
+        let owner_id = create_user(&db).await;
+        let first = upload_pdf(&uploads, owner_id).await;
+        let second = upload_pdf(&uploads, owner_id).await;
+        let key = storage_key(&db, first).await;
+
+        assert!(uploads.delete_media_family(first).await.unwrap());
+        assert_eq!(ref_count(&db, &key).await, Some(1));
+        assert!(root.join(&key).exists());
+
+        assert!(uploads.delete_media_family(second).await.unwrap());
+        assert_eq!(ref_count(&db, &key).await, Some(0));
+        // Still inside the grace period, so the object survives for now.
+        assert!(root.join(&key).exists());
+
+        expire_claim(&db, &key).await;
+        assert_eq!(uploads.cleanup_unreferenced_blobs().await.unwrap(), 1);
+        assert_eq!(ref_count(&db, &key).await, None);
+        assert!(!root.join(&key).exists());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[sqlx::test]
+    #[ignore = "needs DATABASE_URL pointing at a PostgreSQL server"]
+    async fn cleanup_spares_blobs_claimed_within_the_grace_period(db: PgPool) {
+        let (uploads, root) = upload_service(db.clone()).await;
+        let owner_id = create_user(&db).await;
+        let media_id = upload_pdf(&uploads, owner_id).await;
+        let key = storage_key(&db, media_id).await;
+        assert!(uploads.delete_media_family(media_id).await.unwrap());
+        expire_claim(&db, &key).await;
+
+        // An upload of the same bytes claims the blob but has not inserted
+        // its media row yet.
+        let (claimed_key, created) = uploads
+            .claim_blob(&sha256_hex(PDF), PDF.len() as u64)
+            .await
+            .unwrap();
+        assert_eq!(claimed_key, key);
+        assert!(!created);
+
+        assert_eq!(uploads.cleanup_unreferenced_blobs().await.unwrap(), 0);
+        assert_eq!(ref_count(&db, &key).await, Some(0));
+        assert!(root.join(&key).exists());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -0,0 +1,178 @@
+use base64::Engine;
+use image::{imageops::FilterType, DynamicImage, GenericImageView};
+use std::f32::consts::PI;
+
+/// ThumbHash encodes at most 100x100 pixels; larger inputs add nothing.
+const MAX_DIMENSION: u32 = 100;
+
+/// Encodes a ThumbHash (https://evanw.github.io/thumbhash/) of `image` as
+/// standard base64. The hash keeps the aspect ratio, average color and
+/// alpha, so clients can size and paint a blurred stand-in in ~30 bytes.
+pub(super) fn thumbhash_base64(image: &DynamicImage) -> String {
+    let small = if image.width() > MAX_DIMENSION || image.height() > MAX_DIMENSION {
+        image.resize(MAX_DIMENSION, MAX_DIMENSION, FilterType::Triangle)
+    } else {
+        image.clone()
+    };
+    let (width, height) = small.dimensions();
+    let hash = rgba_to_thumbhash(width as usize, height as usize, small.to_rgba8().as_raw());
+    base64::engine::general_purpose::STANDARD.encode(hash)
+}
+
+/// Port of the reference encoder: the image is converted to LPQA channels
+/// (luminance, yellow-blue, red-green, alpha) and each is reduced to a few
+/// DCT coefficients quantized to 4 bits.
+fn rgba_to_thumbhash(width: usize, height: usize, rgba: &[u8]) -> Vec<u8> {
+    let pixel_count = (width * height) as f32;
+
+    let (mut avg_r, mut avg_g, mut avg_b, mut avg_a) = (0.0, 0.0, 0.0, 0.0);
+    for pixel in rgba.chunks_exact(4) {
+        let alpha = pixel[3] as f32 / 255.0;
+        avg_r += alpha / 255.0 * pixel[0] as f32;
+        avg_g += alpha / 255.0 * pixel[1] as f32;
+        avg_b += alpha / 255.0 * pixel[2] as f32;
+        avg_a += alpha;
+    }
+    if avg_a > 0.0 {
+        avg_r /= avg_a;
+        avg_g /= avg_a;
+        avg_b /= avg_a;
+    }
+
+    // Transparent images spend fewer bits on luminance to make room for alpha.
+    let has_alpha = avg_a < pixel_count;
+    let l_limit = if has_alpha { 5.0 } else { 7.0 };
+    let longest = width.max(height) as f32;
+    let lx = (l_limit * width as f32 / longest).round().max(1.0) as usize;
+    let ly = (l_limit * height as f32 / longest).round().max(1.0) as usize;
+
+    let mut l = Vec::with_capacity(width * height);
+    let mut p = Vec::with_capacity(width * height);
+    let mut q = Vec::with_capacity(width * height);
+    let mut a = Vec::with_capacity(width * height);
+    for pixel in rgba.chunks_exact(4) {
+        // Composite over the average color so transparent areas stay neutral.
+        let alpha = pixel[3] as f32 / 255.0;
+        let r = avg_r * (1.0 - alpha) + alpha / 255.0 * pixel[0] as f32;
+        let g = avg_g * (1.0 - alpha) + alpha / 255.0 * pixel[1] as f32;
+        let b = avg_b * (1.0 - alpha) + alpha / 255.0 * pixel[2] as f32;
+        l.push((r + g + b) / 3.0);
+        p.push((r + g) / 2.0 - b);
+        q.push(r - g);
+        a.push(alpha);
+    }
+
+    let encode_channel = |channel: &[f32], nx: usize, ny: usize| -> (f32, Vec<f32>, f32) {
+        let mut dc = 0.0;
+        let mut ac = Vec::with_capacity(nx * ny / 2);
+        let mut scale = 0.0_f32;
+        let mut fx = vec![0.0; width];
+        for cy in 0..ny {
+            let mut cx = 0;
+            while cx * ny < nx * (ny - cy) {
+                for (x, factor) in fx.iter_mut().enumerate() {
+                    *factor = (PI / width as f32 * cx as f32 * (x as f32 + 0.5)).cos();
+                }
+                let mut f = 0.0;
+                for y in 0..height {
+                    let fy = (PI / height as f32 * cy as f32 * (y as f32 + 0.5)).cos();
+                    for (x, factor) in fx.iter().enumerate() {
+                        f += channel[x + y * width] * factor * fy;
+                    }
+                }
+                f /= pixel_count;
+                if cx > 0 || cy > 0 {
+                    ac.push(f);
+                    scale = scale.max(f.abs());
+                } else {
+                    dc = f;
+                }
+                cx += 1;
+            }
+        }
+        if scale > 0.0 {
+            for value in &mut ac {
+                *value = 0.5 + 0.5 / scale * *value;
+            }
+        }
+        (dc, ac, scale)
+    };
+
+    let (l_dc, l_ac, l_scale) = encode_channel(&l, lx.max(3), ly.max(3));
+    let (p_dc, p_ac, p_scale) = encode_channel(&p, 3, 3);
+    let (q_dc, q_ac, q_scale) = encode_channel(&q, 3, 3);
+    let (a_dc, a_ac, a_scale) = if has_alpha {
+        encode_channel(&a, 5, 5)
+    } else {
+        (1.0, Vec::new(), 1.0)
+    };
+
+    let is_landscape = width > height;
+    let header24 = (63.0 * l_dc).round() as u32
+        | ((31.5 + 31.5 * p_dc).round() as u32) << 6
+        | ((31.5 + 31.5 * q_dc).round() as u32) << 12
+        | ((31.0 * l_scale).round() as u32) << 18
+        | u32::from(has_alpha) << 23;
+    let header16 = (if is_landscape { ly } else { lx }) as u16
+        | ((63.0 * p_scale).round() as u16) << 3
+        | ((63.0 * q_scale).round() as u16) << 9
+        | u16::from(is_landscape) << 15;
+
+    let mut hash = vec![
+        (header24 & 0xff) as u8,
+        ((header24 >> 8) & 0xff) as u8,
+        (header24 >> 16) as u8,
+        (header16 & 0xff) as u8,
+        (header16 >> 8) as u8,
+    ];
+    if has_alpha {
+        hash.push((15.0 * a_dc).round() as u8 | ((15.0 * a_scale).round() as u8) << 4);
+    }
+
+    let mut is_odd = false;
+    for value in l_ac.into_iter().chain(p_ac).chain(q_ac).chain(a_ac) {
+        let nibble = (15.0 * value).round() as u8;
+        if is_odd {
+            if let Some(last) = hash.last_mut() {
+                *last |= nibble << 4;
+            }
+        } else {
+            hash.push(nibble);
+        }
+        is_odd = !is_odd;
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    #[test]
+    fn encodes_orientation_and_alpha_in_the_header() {
+        let landscape = RgbaImage::from_pixel(40, 20, Rgba([30, 90, 200, 255]));
+        let hash = rgba_to_thumbhash(40, 20, landscape.as_raw());
+        assert_eq!(hash[4] & 0x80, 0x80, "landscape bit");
+        assert_eq!(hash[2] & 0x80, 0, "opaque images carry no alpha");
+
+        let mut translucent = RgbaImage::from_pixel(20, 40, Rgba([255, 255, 255, 255]));
+        translucent.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        let hash = rgba_to_thumbhash(20, 40, translucent.as_raw());
+        assert_eq!(hash[4] & 0x80, 0, "portrait images clear the landscape bit");
+        assert_eq!(hash[2] & 0x80, 0x80, "alpha bit");
+    }
+
+    #[test]
+    fn downscales_large_images_before_hashing() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(2000, 1000, Rgb([10, 10, 10])));
+        let encoded = thumbhash_base64(&image);
+        let hash = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        // Hashed at 100x50: 5 header bytes, then 4-bit AC terms for L (18),
+        // P and Q (5 each).
+        assert_eq!(hash.len(), 5 + (18 + 5 + 5) / 2);
+    }
+}
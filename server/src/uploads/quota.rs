@@ -0,0 +1,97 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::config::StorageConfig;
+use crate::errors::AppError;
+
+/// Storage limits checked before every user upload; `None` is unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageQuota {
+    pub user_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+}
+
+impl StorageQuota {
+    pub fn from_config(config: &StorageConfig) -> Self {
+        Self {
+            user_bytes: (config.user_quota_bytes > 0).then_some(config.user_quota_bytes),
+            total_bytes: (config.total_quota_bytes > 0).then_some(config.total_quota_bytes),
+        }
+    }
+
+    pub(super) fn is_unlimited(&self) -> bool {
+        self.user_bytes.is_none() && self.total_bytes.is_none()
+    }
+
+    /// Rejects `incoming` bytes that would take the uploader past their
+    /// quota or the instance past its total.
+    pub(super) fn check(
+        &self,
+        user_used: u64,
+        total_used: u64,
+        incoming: u64,
+    ) -> Result<(), AppError> {
+        if let Some(quota) = self.user_bytes {
+            if user_used.saturating_add(incoming) > quota {
+                return Err(AppError::BadRequest(format!(
+                    "Upload exceeds your storage quota of {quota} bytes ({user_used} bytes used)"
+                )));
+            }
+        }
+
+        if let Some(quota) = self.total_bytes {
+            if total_used.saturating_add(incoming) > quota {
+                return Err(AppError::BadRequest(
+                    "Server storage is full; ask an admin to free up space".into(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StorageUsage {
+    pub used_bytes: u64,
+    pub media_count: u64,
+    pub quota_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct StorageConsumer {
+    pub user_id: Uuid,
+    pub username: String,
+    pub used_bytes: i64,
+    pub media_count: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_disables_limits() {
+        let quota = StorageQuota::from_config(&StorageConfig {
+            user_quota_bytes: 0,
+            total_quota_bytes: 0,
+            ..StorageConfig::default()
+        });
+
+        assert!(quota.is_unlimited());
+        assert!(quota.check(u64::MAX, u64::MAX, u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn rejects_uploads_past_either_limit() {
+        let quota = StorageQuota {
+            user_bytes: Some(100),
+            total_bytes: Some(1000),
+        };
+
+        assert!(quota.check(60, 500, 40).is_ok());
+        assert!(quota.check(61, 500, 40).is_err());
+        assert!(quota.check(0, 990, 11).is_err());
+        assert!(quota.check(0, 0, 101).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::{files, sha256_hex, UploadService};
+use crate::errors::AppError;
+
+/// How long an unfinished upload can be resumed before it is discarded.
+const RESUMABLE_UPLOAD_TTL_HOURS: i32 = 24;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ResumableUpload {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub upload_length: i64,
+    pub upload_offset: i64,
+    pub chunk_offsets: Vec<i64>,
+    pub filename: Option<String>,
+    pub expected_sha256: Option<String>,
+    pub media_id: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ResumableUpload {
+    pub fn is_complete(&self) -> bool {
+        self.upload_offset == self.upload_length
+    }
+}
+
+/// Parses tus `Upload-Metadata`: comma-separated `key base64(value)` pairs
+/// where the value may be left out.
+pub fn parse_upload_metadata(header: &str) -> Result<HashMap<String, String>, AppError> {
+    let mut metadata = HashMap::new();
+    for pair in header
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let (key, encoded) = pair.split_once(' ').unwrap_or((pair, ""));
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(|| {
+                AppError::BadRequest(format!("Upload-Metadata value for '{key}' is not base64"))
+            })?;
+        metadata.insert(key.to_string(), decoded);
+    }
+
+    Ok(metadata)
+}
+
+/// Parses a tus `Upload-Checksum` header into the SHA-256 digest it names;
+/// other algorithms are rejected.
+pub fn parse_upload_checksum(header: &str) -> Result<Vec<u8>, AppError> {
+    let (algorithm, encoded) = header.trim().split_once(' ').ok_or_else(|| {
+        AppError::BadRequest("Upload-Checksum must be '<algorithm> <base64>'".into())
+    })?;
+    if !algorithm.eq_ignore_ascii_case("sha256") {
+        return Err(AppError::BadRequest(format!(
+            "Unsupported Upload-Checksum algorithm '{algorithm}'"
+        )));
+    }
+
+    base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .filter(|digest| digest.len() == 32)
+        .ok_or_else(|| AppError::BadRequest("Upload-Checksum digest is not valid".into()))
+}
+
+pub fn chunk_matches_checksum(bytes: &[u8], digest: &[u8]) -> bool {
+    Sha256::digest(bytes).as_slice() == digest
+}
+
+/// Where the chunk received at `offset` is staged until the upload
+/// completes.
+pub fn chunk_storage_key(upload_id: Uuid, offset: i64) -> String {
+    format!("uploads/{upload_id}/{offset:020}")
+}
+
+impl UploadService {
+    /// Starts a tus upload of `upload_length` bytes. Size and quota are
+    /// checked now so a client learns before sending anything; the type
+    /// policy runs once the content can be sniffed.
+    pub async fn create_resumable_upload(
+        &self,
+        owner_id: Uuid,
+        upload_length: u64,
+        metadata: &HashMap<String, String>,
+    ) -> Result<ResumableUpload, AppError> {
+        if upload_length == 0 {
+            return Err(AppError::BadRequest("Upload payload is empty".into()));
+        }
+
+        if upload_length > self.file_policy.max_bytes as u64 {
+            return Err(AppError::PayloadTooLarge(format!(
+                "File exceeds limit of {} bytes",
+                self.file_policy.max_bytes,
+            )));
+        }
+
+        self.ensure_quota(owner_id, upload_length as usize).await?;
+
+        let filename = metadata
+            .get("filename")
+            .or_else(|| metadata.get("name"))
+            .map(|filename| files::sanitize_filename(Some(filename)));
+        let expected_sha256 = metadata
+            .get("sha256")
+            .map(|digest| digest.trim().to_ascii_lowercase());
+        if let Some(digest) = &expected_sha256 {
+            if digest.len() != 64 || !digest.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                return Err(AppError::BadRequest(
+                    "Upload-Metadata sha256 must be a hex SHA-256 digest".into(),
+                ));
+            }
+        }
+
+        let upload = sqlx::query_as(
+            "INSERT INTO resumable_uploads (id, owner_id, upload_length, filename, expected_sha256, expires_at)
+             VALUES ($1, $2, $3, $4, $5, now() + make_interval(hours => $6))
+             RETURNING id, owner_id, upload_length, upload_offset, chunk_offsets, filename, expected_sha256, media_id, expires_at",
+        )
+        .bind(Uuid::new_v4())
+        .bind(owner_id)
+        .bind(upload_length as i64)
+        .bind(filename)
+        .bind(expected_sha256)
+        .bind(RESUMABLE_UPLOAD_TTL_HOURS)
+        .fetch_one(&self.db)
+        .await?;
+
+        Ok(upload)
+    }
+
+    pub async fn resumable_upload(
+        &self,
+        owner_id: Uuid,
+        upload_id: Uuid,
+    ) -> Result<ResumableUpload, AppError> {
+        sqlx::query_as(
+            "SELECT id, owner_id, upload_length, upload_offset, chunk_offsets, filename, expected_sha256, media_id, expires_at
+             FROM resumable_uploads
+             WHERE id = $1 AND owner_id = $2 AND expires_at > now()",
+        )
+        .bind(upload_id)
+        .bind(owner_id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Upload not found".into()))
+    }
+
+    /// Stages `bytes` received at `offset`. The PATCH that brings the upload
+    /// to its full length (or an empty one retrying after a failed hand-off)
+    /// verifies the checksum and passes the file to `upload_file`, all under
+    /// the row lock so a file is only ever handed off once.
+    pub async fn append_resumable_chunk(
+        &self,
+        owner_id: Uuid,
+        upload_id: Uuid,
+        offset: u64,
+        bytes: Vec<u8>,
+        ffmpeg_bin: &str,
+        ffprobe_bin: &str,
+    ) -> Result<ResumableUpload, AppError> {
+        let mut tx = self.db.begin().await?;
+        let mut upload: ResumableUpload = sqlx::query_as(
+            "SELECT id, owner_id, upload_length, upload_offset, chunk_offsets, filename, expected_sha256, media_id, expires_at
+             FROM resumable_uploads
+             WHERE id = $1 AND owner_id = $2 AND expires_at > now()
+             FOR UPDATE",
+        )
+        .bind(upload_id)
+        .bind(owner_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Upload not found".into()))?;
+
+        if upload.media_id.is_some() || offset != upload.upload_offset as u64 {
+            return Err(AppError::Conflict(format!(
+                "Upload-Offset does not match the current offset {}",
+                upload.upload_offset
+            )));
+        }
+
+        if offset + bytes.len() as u64 > upload.upload_length as u64 {
+            return Err(AppError::BadRequest(
+                "Chunk extends past Upload-Length".into(),
+            ));
+        }
+
+        if !bytes.is_empty() {
+            let byte_len = bytes.len() as i64;
+            let chunk_offset = upload.upload_offset;
+            self.storage
+                .put(
+                    &chunk_storage_key(upload.id, chunk_offset),
+                    bytes,
+                    "application/octet-stream",
+                )
+                .await?;
+
+            sqlx::query(
+                "UPDATE resumable_uploads
+                 SET upload_offset = upload_offset + $2,
+                     chunk_offsets = array_append(chunk_offsets, $3)
+                 WHERE id = $1",
+            )
+            .bind(upload.id)
+            .bind(byte_len)
+            .bind(chunk_offset)
+            .execute(&mut *tx)
+            .await?;
+            upload.upload_offset += byte_len;
+            upload.chunk_offsets.push(chunk_offset);
+        }
+
+        if !upload.is_complete() {
+            tx.commit().await?;
+            return Ok(upload);
+        }
+
+        match self
+            .finish_resumable_upload(&upload, ffmpeg_bin, ffprobe_bin)
+            .await
+        {
+            Ok(uploaded) => {
+                sqlx::query("UPDATE resumable_uploads SET media_id = $2 WHERE id = $1")
+                    .bind(upload.id)
+                    .bind(uploaded.id)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                upload.media_id = Some(uploaded.id);
+                self.delete_resumable_chunks(upload.id, &upload.chunk_offsets)
+                    .await;
+                Ok(upload)
+            }
+            Err(error) => {
+                sqlx::query("DELETE FROM resumable_uploads WHERE id = $1")
+                    .bind(upload.id)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                self.delete_resumable_chunks(upload.id, &upload.chunk_offsets)
+                    .await;
+                Err(error)
+            }
+        }
+    }
+
+    pub async fn cancel_resumable_upload(
+        &self,
+        owner_id: Uuid,
+        upload_id: Uuid,
+    ) -> Result<(), AppError> {
+        let (chunk_offsets,): (Vec<i64>,) = sqlx::query_as(
+            "DELETE FROM resumable_uploads WHERE id = $1 AND owner_id = $2 RETURNING chunk_offsets",
+        )
+        .bind(upload_id)
+        .bind(owner_id)
+        .fetch_optional(&self.db)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Upload not found".into()))?;
+
+        self.delete_resumable_chunks(upload_id, &chunk_offsets)
+            .await;
+        Ok(())
+    }
+
+    /// Drops expired uploads along with any chunks they staged.
+    pub async fn cleanup_expired_resumable_uploads(&self) -> Result<u64, AppError> {
+        let expired: Vec<(Uuid, Vec<i64>)> = sqlx::query_as(
+            "DELETE FROM resumable_uploads WHERE expires_at < now() RETURNING id, chunk_offsets",
+        )
+        .fetch_all(&self.db)
+        .await?;
+
+        for (upload_id, chunk_offsets) in &expired {
+            self.delete_resumable_chunks(*upload_id, chunk_offsets)
+                .await;
+        }
+
+        Ok(expired.len() as u64)
+    }
+
+    async fn finish_resumable_upload(
+        &self,
+        upload: &ResumableUpload,
+        ffmpeg_bin: &str,
+        ffprobe_bin: &str,
+    ) -> Result<super::UploadResult, AppError> {
+        let mut bytes = Vec::with_capacity(upload.upload_length as usize);
+        for offset in &upload.chunk_offsets {
+            bytes.extend(
+                self.storage
+                    .read(&chunk_storage_key(upload.id, *offset))
+                    .await?,
+            );
+        }
+
+        if bytes.len() as i64 != upload.upload_length {
+            return Err(AppError::Internal(format!(
+                "Staged upload {} has {} bytes, expected {}",
+                upload.id,
+                bytes.len(),
+                upload.upload_length
+            )));
+        }
+
+        if let Some(expected) = &upload.expected_sha256 {
+            if sha256_hex(&bytes) != *expected {
+                return Err(AppError::BadRequest(
+                    "Upload does not match its declared sha256 checksum".into(),
+                ));
+            }
+        }
+
+        self.upload_file(
+            upload.owner_id,
+            upload.filename.as_deref(),
+            bytes,
+            ffmpeg_bin,
+            ffprobe_bin,
+        )
+        .await
+    }
+
+    async fn delete_resumable_chunks(&self, upload_id: Uuid, chunk_offsets: &[i64]) {
+        for offset in chunk_offsets {
+            let storage_key = chunk_storage_key(upload_id, *offset);
+            if let Err(error) = self.storage.delete(&storage_key).await {
+                tracing::warn!(
+                    upload_id = %upload_id,
+                    storage_key = %storage_key,
+                    error = ?error,
+                    "Failed to delete staged upload chunk"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_tus_metadata_and_checksums() {
+        let metadata = parse_upload_metadata("filename cmVwb3J0LnBkZg==,is_confidential").unwrap();
+        assert_eq!(
+            metadata.get("filename").map(String::as_str),
+            Some("report.pdf")
+        );
+        assert_eq!(
+            metadata.get("is_confidential").map(String::as_str),
+            Some("")
+        );
+        assert!(parse_upload_metadata("filename not-base64!").is_err());
+
+        let digest = Sha256::digest(b"hello");
+        let header = format!(
+            "sha256 {}",
+            base64::engine::general_purpose::STANDARD.encode(digest)
+        );
+        let parsed = parse_upload_checksum(&header).unwrap();
+        assert!(chunk_matches_checksum(b"hello", &parsed));
+        assert!(!chunk_matches_checksum(b"hellp", &parsed));
+        assert!(parse_upload_checksum("md5 XUFAKrxLKna5cZ2REBfFkg==").is_err());
+    }
+
+    #[test]
+    fn chunk_keys_sort_by_offset() {
+        let upload_id = Uuid::nil();
+        assert!(chunk_storage_key(upload_id, 9) < chunk_storage_key(upload_id, 10));
+        assert_eq!(
+            chunk_storage_key(upload_id, 42),
+            format!("uploads/{upload_id}/00000000000000000042")
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+use uuid::Uuid;
+
+use crate::config::StorageConfig;
+use crate::errors::AppError;
+
+/// Size of each length-prefixed INSTREAM chunk.
+const CHUNK_BYTES: usize = 64 * 1024;
+const SCAN_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_REPLY_BYTES: usize = 4096;
+
+/// Sent to subscribers when an upload is quarantined, so admins can be told.
+#[derive(Debug, Clone)]
+pub struct QuarantinedUpload {
+    pub media_id: Uuid,
+    pub owner_id: Uuid,
+    pub filename: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct QuarantinedAsset {
+    pub media_id: Uuid,
+    pub owner_id: Uuid,
+    pub username: Option<String>,
+    pub filename: Option<String>,
+    pub mime_type: String,
+    pub bytes: i64,
+    pub error_message: Option<String>,
+    pub quarantined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum ClamdAddress {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl ClamdAddress {
+    /// `unix:/run/clamav/clamd.ctl` or a bare absolute path for a Unix
+    /// socket, `host:port` for TCP. Empty means scanning is off.
+    pub(super) fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        if let Some(path) = value.strip_prefix("unix:") {
+            return Some(Self::Unix(PathBuf::from(path)));
+        }
+        if value.starts_with('/') {
+            return Some(Self::Unix(PathBuf::from(value)));
+        }
+        Some(Self::Tcp(
+            value.strip_prefix("tcp://").unwrap_or(value).to_string(),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum ScanVerdict {
+    Clean,
+    /// Carries the signature clamd matched, e.g. `Eicar-Test-Signature`.
+    Infected(String),
+}
+
+/// Streams uploads to clamd with the INSTREAM command.
+#[derive(Debug, Clone)]
+pub(super) struct ClamdScanner {
+    address: ClamdAddress,
+}
+
+impl ClamdScanner {
+    pub(super) fn from_config(config: &StorageConfig) -> Option<Self> {
+        config
+            .clamd_address
+            .as_deref()
+            .and_then(ClamdAddress::parse)
+            .map(|address| Self { address })
+    }
+
+    pub(super) async fn scan(&self, bytes: &[u8]) -> Result<ScanVerdict, AppError> {
+        let reply = tokio::time::timeout(SCAN_TIMEOUT, async {
+            match &self.address {
+                ClamdAddress::Tcp(address) => {
+                    instream(TcpStream::connect(address).await?, bytes).await
+                }
+                ClamdAddress::Unix(path) => instream(UnixStream::connect(path).await?, bytes).await,
+            }
+        })
+        .await
+        .map_err(|_| AppError::Internal("Malware scan timed out".into()))?
+        .map_err(|error| AppError::Internal(format!("Malware scan failed: {error}")))?;
+
+        parse_reply(&reply)
+    }
+}
+
+/// Sends `bytes` as length-prefixed chunks ending in a zero-length one and
+/// returns clamd's NUL-terminated reply.
+async fn instream<S>(mut stream: S, bytes: &[u8]) -> std::io::Result<String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let sent = async {
+        stream.write_all(b"zINSTREAM\0").await?;
+        for chunk in bytes.chunks(CHUNK_BYTES) {
+            stream
+                .write_all(&(chunk.len() as u32).to_be_bytes())
+                .await?;
+            stream.write_all(chunk).await?;
+        }
+        stream.write_all(&0_u32.to_be_bytes()).await?;
+        stream.flush().await
+    }
+    .await;
+
+    // clamd answers and hangs up mid-stream when the upload is over its
+    // size limit, so a failed write may still have a reply to read.
+    let mut reply = Vec::new();
+    let mut buffer = [0_u8; 512];
+    while reply.len() < MAX_REPLY_BYTES {
+        let read = match stream.read(&mut buffer).await {
+            Ok(read) => read,
+            Err(error) if reply.is_empty() => return Err(sent.err().unwrap_or(error)),
+            Err(_) => break,
+        };
+        if read == 0 {
+            break;
+        }
+        reply.extend_from_slice(&buffer[..read]);
+        if reply.contains(&0) {
+            break;
+        }
+    }
+
+    if reply.is_empty() {
+        sent?;
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "clamd closed the connection without a reply",
+        ));
+    }
+
+    let end = reply
+        .iter()
+        .position(|byte| *byte == 0)
+        .unwrap_or(reply.len());
+    Ok(String::from_utf8_lossy(&reply[..end]).into_owned())
+}
+
+fn parse_reply(reply: &str) -> Result<ScanVerdict, AppError> {
+    let reply = reply.trim();
+    let result = reply.strip_prefix("stream:").unwrap_or(reply).trim();
+
+    if result == "OK" {
+        return Ok(ScanVerdict::Clean);
+    }
+    if let Some(signature) = result.strip_suffix(" FOUND") {
+        return Ok(ScanVerdict::Infected(signature.trim().to_string()));
+    }
+    Err(AppError::Internal(format!(
+        "Malware scanner returned an unexpected reply: {reply}"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::{TcpListener, UnixListener};
+
+    const EICAR: &[u8] = b"X5O!P%@AP[4\\PZX54(P^)7CC)7}$EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*";
+
+    /// Minimal clamd: reads one INSTREAM request and flags the EICAR string.
+    async fn serve_one<S>(mut stream: S) -> Vec<u8>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut command = [0_u8; 10];
+        stream.read_exact(&mut command).await.unwrap();
+        assert_eq!(&command, b"zINSTREAM\0");
+
+        let mut received = Vec::new();
+        loop {
+            let mut length = [0_u8; 4];
+            stream.read_exact(&mut length).await.unwrap();
+            let length = u32::from_be_bytes(length) as usize;
+            if length == 0 {
+                break;
+            }
+            let mut chunk = vec![0_u8; length];
+            stream.read_exact(&mut chunk).await.unwrap();
+            received.extend_from_slice(&chunk);
+        }
+
+        let infected = received.windows(EICAR.len()).any(|window| window == EICAR);
+        let reply: &[u8] = if infected {
+            b"stream: Eicar-Test-Signature FOUND\0"
+        } else {
+            b"stream: OK\0"
+        };
+        stream.write_all(reply).await.unwrap();
+        received
+    }
+
+    #[test]
+    fn parses_addresses() {
+        assert_eq!(ClamdAddress::parse(" "), None);
+        assert_eq!(
+            ClamdAddress::parse("unix:/run/clamav/clamd.ctl"),
+            Some(ClamdAddress::Unix("/run/clamav/clamd.ctl".into()))
+        );
+        assert_eq!(
+            ClamdAddress::parse("/tmp/clamd.sock"),
+            Some(ClamdAddress::Unix("/tmp/clamd.sock".into()))
+        );
+        assert_eq!(
+            ClamdAddress::parse("tcp://clamav:3310"),
+            Some(ClamdAddress::Tcp("clamav:3310".into()))
+        );
+    }
+
+    #[test]
+    fn parses_replies() {
+        assert_eq!(parse_reply("stream: OK").unwrap(), ScanVerdict::Clean);
+        assert_eq!(
+            parse_reply("stream: Win.Test.EICAR_HDB-1 FOUND").unwrap(),
+            ScanVerdict::Infected("Win.Test.EICAR_HDB-1".into())
+        );
+        assert!(parse_reply("INSTREAM size limit exceeded. ERROR").is_err());
+    }
+
+    #[tokio::test]
+    async fn scans_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let scanner = ClamdScanner {
+            address: ClamdAddress::Tcp(listener.local_addr().unwrap().to_string()),
+        };
+
+        // Large enough to span several chunks.
+        let clean = vec![7_u8; CHUNK_BYTES * 2 + 5];
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            serve_one(stream).await
+        });
+        assert_eq!(scanner.scan(&clean).await.unwrap(), ScanVerdict::Clean);
+        assert_eq!(server.await.unwrap(), clean);
+    }
+
+    #[tokio::test]
+    async fn scans_over_unix_socket() {
+        let path =
+            std::env::temp_dir().join(format!("yankcord-clamd-{}.sock", uuid::Uuid::new_v4()));
+        let listener = UnixListener::bind(&path).unwrap();
+        let scanner = ClamdScanner {
+            address: ClamdAddress::Unix(path.clone()),
+        };
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            serve_one(stream).await
+        });
+        let mut infected = b"attachment: ".to_vec();
+        infected.extend_from_slice(EICAR);
+        assert_eq!(
+            scanner.scan(&infected).await.unwrap(),
+            ScanVerdict::Infected("Eicar-Test-Signature".into())
+        );
+        server.await.unwrap();
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,402 @@
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use tokio::process::Command;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+const POSTER_TIMEOUT: Duration = Duration::from_secs(60);
+const TRANSCODE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+const POSTER_OFFSET_MS: u64 = 1000;
+pub(super) const INPUT_NAME: &str = "input";
+pub(super) const POSTER_MAX_DIMENSION: u32 = 1280;
+
+/// Whether an uploaded file goes through video processing.
+pub(super) fn is_processable_video(mime_type: &str) -> bool {
+    matches!(mime_type, "video/mp4" | "video/webm" | "video/quicktime")
+}
+
+/// Web-safe variant produced for videos browsers cannot play as uploaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoTranscodeTarget {
+    Off,
+    H264,
+    Vp9,
+}
+
+impl VideoTranscodeTarget {
+    pub fn from_config(value: &str) -> Self {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "off" | "none" | "false" => Self::Off,
+            "vp9" => Self::Vp9,
+            _ => Self::H264,
+        }
+    }
+
+    pub(super) fn mime_type(self) -> &'static str {
+        match self {
+            Self::Vp9 => "video/webm",
+            Self::Off | Self::H264 => "video/mp4",
+        }
+    }
+
+    pub(super) fn file_name(self) -> &'static str {
+        match self {
+            Self::Vp9 => "web.webm",
+            Self::Off | Self::H264 => "web.mp4",
+        }
+    }
+
+    fn codec_args(self) -> &'static [&'static str] {
+        match self {
+            Self::Vp9 => &[
+                "-c:v",
+                "libvpx-vp9",
+                "-b:v",
+                "0",
+                "-crf",
+                "33",
+                "-deadline",
+                "good",
+                "-cpu-used",
+                "4",
+                "-row-mt",
+                "1",
+                "-c:a",
+                "libopus",
+                "-b:a",
+                "128k",
+                "-f",
+                "webm",
+            ],
+            Self::Off | Self::H264 => &[
+                "-c:v",
+                "libx264",
+                "-preset",
+                "veryfast",
+                "-crf",
+                "23",
+                "-c:a",
+                "aac",
+                "-b:a",
+                "128k",
+                "-movflags",
+                "+faststart",
+                "-f",
+                "mp4",
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeStream {
+    codec_type: Option<String>,
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    duration: Option<String>,
+    #[serde(default)]
+    side_data_list: Vec<ProbeSideData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeSideData {
+    rotation: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+}
+
+/// What ffprobe reports about an uploaded video. Dimensions are as
+/// displayed, so phone clips recorded in portrait come out portrait.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct VideoProbe {
+    pub duration_ms: u64,
+    pub width: u32,
+    pub height: u32,
+    pub video_codec: String,
+    pub audio_codec: Option<String>,
+}
+
+impl VideoProbe {
+    fn parse(json: &[u8]) -> Option<Self> {
+        let output: ProbeOutput = serde_json::from_slice(json).ok()?;
+        let video = output
+            .streams
+            .iter()
+            .find(|stream| stream.codec_type.as_deref() == Some("video"))?;
+        let audio_codec = output
+            .streams
+            .iter()
+            .find(|stream| stream.codec_type.as_deref() == Some("audio"))
+            .and_then(|stream| stream.codec_name.clone());
+
+        let seconds = output
+            .format
+            .and_then(|format| format.duration)
+            .or_else(|| video.duration.clone())
+            .and_then(|duration| duration.parse::<f64>().ok())
+            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+            .unwrap_or(0.0);
+
+        let (mut width, mut height) = (video.width?, video.height?);
+        let rotation = video
+            .side_data_list
+            .iter()
+            .find_map(|side_data| side_data.rotation)
+            .unwrap_or(0);
+        if rotation.rem_euclid(180) == 90 {
+            std::mem::swap(&mut width, &mut height);
+        }
+
+        Some(Self {
+            duration_ms: (seconds * 1000.0).round() as u64,
+            width,
+            height,
+            video_codec: video.codec_name.clone()?,
+            audio_codec,
+        })
+    }
+
+    /// Whether browsers play the upload as-is, so no variant is needed.
+    pub(super) fn is_web_safe(&self, mime_type: &str) -> bool {
+        let audio = self.audio_codec.as_deref();
+        match mime_type {
+            "video/mp4" => {
+                self.video_codec == "h264" && matches!(audio, None | Some("aac") | Some("mp3"))
+            }
+            "video/webm" => {
+                matches!(self.video_codec.as_str(), "vp8" | "vp9" | "av1")
+                    && matches!(audio, None | Some("opus") | Some("vorbis"))
+            }
+            _ => false,
+        }
+    }
+
+    /// One second in, or halfway through clips shorter than two seconds, to
+    /// skip the black or faded first frame most clips start with.
+    fn poster_offset_ms(&self) -> u64 {
+        POSTER_OFFSET_MS.min(self.duration_ms / 2)
+    }
+}
+
+pub(super) fn work_dir_for(media_id: Uuid) -> PathBuf {
+    std::env::temp_dir()
+        .join("yankcord-videos")
+        .join(media_id.to_string())
+}
+
+async fn run(
+    bin: &str,
+    args: &[&str],
+    work_dir: &Path,
+    time_limit: Duration,
+    what: &str,
+) -> Result<Output, AppError> {
+    let output = tokio::time::timeout(
+        time_limit,
+        Command::new(bin)
+            .args(args)
+            .current_dir(work_dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| AppError::Internal(format!("Timed out while {what}")))?
+    .map_err(|error| AppError::Internal(format!("Failed to start {bin}: {error}")))?;
+
+    if !output.status.success() {
+        return Err(AppError::Internal(format!(
+            "{bin} failed while {what}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(output)
+}
+
+pub(super) async fn probe(ffprobe_bin: &str, work_dir: &Path) -> Result<VideoProbe, AppError> {
+    let output = run(
+        ffprobe_bin,
+        &[
+            "-v",
+            "error",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+            INPUT_NAME,
+        ],
+        work_dir,
+        PROBE_TIMEOUT,
+        "probing video",
+    )
+    .await?;
+
+    VideoProbe::parse(&output.stdout)
+        .ok_or_else(|| AppError::BadRequest("Upload has no decodable video stream".into()))
+}
+
+/// Grabs a single frame as PNG for the poster derivative.
+pub(super) async fn extract_poster(
+    ffmpeg_bin: &str,
+    work_dir: &Path,
+    probe: &VideoProbe,
+) -> Result<Vec<u8>, AppError> {
+    let offset = format!("{:.3}", probe.poster_offset_ms() as f64 / 1000.0);
+    let output = run(
+        ffmpeg_bin,
+        &[
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-ss",
+            &offset,
+            "-i",
+            INPUT_NAME,
+            "-frames:v",
+            "1",
+            "-f",
+            "image2pipe",
+            "-c:v",
+            "png",
+            "pipe:1",
+        ],
+        work_dir,
+        POSTER_TIMEOUT,
+        "extracting poster frame",
+    )
+    .await?;
+
+    if output.stdout.is_empty() {
+        return Err(AppError::Internal("ffmpeg produced no poster frame".into()));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Re-encodes the upload into `target.file_name()` inside `work_dir`, with
+/// even dimensions and 4:2:0 chroma so every browser decoder accepts it.
+pub(super) async fn transcode(
+    ffmpeg_bin: &str,
+    work_dir: &Path,
+    target: VideoTranscodeTarget,
+) -> Result<Vec<u8>, AppError> {
+    let mut args = vec![
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-nostats",
+        "-i",
+        INPUT_NAME,
+        "-map",
+        "0:v:0",
+        "-map",
+        "0:a:0?",
+        "-map_metadata",
+        "-1",
+        "-vf",
+        "scale=trunc(iw/2)*2:trunc(ih/2)*2",
+        "-pix_fmt",
+        "yuv420p",
+        "-ac",
+        "2",
+    ];
+    args.extend_from_slice(target.codec_args());
+    args.extend_from_slice(&["-y", target.file_name()]);
+
+    run(
+        ffmpeg_bin,
+        &args,
+        work_dir,
+        TRANSCODE_TIMEOUT,
+        "transcoding video",
+    )
+    .await?;
+
+    tokio::fs::read(work_dir.join(target.file_name()))
+        .await
+        .map_err(|error| AppError::Internal(format!("Failed to read transcoded video: {error}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probe_reads_duration_codecs_and_rotated_dimensions() {
+        let probe = VideoProbe::parse(
+            br#"{
+                "streams": [
+                    {"codec_type": "video", "codec_name": "hevc", "width": 1920, "height": 1080,
+                     "side_data_list": [{"rotation": -90}]},
+                    {"codec_type": "audio", "codec_name": "aac"}
+                ],
+                "format": {"duration": "4.2049"}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            probe,
+            VideoProbe {
+                duration_ms: 4205,
+                width: 1080,
+                height: 1920,
+                video_codec: "hevc".to_string(),
+                audio_codec: Some("aac".to_string()),
+            }
+        );
+        assert!(!probe.is_web_safe("video/quicktime"));
+        assert_eq!(probe.poster_offset_ms(), 1000);
+
+        assert!(VideoProbe::parse(br#"{"streams": [{"codec_type": "audio"}]}"#).is_none());
+    }
+
+    #[test]
+    fn web_safe_originals_skip_transcoding() {
+        let probe = |video: &str, audio: Option<&str>| VideoProbe {
+            duration_ms: 1200,
+            width: 640,
+            height: 360,
+            video_codec: video.to_string(),
+            audio_codec: audio.map(ToOwned::to_owned),
+        };
+
+        assert!(probe("h264", Some("aac")).is_web_safe("video/mp4"));
+        assert!(probe("h264", None).is_web_safe("video/mp4"));
+        assert!(!probe("hevc", Some("aac")).is_web_safe("video/mp4"));
+        assert!(probe("vp9", Some("opus")).is_web_safe("video/webm"));
+        assert!(!probe("h264", Some("aac")).is_web_safe("video/quicktime"));
+        assert_eq!(probe("h264", None).poster_offset_ms(), 600);
+
+        assert_eq!(
+            VideoTranscodeTarget::from_config("VP9"),
+            VideoTranscodeTarget::Vp9
+        );
+        assert_eq!(
+            VideoTranscodeTarget::from_config("off"),
+            VideoTranscodeTarget::Off
+        );
+        assert_eq!(
+            VideoTranscodeTarget::from_config(""),
+            VideoTranscodeTarget::H264
+        );
+    }
+}
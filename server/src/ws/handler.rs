@@ -6,6 +6,7 @@ use axum::{
     response::IntoResponse,
 };
 use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
 use tokio::time::timeout;
@@ -179,6 +180,17 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
     );
 
     let voice_presence_channels: Vec<VoicePresenceChannel> = {
+        let mut viewers_by_channel: HashMap<Uuid, (Vec<String>, usize)> = HashMap::new();
+        for viewer in state.whep_sessions.read().await.values() {
+            let (viewers, anonymous_viewers) =
+                viewers_by_channel.entry(viewer.channel_id).or_default();
+            match &viewer.username {
+                Some(username) if !viewers.contains(username) => viewers.push(username.clone()),
+                Some(_) => {}
+                None => *anonymous_viewers += 1,
+            }
+        }
+
         let voice_members_by_channel = state.voice_members_by_channel.read().await;
         let voice_mute_state_by_username = state.voice_mute_state_by_username.read().await;
         let mut channels: Vec<VoicePresenceChannel> = voice_members_by_channel
@@ -200,13 +212,30 @@ async fn handle_socket(socket: WebSocket, state: AppState) {
                         })
                     })
                     .collect();
+                let (mut viewers, anonymous_viewers) =
+                    viewers_by_channel.remove(channel_id).unwrap_or_default();
+                viewers.sort_unstable();
                 VoicePresenceChannel {
                     channel_id: *channel_id,
                     usernames: sorted_usernames,
                     mute_states,
+                    viewers,
+                    anonymous_viewers,
                 }
             })
             .collect();
+        channels.extend(viewers_by_channel.into_iter().map(
+            |(channel_id, (mut viewers, anonymous_viewers))| {
+                viewers.sort_unstable();
+                VoicePresenceChannel {
+                    channel_id,
+                    usernames: Vec::new(),
+                    mute_states: HashMap::new(),
+                    viewers,
+                    anonymous_viewers,
+                }
+            },
+        ));
         channels.sort_by_key(|entry| entry.channel_id);
         channels
     };
@@ -1138,6 +1167,7 @@ async fn handle_send_message(
                     | crate::errors::AppError::Unauthorized(message)
                     | crate::errors::AppError::NotFound(message)
                     | crate::errors::AppError::Conflict(message)
+                    | crate::errors::AppError::PayloadTooLarge(message)
                     | crate::errors::AppError::TooManyRequests(message)
                     | crate::errors::AppError::Internal(message) => message,
                 };
@@ -1,4 +1,6 @@
-use mediasoup::prelude::{DtlsParameters, MediaKind, RtpCapabilities, RtpParameters};
+use mediasoup::prelude::{
+    DtlsParameters, MediaKind, RtpCapabilities, RtpParameters, SctpStreamParameters,
+};
 use serde::Deserialize;
 use std::time::{Duration, Instant};
 use tokio::sync::mpsc;
@@ -7,8 +9,11 @@ use uuid::Uuid;
 use super::broadcast::{send_server_message, WsEnqueueResult};
 use super::messages::ServerMessage;
 use super::voice::{broadcast_closed_producers, broadcast_media_signal_to_voice_channel};
+use crate::media::data_channel::{MAX_DATA_CHANNEL_LABEL_CHARS, MAX_DATA_CHANNEL_PROTOCOL_CHARS};
 use crate::media::router::OpusConfig;
-use crate::media::transport::{ProducerSource, RoutingMode, TransportDirection};
+use crate::media::transport::{
+    ClosedProducer, ProducerSource, RoutingMode, TransportDirection, DATA_PRODUCER_SOURCE,
+};
 use crate::AppState;
 
 pub const MAX_MEDIA_SIGNAL_PAYLOAD_BYTES: usize = 32 * 1024;
@@ -36,12 +41,8 @@ async fn get_channel_opus_config(state: &AppState, channel_id: Uuid) -> OpusConf
         }
     };
 
-    row.map(|(bitrate, dtx, fec)| OpusConfig {
-        bitrate: bitrate.map(|b| b as u32),
-        dtx,
-        fec,
-    })
-    .unwrap_or_default()
+    row.map(|(bitrate, dtx, fec)| OpusConfig::from_channel_columns(bitrate, dtx, fec))
+        .unwrap_or_default()
 }
 
 #[derive(Debug, Deserialize)]
@@ -82,6 +83,29 @@ pub enum MediaSignalRequest {
     CreateNativeSenderSession {
         request_id: Option<String>,
         preferred_codecs: Option<Vec<String>>,
+        #[serde(default)]
+        include_audio: bool,
+        #[serde(default)]
+        enable_srtp: bool,
+    },
+    MediaGetStats {
+        request_id: Option<String>,
+    },
+    DataProduce {
+        request_id: Option<String>,
+        sctp_stream_parameters: SctpStreamParameters,
+        #[serde(default)]
+        label: String,
+        #[serde(default)]
+        protocol: String,
+    },
+    DataConsume {
+        request_id: Option<String>,
+        data_producer_id: String,
+    },
+    DataCloseProducer {
+        request_id: Option<String>,
+        data_producer_id: String,
     },
     ClientDiagnostic {
         request_id: Option<String>,
@@ -100,6 +124,10 @@ pub fn request_id_for(request: &MediaSignalRequest) -> Option<String> {
         | MediaSignalRequest::MediaResumeConsumer { request_id, .. }
         | MediaSignalRequest::MediaCloseProducer { request_id, .. }
         | MediaSignalRequest::CreateNativeSenderSession { request_id, .. }
+        | MediaSignalRequest::MediaGetStats { request_id }
+        | MediaSignalRequest::DataProduce { request_id, .. }
+        | MediaSignalRequest::DataConsume { request_id, .. }
+        | MediaSignalRequest::DataCloseProducer { request_id, .. }
         | MediaSignalRequest::ClientDiagnostic { request_id, .. } => request_id.clone(),
     }
 }
@@ -187,6 +215,27 @@ pub fn validate_media_signal_request_fields(
                 return Err("producer_id is invalid");
             }
         }
+        MediaSignalRequest::DataProduce {
+            label, protocol, ..
+        } => {
+            if label.chars().count() > MAX_DATA_CHANNEL_LABEL_CHARS {
+                return Err("label is too long");
+            }
+
+            if protocol.chars().count() > MAX_DATA_CHANNEL_PROTOCOL_CHARS {
+                return Err("protocol is too long");
+            }
+        }
+        MediaSignalRequest::DataConsume {
+            data_producer_id, ..
+        }
+        | MediaSignalRequest::DataCloseProducer {
+            data_producer_id, ..
+        } => {
+            if data_producer_id.is_empty() || data_producer_id.len() > MAX_ENTITY_ID_CHARS {
+                return Err("data_producer_id is invalid");
+            }
+        }
         MediaSignalRequest::ClientDiagnostic { event, detail, .. } => {
             if event.is_empty() || event.len() > 64 {
                 return Err("event is invalid");
@@ -199,6 +248,7 @@ pub fn validate_media_signal_request_fields(
             }
         }
         MediaSignalRequest::CreateNativeSenderSession { .. } => {}
+        MediaSignalRequest::MediaGetStats { .. } => {}
         MediaSignalRequest::GetRouterRtpCapabilities { .. } => {}
     }
 
@@ -260,6 +310,30 @@ pub fn resolve_producer_source(
     }
 }
 
+/// Username to announce a producer under: the voice member's, the WHIP
+/// publisher's for ingest sessions, or whoever played a soundboard clip.
+async fn producer_owner_username(state: &AppState, owner_connection_id: Uuid) -> Option<String> {
+    if let Some(username) = state
+        .connection_usernames
+        .read()
+        .await
+        .get(&owner_connection_id)
+    {
+        return Some(username.clone());
+    }
+
+    if let Some(session) = state.whip_sessions.read().await.get(&owner_connection_id) {
+        return Some(session.username.clone());
+    }
+
+    state
+        .soundboard_playbacks
+        .read()
+        .await
+        .get(&owner_connection_id)
+        .map(|playback| playback.username.clone())
+}
+
 pub fn resolve_routing_mode(requested: Option<&str>) -> Result<RoutingMode, String> {
     match requested {
         Some("sfu") | None => Ok(RoutingMode::Sfu),
@@ -532,6 +606,18 @@ pub async fn handle_media_signal_message(
                 .await
             {
                 Ok(transport) => {
+                    let ice_servers = match state
+                        .connection_user_ids
+                        .read()
+                        .await
+                        .get(&connection_id)
+                        .copied()
+                    {
+                        Some(user_id) => {
+                            crate::turn_server::ice_servers_for_user(&state.config, user_id)
+                        }
+                        None => Vec::new(),
+                    };
                     let send_outcome = send_media_signal_payload(
                         state,
                         connection_id,
@@ -543,6 +629,7 @@ pub async fn handle_media_signal_message(
                             "request_id": request_id,
                             "direction": direction.as_str(),
                             "transport": transport,
+                            "ice_servers": ice_servers,
                         }),
                     );
                     if send_outcome.should_disconnect() {
@@ -559,14 +646,9 @@ pub async fn handle_media_signal_message(
                             .await;
 
                         for producer in existing_producers {
-                            let producer_owner_username = {
-                                let connection_usernames = state.connection_usernames.read().await;
-                                connection_usernames
-                                    .get(&producer.owner_connection_id)
-                                    .cloned()
-                            };
-
-                            let Some(producer_owner_username) = producer_owner_username else {
+                            let Some(producer_owner_username) =
+                                producer_owner_username(state, producer.owner_connection_id).await
+                            else {
                                 tracing::warn!(
                                     producer_id = %producer.producer_id,
                                     owner_connection_id = %producer.owner_connection_id,
@@ -597,6 +679,41 @@ pub async fn handle_media_signal_message(
                                 return false;
                             }
                         }
+
+                        let existing_data_producers = state
+                            .media
+                            .list_channel_data_producers(channel_id, Some(connection_id))
+                            .await;
+
+                        for data_producer in existing_data_producers {
+                            let Some(owner_username) =
+                                producer_owner_username(state, data_producer.owner_connection_id)
+                                    .await
+                            else {
+                                continue;
+                            };
+
+                            let send_outcome = send_media_signal_payload(
+                                state,
+                                connection_id,
+                                username,
+                                out_tx,
+                                channel_id,
+                                serde_json::json!({
+                                    "action": "new_data_producer",
+                                    "data_producer_id": data_producer.data_producer_id,
+                                    "label": data_producer.label,
+                                    "protocol": data_producer.protocol,
+                                    "username": owner_username,
+                                }),
+                            );
+                            if send_outcome.should_disconnect() {
+                                return true;
+                            }
+                            if send_outcome.should_stop_processing() {
+                                return false;
+                            }
+                        }
                     }
                 }
                 Err(error_message) => {
@@ -904,7 +1021,8 @@ pub async fn handle_media_signal_message(
                 .close_producer_for_connection(connection_id, channel_id, &producer_id)
                 .await
             {
-                Ok(closed_producer) => {
+                Ok(closed_producers) => {
+                    let closed_producer = &closed_producers[0];
                     let send_outcome = send_media_signal_payload(
                         state,
                         connection_id,
@@ -926,8 +1044,219 @@ pub async fn handle_media_signal_message(
                         return false;
                     }
 
-                    broadcast_closed_producers(state, &[closed_producer], Some(connection_id))
-                        .await;
+                    broadcast_closed_producers(state, &closed_producers, Some(connection_id)).await;
+                }
+                Err(error_message) => {
+                    if send_media_signal_error(
+                        state,
+                        connection_id,
+                        username,
+                        out_tx,
+                        channel_id,
+                        request_id,
+                        &error_message,
+                    )
+                    .should_disconnect()
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+        MediaSignalRequest::MediaGetStats { request_id } => {
+            match state
+                .media
+                .connection_stats(connection_id, channel_id)
+                .await
+            {
+                Ok(stats) => {
+                    if send_media_signal_payload(
+                        state,
+                        connection_id,
+                        username,
+                        out_tx,
+                        channel_id,
+                        serde_json::json!({
+                            "action": "media_stats",
+                            "request_id": request_id,
+                            "stats": stats,
+                        }),
+                    )
+                    .should_disconnect()
+                    {
+                        return true;
+                    }
+                }
+                Err(error_message) => {
+                    if send_media_signal_error(
+                        state,
+                        connection_id,
+                        username,
+                        out_tx,
+                        channel_id,
+                        request_id,
+                        &error_message,
+                    )
+                    .should_disconnect()
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+        MediaSignalRequest::DataProduce {
+            request_id,
+            sctp_stream_parameters,
+            label,
+            protocol,
+        } => {
+            match state
+                .media
+                .create_data_producer_for_connection(
+                    connection_id,
+                    channel_id,
+                    sctp_stream_parameters,
+                    label,
+                    protocol,
+                )
+                .await
+            {
+                Ok(data_producer) => {
+                    let send_outcome = send_media_signal_payload(
+                        state,
+                        connection_id,
+                        username,
+                        out_tx,
+                        channel_id,
+                        serde_json::json!({
+                            "action": "data_produced",
+                            "request_id": request_id,
+                            "id": data_producer.id,
+                            "data_producer_id": data_producer.published.data_producer_id,
+                        }),
+                    );
+                    if send_outcome.should_disconnect() {
+                        return true;
+                    }
+                    if send_outcome.should_stop_processing() {
+                        return false;
+                    }
+
+                    broadcast_media_signal_to_voice_channel(
+                        state,
+                        channel_id,
+                        serde_json::json!({
+                            "action": "new_data_producer",
+                            "data_producer_id": data_producer.published.data_producer_id,
+                            "label": data_producer.published.label,
+                            "protocol": data_producer.published.protocol,
+                            "username": username,
+                        }),
+                        Some(connection_id),
+                    )
+                    .await;
+                }
+                Err(error_message) => {
+                    if send_media_signal_error(
+                        state,
+                        connection_id,
+                        username,
+                        out_tx,
+                        channel_id,
+                        request_id,
+                        &error_message,
+                    )
+                    .should_disconnect()
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+        MediaSignalRequest::DataConsume {
+            request_id,
+            data_producer_id,
+        } => {
+            match state
+                .media
+                .create_data_consumer_for_connection(connection_id, channel_id, &data_producer_id)
+                .await
+            {
+                Ok(data_consumer) => {
+                    if send_media_signal_payload(
+                        state,
+                        connection_id,
+                        username,
+                        out_tx,
+                        channel_id,
+                        serde_json::json!({
+                            "action": "data_consumer_created",
+                            "request_id": request_id,
+                            "data_consumer": data_consumer,
+                        }),
+                    )
+                    .should_disconnect()
+                    {
+                        return true;
+                    }
+                }
+                Err(error_message) => {
+                    if send_media_signal_error(
+                        state,
+                        connection_id,
+                        username,
+                        out_tx,
+                        channel_id,
+                        request_id,
+                        &error_message,
+                    )
+                    .should_disconnect()
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+        MediaSignalRequest::DataCloseProducer {
+            request_id,
+            data_producer_id,
+        } => {
+            match state
+                .media
+                .close_data_producer_for_connection(connection_id, channel_id, &data_producer_id)
+                .await
+            {
+                Ok(relay_id) => {
+                    let send_outcome = send_media_signal_payload(
+                        state,
+                        connection_id,
+                        username,
+                        out_tx,
+                        channel_id,
+                        serde_json::json!({
+                            "action": "media_data_producer_closed",
+                            "request_id": request_id,
+                            "id": data_producer_id,
+                        }),
+                    );
+                    if send_outcome.should_disconnect() {
+                        return true;
+                    }
+                    if send_outcome.should_stop_processing() {
+                        return false;
+                    }
+
+                    broadcast_closed_producers(
+                        state,
+                        &[ClosedProducer {
+                            channel_id,
+                            producer_id: relay_id,
+                            source: DATA_PRODUCER_SOURCE.to_string(),
+                            routing_mode: RoutingMode::Sfu.as_str().to_string(),
+                        }],
+                        Some(connection_id),
+                    )
+                    .await;
                 }
                 Err(error_message) => {
                     if send_media_signal_error(
@@ -949,6 +1278,8 @@ pub async fn handle_media_signal_message(
         MediaSignalRequest::CreateNativeSenderSession {
             request_id,
             preferred_codecs,
+            include_audio,
+            enable_srtp,
         } => {
             let opus_config = get_channel_opus_config(state, channel_id).await;
             match state
@@ -957,6 +1288,8 @@ pub async fn handle_media_signal_message(
                     connection_id,
                     channel_id,
                     preferred_codecs,
+                    include_audio,
+                    enable_srtp,
                     opus_config,
                 )
                 .await
@@ -984,6 +1317,11 @@ pub async fn handle_media_signal_message(
                             "profile_level_id": session.profile_level_id,
                             "codec": session.codec,
                             "available_codecs": session.available_codecs,
+                            "rtx": session.rtx,
+                            "transport_cc_extension_id": session.transport_cc_extension_id,
+                            "abs_send_time_extension_id": session.abs_send_time_extension_id,
+                            "audio": session.audio,
+                            "srtp": session.srtp,
                         }),
                     );
                     if send_outcome.should_disconnect() {
@@ -1007,6 +1345,23 @@ pub async fn handle_media_signal_message(
                         Some(connection_id),
                     )
                     .await;
+
+                    if let Some(audio) = &session.audio {
+                        broadcast_media_signal_to_voice_channel(
+                            state,
+                            channel_id,
+                            serde_json::json!({
+                                "action": "new_producer",
+                                "producer_id": audio.producer_id,
+                                "kind": "audio",
+                                "source": session.source,
+                                "routing_mode": session.routing_mode,
+                                "username": username,
+                            }),
+                            Some(connection_id),
+                        )
+                        .await;
+                    }
                 }
                 Err(error_message) => {
                     if send_media_signal_error(
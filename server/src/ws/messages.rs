@@ -9,6 +9,8 @@ pub struct VoicePresenceChannel {
     pub channel_id: Uuid,
     pub usernames: Vec<String>,
     pub mute_states: std::collections::HashMap<String, VoiceMuteState>,
+    pub viewers: Vec<String>,
+    pub anonymous_viewers: usize,
 }
 
 #[derive(Debug, Serialize)]
@@ -17,6 +19,15 @@ pub struct VoiceMuteState {
     pub speaker_muted: bool,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct VoiceMemberNetworkQuality {
+    pub username: String,
+    pub score: u8,
+    pub level: String,
+    pub rtt_ms: Option<f64>,
+    pub packet_loss: Option<f64>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct PresenceUser {
     pub username: String,
@@ -276,9 +287,40 @@ pub enum ServerMessage {
         speaker_muted: bool,
     },
 
+    #[serde(rename = "voice_network_quality")]
+    VoiceNetworkQuality {
+        channel_id: Uuid,
+        members: Vec<VoiceMemberNetworkQuality>,
+    },
+
+    #[serde(rename = "voice_channel_viewers")]
+    VoiceChannelViewers {
+        channel_id: Uuid,
+        viewers: Vec<String>,
+        anonymous_viewers: usize,
+    },
+
+    #[serde(rename = "soundboard_clip_played")]
+    SoundboardClipPlayed {
+        channel_id: Uuid,
+        clip_id: Uuid,
+        clip_name: String,
+        username: String,
+    },
+
     #[serde(rename = "media_signal")]
     MediaSignal {
         channel_id: Uuid,
         payload: serde_json::Value,
     },
+
+    /// Sent to operators and admins when the malware scanner quarantines
+    /// an upload.
+    #[serde(rename = "upload_quarantined")]
+    UploadQuarantined {
+        media_id: Uuid,
+        username: String,
+        filename: String,
+        signature: String,
+    },
 }
@@ -1,8 +1,11 @@
+use std::collections::HashMap;
 use uuid::Uuid;
 
-use super::broadcast::{send_server_message, WsEnqueueResult};
-use super::messages::ServerMessage;
-use crate::media::transport::ClosedProducer;
+use super::broadcast::{broadcast_global_message, send_server_message, WsEnqueueResult};
+use super::messages::{ServerMessage, VoiceMemberNetworkQuality};
+use crate::media::router::OpusConfig;
+use crate::media::stats::NetworkQuality;
+use crate::media::transport::{ClosedProducer, DATA_PRODUCER_SOURCE};
 use crate::AppState;
 
 pub async fn broadcast_media_signal_to_voice_channel(
@@ -101,17 +104,204 @@ pub async fn broadcast_closed_producers(
     exclude_connection_id: Option<Uuid>,
 ) {
     for closed in closed_producers {
-        broadcast_media_signal_to_voice_channel(
-            state,
-            closed.channel_id,
+        let payload = if closed.source == DATA_PRODUCER_SOURCE {
+            serde_json::json!({
+                "action": "data_producer_closed",
+                "data_producer_id": closed.producer_id,
+            })
+        } else {
             serde_json::json!({
                 "action": "producer_closed",
                 "producer_id": closed.producer_id,
                 "source": closed.source,
                 "routing_mode": closed.routing_mode,
-            }),
+            })
+        };
+        broadcast_media_signal_to_voice_channel(
+            state,
+            closed.channel_id,
+            payload,
             exclude_connection_id,
         )
         .await;
     }
 }
+
+/// Named WHEP viewers of a voice channel, sorted and deduplicated, and the
+/// number of anonymous stream-viewer link sessions.
+pub async fn channel_viewers(state: &AppState, channel_id: Uuid) -> (Vec<String>, usize) {
+    let whep_sessions = state.whep_sessions.read().await;
+    let mut viewers = Vec::new();
+    let mut anonymous_viewers = 0;
+    for viewer in whep_sessions.values() {
+        if viewer.channel_id != channel_id {
+            continue;
+        }
+        match &viewer.username {
+            Some(username) => viewers.push(username.clone()),
+            None => anonymous_viewers += 1,
+        }
+    }
+    viewers.sort_unstable();
+    viewers.dedup();
+    (viewers, anonymous_viewers)
+}
+
+pub async fn broadcast_channel_viewers(state: &AppState, channel_id: Uuid) {
+    let (viewers, anonymous_viewers) = channel_viewers(state, channel_id).await;
+    broadcast_global_message(
+        state,
+        ServerMessage::VoiceChannelViewers {
+            channel_id,
+            viewers,
+            anonymous_viewers,
+        },
+        None,
+    )
+    .await;
+}
+
+/// Moves a voice channel onto a router with the new Opus settings and asks
+/// every member to renegotiate their transports against it.
+pub async fn renegotiate_voice_channel_media(
+    state: &AppState,
+    channel_id: Uuid,
+    opus_config: OpusConfig,
+    reason: &str,
+) {
+    let renegotiated = match state
+        .media
+        .renegotiate_channel_media(channel_id, opus_config)
+        .await
+    {
+        Ok(renegotiated) => renegotiated,
+        Err(error) => {
+            tracing::error!(
+                channel_id = %channel_id,
+                error = %error,
+                "Failed to move voice channel onto a new router"
+            );
+            return;
+        }
+    };
+
+    if renegotiated.connection_ids.is_empty() {
+        return;
+    }
+
+    tracing::info!(
+        channel_id = %channel_id,
+        connections = renegotiated.connection_ids.len(),
+        closed_producers = renegotiated.closed_producers.len(),
+        reason = %reason,
+        "Requesting media renegotiation for voice channel"
+    );
+
+    request_media_renegotiation(state, &[channel_id], &renegotiated.closed_producers, reason).await;
+}
+
+/// Announces the closed producers and asks every voice member of the given
+/// channels to tear down and recreate their media transports.
+pub async fn request_media_renegotiation(
+    state: &AppState,
+    channel_ids: &[Uuid],
+    closed_producers: &[ClosedProducer],
+    reason: &str,
+) {
+    broadcast_closed_producers(state, closed_producers, None).await;
+
+    for channel_id in channel_ids {
+        broadcast_media_signal_to_voice_channel(
+            state,
+            *channel_id,
+            serde_json::json!({
+                "action": "media_renegotiate",
+                "reason": reason,
+            }),
+            None,
+        )
+        .await;
+    }
+}
+
+/// Last reported quality level per member, keyed by channel.
+pub type NetworkQualityLevels = HashMap<Uuid, HashMap<String, &'static str>>;
+
+/// Sends each voice channel one batched network quality snapshot, but only
+/// when a member's level changed since the last report or the membership
+/// did. `last_levels` carries that state between ticks.
+pub async fn broadcast_network_quality(state: &AppState, last_levels: &mut NetworkQualityLevels) {
+    let members_by_channel: HashMap<Uuid, Vec<Uuid>> = {
+        let voice_members_by_connection = state.voice_members_by_connection.read().await;
+        let mut members_by_channel: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+        for (connection_id, channel_id) in voice_members_by_connection.iter() {
+            members_by_channel
+                .entry(*channel_id)
+                .or_default()
+                .push(*connection_id);
+        }
+        members_by_channel
+    };
+    last_levels.retain(|channel_id, _| members_by_channel.contains_key(channel_id));
+
+    for (channel_id, member_connections) in members_by_channel {
+        let quality_by_connection = state.media.channel_network_quality(channel_id).await;
+
+        let mut updates: Vec<(String, NetworkQuality)> = {
+            let connection_usernames = state.connection_usernames.read().await;
+            quality_by_connection
+                .into_iter()
+                .filter_map(|(connection_id, quality)| {
+                    let username = connection_usernames.get(&connection_id)?;
+                    Some((username.clone(), quality))
+                })
+                .collect()
+        };
+        updates.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+        let levels: HashMap<String, &'static str> = updates
+            .iter()
+            .map(|(username, quality)| (username.clone(), quality.level))
+            .collect();
+        if last_levels.get(&channel_id) == Some(&levels) {
+            continue;
+        }
+        last_levels.insert(channel_id, levels);
+        if updates.is_empty() {
+            continue;
+        }
+
+        let members: Vec<VoiceMemberNetworkQuality> = updates
+            .into_iter()
+            .map(|(username, quality)| VoiceMemberNetworkQuality {
+                username,
+                score: quality.score,
+                level: quality.level.to_string(),
+                rtt_ms: quality.rtt_ms,
+                packet_loss: quality.packet_loss,
+            })
+            .collect();
+
+        let connections = state.ws_connections.read().await;
+        for connection_id in member_connections {
+            let Some(tx) = connections.get(&connection_id) else {
+                continue;
+            };
+
+            let message = ServerMessage::VoiceNetworkQuality {
+                channel_id,
+                members: members.clone(),
+            };
+            if send_server_message(tx, message) == WsEnqueueResult::QueueFull {
+                state.telemetry.inc_ws_queue_pressure();
+                tracing::warn!(
+                    connection_id = %connection_id,
+                    channel_id = %channel_id,
+                    "Dropped network quality update due to full outbound websocket queue"
+                );
+                // Report again next tick even if nothing changed.
+                last_levels.remove(&channel_id);
+            }
+        }
+    }
+}